@@ -0,0 +1,54 @@
+//! Conversion of JSON and YAML values to toml.
+//!
+//! Every config format is converted to toml, so they're all decoded with the same structs. Each
+//! format only says what kind of value it has, and the walk over arrays and tables is shared.
+
+use std::result;
+
+use toml;
+
+/// A value in a config format that can be converted to toml.
+pub trait ToToml: Sized {
+    /// Returns what kind of value this is, or a message if toml can't hold it.
+    fn node(self) -> result::Result<Node<Self>, String>;
+}
+
+/// One level of a config value.
+#[derive(Debug)]
+pub enum Node<T> {
+    /// A string, number, or boolean.
+    Scalar(toml::Value),
+    /// An array of values.
+    Array(Vec<T>),
+    /// A table of values by key.
+    Table(Vec<(String, T)>),
+    /// A null, which toml has no equivalent for.
+    Null,
+}
+
+/// Converts a value to toml, or `None` for nulls, which are dropped from tables.
+pub fn to_toml<T: ToToml>(value: T) -> result::Result<Option<toml::Value>, String> {
+    Ok(Some(match try!(value.node()) {
+        Node::Scalar(value) => value,
+        Node::Array(array) => {
+            let mut values = Vec::new();
+            for value in array {
+                match try!(to_toml(value)) {
+                    Some(value) => values.push(value),
+                    None => return Err("arrays cannot contain nulls".to_string()),
+                }
+            }
+            toml::Value::Array(values)
+        }
+        Node::Table(entries) => {
+            let mut table = toml::Table::new();
+            for (key, value) in entries {
+                if let Some(value) = try!(to_toml(value)) {
+                    let _ = table.insert(key, value);
+                }
+            }
+            toml::Value::Table(table)
+        }
+        Node::Null => return Ok(None),
+    }))
+}
@@ -0,0 +1,365 @@
+//! Read trajectories from delimited text files.
+//!
+//! Column order, delimiter, and units vary from shop to shop, so they are all configurable,
+//! usually from a `[trajectory]` table:
+//!
+//! ```toml
+//! [trajectory]
+//! delimiter = ","
+//! skip_lines = 1
+//! angle_units = "degrees"
+//! height_units = "meters"
+//!
+//! [trajectory.columns]
+//! time = 0
+//! latitude = 1
+//! longitude = 2
+//! height = 3
+//! roll = 4
+//! pitch = 5
+//! heading = 6
+//! ```
+//!
+//! NovAtel Inertial Explorer ASCII profile exports are whitespace-delimited with a header, so a
+//! profile that writes GPS time, latitude, longitude, ellipsoidal height, roll, pitch, and heading,
+//! in that order and in degrees, can be read with e.g.:
+//!
+//! ```toml
+//! [trajectory]
+//! delimiter = "whitespace"
+//! skip_lines = 20
+//! ```
+//!
+//! Set `skip_lines` to the number of header lines in your profile's output.
+//!
+//! Photogrammetric trajectories give attitude as omega, phi, and kappa instead of roll, pitch, and
+//! heading. Map those angles onto the roll, pitch, and heading columns, in that order, and set
+//! `angle_convention = "omega_phi_kappa"`; they're converted to roll, pitch, and yaw under the
+//! default rotation order as they're read, so leave `rotation_order` and `frame` at their
+//! defaults.
+//!
+//! IMU vendors don't agree on which way roll, pitch, and heading are positive. Rather than making
+//! up for a flipped angle in the SOCS map or rotation order, name it with e.g. `negate =
+//! ["pitch"]`, and its column is negated as it's read. SBET and POS files have fixed conventions,
+//! so this is only for delimited text.
+//!
+//! Trajectories that give attitude as a unit quaternion, which rotates body vectors into the
+//! navigation frame, can name its w, x, y, and z columns with e.g. `quaternion_columns = [4, 5, 6,
+//! 7]`. The quaternion is then used as is, and the roll, pitch, and heading columns are ignored.
+//!
+//! Records must increase in time. Exports with a duplicated epoch or a few records out of order
+//! can be repaired as they're read with `repair = "drop_duplicates"` or `repair = "sort"`.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::result;
+
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+use imu_gnss::{ImuGnss, ImuGnssPoint, Quaternion, RepairPolicy};
+use rotation::AngleConvention;
+
+/// The feet-to-meters factor.
+const FEET: f64 = 0.3048;
+/// The US survey feet-to-meters factor.
+const US_SURVEY_FEET: f64 = 1200.0 / 3937.0;
+
+/// Zero-based column indices for each trajectory field.
+#[derive(Clone, Copy, Debug, RustcDecodable)]
+pub struct CsvColumns {
+    /// The gps time column.
+    pub time: usize,
+    /// The latitude column.
+    pub latitude: usize,
+    /// The longitude column.
+    pub longitude: usize,
+    /// The ellipsoidal height column.
+    pub height: usize,
+    /// The roll column.
+    pub roll: usize,
+    /// The pitch column.
+    pub pitch: usize,
+    /// The heading column.
+    pub heading: usize,
+}
+
+impl Default for CsvColumns {
+    fn default() -> CsvColumns {
+        CsvColumns {
+            time: 0,
+            latitude: 1,
+            longitude: 2,
+            height: 3,
+            roll: 4,
+            pitch: 5,
+            heading: 6,
+        }
+    }
+}
+
+/// A decodable description of a delimited text trajectory.
+#[derive(Debug, Default, RustcDecodable)]
+pub struct CsvConfig {
+    /// The column of each field. Defaults to time, latitude, longitude, height, roll, pitch, and
+    /// heading, in that order.
+    pub columns: Option<CsvColumns>,
+    /// The field delimiter, either a single character or "whitespace". Defaults to ",".
+    pub delimiter: Option<String>,
+    /// The number of header lines to skip. Defaults to zero.
+    pub skip_lines: Option<usize>,
+    /// The units of latitude, longitude, roll, pitch, and heading, either "degrees" (the default)
+    /// or "radians".
+    pub angle_units: Option<String>,
+    /// The units of height, either "meters" (the default), "feet", or "us_survey_feet".
+    pub height_units: Option<String>,
+    /// How the roll, pitch, and heading columns are defined, either "roll_pitch_heading" (the
+    /// default) or "omega_phi_kappa".
+    pub angle_convention: Option<String>,
+    /// The attitude columns whose signs are flipped as they're read, any of "roll", "pitch", and
+    /// "heading", for IMUs whose positive directions differ from georef's, e.g. `["pitch"]`.
+    pub negate: Option<Vec<String>>,
+    /// The columns of an attitude quaternion's w, x, y, and z, in that order, if the trajectory
+    /// gives one. The roll, pitch, and heading columns are then ignored.
+    pub quaternion_columns: Option<[usize; 4]>,
+    /// What to do with records whose times don't increase monotonically, either "error" (the
+    /// default), "sort", or "drop_duplicates".
+    pub repair: Option<String>,
+}
+
+impl CsvConfig {
+    /// Creates a new csv config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<CsvConfig, toml::DecodeError> {
+        CsvConfig::decode(&mut toml::Decoder::new(table))
+    }
+}
+
+/// Reads a delimited text trajectory from a path.
+///
+/// # Examples
+///
+/// ```no_run
+/// use georef::csv::{self, CsvConfig};
+/// let imu_gnss = csv::read_imu_gnss_from_path("trajectory.csv", &CsvConfig::default()).unwrap();
+/// ```
+pub fn read_imu_gnss_from_path<P: AsRef<Path>>(path: P, config: &CsvConfig) -> Result<ImuGnss> {
+    read_imu_gnss(BufReader::new(try!(File::open(path))), config)
+}
+
+/// Reads a delimited text trajectory.
+///
+/// Blank lines and lines that start with `#` are ignored.
+pub fn read_imu_gnss<R: BufRead>(reader: R, config: &CsvConfig) -> Result<ImuGnss> {
+    let angle_factor = match config.angle_units.as_ref().map(|s| s.as_ref()) {
+        None | Some("degrees") => 1f64.to_radians(),
+        Some("radians") => 1.0,
+        Some(s) => return Err(Error::Csv(format!("unknown angle units: {}", s))),
+    };
+    let height_factor = match config.height_units.as_ref().map(|s| s.as_ref()) {
+        None | Some("meters") => 1.0,
+        Some("feet") => FEET,
+        Some("us_survey_feet") => US_SURVEY_FEET,
+        Some(s) => return Err(Error::Csv(format!("unknown height units: {}", s))),
+    };
+    let angle_convention = match config.angle_convention {
+        Some(ref s) => {
+            try!(s.parse::<AngleConvention>()
+                  .map_err(|_| Error::Csv(format!("unknown angle convention: {}", s))))
+        }
+        None => AngleConvention::default(),
+    };
+    let delimiter = match config.delimiter.as_ref().map(|s| s.as_ref()) {
+        None => Some(','),
+        Some("whitespace") => None,
+        Some(s) if s.chars().count() == 1 => s.chars().next(),
+        Some(s) => return Err(Error::Csv(format!("invalid delimiter: {}", s))),
+    };
+    let mut signs = [1.0; 3];
+    for angle in config.negate.as_ref().map_or(&[][..], |negate| &negate[..]) {
+        if config.quaternion_columns.is_some() {
+            return Err(Error::Csv("negate cannot be used with quaternion_columns".to_string()));
+        }
+        let i = match angle.as_ref() {
+            "roll" => 0,
+            "pitch" => 1,
+            "heading" => 2,
+            _ => return Err(Error::Csv(format!("unknown angle to negate: {}", angle))),
+        };
+        signs[i] = -1.0;
+    }
+    let repair = match config.repair {
+        Some(ref s) => try!(s.parse()),
+        None => RepairPolicy::default(),
+    };
+    let columns = config.columns.unwrap_or(CsvColumns::default());
+    let mut points = Vec::new();
+    for (i, line) in reader.lines().enumerate().skip(config.skip_lines.unwrap_or(0)) {
+        let line = try!(line);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = match delimiter {
+            Some(delimiter) => line.split(delimiter).map(|s| s.trim()).collect(),
+            None => line.split_whitespace().collect(),
+        };
+        let field = |column: usize| -> Result<f64> {
+            match fields.get(column) {
+                Some(field) => {
+                    field.parse().map_err(|_| {
+                        Error::Csv(format!("line {}: could not parse column {} as a number: {}",
+                                           i + 1,
+                                           column,
+                                           field))
+                    })
+                }
+                None => Err(Error::Csv(format!("line {}: missing column {}", i + 1, column))),
+            }
+        };
+        let (roll, pitch, yaw, orientation) = match config.quaternion_columns {
+            Some(q) => {
+                let orientation = try!(Quaternion::new(try!(field(q[0])),
+                                                       try!(field(q[1])),
+                                                       try!(field(q[2])),
+                                                       try!(field(q[3])))
+                                           .ok_or_else(|| {
+                                               Error::Csv(format!("line {}: invalid quaternion",
+                                                                  i + 1))
+                                           }));
+                let (roll, pitch, yaw) = orientation.rpy();
+                (roll, pitch, yaw, Some(orientation))
+            }
+            None => {
+                let (roll, pitch, yaw) =
+                    angle_convention.roll_pitch_yaw(try!(field(columns.roll)) * angle_factor *
+                                                    signs[0],
+                                                    try!(field(columns.pitch)) * angle_factor *
+                                                    signs[1],
+                                                    try!(field(columns.heading)) * angle_factor *
+                                                    signs[2]);
+                (roll, pitch, yaw, None)
+            }
+        };
+        points.push(ImuGnssPoint {
+            time: try!(field(columns.time)),
+            latitude: try!(field(columns.latitude)) * angle_factor,
+            longitude: try!(field(columns.longitude)) * angle_factor,
+            height: try!(field(columns.height)) * height_factor,
+            roll: roll,
+            pitch: pitch,
+            yaw: yaw,
+            attitude_rate: None,
+            orientation: orientation,
+            accuracy: None,
+            utm: None,
+        });
+    }
+    ImuGnss::sanitize(points, repair).map(|(imu_gnss, _)| imu_gnss)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_default() {
+        let text = "time,lat,lon,h,r,p,y\n1,61,-149,100,0,0,90\n2,61,-149,110,0,0,180\n";
+        let config = CsvConfig { skip_lines: Some(1), ..Default::default() };
+        let imu_gnss = read_imu_gnss(text.as_bytes(), &config).unwrap();
+        let points = imu_gnss.points();
+        assert_eq!(2, points.len());
+        assert_eq!(1.0, points[0].time);
+        assert!((points[0].latitude - 61f64.to_radians()).abs() < 1e-12);
+        assert_eq!(110.0, points[1].height);
+        assert!((points[1].yaw - 180f64.to_radians()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn read_custom_columns() {
+        let text = "# heading pitch roll height lon lat time\n0.5 0.1 0.2 10 -2.6 1.06 3\n";
+        let config = CsvConfig {
+            columns: Some(CsvColumns {
+                time: 6,
+                latitude: 5,
+                longitude: 4,
+                height: 3,
+                roll: 2,
+                pitch: 1,
+                heading: 0,
+            }),
+            delimiter: Some("whitespace".to_string()),
+            angle_units: Some("radians".to_string()),
+            height_units: Some("feet".to_string()),
+            ..Default::default()
+        };
+        let imu_gnss = read_imu_gnss(text.as_bytes(), &config).unwrap();
+        let point = imu_gnss.points()[0];
+        assert_eq!(3.0, point.time);
+        assert_eq!(1.06, point.latitude);
+        assert_eq!(0.5, point.yaw);
+        assert!((point.height - 3.048).abs() < 1e-12);
+    }
+
+    #[test]
+    fn omega_phi_kappa() {
+        let text = "1,61,-149,100,0,0,30\n";
+        let config = CsvConfig {
+            angle_convention: Some("omega_phi_kappa".to_string()),
+            ..Default::default()
+        };
+        let point = read_imu_gnss(text.as_bytes(), &config).unwrap().points()[0];
+        assert!(point.roll.abs() < 1e-12);
+        assert!(point.pitch.abs() < 1e-12);
+        assert!((point.yaw - 30f64.to_radians()).abs() < 1e-12);
+        let config = CsvConfig { angle_convention: Some("opk".to_string()), ..Default::default() };
+        assert!(read_imu_gnss(text.as_bytes(), &config).is_err());
+    }
+
+    #[test]
+    fn quaternion() {
+        let text = "1,61,-149,100,0.5,0,0,0.5\n1,61,-149,100,0,0,0,0\n";
+        let config = CsvConfig { quaternion_columns: Some([4, 5, 6, 7]), ..Default::default() };
+        assert!(read_imu_gnss(text.as_bytes(), &config).is_err());
+        let imu_gnss = read_imu_gnss(text.lines().next().unwrap().as_bytes(), &config).unwrap();
+        let point = imu_gnss.points()[0];
+        let orientation = point.orientation.unwrap();
+        assert!((orientation.w - 0.5f64.sqrt()).abs() < 1e-12);
+        assert!((point.yaw - 90f64.to_radians()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn bad_field() {
+        let config = CsvConfig::default();
+        assert!(read_imu_gnss("1,2,3\n".as_bytes(), &config).is_err());
+        assert!(read_imu_gnss("1,2,3,4,5,6,x\n".as_bytes(), &config).is_err());
+    }
+
+    #[test]
+    fn repair() {
+        let text = "1,61,-149,100,0,0,0\n1,61,-149,100,0,0,0\n2,61,-149,100,0,0,0\n";
+        assert!(read_imu_gnss(text.as_bytes(), &CsvConfig::default()).is_err());
+        let config = CsvConfig {
+            repair: Some("drop_duplicates".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(2, read_imu_gnss(text.as_bytes(), &config).unwrap().len());
+    }
+
+    #[test]
+    fn negate() {
+        let text = "1,61,-149,100,10,20,30\n";
+        let config = CsvConfig {
+            negate: Some(vec!["pitch".to_string(), "heading".to_string()]),
+            ..Default::default()
+        };
+        let point = read_imu_gnss(text.as_bytes(), &config).unwrap().points()[0];
+        assert!((point.roll - 10f64.to_radians()).abs() < 1e-12);
+        assert!((point.pitch + 20f64.to_radians()).abs() < 1e-12);
+        assert!((point.yaw + 30f64.to_radians()).abs() < 1e-12);
+        let config = CsvConfig { negate: Some(vec!["yaw".to_string()]), ..Default::default() };
+        assert!(read_imu_gnss(text.as_bytes(), &config).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Datum shifts between terrestrial reference frames.
+//!
+//! Trajectories are usually processed in a realization of ITRF, e.g. ITRF2014, while deliverables
+//! are often in a regional frame like NAD83(2011). The two are related by a Helmert transform of
+//! seven parameters, a translation, three small rotations, and a scale, each of which may drift
+//! linearly with time, for fourteen in all. Every parameter `p` is evaluated at the epoch of the
+//! observations, `p(epoch) = p + rate * (epoch - reference_epoch)`, and the shift is applied to
+//! earth-centered, earth-fixed coordinates before they're projected:
+//!
+//! ```text
+//! X' = T + (1 + s) X + R X
+//! ```
+//!
+//! The parameters are published by the agencies that maintain each frame, e.g. for ITRF2014 to
+//! NAD83(2011):
+//!
+//! ```toml
+//! [georef.datum_shift]
+//! translation = [1.00530, -1.90210, -0.54157]
+//! rotation = [26.78138, -0.42027, 10.93206]
+//! scale = 0.36891
+//! translation_rate = [0.00079, -0.00060, -0.00144]
+//! rotation_rate = [0.06667, -0.75744, -0.05133]
+//! scale_rate = -0.07201
+//! reference_epoch = 2010.0
+//! epoch = 2016.5
+//! ```
+//!
+//! Check the sign convention of your parameters: "position_vector" (the default, used by the IERS)
+//! and "coordinate_frame" rotations differ only in sign.
+
+use std::f64;
+
+use nalgebra::Vec3;
+
+use Result;
+use error::Error;
+
+/// Milliarcseconds per radian.
+const MILLIARCSECONDS_PER_RADIAN: f64 = 180.0 * 3600.0 * 1000.0 / f64::consts::PI;
+
+/// A decodable description of a Helmert datum shift.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct HelmertConfig {
+    /// The x, y, and z translations, in meters.
+    pub translation: [f64; 3],
+    /// The rotations about the x, y, and z axes, in milliarcseconds.
+    pub rotation: [f64; 3],
+    /// The scale difference, in parts per billion.
+    pub scale: f64,
+    /// The rates of the translations, in meters per year.
+    pub translation_rate: Option<[f64; 3]>,
+    /// The rates of the rotations, in milliarcseconds per year.
+    pub rotation_rate: Option<[f64; 3]>,
+    /// The rate of the scale difference, in parts per billion per year.
+    pub scale_rate: Option<f64>,
+    /// The epoch, as a decimal year, at which the parameters are given.
+    ///
+    /// Required if any rate is given.
+    pub reference_epoch: Option<f64>,
+    /// The epoch, as a decimal year, of the observations.
+    ///
+    /// Required if any rate is given.
+    pub epoch: Option<f64>,
+    /// The sign convention of the rotations, either "position_vector" (the default) or
+    /// "coordinate_frame".
+    pub convention: Option<String>,
+}
+
+impl HelmertConfig {
+    /// Returns a message for each non-finite parameter group, for rates without both epochs, and
+    /// for an unknown convention.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let finite = |values: &[f64]| values.iter().all(|value| value.is_finite());
+        if !finite(&self.translation) || !finite(&self.rotation) || !self.scale.is_finite() {
+            problems.push("translation, rotation, and scale must be finite".to_string());
+        }
+        let rates = [self.translation_rate.unwrap_or([0.0; 3]),
+                     self.rotation_rate.unwrap_or([0.0; 3]),
+                     [self.scale_rate.unwrap_or(0.0), 0.0, 0.0]];
+        if !rates.iter().all(|rate| finite(rate)) {
+            problems.push("rates must be finite".to_string());
+        }
+        if self.reference_epoch.map_or(false, |epoch| !epoch.is_finite()) ||
+           self.epoch.map_or(false, |epoch| !epoch.is_finite()) {
+            problems.push("epochs must be finite".to_string());
+        }
+        let has_rates = self.translation_rate.is_some() || self.rotation_rate.is_some() ||
+                        self.scale_rate.is_some();
+        if has_rates && (self.reference_epoch.is_none() || self.epoch.is_none()) {
+            problems.push("rates require both reference_epoch and epoch".to_string());
+        }
+        if let Some(ref convention) = self.convention {
+            if convention != "position_vector" && convention != "coordinate_frame" {
+                problems.push(format!("unknown convention: {}", convention));
+            }
+        }
+        problems
+    }
+}
+
+/// A Helmert datum shift, evaluated at the epoch of the observations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Helmert {
+    translation: Vec3<f64>,
+    rotation: Vec3<f64>,
+    scale: f64,
+}
+
+impl Helmert {
+    /// Creates a datum shift from its configuration.
+    pub fn new(config: &HelmertConfig) -> Result<Helmert> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("datum_shift", problem));
+        }
+        let years = match (config.epoch, config.reference_epoch) {
+            (Some(epoch), Some(reference_epoch)) => epoch - reference_epoch,
+            _ => 0.0,
+        };
+        let at_epoch = |value: [f64; 3], rate: Option<[f64; 3]>| {
+            let rate = rate.unwrap_or([0.0; 3]);
+            Vec3::new(value[0] + rate[0] * years,
+                      value[1] + rate[1] * years,
+                      value[2] + rate[2] * years)
+        };
+        let sign = match config.convention.as_ref().map(|s| s.as_ref()) {
+            Some("coordinate_frame") => -1.0,
+            _ => 1.0,
+        };
+        Ok(Helmert {
+            translation: at_epoch(config.translation, config.translation_rate),
+            rotation: at_epoch(config.rotation, config.rotation_rate) *
+                      (sign / MILLIARCSECONDS_PER_RADIAN),
+            scale: (config.scale + config.scale_rate.unwrap_or(0.0) * years) * 1e-9,
+        })
+    }
+
+    /// Shifts an earth-centered, earth-fixed point into the target frame.
+    pub fn apply(&self, p: Vec3<f64>) -> Vec3<f64> {
+        let r = self.rotation;
+        self.translation + p * (1.0 + self.scale) +
+        Vec3::new(r.y * p.z - r.z * p.y,
+                  r.z * p.x - r.x * p.z,
+                  r.x * p.y - r.y * p.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::Vec3;
+
+    fn config() -> HelmertConfig {
+        HelmertConfig {
+            translation: [1.0, -2.0, 0.5],
+            rotation: [0.0, 0.0, 1000.0],
+            scale: 10.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let helmert = Helmert::new(&config()).unwrap();
+        let p = helmert.apply(Vec3::new(6378137.0, 0.0, 0.0));
+        assert!((p.x - (6378137.0 * (1.0 + 1e-8) + 1.0)).abs() < 1e-6);
+        // One arcsecond about z moves a point on the equator about 30.9 m east.
+        let east = 6378137.0 * 1000.0 / MILLIARCSECONDS_PER_RADIAN;
+        assert!((p.y - (east - 2.0)).abs() < 1e-6);
+        assert!((p.z - 0.5).abs() < 1e-9);
+        let config = HelmertConfig {
+            convention: Some("coordinate_frame".to_string()),
+            ..config()
+        };
+        let q = Helmert::new(&config).unwrap().apply(Vec3::new(6378137.0, 0.0, 0.0));
+        assert!((q.y + p.y + 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn epoch() {
+        let config = HelmertConfig {
+            translation_rate: Some([0.01, 0.0, 0.0]),
+            scale_rate: Some(-1.0),
+            reference_epoch: Some(2010.0),
+            epoch: Some(2015.0),
+            ..config()
+        };
+        let helmert = Helmert::new(&config).unwrap();
+        let p = helmert.apply(Vec3::new(0.0, 0.0, 1e9));
+        assert!((p.x - 1.05).abs() < 1e-9);
+        assert!((p.z - (1e9 + 5.0 + 0.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn problems() {
+        let config = HelmertConfig {
+            scale: ::std::f64::NAN,
+            rotation_rate: Some([0.0; 3]),
+            convention: Some("bursa_wolf".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(vec!["translation, rotation, and scale must be finite".to_string(),
+                        "rates require both reference_epoch and epoch".to_string(),
+                        "unknown convention: bursa_wolf".to_string()],
+                   config.problems());
+        assert!(Helmert::new(&config).is_err());
+        assert!(HelmertConfig::default().problems().is_empty());
+    }
+}
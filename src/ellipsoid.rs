@@ -0,0 +1,299 @@
+//! Reference ellipsoids for projecting output points.
+//!
+//! By default, UTM output is projected on WGS84. Regional reference systems sit on other
+//! ellipsoids, e.g. NAD83 on GRS80, and projecting their coordinates on the wrong one moves them,
+//! slightly for GRS80 and by hundreds of meters for older ellipsoids like Clarke 1866. The
+//! ellipsoid is given either by name or by its semi-major axis and inverse flattening:
+//!
+//! ```toml
+//! [georef]
+//! ellipsoid = { name = "GRS80" }
+//! # or
+//! ellipsoid = { semi_major_axis = 6378206.4, inverse_flattening = 294.978698214 }
+//! ```
+//!
+//! Trajectory latitudes and longitudes are taken to be on this ellipsoid, unless a datum shift is
+//! given, in which case the shifted earth-centered coordinates are converted back to geodetic
+//! coordinates on it. Earth-centered, local, and grid-to-ground output use it as well.
+
+use utm;
+
+use Result;
+use error::Error;
+
+/// The UTM scale factor on the central meridian.
+const UTM_K0: f64 = 0.9996;
+/// The UTM false easting, in meters.
+const UTM_FALSE_EASTING: f64 = 500000.0;
+/// The UTM false northing in the southern hemisphere, in meters.
+const UTM_FALSE_NORTHING_SOUTH: f64 = 10000000.0;
+
+/// A decodable description of an ellipsoid.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct EllipsoidConfig {
+    /// A named ellipsoid, either "WGS84" or "GRS80".
+    pub name: Option<String>,
+    /// The semi-major axis, in meters.
+    pub semi_major_axis: Option<f64>,
+    /// The inverse flattening.
+    pub inverse_flattening: Option<f64>,
+}
+
+impl EllipsoidConfig {
+    /// Returns a message if the ellipsoid is neither a known name nor a positive semi-major axis
+    /// and an inverse flattening greater than one.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        match (&self.name, self.semi_major_axis, self.inverse_flattening) {
+            (&Some(ref name), None, None) => {
+                if Ellipsoid::from_name(name).is_none() {
+                    problems.push(format!("unknown ellipsoid: {}", name));
+                }
+            }
+            (&None, Some(semi_major_axis), Some(inverse_flattening)) => {
+                if !(semi_major_axis > 0.0 && semi_major_axis.is_finite()) {
+                    problems.push("semi_major_axis must be greater than zero".to_string());
+                }
+                if !(inverse_flattening > 1.0 && inverse_flattening.is_finite()) {
+                    problems.push("inverse_flattening must be greater than one".to_string());
+                }
+            }
+            _ => {
+                problems.push("give either a name or both semi_major_axis and inverse_flattening"
+                                  .to_string())
+            }
+        }
+        problems
+    }
+}
+
+/// An ellipsoid of revolution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ellipsoid {
+    semi_major_axis: f64,
+    flattening: f64,
+}
+
+impl Default for Ellipsoid {
+    fn default() -> Ellipsoid {
+        Ellipsoid::wgs84()
+    }
+}
+
+impl Ellipsoid {
+    /// Creates an ellipsoid from its configuration.
+    pub fn new(config: &EllipsoidConfig) -> Result<Ellipsoid> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("ellipsoid", problem));
+        }
+        Ok(match config.name {
+            Some(ref name) => Ellipsoid::from_name(name).unwrap(),
+            None => {
+                Ellipsoid {
+                    semi_major_axis: config.semi_major_axis.unwrap(),
+                    flattening: 1.0 / config.inverse_flattening.unwrap(),
+                }
+            }
+        })
+    }
+
+    /// Returns the WGS84 ellipsoid.
+    pub fn wgs84() -> Ellipsoid {
+        Ellipsoid {
+            semi_major_axis: 6378137.0,
+            flattening: 1.0 / 298.257223563,
+        }
+    }
+
+    /// Returns the GRS80 ellipsoid.
+    pub fn grs80() -> Ellipsoid {
+        Ellipsoid {
+            semi_major_axis: 6378137.0,
+            flattening: 1.0 / 298.257222101,
+        }
+    }
+
+    /// Returns a named ellipsoid, "WGS84" or "GRS80".
+    pub fn from_name(name: &str) -> Option<Ellipsoid> {
+        match name {
+            "WGS84" | "wgs84" => Some(Ellipsoid::wgs84()),
+            "GRS80" | "grs80" => Some(Ellipsoid::grs80()),
+            _ => None,
+        }
+    }
+
+    /// Converts a latitude and longitude, in radians, to UTM northing and easting, in meters, and
+    /// meridian convergence, in radians.
+    ///
+    /// WGS84 goes through the `utm` crate, as it always has. Other ellipsoids use Krüger's series
+    /// to fourth order in the third flattening, which is good to well under a millimeter across a
+    /// zone.
+    pub fn to_utm(&self, latitude: f64, longitude: f64, utm_zone: u8) -> (f64, f64, f64) {
+        if *self == Ellipsoid::wgs84() {
+            return utm::radians_to_utm_wgs84(latitude, longitude, utm_zone);
+        }
+        let f = self.flattening;
+        let e = (f * (2.0 - f)).sqrt();
+        let n = f / (2.0 - f);
+        let (n2, n3, n4) = (n * n, n * n * n, n * n * n * n);
+        let rectifying_radius = self.semi_major_axis / (1.0 + n) * (1.0 + n2 / 4.0 + n4 / 64.0);
+        let alpha = [n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0 + 41.0 * n4 / 180.0,
+                     13.0 * n2 / 48.0 - 3.0 * n3 / 5.0 + 557.0 * n4 / 1440.0,
+                     61.0 * n3 / 240.0 - 103.0 * n4 / 140.0,
+                     49561.0 * n4 / 161280.0];
+        let lambda = longitude - (6.0 * utm_zone as f64 - 183.0).to_radians();
+        let sin_lat = latitude.sin();
+        let t = (sin_lat.atanh() - e * (e * sin_lat).atanh()).sinh();
+        let xi_prime = t.atan2(lambda.cos());
+        let eta_prime = (lambda.sin() / (1.0 + t * t).sqrt()).atanh();
+        let (mut xi, mut eta) = (xi_prime, eta_prime);
+        let (mut sigma, mut tau) = (1.0, 0.0);
+        for (j, alpha) in alpha.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi += alpha * (k * xi_prime).sin() * (k * eta_prime).cosh();
+            eta += alpha * (k * xi_prime).cos() * (k * eta_prime).sinh();
+            sigma += k * alpha * (k * xi_prime).cos() * (k * eta_prime).cosh();
+            tau += k * alpha * (k * xi_prime).sin() * (k * eta_prime).sinh();
+        }
+        let false_northing = if latitude < 0.0 {
+            UTM_FALSE_NORTHING_SOUTH
+        } else {
+            0.0
+        };
+        let convergence = (t / (1.0 + t * t).sqrt() * lambda.tan()).atan() + tau.atan2(sigma);
+        (false_northing + UTM_K0 * rectifying_radius * xi,
+         UTM_FALSE_EASTING + UTM_K0 * rectifying_radius * eta,
+         convergence)
+    }
+
+    /// Converts a latitude and longitude, in radians, and height on this ellipsoid to ECEF x, y,
+    /// and z.
+    pub fn to_ecef(&self, latitude: f64, longitude: f64, height: f64) -> (f64, f64, f64) {
+        let e2 = self.flattening * (2.0 - self.flattening);
+        let (sin_lat, cos_lat) = latitude.sin_cos();
+        let n = self.semi_major_axis / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+        ((n + height) * cos_lat * longitude.cos(),
+         (n + height) * cos_lat * longitude.sin(),
+         (n * (1.0 - e2) + height) * sin_lat)
+    }
+
+    /// Returns the UTM point scale factor at a latitude and longitude, in radians, in a zone.
+    pub fn utm_scale_factor(&self, latitude: f64, longitude: f64, utm_zone: u8) -> f64 {
+        let e2 = self.flattening * (2.0 - self.flattening);
+        let ep2 = e2 / (1.0 - e2);
+        let central_meridian = (6.0 * utm_zone as f64 - 183.0).to_radians();
+        let cos_lat = latitude.cos();
+        let t = latitude.tan().powi(2);
+        let c = ep2 * cos_lat * cos_lat;
+        let a = (longitude - central_meridian) * cos_lat;
+        UTM_K0 *
+        (1.0 + (1.0 + c) * a.powi(2) / 2.0 +
+         (5.0 - 4.0 * t + 42.0 * c + 13.0 * c * c - 28.0 * ep2) * a.powi(4) / 24.0 +
+         (61.0 - 148.0 * t + 16.0 * t * t) * a.powi(6) / 720.0)
+    }
+
+    /// Returns the elevation factor, the ratio of distances on this ellipsoid to distances at an
+    /// ellipsoidal height, at a latitude in radians.
+    pub fn elevation_factor(&self, latitude: f64, height: f64) -> f64 {
+        let e2 = self.flattening * (2.0 - self.flattening);
+        let w2 = 1.0 - e2 * latitude.sin().powi(2);
+        let radius = self.semi_major_axis * (1.0 - e2).sqrt() / w2;
+        radius / (radius + height)
+    }
+
+    /// Converts ECEF x, y, and z to latitude and longitude, in radians, and height above this
+    /// ellipsoid.
+    pub fn to_geodetic(&self, x: f64, y: f64, z: f64) -> (f64, f64, f64) {
+        let e2 = self.flattening * (2.0 - self.flattening);
+        let p = (x * x + y * y).sqrt();
+        let longitude = y.atan2(x);
+        let mut latitude = z.atan2(p * (1.0 - e2));
+        let mut height = 0.0;
+        for _ in 0..5 {
+            let sin_lat = latitude.sin();
+            let n = self.semi_major_axis / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+            height = if latitude.cos().abs() > 1e-10 {
+                p / latitude.cos() - n
+            } else {
+                z.abs() - n * (1.0 - e2)
+            };
+            latitude = z.atan2(p * (1.0 - e2 * n / (n + height)));
+        }
+        (latitude, longitude, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use utm;
+
+    #[test]
+    fn kruger_matches_utm() {
+        // A hair off WGS84, so the series is used, but not enough to move anything measurably.
+        let ellipsoid = Ellipsoid {
+            semi_major_axis: 6378137.0,
+            flattening: 1.0 / 298.257223563 + 1e-15,
+        };
+        let places = [(61.2f64, -149.9f64, 6), (0.0, 0.0, 31), (40.0, 5.0, 31)];
+        for &(latitude, longitude, zone) in &places {
+            let (latitude, longitude) = (latitude.to_radians(), longitude.to_radians());
+            let expected = utm::radians_to_utm_wgs84(latitude, longitude, zone);
+            let actual = ellipsoid.to_utm(latitude, longitude, zone);
+            assert!((expected.0 - actual.0).abs() < 1e-2);
+            assert!((expected.1 - actual.1).abs() < 1e-2);
+            assert!((expected.2 - actual.2).abs() < 1e-7);
+        }
+    }
+
+    #[test]
+    fn grs80() {
+        let config = EllipsoidConfig { name: Some("GRS80".to_string()), ..Default::default() };
+        let grs80 = Ellipsoid::new(&config).unwrap();
+        let (latitude, longitude) = (61.2f64.to_radians(), -149.9f64.to_radians());
+        let (northing, easting, _) = grs80.to_utm(latitude, longitude, 6);
+        let (wgs84_northing, wgs84_easting, _) = Ellipsoid::wgs84().to_utm(latitude, longitude, 6);
+        // GRS80 and WGS84 differ by a tenth of a millimeter in their semi-minor axes.
+        assert!((northing - wgs84_northing).abs() < 1e-3);
+        assert!((easting - wgs84_easting).abs() < 1e-3);
+    }
+
+    #[test]
+    fn custom() {
+        let config = EllipsoidConfig {
+            semi_major_axis: Some(6378206.4),
+            inverse_flattening: Some(294.978698214),
+            ..Default::default()
+        };
+        let clarke = Ellipsoid::new(&config).unwrap();
+        let (latitude, longitude) = (45f64.to_radians(), -93f64.to_radians());
+        let (northing, easting, convergence) = clarke.to_utm(latitude, longitude, 15);
+        assert!((easting - 500000.0).abs() < 1e-6);
+        assert!(convergence.abs() < 1e-12);
+        let (wgs84_northing, _, _) = Ellipsoid::wgs84().to_utm(latitude, longitude, 15);
+        assert!((northing - wgs84_northing).abs() > 10.0);
+        // A point on the ellipsoid, at a parametric latitude of 45 degrees.
+        let (x, y, z) = (6378206.4 * 0.5f64.sqrt(), 0.0, 6356583.8 * 0.5f64.sqrt());
+        let (_, _, height) = clarke.to_geodetic(x, y, z);
+        assert!(height.abs() < 1e-2);
+    }
+
+    #[test]
+    fn problems() {
+        assert_eq!(vec!["give either a name or both semi_major_axis and inverse_flattening"
+                            .to_string()],
+                   EllipsoidConfig::default().problems());
+        let config = EllipsoidConfig { name: Some("Bessel".to_string()), ..Default::default() };
+        assert_eq!(vec!["unknown ellipsoid: Bessel".to_string()], config.problems());
+        let config = EllipsoidConfig {
+            semi_major_axis: Some(-1.0),
+            inverse_flattening: Some(0.5),
+            ..Default::default()
+        };
+        assert_eq!(vec!["semi_major_axis must be greater than zero".to_string(),
+                        "inverse_flattening must be greater than one".to_string()],
+                   config.problems());
+        assert!(Ellipsoid::new(&config).is_err());
+    }
+}
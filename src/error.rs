@@ -12,41 +12,175 @@ use pos;
 #[derive(Debug)]
 #[allow(variant_size_differences)]
 pub enum Error {
+    /// A configuration problem that isn't about a single field, e.g. a missing table.
+    Config(String),
+    /// An error when reading a delimited text trajectory.
+    Csv(String),
+    /// Two merged trajectories have overlapping records whose attitudes are too far apart.
+    ///
+    /// Holds the time of the later trajectory's record and the largest difference of its roll,
+    /// pitch, and yaw, in degrees.
+    DisagreeingImuGnssAttitudes(f64, f64),
+    /// Two merged trajectories have overlapping records that are too far apart.
+    ///
+    /// Holds the time of the later trajectory's record and the distance, in meters.
+    DisagreeingImuGnssRecords(f64, f64),
+    /// An error with a geoid grid.
+    Geoid(String),
+    /// An error when reading a stabilized mount's angle log.
+    Gimbal(String),
+    /// A configuration field has an invalid value.
+    InvalidField(&'static str, String),
     /// Wrapper around `std::io::Error`.
     Io(io::Error),
+    /// An error when reading JSON.
+    Json(String),
+    /// An error when reading a leap second table.
+    LeapSeconds(String),
     /// A source point is missing a gps time value.
     MissingGpsTime,
+    /// An error when fusing GNSS and IMU measurements.
+    Navigation(String),
     /// The IMU/GNSS records do not increase monotonically.
     NonmonotonicImuGnssRecords,
     /// The point is outside of the IMU/GNSS records.
     OutsideOfImuGnssRecords,
+    /// An error when reading an orthophoto.
+    Orthophoto(String),
     /// Wrapper around `pabst::Error`.
     Pabst(pabst::Error),
+    /// An error when writing a PLY file.
+    Ply(String),
     /// Wrapper around `std::num::ParseIntError`.
     ParseInt(ParseIntError),
     /// Wrapper around `std::num::ParseFloatError`.
     ParseFloat(ParseFloatError),
+    /// Unable to parse an error policy from a string.
+    ParseErrorPolicy(String),
+    /// Unable to parse an interpolation method from a string.
+    ParseInterpolation(String),
+    /// Unable to parse a navigation frame from a string.
+    ParseNavigationFrame(String),
+    /// Unable to parse an output frame from a string.
+    ParseOutputFrame(String),
     /// Unable to parse a rotation from a string.
     ParseRotate(String),
     /// Wrapper around `pos::Error`.
     Pos(pos::Error),
+    /// An error when reading a ROS bag.
+    Rosbag(String),
     /// An error when creating a SOCS map.
     SocsMap(String),
+    /// The trajectory records around a point are too far apart to interpolate between.
+    ///
+    /// Holds the times of the records on either side of the gap.
+    TrajectoryGap(f64, f64),
+    /// An error when writing a delimited text file.
+    Text(String),
+    /// The coordinate reference system is not supported.
+    UnsupportedCrs(String),
+    /// A worker thread exited unexpectedly.
+    WorkerThread,
+    /// An error when reading YAML.
+    Yaml(String),
+}
+
+impl Error {
+    /// Returns the class of this error, for reporting it to scripts and schedulers.
+    ///
+    /// The classes are `"config"` for bad configuration values and the files they name, e.g.
+    /// geoid grids, `"trajectory"` for IMU/GNSS trajectories that can't be read or used,
+    /// `"format"` for point cloud and output format errors, `"point"` for points that can't be
+    /// georeferenced, `"io"` for IO errors, and `"internal"` for everything else.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::Error;
+    /// assert_eq!("point", Error::MissingGpsTime.kind());
+    /// ```
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Error::Config(_) |
+            Error::Geoid(_) |
+            Error::Gimbal(_) |
+            Error::InvalidField(_, _) |
+            Error::Json(_) |
+            Error::LeapSeconds(_) |
+            Error::Orthophoto(_) |
+            Error::ParseInt(_) |
+            Error::ParseFloat(_) |
+            Error::ParseErrorPolicy(_) |
+            Error::ParseInterpolation(_) |
+            Error::ParseNavigationFrame(_) |
+            Error::ParseOutputFrame(_) |
+            Error::ParseRotate(_) |
+            Error::SocsMap(_) |
+            Error::UnsupportedCrs(_) |
+            Error::Yaml(_) => "config",
+            Error::Csv(_) |
+            Error::DisagreeingImuGnssAttitudes(_, _) |
+            Error::DisagreeingImuGnssRecords(_, _) |
+            Error::Navigation(_) |
+            Error::NonmonotonicImuGnssRecords |
+            Error::Pos(_) => "trajectory",
+            Error::Pabst(_) | Error::Ply(_) | Error::Rosbag(_) | Error::Text(_) => "format",
+            Error::MissingGpsTime |
+            Error::OutsideOfImuGnssRecords |
+            Error::TrajectoryGap(_, _) => "point",
+            Error::Io(_) => "io",
+            Error::WorkerThread => "internal",
+        }
+    }
+
+    /// Returns the gps time of the point or trajectory record that this error is about, if any.
+    ///
+    /// For a trajectory gap, this is the time of the record before the gap.
+    pub fn time(&self) -> Option<f64> {
+        match *self {
+            Error::DisagreeingImuGnssAttitudes(time, _) |
+            Error::DisagreeingImuGnssRecords(time, _) |
+            Error::TrajectoryGap(time, _) => Some(time),
+            _ => None,
+        }
+    }
 }
 
 impl error::Error for Error {
     fn description(&self) -> &str {
         match *self {
+            Error::Config(_) => "invalid configuration",
+            Error::Csv(_) => "could not read delimited text trajectory",
+            Error::DisagreeingImuGnssAttitudes(_, _) => "merged imu/gnss attitudes disagree",
+            Error::DisagreeingImuGnssRecords(_, _) => "merged imu/gnss records disagree",
+            Error::Geoid(_) => "geoid error",
+            Error::Gimbal(_) => "could not read gimbal angle log",
+            Error::InvalidField(_, _) => "invalid configuration field",
             Error::Io(ref err) => err.description(),
+            Error::Json(_) => "could not read json",
+            Error::LeapSeconds(_) => "could not read leap second table",
             Error::MissingGpsTime => "missing gps time from point",
+            Error::Navigation(_) => "could not fuse gnss and imu measurements",
             Error::NonmonotonicImuGnssRecords => "imu/gnss records do not monotonically increase",
             Error::OutsideOfImuGnssRecords => "lidar point is outside of imu/gnss records",
+            Error::Orthophoto(_) => "could not read orthophoto",
             Error::Pabst(ref err) => err.description(),
+            Error::Ply(_) => "could not write ply",
             Error::ParseInt(ref err) => err.description(),
             Error::ParseFloat(ref err) => err.description(),
+            Error::ParseErrorPolicy(_) => "could not parse error policy",
+            Error::ParseInterpolation(_) => "could not parse interpolation method",
+            Error::ParseNavigationFrame(_) => "could not parse navigation frame",
+            Error::ParseOutputFrame(_) => "could not parse output frame",
             Error::ParseRotate(_) => "could not parse rotation",
             Error::Pos(ref err) => err.description(),
+            Error::Rosbag(_) => "could not read ROS bag",
             Error::SocsMap(_) => "could not create SOCS map",
+            Error::TrajectoryGap(_, _) => "trajectory gap is too long to interpolate across",
+            Error::Text(_) => "could not write delimited text",
+            Error::UnsupportedCrs(_) => "unsupported coordinate reference system",
+            Error::WorkerThread => "worker thread exited unexpectedly",
+            Error::Yaml(_) => "could not read yaml",
         }
     }
 
@@ -65,16 +199,73 @@ impl error::Error for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Error::Config(ref s) => write!(f, "Invalid configuration: {}", s),
+            Error::Csv(ref s) => write!(f, "Could not read delimited text trajectory: {}", s),
+            Error::DisagreeingImuGnssAttitudes(time, angle) => {
+                write!(f,
+                       "Merged IMU/GNSS records at time {} differ in attitude by {:.3} degrees",
+                       time,
+                       angle)
+            }
+            Error::DisagreeingImuGnssRecords(time, distance) => {
+                write!(f,
+                       "Merged IMU/GNSS records at time {} are {:.3} m apart",
+                       time,
+                       distance)
+            }
+            Error::Geoid(ref s) => write!(f, "Geoid error: {}", s),
+            Error::Gimbal(ref s) => write!(f, "Could not read gimbal angle log: {}", s),
+            Error::InvalidField(field, ref s) => write!(f, "Invalid value for {}: {}", field, s),
             Error::Io(ref err) => write!(f, "IO error: {}", err),
+            Error::Json(ref s) => write!(f, "Could not read JSON: {}", s),
+            Error::LeapSeconds(ref s) => write!(f, "Could not read leap second table: {}", s),
             Error::MissingGpsTime => write!(f, "Missing gps time"),
-            Error::NonmonotonicImuGnssRecords => write!(f, "IMU/GNSS records do not increase monotonically"),
-            Error::OutsideOfImuGnssRecords => write!(f, "LiDAR point is outside of IMU/GNSS records"),
+            Error::Navigation(ref s) => {
+                write!(f, "Could not fuse GNSS and IMU measurements: {}", s)
+            }
+            Error::NonmonotonicImuGnssRecords => {
+                write!(f, "IMU/GNSS records do not increase monotonically")
+            }
+            Error::OutsideOfImuGnssRecords => {
+                write!(f, "LiDAR point is outside of IMU/GNSS records")
+            }
+            Error::Orthophoto(ref s) => write!(f, "Could not read orthophoto: {}", s),
             Error::Pabst(ref err) => write!(f, "Pabst error: {}", err),
+            Error::Ply(ref s) => write!(f, "Could not write PLY: {}", s),
             Error::ParseInt(ref err) => write!(f, "Parse int error: {}", err),
             Error::ParseFloat(ref err) => write!(f, "Parse float error: {}", err),
+            Error::ParseErrorPolicy(ref s) => {
+                write!(f, "Unable to parse string as error policy: {}", s)
+            }
+            Error::ParseInterpolation(ref s) => {
+                write!(f, "Unable to parse string as interpolation method: {}", s)
+            }
+            Error::ParseNavigationFrame(ref s) => {
+                write!(f, "Unable to parse string as navigation frame: {}", s)
+            }
+            Error::ParseOutputFrame(ref s) => {
+                write!(f, "Unable to parse string as output frame: {}", s)
+            }
             Error::ParseRotate(ref err) => write!(f, "Unable to parse string as rotation: {}", err),
             Error::Pos(ref err) => write!(f, "Pos error: {}", err),
+            Error::Rosbag(ref s) => write!(f, "Could not read ROS bag: {}", s),
             Error::SocsMap(ref s) => write!(f, "Could not create a SOCS map: {}", s),
+            Error::TrajectoryGap(start, end) => {
+                write!(f,
+                       "Gap in the trajectory from {} to {} is too long to interpolate across",
+                       start,
+                       end)
+            }
+            Error::Text(ref s) => write!(f, "Could not write delimited text: {}", s),
+            Error::UnsupportedCrs(ref s) => {
+                write!(f,
+                       "Unsupported coordinate reference system: {} (only EPSG:4978, EPSG:4326, \
+                        EPSG:4979, EPSG:32601 through EPSG:32660, and EPSG:32701 through \
+                        EPSG:32760 are supported)",
+                       s)
+            }
+            Error::WorkerThread => write!(f, "A worker thread exited unexpectedly"),
+            Error::Yaml(ref s) => write!(f, "Could not read YAML: {}", s),
         }
     }
 }
@@ -108,3 +299,24 @@ impl From<pos::Error> for Error {
         Error::Pos(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind() {
+        assert_eq!("config",
+                   Error::InvalidField("utm_zone", "must be between 1 and 60".to_string()).kind());
+        assert_eq!("trajectory", Error::NonmonotonicImuGnssRecords.kind());
+        assert_eq!("point", Error::TrajectoryGap(1.0, 2.0).kind());
+        assert_eq!("internal", Error::WorkerThread.kind());
+    }
+
+    #[test]
+    fn time() {
+        assert_eq!(Some(1.0), Error::TrajectoryGap(1.0, 2.0).time());
+        assert_eq!(Some(3.0), Error::DisagreeingImuGnssRecords(3.0, 1.0).time());
+        assert_eq!(None, Error::MissingGpsTime.time());
+    }
+}
@@ -0,0 +1,354 @@
+//! Drop points that aren't wanted in the output.
+//!
+//! Filters are usually set from the `[filter]` table:
+//!
+//! ```toml
+//! [filter]
+//! bounds = [500000.0, 6700000.0, 501000.0, 6701000.0]
+//! min_range = 2.0
+//! max_range = 800.0
+//! max_scan_angle = 30.0
+//! keep_every_nth = 10
+//! returns = "last"
+//! classifications = [1, 2]
+//! ```
+//!
+//! Return and classification filters use the attributes as read from the source. Points without
+//! return numbers are treated as single returns, and points without a classification as class 0.
+//!
+//! Thinning, with `keep_every_nth` or `keep_fraction`, is for quick previews. It picks points by
+//! their position in the source, so the same points are kept on every run.
+//!
+//! Range and scan angle filters are applied before a point is georeferenced, and use the point's
+//! coordinates after any intrinsic calibration and range correction. Filtered points are counted
+//! in the run's summary but never reach the sink.
+
+use std::result;
+
+use nalgebra::{Norm, Vec3};
+use pabst;
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+use point::{NavigationFrame, scan_angle};
+
+/// A decodable description of which points to keep.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct FilterConfig {
+    /// The area of interest, as `[min_x, min_y, max_x, max_y]` in the output frame.
+    ///
+    /// Points outside of it are dropped. The bounds are inclusive.
+    pub bounds: Option<[f64; 4]>,
+    /// Points closer to the scanner than this, in meters, are dropped.
+    ///
+    /// Useful for removing returns from the aircraft, vehicle, or nearby noise.
+    pub min_range: Option<f64>,
+    /// Points farther from the scanner than this, in meters, are dropped.
+    pub max_range: Option<f64>,
+    /// Points with a scan angle farther than this from nadir, in degrees, are dropped.
+    ///
+    /// See `point::scan_angle` for how scan angles are measured.
+    pub max_scan_angle: Option<f64>,
+    /// Keep only every nth point read from the source, starting with the first.
+    pub keep_every_nth: Option<usize>,
+    /// Keep a pseudo-random fraction, between zero and one, of the points read from the source.
+    pub keep_fraction: Option<f64>,
+    /// Which returns to keep: "first", "last", or "all" (the default).
+    pub returns: Option<String>,
+    /// The classification codes to keep. By default, every class is kept.
+    pub classifications: Option<Vec<u8>>,
+}
+
+impl FilterConfig {
+    /// Creates a new filter config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<FilterConfig, toml::DecodeError> {
+        FilterConfig::decode(&mut toml::Decoder::new(table))
+    }
+
+    /// Returns a message for bounds that are out of order, for negative or crossed ranges, for a
+    /// scan angle outside of 0–180°, for thinning that keeps nothing, and for unknown returns.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Some(bounds) = self.bounds {
+            if !bounds.iter().all(|b| b.is_finite()) {
+                problems.push("bounds must be finite".to_string());
+            } else if !(bounds[0] < bounds[2] && bounds[1] < bounds[3]) {
+                problems.push("bounds must be [min_x, min_y, max_x, max_y]".to_string());
+            }
+        }
+        for &(name, range) in &[("min_range", self.min_range), ("max_range", self.max_range)] {
+            if range.map_or(false, |r| !(r >= 0.0 && r.is_finite())) {
+                problems.push(format!("{} must be zero or greater", name));
+            }
+        }
+        if self.max_scan_angle.map_or(false, |a| !(a >= 0.0 && a <= 180.0)) {
+            problems.push("max_scan_angle must be between 0 and 180".to_string());
+        }
+        if self.keep_every_nth == Some(0) {
+            problems.push("keep_every_nth must be greater than zero".to_string());
+        }
+        if self.keep_fraction.map_or(false, |f| !(f > 0.0 && f <= 1.0)) {
+            problems.push("keep_fraction must be greater than zero and at most one".to_string());
+        }
+        if let Some(ref returns) = self.returns {
+            if Returns::from_name(returns).is_none() {
+                problems.push(format!("returns must be \"first\", \"last\", or \"all\", not \"{}\"",
+                                      returns));
+            }
+        }
+        if let (Some(min_range), Some(max_range)) = (self.min_range, self.max_range) {
+            if min_range >= max_range {
+                problems.push("min_range must be less than max_range".to_string());
+            }
+        }
+        problems
+    }
+}
+
+/// Which returns of each pulse to keep.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Returns {
+    All,
+    First,
+    Last,
+}
+
+impl Default for Returns {
+    fn default() -> Returns {
+        Returns::All
+    }
+}
+
+impl Returns {
+    fn from_name(name: &str) -> Option<Returns> {
+        match name {
+            "all" => Some(Returns::All),
+            "first" => Some(Returns::First),
+            "last" => Some(Returns::Last),
+            _ => None,
+        }
+    }
+}
+
+/// Decides which points to keep.
+///
+/// The default filter keeps every point.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    bounds: Option<[f64; 4]>,
+    min_range: Option<f64>,
+    max_range: Option<f64>,
+    max_scan_angle: Option<f64>,
+    keep_every_nth: Option<usize>,
+    keep_fraction: Option<f64>,
+    returns: Returns,
+    classifications: Option<Vec<u8>>,
+}
+
+impl Filter {
+    /// Creates a filter from its configuration.
+    pub fn new(config: &FilterConfig) -> Result<Filter> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("filter", problem));
+        }
+        Ok(Filter {
+            bounds: config.bounds,
+            min_range: config.min_range,
+            max_range: config.max_range,
+            max_scan_angle: config.max_scan_angle,
+            keep_every_nth: config.keep_every_nth,
+            keep_fraction: config.keep_fraction,
+            returns: config.returns
+                           .as_ref()
+                           .and_then(|returns| Returns::from_name(returns))
+                           .unwrap_or_default(),
+            classifications: config.classifications.clone(),
+        })
+    }
+
+    /// Returns true if a point's attributes, as read from the source, pass the return and
+    /// classification filters.
+    pub fn keeps_source(&self, point: &pabst::Point) -> bool {
+        let return_number = point.return_number.unwrap_or(1);
+        let keeps_return = match self.returns {
+            Returns::All => true,
+            Returns::First => return_number == 1,
+            Returns::Last => return_number >= point.number_of_returns.unwrap_or(return_number),
+        };
+        keeps_return &&
+        self.classifications.as_ref().map_or(true, |classifications| {
+            classifications.contains(&point.classification.unwrap_or(0))
+        })
+    }
+
+    /// Returns true if the point at an index in the source survives thinning.
+    pub fn keeps_index(&self, index: usize) -> bool {
+        self.keep_every_nth.map_or(true, |n| index % n == 0) &&
+        self.keep_fraction.map_or(true, |fraction| unit_hash(index as u64) < fraction)
+    }
+
+    /// Returns true if a point, in the scanner's own coordinate system after the SOCS map has
+    /// been applied, should be georeferenced.
+    ///
+    /// The navigation frame decides which way is down for the scan angle.
+    pub fn keeps_socs(&self, socs: Vec3<f64>, frame: NavigationFrame) -> bool {
+        let range = socs.norm();
+        self.min_range.map_or(true, |min_range| range >= min_range) &&
+        self.max_range.map_or(true, |max_range| range <= max_range) &&
+        self.max_scan_angle.map_or(true, |max_angle| scan_angle(socs, frame).abs() <= max_angle)
+    }
+
+    /// Returns true if a georeferenced point should be written.
+    pub fn keeps_output(&self, point: &pabst::Point) -> bool {
+        match self.bounds {
+            Some(bounds) => {
+                point.x >= bounds[0] && point.y >= bounds[1] && point.x <= bounds[2] &&
+                point.y <= bounds[3]
+            }
+            None => true,
+        }
+    }
+}
+
+/// Hashes an index to a number in [0, 1), using the SplitMix64 finalizer.
+fn unit_hash(index: u64) -> f64 {
+    let mut z = index.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z = z ^ (z >> 31);
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::Vec3;
+    use pabst;
+
+    use point::NavigationFrame;
+
+    #[test]
+    fn bounds() {
+        let filter = Filter::new(&FilterConfig {
+                         bounds: Some([0.0, 10.0, 1.0, 11.0]),
+                         ..Default::default()
+                     })
+                         .unwrap();
+        let point = |x, y| pabst::Point { x: x, y: y, ..Default::default() };
+        assert!(filter.keeps_output(&point(0.5, 10.5)));
+        assert!(filter.keeps_output(&point(1.0, 10.0)));
+        assert!(!filter.keeps_output(&point(-0.5, 10.5)));
+        assert!(!filter.keeps_output(&point(0.5, 12.0)));
+        assert!(Filter::default().keeps_output(&point(-0.5, 10.5)));
+    }
+
+    #[test]
+    fn range() {
+        let filter = Filter::new(&FilterConfig {
+                         min_range: Some(2.0),
+                         max_range: Some(10.0),
+                         ..Default::default()
+                     })
+                         .unwrap();
+        assert!(filter.keeps_socs(Vec3::new(3.0, 0.0, 4.0), NavigationFrame::Enu));
+        assert!(filter.keeps_socs(Vec3::new(0.0, -2.0, 0.0), NavigationFrame::Enu));
+        assert!(!filter.keeps_socs(Vec3::new(1.0, 1.0, 0.0), NavigationFrame::Enu));
+        assert!(!filter.keeps_socs(Vec3::new(6.0, 0.0, 8.5), NavigationFrame::Enu));
+        assert!(Filter::default().keeps_socs(Vec3::new(0.0, 0.0, 0.0), NavigationFrame::Enu));
+    }
+
+    #[test]
+    fn scan_angles() {
+        let filter = Filter::new(&FilterConfig { max_scan_angle: Some(30.0), ..Default::default() })
+                         .unwrap();
+        let down = Vec3::new(0.0, 0.0, -10.0);
+        assert!(filter.keeps_socs(down, NavigationFrame::Enu));
+        assert!(!filter.keeps_socs(down, NavigationFrame::Ned));
+        assert!(filter.keeps_socs(-down, NavigationFrame::Ned));
+        assert!(!filter.keeps_socs(-down, NavigationFrame::Enu));
+        assert!(filter.keeps_socs(Vec3::new(0.0, 5.0, -10.0), NavigationFrame::Enu));
+        assert!(!filter.keeps_socs(Vec3::new(0.0, 10.0, -10.0), NavigationFrame::Enu));
+        assert!(!filter.keeps_socs(Vec3::new(0.0, 10.0, 10.0), NavigationFrame::Ned));
+    }
+
+    #[test]
+    fn thinning() {
+        let filter = Filter::new(&FilterConfig { keep_every_nth: Some(3), ..Default::default() })
+                         .unwrap();
+        let kept: Vec<usize> = (0..10).filter(|&i| filter.keeps_index(i)).collect();
+        assert_eq!(vec![0, 3, 6, 9], kept);
+        let filter = Filter::new(&FilterConfig { keep_fraction: Some(0.25), ..Default::default() })
+                         .unwrap();
+        let kept = (0..10000).filter(|&i| filter.keeps_index(i)).count();
+        assert!(kept > 2300 && kept < 2700);
+        assert!(Filter::default().keeps_index(7));
+    }
+
+    #[test]
+    fn returns_and_classifications() {
+        let point = |return_number, number_of_returns, classification| {
+            pabst::Point {
+                return_number: Some(return_number),
+                number_of_returns: Some(number_of_returns),
+                classification: Some(classification),
+                ..Default::default()
+            }
+        };
+        let first = Filter::new(&FilterConfig {
+                        returns: Some("first".to_string()),
+                        ..Default::default()
+                    })
+                        .unwrap();
+        assert!(first.keeps_source(&point(1, 3, 1)));
+        assert!(!first.keeps_source(&point(2, 3, 1)));
+        let last = Filter::new(&FilterConfig {
+                       returns: Some("last".to_string()),
+                       classifications: Some(vec![2]),
+                       ..Default::default()
+                   })
+                       .unwrap();
+        assert!(last.keeps_source(&point(3, 3, 2)));
+        assert!(last.keeps_source(&point(1, 1, 2)));
+        assert!(!last.keeps_source(&point(1, 3, 2)));
+        assert!(!last.keeps_source(&point(3, 3, 1)));
+        assert!(last.keeps_source(&pabst::Point { classification: Some(2), ..Default::default() }));
+        assert!(Filter::default().keeps_source(&point(2, 3, 7)));
+    }
+
+    #[test]
+    fn problems() {
+        let config = FilterConfig { bounds: Some([1.0, 10.0, 0.0, 11.0]), ..Default::default() };
+        assert_eq!(vec!["bounds must be [min_x, min_y, max_x, max_y]".to_string()],
+                   config.problems());
+        let config = FilterConfig {
+            bounds: Some([0.0, 0.0, 1.0, ::std::f64::NAN]),
+            ..Default::default()
+        };
+        assert!(Filter::new(&config).is_err());
+        let config = FilterConfig {
+            min_range: Some(5.0),
+            max_range: Some(1.0),
+            ..Default::default()
+        };
+        assert_eq!(vec!["min_range must be less than max_range".to_string()],
+                   config.problems());
+        let config = FilterConfig {
+            min_range: Some(-1.0),
+            max_scan_angle: Some(200.0),
+            keep_every_nth: Some(0),
+            keep_fraction: Some(1.5),
+            returns: Some("second".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(vec!["min_range must be zero or greater".to_string(),
+                        "max_scan_angle must be between 0 and 180".to_string(),
+                        "keep_every_nth must be greater than zero".to_string(),
+                        "keep_fraction must be greater than zero and at most one".to_string(),
+                        "returns must be \"first\", \"last\", or \"all\", not \"second\""
+                            .to_string()],
+                   config.problems());
+    }
+}
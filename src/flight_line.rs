@@ -0,0 +1,111 @@
+//! Split a mission into flight lines.
+//!
+//! Flight lines are found in the trajectory: they are the stretches where the heading changes
+//! slowly, separated by turns. Heading rates are measured over steps of at least one second, so
+//! IMU noise doesn't break up lines. Stretches shorter than the minimum duration, e.g. the run-in
+//! to a turn, aren't lines. Lines are numbered from one, in time order, and usually configured
+//! with the `[flight_lines]` table:
+//!
+//! ```toml
+//! [flight_lines]
+//! max_heading_rate = 2.0
+//! min_duration = 20.0
+//! ```
+
+use std::result;
+
+use rustc_serialize::Decodable;
+use toml;
+
+/// The default largest heading rate within a line, in degrees per second.
+const MAX_HEADING_RATE: f64 = 2.0;
+/// The default shortest line, in seconds.
+const MIN_DURATION: f64 = 10.0;
+
+/// A decodable description of how to find flight lines.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct FlightLineConfig {
+    /// The largest heading rate within a line, in degrees per second. Defaults to 2.
+    pub max_heading_rate: Option<f64>,
+    /// The shortest line, in seconds. Defaults to 10.
+    pub min_duration: Option<f64>,
+}
+
+impl FlightLineConfig {
+    /// Creates a new flight line config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<FlightLineConfig, toml::DecodeError> {
+        FlightLineConfig::decode(&mut toml::Decoder::new(table))
+    }
+
+    /// Returns a message for a maximum heading rate or minimum duration that isn't positive.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for &(name, value) in &[("max_heading_rate", self.max_heading_rate),
+                                ("min_duration", self.min_duration)] {
+            if value.map_or(false, |v| !(v > 0.0 && v.is_finite())) {
+                problems.push(format!("{} must be greater than zero", name));
+            }
+        }
+        problems
+    }
+
+    /// Returns the largest heading rate within a line, in radians per second.
+    pub fn max_heading_rate(&self) -> f64 {
+        self.max_heading_rate.unwrap_or(MAX_HEADING_RATE).to_radians()
+    }
+
+    /// Returns the shortest line, in seconds.
+    pub fn min_duration(&self) -> f64 {
+        self.min_duration.unwrap_or(MIN_DURATION)
+    }
+}
+
+/// One flight line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlightLine {
+    /// The line's number, counting from one.
+    pub number: u16,
+    /// The trajectory time at which the line starts.
+    pub start: f64,
+    /// The trajectory time at which the line ends.
+    pub end: f64,
+}
+
+/// Returns the line that contains a trajectory time, if any.
+///
+/// The lines must be in time order.
+pub fn line_at(lines: &[FlightLine], time: f64) -> Option<&FlightLine> {
+    let i = match lines.binary_search_by(|line| line.start.partial_cmp(&time).unwrap()) {
+        Ok(i) => i,
+        Err(0) => return None,
+        Err(i) => i - 1,
+    };
+    lines.get(i).and_then(|line| if time <= line.end { Some(line) } else { None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let lines = [FlightLine { number: 1, start: 0.0, end: 10.0 },
+                     FlightLine { number: 2, start: 20.0, end: 30.0 }];
+        assert_eq!(1, line_at(&lines, 0.0).unwrap().number);
+        assert_eq!(1, line_at(&lines, 10.0).unwrap().number);
+        assert_eq!(2, line_at(&lines, 25.0).unwrap().number);
+        assert!(line_at(&lines, -1.0).is_none());
+        assert!(line_at(&lines, 15.0).is_none());
+        assert!(line_at(&lines, 31.0).is_none());
+        assert!(line_at(&[], 1.0).is_none());
+    }
+
+    #[test]
+    fn problems() {
+        let config = FlightLineConfig { max_heading_rate: Some(0.0), min_duration: Some(-1.0) };
+        assert_eq!(vec!["max_heading_rate must be greater than zero".to_string(),
+                        "min_duration must be greater than zero".to_string()],
+                   config.problems());
+        assert!(FlightLineConfig::default().problems().is_empty());
+    }
+}
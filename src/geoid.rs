@@ -0,0 +1,157 @@
+//! Geoid models for converting ellipsoidal heights to orthometric heights.
+//!
+//! Grids are read in the ASCII format that NGA distributes EGM96 in (e.g. `WW15MGH.GRD`): a header
+//! of south, north, west, and east bounds and the latitude and longitude spacing, all in degrees,
+//! followed by undulations in meters, row by row from north to south and west to east.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use Result;
+use error::Error;
+
+/// Tolerance, in grid degrees, for points that fall just outside of the grid due to rounding.
+const EPSILON: f64 = 1e-9;
+
+/// A gridded geoid model.
+#[derive(Debug)]
+pub struct Geoid {
+    south: f64,
+    north: f64,
+    west: f64,
+    dlat: f64,
+    dlon: f64,
+    nrows: usize,
+    ncols: usize,
+    undulations: Vec<f64>,
+}
+
+impl Geoid {
+    /// Reads a geoid grid from a path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::geoid::Geoid;
+    /// let geoid = Geoid::from_path("WW15MGH.GRD").unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Geoid> {
+        Geoid::from_reader(try!(File::open(path)))
+    }
+
+    /// Reads a geoid grid from something that implements `Read`.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Geoid> {
+        let mut s = String::new();
+        let _ = try!(reader.read_to_string(&mut s));
+        let mut values = Vec::new();
+        for word in s.split_whitespace() {
+            values.push(try!(word.parse::<f64>()));
+        }
+        if values.len() < 6 {
+            return Err(Error::Geoid("grid header is incomplete".to_string()));
+        }
+        let (south, north, west, east, dlat, dlon) = (values[0],
+                                                      values[1],
+                                                      values[2],
+                                                      values[3],
+                                                      values[4],
+                                                      values[5]);
+        if !(north > south && east > west && dlat > 0.0 && dlon > 0.0) ||
+           !values[..6].iter().all(|value| value.is_finite()) {
+            return Err(Error::Geoid("grid header has invalid bounds or spacing".to_string()));
+        }
+        // The sizes stay floating point until they're checked against the undulations, so a
+        // corrupt spacing can't overflow them.
+        let nrows = ((north - south) / dlat).round() + 1.0;
+        let ncols = ((east - west) / dlon).round() + 1.0;
+        let undulations = values.split_off(6);
+        if nrows * ncols != undulations.len() as f64 {
+            return Err(Error::Geoid(format!("expected {} undulations, found {}",
+                                            nrows * ncols,
+                                            undulations.len())));
+        }
+        let (nrows, ncols) = (nrows as usize, ncols as usize);
+        Ok(Geoid {
+            south: south,
+            north: north,
+            west: west,
+            dlat: dlat,
+            dlon: dlon,
+            nrows: nrows,
+            ncols: ncols,
+            undulations: undulations,
+        })
+    }
+
+    /// Returns the bilinearly-interpolated geoid undulation, in meters, at a latitude and
+    /// longitude in radians.
+    ///
+    /// Longitudes are wrapped into the grid's range, so both -180 to 180 and 0 to 360 grids work.
+    pub fn undulation(&self, latitude: f64, longitude: f64) -> Result<f64> {
+        let latitude = latitude.to_degrees();
+        if latitude < self.south - EPSILON || latitude > self.north + EPSILON {
+            return Err(Error::Geoid(format!("latitude {} is outside of the geoid grid", latitude)));
+        }
+        let mut longitude = (longitude.to_degrees() - self.west) % 360.0;
+        if longitude < 0.0 {
+            longitude += 360.0;
+        }
+        let max_col = (self.ncols - 1) as f64;
+        let col = longitude / self.dlon;
+        if col > max_col + EPSILON {
+            return Err(Error::Geoid(format!("longitude {} is outside of the geoid grid",
+                                            longitude + self.west)));
+        }
+        let row = ((self.north - latitude) / self.dlat).max(0.0).min((self.nrows - 1) as f64);
+        let col = col.min(max_col);
+        let (r0, c0) = (row.floor() as usize, col.floor() as usize);
+        let (r1, c1) = ((r0 + 1).min(self.nrows - 1), (c0 + 1).min(self.ncols - 1));
+        let (fr, fc) = (row - r0 as f64, col - c0 as f64);
+        let value = |r: usize, c: usize| self.undulations[r * self.ncols + c];
+        Ok(value(r0, c0) * (1.0 - fr) * (1.0 - fc) + value(r0, c1) * (1.0 - fr) * fc +
+           value(r1, c0) * fr * (1.0 - fc) + value(r1, c1) * fr * fc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geoid() -> Geoid {
+        Geoid::from_reader("0.0 1.0 0.0 2.0 1.0 1.0\n1.0 2.0 3.0\n4.0 5.0 6.0\n".as_bytes())
+            .unwrap()
+    }
+
+    #[test]
+    fn grid_nodes() {
+        let geoid = geoid();
+        assert!((geoid.undulation(1f64.to_radians(), 0.0).unwrap() - 1.0).abs() < 1e-9);
+        assert!((geoid.undulation(0.0, 2f64.to_radians()).unwrap() - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bilinear() {
+        let geoid = geoid();
+        let undulation = geoid.undulation(0.5f64.to_radians(), 0.5f64.to_radians()).unwrap();
+        assert!((undulation - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn outside() {
+        let geoid = geoid();
+        assert!(geoid.undulation(2f64.to_radians(), 0.0).is_err());
+        assert!(geoid.undulation(0.0, 3f64.to_radians()).is_err());
+    }
+
+    #[test]
+    fn wrong_count() {
+        assert!(Geoid::from_reader("0.0 1.0 0.0 2.0 1.0 1.0\n1.0 2.0 3.0\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn corrupt_header() {
+        assert!(Geoid::from_reader("0.0 1.0 0.0 2.0 1e-300 1e-300\n1.0\n".as_bytes()).is_err());
+        assert!(Geoid::from_reader("0.0 inf 0.0 2.0 1.0 1.0\n1.0\n".as_bytes()).is_err());
+    }
+}
@@ -1,43 +1,203 @@
 //! Georeference LiDAR points.
 
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::env;
+use std::f64;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
 use std::result;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
 
 use nalgebra::{Col, Eye, Rot3, Vec3};
 use pabst;
-use pos;
-use rustc_serialize::Decodable;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use rustc_serialize::json::{self, Json};
 use toml;
 
 use Result;
+use datum::{Helmert, HelmertConfig};
+use ellipsoid::{Ellipsoid, EllipsoidConfig};
 use error::Error;
-use point::UtmPoint;
+use filter::{Filter, FilterConfig};
+use flight_line::{self, FlightLine};
+use geoid::Geoid;
+use gimbal::{Gimbal, GimbalConfig};
+use ground::{GroundScale, GroundScaleConfig};
+use imu_gnss::{ImuGnss, ImuGnssPoint, Quaternion};
+use intrinsics::{Intrinsics, IntrinsicsConfig};
+use orthophoto::Orthophoto;
+use polar::{Polar, PolarConfig};
+use point::{EcefPoint, LocalFrame, NavigationFrame, OutputFrame, UtmPoint, crs_code,
+            natural_utm_zone, parse_crs, scan_angle};
+use range::{RangeCorrection, RangeCorrectionConfig};
 use rotation::RotationOrder;
+use stream::{PointSink, PointSource};
+use time::TimeSystemConfig;
+use trajectory::{AttitudeInterpolation, Interpolation, Pose, PositionInterpolation, Trajectory};
+#[cfg(feature = "yaml")]
+use yaml;
 
 const DEFAULT_CHUNK_SIZE: usize = 1000;
+/// The longest believable lever arm, in meters.
+const MAX_LEVER_ARM: f64 = 100.0;
+/// How far a boresight matrix's rows may be from unit length and perpendicular.
+///
+/// Calibration reports round their matrices, and five decimal places leaves rows about 1e-5 off.
+const ORTHONORMAL_TOLERANCE: f64 = 1e-4;
+/// The mean radius of the earth, in meters, for finding where a point is on a geoid grid.
+const EARTH_RADIUS: f64 = 6_371_000.0;
 
 /// A decodable configuration object.
-#[derive(Debug, RustcDecodable)]
+#[derive(Clone, Debug, RustcDecodable, RustcEncodable)]
 pub struct GeorefConfig {
     /// The boresight matrix.
     ///
-    /// This is the rotational offset between the scanner and the GNSS/IMU.
+    /// This is the rotational offset between the scanner and the GNSS/IMU, either as roll, pitch,
+    /// and yaw or as a row-major rotation matrix, e.g. `{ matrix = [1.0, 0.0, 0.0, ...] }`.
     pub boresight: Rpy,
     /// The size of each processing chunk.
     pub chunk_size: Option<usize>,
+    /// A Helmert datum shift, e.g. from ITRF2014 to NAD83(2011), applied before projection.
+    ///
+    /// By default, output points are in the trajectory's own frame.
+    pub datum_shift: Option<HelmertConfig>,
+    /// The ellipsoid that output is on, and that the grid-to-ground factor is computed on.
+    ///
+    /// Defaults to WGS84. Use e.g. `{ name = "GRS80" }` for NAD83, or give a semi-major axis and
+    /// inverse flattening.
+    pub ellipsoid: Option<EllipsoidConfig>,
+    /// What to do with points outside of the IMU/GNSS records: "strict" (the default), "skip",
+    /// or "clamp".
+    pub error_policy: Option<String>,
+    /// Which points to keep.
+    pub filter: Option<FilterConfig>,
+    /// The navigation frame that the IMU/GNSS attitude rotates body vectors into: "ENU" (the
+    /// default) or "NED".
+    ///
+    /// With "NED", the body's z axis points down and the attitude's yaw is clockwise from north,
+    /// as in most SBET and POS files, so the SOCS map doesn't need to flip axes to make up for it.
+    pub frame: Option<String>,
+    /// The path to a geoid grid.
+    ///
+    /// If provided, output heights are orthometric instead of ellipsoidal.
+    pub geoid: Option<String>,
+    /// The angle log of a stabilized mount between the scanner and the body.
+    ///
+    /// If provided, the mount's angles at each point's time rotate the scanner, after the
+    /// boresight, into the body frame. See the `gimbal` module.
+    pub gimbal: Option<GimbalConfig>,
+    /// Scales UTM output from grid to ground distances about a project origin.
+    pub ground_scale: Option<GroundScaleConfig>,
+    /// A constant correction added to every trajectory record's yaw, in degrees.
+    ///
+    /// Use this for e.g. the magnetic declination of a magnetometer-derived heading, or a known
+    /// convergence offset. The correction turns the yaw the same way a positive yaw does, so in
+    /// the NED frame an easterly declination is positive. Quaternion attitudes are corrected too.
+    pub heading_offset: Option<f64>,
+    /// How IMU/GNSS records are interpolated.
+    pub interpolation: Option<InterpolationConfig>,
+    /// The scanner's intrinsic calibration, applied to points in the scanner's own coordinate
+    /// system.
+    pub intrinsics: Option<IntrinsicsConfig>,
     /// The lever arm.
     ///
     /// This is the x, y, and z displacements between the GNSS/IMU and the scanner.
     pub lever_arm: Vec3<f64>,
+    /// The origin of the local tangent plane, used when the output frame is "local".
+    pub local_origin: Option<LocalOrigin>,
+    /// The most chunks that may be in flight between reading and writing when more than one
+    /// thread is used.
+    ///
+    /// Chunks that finish early wait in a reorder buffer so points are written in the order they
+    /// were read, and this caps that buffer: at most this many chunks of `chunk_size` points are
+    /// held in memory at once. Defaults to two per thread.
+    pub max_chunks_in_flight: Option<usize>,
+    /// The largest gap, in seconds, between IMU/GNSS records that points may be interpolated
+    /// across.
+    ///
+    /// Points in longer gaps are treated like points outside of the records, except that they are
+    /// never clamped. By default, gaps of any length are interpolated across.
+    pub max_interpolation_gap: Option<f64>,
+    /// The scanner-to-body transform, as a homogeneous 4x4 matrix in row-major order.
+    ///
+    /// This replaces the boresight, lever arm, and SOCS map, which must then be left at the
+    /// identity: no rotation, no offset, and `{ x = "x", y = "y", z = "z" }`. The upper left 3x3
+    /// must be a rotation and the last row must be 0, 0, 0, 1.
+    pub mounting: Option<[f64; 16]>,
+    /// The length, in seconds, of the time buckets that share one interpolated pose.
+    ///
+    /// By default, a pose is interpolated for every point. With a bucket length, one pose is
+    /// interpolated at the middle of each bucket and reused for every point in it, which saves
+    /// time on slow platforms at the cost of accuracy. Each point's pose is off by at most half a
+    /// bucket: at 0.001 s, 5 mm of position at 10 m/s, and 0.26 mrad of attitude, or 2.6 cm at
+    /// 100 m range, at 30 degrees per second. Leave unset for fast-turning platforms like UAVs.
+    pub pose_interval: Option<f64>,
+    /// The path to an orthophoto, an uncompressed GeoTIFF in the output coordinate reference
+    /// system.
+    ///
+    /// If provided, each point that falls on the orthophoto is colored with its pixel's RGB.
+    pub orthophoto: Option<String>,
+    /// The coordinate reference system of the output points, e.g. "EPSG:32606".
+    ///
+    /// This sets both the output frame and the UTM zone, so it cannot be combined with
+    /// `output_frame`. Only WGS84 ECEF, geographic, and UTM systems are supported, as listed by
+    /// `point::parse_crs`. Other systems, e.g. EPSG:2927, are rejected.
+    pub output_crs: Option<String>,
+    /// The coordinate frame of the output points: "utm" (the default), "ecef", "geographic", or
+    /// "local", a local east-north-up tangent plane anchored at `local_origin`.
+    ///
+    /// For debugging, "sensor" stops after the boresight and "body" after the lever arm, before
+    /// the trajectory is applied, to tell mounting problems from trajectory problems.
+    pub output_frame: Option<String>,
+    /// The point source id written to every point, e.g. to number flight lines.
+    ///
+    /// By default, points keep the point source id that they were read with.
+    pub point_source_id: Option<u16>,
+    /// The scanner geometry of sources that deliver a range and encoder angles instead of SOCS
+    /// points. See the `polar` module.
+    pub polar: Option<PolarConfig>,
+    /// Corrections applied to each point's range, in the scanner's coordinate system.
+    pub range_correction: Option<RangeCorrectionConfig>,
+    /// Time values added to each laser point by return number, on top of `time_offset`.
+    ///
+    /// The first value is for first returns, the second for second returns, and so on. Used for
+    /// scanners that timestamp later returns with some latency. Returns past the end of the list,
+    /// and points without a return number, get no extra offset.
+    pub return_time_offsets: Option<Vec<f64>>,
     /// A mapping between the scanner's own coordinate frame and that of the IMU's.
     pub socs_map: SocsStringMap,
     /// The rotation order for our IMU.
     pub rotation_order: [String; 3],
+    /// The scanners on a multi-scanner rig, each of which can override the mounting above.
+    ///
+    /// Use `for_scanner` to get the configuration for one of them.
+    pub scanner: Option<Vec<ScannerConfig>>,
+    /// The number of worker threads used to georeference points.
+    ///
+    /// Defaults to one, which georeferences every point on the calling thread.
+    pub threads: Option<usize>,
+    /// The time systems of the points and the trajectory.
+    ///
+    /// If they differ, point times are converted before the time offset is applied.
+    pub time_system: Option<TimeSystemConfig>,
     /// A time value to apply to each laser point.
     ///
     /// Used if there is some skew between the laser and scanner clocks.
     pub time_offset: Option<f64>,
     /// The UTM zone of the output points.
+    ///
+    /// Only used when the output frame is UTM.
     pub utm_zone: u8,
+    /// Whether to write each point's scan angle, rounded to whole degrees, to its scan angle rank.
+    ///
+    /// Defaults to false, which passes the source's scan angle rank through.
+    pub write_scan_angle: Option<bool>,
     /// Limit the number of points written out.
     pub limit: Option<usize>,
 }
@@ -45,17 +205,40 @@ pub struct GeorefConfig {
 impl Default for GeorefConfig {
     fn default() -> GeorefConfig {
         GeorefConfig {
-            boresight: Rpy {
-                roll: 0.0,
-                pitch: 0.0,
-                yaw: 0.0,
-            },
+            boresight: Rpy::default(),
             chunk_size: None,
+            datum_shift: None,
+            ellipsoid: None,
+            error_policy: None,
+            filter: None,
+            frame: None,
+            geoid: None,
+            gimbal: None,
+            ground_scale: None,
+            heading_offset: None,
+            interpolation: None,
+            intrinsics: None,
             lever_arm: Vec3::new(0.0, 0.0, 0.0),
+            local_origin: None,
+            max_chunks_in_flight: None,
+            max_interpolation_gap: None,
+            mounting: None,
+            pose_interval: None,
+            orthophoto: None,
+            output_crs: None,
+            output_frame: None,
+            point_source_id: None,
+            polar: None,
+            range_correction: None,
+            return_time_offsets: None,
             rotation_order: Default::default(),
+            scanner: None,
             socs_map: Default::default(),
+            threads: None,
+            time_system: None,
             time_offset: None,
             utm_zone: 0,
+            write_scan_angle: None,
             limit: None,
         }
     }
@@ -66,32 +249,847 @@ impl GeorefConfig {
     pub fn from_toml(table: toml::Value) -> result::Result<GeorefConfig, toml::DecodeError> {
         GeorefConfig::decode(&mut toml::Decoder::new(table))
     }
+
+    /// Creates a new georef config from a JSON string.
+    ///
+    /// The JSON object has the same fields as the toml table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::GeorefConfig;
+    /// let config = GeorefConfig::from_json(r#"{
+    ///     "boresight": {"roll": 0.0, "pitch": 0.0, "yaw": 0.0},
+    ///     "lever_arm": {"x": 0.0, "y": 0.0, "z": 0.0},
+    ///     "socs_map": {"x": "x", "y": "y", "z": "z"},
+    ///     "rotation_order": ["r3(yaw)", "r2(pitch)", "r1(roll)"],
+    ///     "utm_zone": 6
+    /// }"#).unwrap();
+    /// assert_eq!(6, config.utm_zone);
+    /// ```
+    pub fn from_json(s: &str) -> result::Result<GeorefConfig, json::DecoderError> {
+        json::decode(s)
+    }
+
+    /// Creates a new georef config from a YAML string.
+    ///
+    /// The YAML mapping has the same fields as the toml table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::GeorefConfig;
+    /// let config = GeorefConfig::from_yaml("
+    /// boresight: {roll: 0.0, pitch: 0.0, yaw: 0.0}
+    /// lever_arm: {x: 0.0, y: 0.0, z: 0.0}
+    /// socs_map: {x: x, y: y, z: z}
+    /// rotation_order: [r3(yaw), r2(pitch), r1(roll)]
+    /// utm_zone: 6
+    /// ").unwrap();
+    /// assert_eq!(6, config.utm_zone);
+    /// ```
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(s: &str) -> Result<GeorefConfig> {
+        GeorefConfig::from_toml(toml::Value::Table(try!(yaml::to_table(s))))
+            .map_err(|err| Error::Yaml(err.to_string()))
+    }
+
+    /// Creates a new georef config from something that reads JSON.
+    pub fn from_json_reader<R: Read>(mut reader: R)
+                                     -> result::Result<GeorefConfig, json::DecoderError> {
+        let json = try!(Json::from_reader(&mut reader).map_err(json::DecoderError::ParseError));
+        GeorefConfig::decode(&mut json::Decoder::new(json))
+    }
+
+    /// Encodes this configuration as a toml table, the inverse of `from_toml`.
+    ///
+    /// Options that aren't set are left out, so they take their defaults when the table is read
+    /// back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::GeorefConfig;
+    /// let config = GeorefConfig { utm_zone: 6, ..Default::default() };
+    /// let table = config.to_toml();
+    /// assert_eq!(6, GeorefConfig::from_toml(table).unwrap().utm_zone);
+    /// ```
+    pub fn to_toml(&self) -> toml::Value {
+        toml::encode(self)
+    }
+
+    /// Returns the configuration for one scanner of a multi-scanner rig.
+    ///
+    /// Each mounting field that the scanner's table sets replaces the top-level one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::GeorefConfig;
+    /// use georef::georef::ScannerConfig;
+    /// let scanner = ScannerConfig { time_offset: Some(0.5), ..ScannerConfig::new("left") };
+    /// let config = GeorefConfig { scanner: Some(vec![scanner]), ..Default::default() };
+    /// assert_eq!(Some(0.5), config.for_scanner("left").unwrap().time_offset);
+    /// ```
+    pub fn for_scanner(mut self, id: &str) -> Result<GeorefConfig> {
+        let scanners = self.scanner.take().unwrap_or(Vec::new());
+        let scanner = match scanners.into_iter().find(|scanner| scanner.id == id) {
+            Some(scanner) => scanner,
+            None => return Err(Error::InvalidField("scanner", format!("no scanner has id {}", id))),
+        };
+        if let Some(boresight) = scanner.boresight {
+            self.boresight = boresight;
+        }
+        if let Some(lever_arm) = scanner.lever_arm {
+            self.lever_arm = lever_arm;
+        }
+        if scanner.mounting.is_some() {
+            self.mounting = scanner.mounting;
+        }
+        if let Some(socs_map) = scanner.socs_map {
+            self.socs_map = socs_map;
+        }
+        if scanner.time_offset.is_some() {
+            self.time_offset = scanner.time_offset;
+        }
+        if scanner.return_time_offsets.is_some() {
+            self.return_time_offsets = scanner.return_time_offsets;
+        }
+        if scanner.intrinsics.is_some() {
+            self.intrinsics = scanner.intrinsics;
+        }
+        if scanner.polar.is_some() {
+            self.polar = scanner.polar;
+        }
+        Ok(self)
+    }
+
+    /// Expands `${VAR}` environment variables in this configuration's file paths, i.e. the
+    /// geoid, the orthophoto, and the leap second table, and makes relative paths relative to a
+    /// directory, usually the config file's, so configs can move between machines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::env;
+    /// use std::path::Path;
+    /// use georef::GeorefConfig;
+    /// env::set_var("GEOREF_EXAMPLE_GEOIDS", "/data/geoids");
+    /// let mut config = GeorefConfig {
+    ///     geoid: Some("${GEOREF_EXAMPLE_GEOIDS}/egm96.grd".to_string()),
+    ///     orthophoto: Some("ortho.tif".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// config.resolve_paths(Path::new("/projects/flight1")).unwrap();
+    /// assert_eq!(Path::new("/data/geoids/egm96.grd"),
+    ///            Path::new(config.geoid.as_ref().unwrap()));
+    /// assert_eq!(Path::new("/projects/flight1/ortho.tif"),
+    ///            Path::new(config.orthophoto.as_ref().unwrap()));
+    /// ```
+    pub fn resolve_paths(&mut self, base: &Path) -> Result<()> {
+        try!(resolve_path("geoid", &mut self.geoid, base));
+        try!(resolve_path("orthophoto", &mut self.orthophoto, base));
+        if let Some(ref mut gimbal) = self.gimbal {
+            let mut path = Some(gimbal.path.clone());
+            try!(resolve_path("gimbal", &mut path, base));
+            if let Some(path) = path {
+                gimbal.path = path;
+            }
+        }
+        if let Some(ref mut time_system) = self.time_system {
+            try!(resolve_path("time_system", &mut time_system.leap_seconds_file, base));
+        }
+        Ok(())
+    }
+
+    /// Returns the ids of this configuration's scanners, in order.
+    pub fn scanner_ids(&self) -> Vec<String> {
+        self.scanner
+            .as_ref()
+            .map_or(Vec::new(), |scanners| scanners.iter().map(|s| s.id.clone()).collect())
+    }
+
+    /// Checks every field and returns all of the problems found, or an empty vector if the
+    /// configuration is valid.
+    ///
+    /// This catches mistakes before any data is read. The geoid and orthophoto files are not
+    /// opened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::GeorefConfig;
+    /// let errors = GeorefConfig::default().validate();
+    /// for error in &errors {
+    ///     println!("{}", error);
+    /// }
+    /// assert!(!errors.is_empty());
+    /// ```
+    pub fn validate(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        let invalid = |field, message: &str| Error::InvalidField(field, message.to_string());
+        let output_frame = match (&self.output_crs, &self.output_frame) {
+            (&Some(_), &Some(_)) => {
+                errors.push(invalid("output_crs", "cannot be combined with output_frame"));
+                None
+            }
+            (&Some(ref crs), &None) => {
+                match parse_crs(crs) {
+                    Ok((output_frame, Some(_))) => Some((output_frame, true)),
+                    Ok((output_frame, None)) => Some((output_frame, false)),
+                    Err(err) => {
+                        errors.push(Error::InvalidField("output_crs", err.to_string()));
+                        None
+                    }
+                }
+            }
+            (&None, &Some(ref output_frame)) => {
+                match output_frame.parse::<OutputFrame>() {
+                    Ok(output_frame) => Some((output_frame, false)),
+                    Err(err) => {
+                        errors.push(Error::InvalidField("output_frame", err.to_string()));
+                        None
+                    }
+                }
+            }
+            (&None, &None) => Some((OutputFrame::Utm, false)),
+        };
+        if let Some((output_frame, zone_from_crs)) = output_frame {
+            if output_frame == OutputFrame::Utm && !zone_from_crs &&
+               (self.utm_zone < 1 || self.utm_zone > 60) {
+                errors.push(Error::InvalidField("utm_zone",
+                                                format!("{} is not between 1 and 60",
+                                                        self.utm_zone)));
+            }
+            if output_frame == OutputFrame::Ecef && self.geoid.is_some() {
+                errors.push(invalid("geoid", "cannot be used with ECEF output"));
+            }
+            if output_frame == OutputFrame::Local {
+                if self.geoid.is_some() {
+                    errors.push(invalid("geoid", "cannot be used with local output"));
+                }
+                if let Err(err) = local_frame(self.local_origin, Ellipsoid::default()) {
+                    errors.push(err);
+                }
+            }
+            if output_frame != OutputFrame::Utm && self.ground_scale.is_some() {
+                errors.push(invalid("ground_scale", "can only be used with UTM output"));
+            }
+            if output_frame == OutputFrame::Ecef && self.orthophoto.is_some() {
+                errors.push(invalid("orthophoto", "cannot be used with ECEF output"));
+            }
+            if output_frame.is_platform() {
+                if self.geoid.is_some() {
+                    errors.push(invalid("geoid", "cannot be used with sensor or body output"));
+                }
+                if self.orthophoto.is_some() {
+                    errors.push(invalid("orthophoto", "cannot be used with sensor or body output"));
+                }
+            }
+        }
+        if let Some(ref error_policy) = self.error_policy {
+            if let Err(err) = error_policy.parse::<ErrorPolicy>() {
+                errors.push(Error::InvalidField("error_policy", err.to_string()));
+            }
+        }
+        if let Some(ref frame) = self.frame {
+            if let Err(err) = frame.parse::<NavigationFrame>() {
+                errors.push(Error::InvalidField("frame", err.to_string()));
+            }
+        }
+        if let Some(ref interpolation) = self.interpolation {
+            if let Err(err) = interpolation.to_interpolation() {
+                errors.push(Error::InvalidField("interpolation", err.to_string()));
+            }
+        }
+        {
+            let order = &self.rotation_order;
+            if let Err(err) = RotationOrder::new(&order[0], &order[1], &order[2]) {
+                errors.push(Error::InvalidField("rotation_order", err.to_string()));
+            }
+        }
+        self.validate_mounting("", &mut errors);
+        if let Some(ref filter) = self.filter {
+            for problem in filter.problems() {
+                errors.push(Error::InvalidField("filter", problem));
+            }
+        }
+        if let Some(ref datum_shift) = self.datum_shift {
+            for problem in datum_shift.problems() {
+                errors.push(Error::InvalidField("datum_shift", problem));
+            }
+        }
+        if let Some(ref ellipsoid) = self.ellipsoid {
+            for problem in ellipsoid.problems() {
+                errors.push(Error::InvalidField("ellipsoid", problem));
+            }
+        }
+        if let Some(ref gimbal) = self.gimbal {
+            if gimbal.path.is_empty() {
+                errors.push(invalid("gimbal", "path must not be empty"));
+            }
+            match gimbal.angle_units.as_ref().map(|s| s.as_ref()) {
+                None | Some("degrees") | Some("radians") => {}
+                Some(s) => {
+                    errors.push(Error::InvalidField("gimbal",
+                                                    format!("unknown angle units: {}", s)))
+                }
+            }
+        }
+        if let Some(ref ground_scale) = self.ground_scale {
+            for problem in ground_scale.problems() {
+                errors.push(Error::InvalidField("ground_scale", problem));
+            }
+        }
+        if let Some(ref range_correction) = self.range_correction {
+            for problem in range_correction.problems() {
+                errors.push(Error::InvalidField("range_correction", problem));
+            }
+        }
+        if let Some(ref time_system) = self.time_system {
+            for problem in time_system.problems() {
+                errors.push(Error::InvalidField("time_system", problem));
+            }
+        }
+        if self.chunk_size == Some(0) {
+            errors.push(invalid("chunk_size", "must be greater than zero"));
+        }
+        if self.threads == Some(0) {
+            errors.push(invalid("threads", "must be greater than zero"));
+        }
+        if self.max_chunks_in_flight == Some(0) {
+            errors.push(invalid("max_chunks_in_flight", "must be greater than zero"));
+        }
+        if self.max_interpolation_gap.map_or(false, |max_gap| !(max_gap > 0.0)) {
+            errors.push(invalid("max_interpolation_gap", "must be greater than zero"));
+        }
+        if self.pose_interval.map_or(false, |interval| !(interval > 0.0 && interval.is_finite())) {
+            errors.push(invalid("pose_interval", "must be greater than zero"));
+        }
+        let mut ids = BTreeSet::new();
+        for id in self.scanner_ids() {
+            if !ids.insert(id.clone()) {
+                errors.push(Error::InvalidField("scanner",
+                                                format!("id {} is used more than once", id)));
+            } else if let Ok(config) = self.clone().for_scanner(&id) {
+                config.validate_mounting(&format!("scanner {}: ", id), &mut errors);
+            }
+        }
+        errors
+    }
+
+    /// Returns a message describing what's wrong with the mounting transform, if anything.
+    fn mounting_problem(&self) -> Option<&'static str> {
+        let mounting = match self.mounting {
+            Some(mounting) => mounting,
+            None => return None,
+        };
+        let map = &self.socs_map;
+        if !(self.boresight.is_identity() && self.lever_arm == Vec3::new(0.0, 0.0, 0.0) &&
+             (map.x.as_ref(), map.y.as_ref(), map.z.as_ref()) == ("x", "y", "z")) {
+            return Some("cannot be combined with a boresight, lever_arm, or socs_map");
+        }
+        transform_problem(mounting)
+    }
+
+    /// Checks the fields that describe how the scanner is mounted, prefixing each message.
+    fn validate_mounting(&self, prefix: &str, errors: &mut Vec<Error>) {
+        let invalid = |field, message: &str| {
+            Error::InvalidField(field, format!("{}{}", prefix, message))
+        };
+        let map = &self.socs_map;
+        match (socs_axis(&map.x), socs_axis(&map.y), socs_axis(&map.z)) {
+            (Some(x), Some(y), Some(z)) => {
+                let determinant = x.x * (y.y * z.z - y.z * z.y) - x.y * (y.x * z.z - y.z * z.x) +
+                                  x.z * (y.x * z.y - y.y * z.x);
+                if determinant.abs() < 0.5 {
+                    errors.push(invalid("socs_map", "axes must be distinct"));
+                }
+            }
+            _ => {
+                errors.push(invalid("socs_map",
+                                    &format!("axes must each be one of x, y, z, -x, -y, or -z, \
+                                              not ({}, {}, {})",
+                                             map.x,
+                                             map.y,
+                                             map.z)))
+            }
+        }
+        let lever_arm = self.lever_arm;
+        let length = (lever_arm.x * lever_arm.x + lever_arm.y * lever_arm.y +
+                      lever_arm.z * lever_arm.z)
+                         .sqrt();
+        if !(length < MAX_LEVER_ARM) {
+            errors.push(invalid("lever_arm",
+                                &format!("{} m is not a believable length", length)));
+        }
+        if let Some(problem) = self.boresight.problem() {
+            errors.push(invalid("boresight", problem));
+        }
+        if let Some(problem) = self.mounting_problem() {
+            errors.push(invalid("mounting", problem));
+        }
+        if self.time_offset.map_or(false, |time_offset| !time_offset.is_finite()) {
+            errors.push(invalid("time_offset", "must be finite"));
+        }
+        if self.heading_offset.map_or(false, |heading_offset| !heading_offset.is_finite()) {
+            errors.push(invalid("heading_offset", "must be finite"));
+        }
+        if let Some(ref return_time_offsets) = self.return_time_offsets {
+            if !return_time_offsets.iter().all(|time_offset| time_offset.is_finite()) {
+                errors.push(invalid("return_time_offsets", "must be finite"));
+            }
+        }
+        if let Some(ref intrinsics) = self.intrinsics {
+            for problem in intrinsics.problems() {
+                errors.push(invalid("intrinsics", &problem));
+            }
+        }
+        if let Some(ref polar) = self.polar {
+            for problem in polar.problems() {
+                errors.push(invalid("polar", &problem));
+            }
+        }
+    }
+}
+
+/// The mounting of one scanner on a multi-scanner rig.
+///
+/// Fields that aren't set are taken from the top-level configuration. In toml, scanners are an
+/// array of tables:
+///
+/// ```toml
+/// [[georef.scanner]]
+/// id = "left"
+/// lever_arm = { x = 0.1, y = -0.5, z = -0.2 }
+/// boresight = { roll = 0.0, pitch = 0.0, yaw = 0.0 }
+///
+/// [[georef.scanner]]
+/// id = "right"
+/// lever_arm = { x = 0.1, y = 0.5, z = -0.2 }
+/// time_offset = 0.001
+/// ```
+#[derive(Clone, Debug, RustcDecodable, RustcEncodable)]
+pub struct ScannerConfig {
+    /// The id that input files are mapped to.
+    pub id: String,
+    /// This scanner's boresight.
+    pub boresight: Option<Rpy>,
+    /// This scanner's lever arm.
+    pub lever_arm: Option<Vec3<f64>>,
+    /// This scanner's scanner-to-body transform.
+    pub mounting: Option<[f64; 16]>,
+    /// This scanner's SOCS map.
+    pub socs_map: Option<SocsStringMap>,
+    /// This scanner's time offset.
+    pub time_offset: Option<f64>,
+    /// This scanner's time offsets by return number.
+    pub return_time_offsets: Option<Vec<f64>>,
+    /// This scanner's intrinsic calibration.
+    pub intrinsics: Option<IntrinsicsConfig>,
+    /// This scanner's polar measurement geometry.
+    pub polar: Option<PolarConfig>,
+    /// The point source id given to this scanner's points when channels are merged. Defaults to
+    /// the scanner's one-based position in the array.
+    pub point_source_id: Option<u16>,
+}
+
+impl ScannerConfig {
+    /// Creates a scanner config that takes its whole mounting from the top-level configuration.
+    pub fn new(id: &str) -> ScannerConfig {
+        ScannerConfig {
+            id: id.to_string(),
+            boresight: None,
+            lever_arm: None,
+            mounting: None,
+            socs_map: None,
+            time_offset: None,
+            return_time_offsets: None,
+            intrinsics: None,
+            polar: None,
+            point_source_id: None,
+        }
+    }
+}
+
+/// Decodable interpolation options.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct InterpolationConfig {
+    /// How attitude is interpolated, either "linear" (the default), "slerp", or "hermite", which
+    /// uses the trajectory's attitude rates where it has them.
+    pub attitude: Option<String>,
+    /// How position is interpolated, either "linear" (the default) or "catmull-rom".
+    pub position: Option<String>,
+}
+
+impl InterpolationConfig {
+    fn to_interpolation(&self) -> Result<Interpolation> {
+        let mut interpolation = Interpolation::default();
+        if let Some(ref attitude) = self.attitude {
+            interpolation.attitude = try!(attitude.parse());
+        }
+        if let Some(ref position) = self.position {
+            interpolation.position = try!(position.parse());
+        }
+        Ok(interpolation)
+    }
+}
+
+/// What to do with points that fall outside of the IMU/GNSS records, or in a gap that is longer
+/// than the maximum interpolation gap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop georeferencing with an error.
+    Strict,
+    /// Skip the point and keep going.
+    Skip,
+    /// Use the first or last IMU/GNSS record, whichever is nearer in time.
+    ///
+    /// Only trajectories that report their time bounds can be clamped, so a point outside of one
+    /// that doesn't is an `error_policy` error rather than a silently strict one. Points in gaps
+    /// are not clamped, so they are errors under this policy.
+    ClampToNearest,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> ErrorPolicy {
+        ErrorPolicy::Strict
+    }
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<ErrorPolicy> {
+        match s {
+            "strict" => Ok(ErrorPolicy::Strict),
+            "skip" => Ok(ErrorPolicy::Skip),
+            "clamp" => Ok(ErrorPolicy::ClampToNearest),
+            _ => Err(Error::ParseErrorPolicy(s.to_string())),
+        }
+    }
+}
+
+/// Statistics about a georeferencing run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, RustcEncodable)]
+pub struct Summary {
+    /// The number of points written to the sink.
+    pub points_written: usize,
+    /// The number of points skipped because they were outside of the IMU/GNSS records or in a
+    /// gap.
+    pub points_skipped: usize,
+    /// The number of points skipped because they were outside of the IMU/GNSS records.
+    pub points_skipped_outside: usize,
+    /// The number of points skipped because they were in a gap in the IMU/GNSS records.
+    pub points_skipped_in_gaps: usize,
+    /// The number of points that were dropped by the filter.
+    pub points_filtered: usize,
+    /// The bounds of the points written, in the output frame, if any were written.
+    ///
+    /// When points are split by UTM zone, these bounds mix coordinates from every zone.
+    pub bounds: Option<Bounds>,
+    /// How long each stage of the run took.
+    pub metrics: Metrics,
+}
+
+impl Summary {
+    /// Returns the number of points read from the source that have been written, skipped, or
+    /// filtered.
+    pub fn points_processed(&self) -> usize {
+        self.points_written + self.points_skipped + self.points_filtered
+    }
+
+    /// Returns the number of points processed per second over the whole run, or `None` if the
+    /// run's duration wasn't measured.
+    pub fn points_per_second(&self) -> Option<f64> {
+        if self.metrics.total > 0.0 {
+            Some(self.points_processed() as f64 / self.metrics.total)
+        } else {
+            None
+        }
+    }
+}
+
+/// How long each stage of a georeferencing run took, in seconds.
+///
+/// Comparing the stages shows whether a run is bound by I/O, i.e. reading and writing, or by the
+/// CPU, i.e. interpolating and transforming. With more than one thread, `transform` is summed
+/// over the worker threads, so it can be longer than `total`, and it overlaps with the other
+/// stages. Writing only counts time spent in the sink, not waiting for workers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, RustcEncodable)]
+pub struct Metrics {
+    /// Reading chunks of points from the source.
+    pub read: f64,
+    /// Filtering points by source attributes and interpolating their poses.
+    pub interpolate: f64,
+    /// Transforming points into the output frame.
+    pub transform: f64,
+    /// Filtering transformed points and writing them to the sink.
+    pub write: f64,
+    /// The whole run.
+    pub total: f64,
+}
+
+/// The outcome of interpolating the IMU/GNSS record for a point.
+#[allow(variant_size_differences)]
+#[derive(Debug)]
+enum Interpolated {
+    /// The record at the point's time.
+    Pose(ImuGnssPoint),
+    /// The point is outside of the IMU/GNSS records and should be skipped.
+    Outside,
+    /// The point is in a gap in the IMU/GNSS records and should be skipped.
+    InGap,
+}
+
+/// The progress of a georeferencing run, reported after each chunk is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of points read from the source and written or skipped so far.
+    pub points_processed: usize,
+    /// The total number of points, if known.
+    pub points_total: Option<usize>,
+    /// The number of chunks written so far.
+    pub chunks_done: usize,
+}
+
+/// The bounding box of a set of points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, RustcEncodable)]
+pub struct Bounds {
+    /// The minimum x value.
+    pub min_x: f64,
+    /// The minimum y value.
+    pub min_y: f64,
+    /// The minimum z value.
+    pub min_z: f64,
+    /// The maximum x value.
+    pub max_x: f64,
+    /// The maximum y value.
+    pub max_y: f64,
+    /// The maximum z value.
+    pub max_z: f64,
+}
+
+impl Bounds {
+    /// Creates the bounds of a single point.
+    pub fn new(point: &pabst::Point) -> Bounds {
+        Bounds {
+            min_x: point.x,
+            min_y: point.y,
+            min_z: point.z,
+            max_x: point.x,
+            max_y: point.y,
+            max_z: point.z,
+        }
+    }
+
+    /// Grows these bounds to include a point.
+    pub fn grow(&mut self, point: &pabst::Point) {
+        self.min_x = self.min_x.min(point.x);
+        self.min_y = self.min_y.min(point.y);
+        self.min_z = self.min_z.min(point.z);
+        self.max_x = self.max_x.max(point.x);
+        self.max_y = self.max_y.max(point.y);
+        self.max_z = self.max_z.max(point.z);
+    }
+
+    /// Returns the number of columns and rows of square tiles, aligned to multiples of
+    /// `tile_size`, that these bounds touch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::Bounds;
+    /// let bounds = Bounds { min_x: 500.0, max_x: 2500.0, min_y: 0.0, max_y: 999.0,
+    ///                       ..Default::default() };
+    /// assert_eq!((3, 1), bounds.tiles(1000.0));
+    /// ```
+    pub fn tiles(&self, tile_size: f64) -> (usize, usize) {
+        let count = |min: f64, max: f64| {
+            ((max / tile_size).floor() - (min / tile_size).floor()) as usize + 1
+        };
+        (count(self.min_x, self.max_x), count(self.min_y, self.max_y))
+    }
+}
+
+/// Returns the seconds since an instant.
+fn seconds_since(start: Instant) -> f64 {
+    let duration = start.elapsed();
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+/// Grows optional bounds to include a point, creating them if necessary.
+fn include(bounds: &mut Option<Bounds>, point: &pabst::Point) {
+    match *bounds {
+        Some(ref mut bounds) => bounds.grow(point),
+        None => *bounds = Some(Bounds::new(point)),
+    }
+}
+
+/// What a georeferencing run would do, estimated from a sample of points.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DryRun {
+    /// The number of points in the source.
+    pub points: usize,
+    /// The number of points that were georeferenced as a sample.
+    pub points_sampled: usize,
+    /// The number of sampled points that the trajectory covers, i.e. that would be written.
+    pub points_covered: usize,
+    /// The bounds of the georeferenced sample, if any points were covered.
+    pub bounds: Option<Bounds>,
+}
+
+/// How much of a point cloud's time span a trajectory covers, estimated from a sample of points.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Coverage {
+    /// The number of points whose times were sampled.
+    pub points_sampled: usize,
+    /// The number of sampled points within the trajectory's time bounds.
+    pub points_covered: usize,
+    /// The earliest and latest sampled point times, after the time offset and any time system
+    /// conversion.
+    pub point_time_bounds: Option<(f64, f64)>,
+    /// The trajectory's time bounds, if known.
+    pub trajectory_time_bounds: Option<(f64, f64)>,
+}
+
+impl Coverage {
+    /// Returns the fraction of sampled points that are covered, or one if no points were sampled.
+    pub fn fraction(&self) -> f64 {
+        if self.points_sampled == 0 {
+            1.0
+        } else {
+            self.points_covered as f64 / self.points_sampled as f64
+        }
+    }
+}
+
+/// The origin of a local tangent plane.
+#[derive(Clone, Copy, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct LocalOrigin {
+    /// The geodetic latitude, in degrees.
+    pub latitude: f64,
+    /// The geodetic longitude, in degrees.
+    pub longitude: f64,
+    /// The ellipsoidal height, in meters.
+    pub height: f64,
+}
+
+/// Returns the local tangent plane at an origin on an ellipsoid, or an error if there is no
+/// usable origin.
+fn local_frame(origin: Option<LocalOrigin>, ellipsoid: Ellipsoid) -> Result<LocalFrame> {
+    let origin = try!(origin.ok_or(Error::InvalidField("local_origin",
+                                                       "is required for local output"
+                                                           .to_string())));
+    if !(origin.latitude.abs() <= 90.0 && origin.longitude.abs() <= 180.0 &&
+         origin.height.is_finite()) {
+        return Err(Error::InvalidField("local_origin",
+                                       format!("({}, {}, {}) is not a valid location",
+                                               origin.latitude,
+                                               origin.longitude,
+                                               origin.height)));
+    }
+    Ok(LocalFrame::new(origin.latitude.to_radians(),
+                       origin.longitude.to_radians(),
+                       origin.height,
+                       ellipsoid))
 }
 
-/// Roll, pitch, and yaw.
-#[derive(Clone, Copy, Debug, Default, RustcDecodable)]
+/// Roll, pitch, and yaw, or a rotation matrix.
+///
+/// A rotation matrix is given as nine numbers in row-major order, e.g. from a calibration report,
+/// and is used as is, without the rotation order. It must be orthonormal with a determinant of
+/// one. In toml, either `{ roll = 0.0, pitch = 0.0, yaw = 0.0 }` or `{ matrix = [...] }`.
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Rpy {
     roll: f64,
     pitch: f64,
     yaw: f64,
+    matrix: Option<[f64; 9]>,
 }
 
 impl Rpy {
     /// Converts this roll, pitch, and yaw into a rotation matrix.
     pub fn into_rot3(self, rotation_order: &RotationOrder) -> Rot3<f64> {
-        rotation_order.rot3(self.roll, self.pitch, self.yaw)
+        match self.matrix {
+            Some(m) => rotation_from_rows(m),
+            None => rotation_order.rot3(self.roll, self.pitch, self.yaw),
+        }
+    }
+
+    /// Returns true if this is no rotation at all.
+    fn is_identity(&self) -> bool {
+        match self.matrix {
+            Some(m) => m == [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            None => self.roll == 0.0 && self.pitch == 0.0 && self.yaw == 0.0,
+        }
+    }
+
+    /// Returns a message describing what's wrong with these angles or this matrix, if anything.
+    fn problem(&self) -> Option<&'static str> {
+        match self.matrix {
+            Some(m) => rotation_problem(m),
+            None => {
+                if self.roll.is_finite() && self.pitch.is_finite() && self.yaw.is_finite() {
+                    None
+                } else {
+                    Some("angles must be finite")
+                }
+            }
+        }
+    }
+}
+
+impl Decodable for Rpy {
+    fn decode<D: Decoder>(d: &mut D) -> result::Result<Rpy, D::Error> {
+        d.read_struct("Rpy", 4, |d| {
+            let roll = try!(d.read_struct_field("roll", 0, Option::<f64>::decode));
+            let pitch = try!(d.read_struct_field("pitch", 1, Option::<f64>::decode));
+            let yaw = try!(d.read_struct_field("yaw", 2, Option::<f64>::decode));
+            let matrix = try!(d.read_struct_field("matrix", 3, Option::<[f64; 9]>::decode));
+            match (roll, pitch, yaw, matrix) {
+                (Some(roll), Some(pitch), Some(yaw), None) => {
+                    Ok(Rpy {
+                        roll: roll,
+                        pitch: pitch,
+                        yaw: yaw,
+                        matrix: None,
+                    })
+                }
+                (None, None, None, Some(matrix)) => {
+                    Ok(Rpy { matrix: Some(matrix), ..Default::default() })
+                }
+                _ => Err(d.error("expected either roll, pitch, and yaw, or a matrix")),
+            }
+        })
+    }
+}
+
+impl Encodable for Rpy {
+    fn encode<S: Encoder>(&self, s: &mut S) -> result::Result<(), S::Error> {
+        match self.matrix {
+            Some(ref matrix) => {
+                s.emit_struct("Rpy", 1, |s| s.emit_struct_field("matrix", 0, |s| matrix.encode(s)))
+            }
+            None => {
+                s.emit_struct("Rpy", 3, |s| {
+                    try!(s.emit_struct_field("roll", 0, |s| self.roll.encode(s)));
+                    try!(s.emit_struct_field("pitch", 1, |s| self.pitch.encode(s)));
+                    s.emit_struct_field("yaw", 2, |s| self.yaw.encode(s))
+                })
+            }
+        }
     }
 }
 
 /// A mapping between the scanner's own coordinate frame and the IMU's that's easy to decode.
-#[derive(Debug, Default, RustcDecodable)]
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
 pub struct SocsStringMap {
     x: String,
     y: String,
     z: String,
 }
 
-#[derive(Debug, RustcDecodable)]
+#[derive(Clone, Debug, RustcDecodable)]
 struct SocsMap {
     rotation_matrix: Rot3<f64>,
 }
@@ -100,37 +1098,153 @@ impl SocsMap {
     fn new(map: SocsStringMap) -> Result<SocsMap> {
         let mut rot = Rot3::new_identity(3);
         for (i, s) in vec![map.x, map.y, map.z].iter().enumerate() {
-            rot.set_col(i,
-                        match s.as_ref() {
-                            "x" => Vec3::x(),
-                            "-x" => -Vec3::x(),
-                            "y" => Vec3::y(),
-                            "-y" => -Vec3::y(),
-                            "z" => Vec3::z(),
-                            "-z" => -Vec3::z(),
-                            _ => return Err(Error::SocsMap(s.clone())),
-                        });
+            rot.set_col(i, try!(socs_axis(s).ok_or(Error::SocsMap(s.clone()))));
         }
         Ok(SocsMap { rotation_matrix: rot })
     }
 
-    fn vec3(&self, point: &pabst::Point) -> Vec3<f64> {
-        Vec3::new(point.x, point.y, point.z) * self.rotation_matrix
+    fn rotate(&self, socs: Vec3<f64>) -> Vec3<f64> {
+        socs * self.rotation_matrix
     }
 }
 
+/// Expands the environment variables in a path and joins it onto a base directory, unless it's
+/// absolute.
+fn resolve_path(field: &'static str, path: &mut Option<String>, base: &Path) -> Result<()> {
+    let resolved = match *path {
+        Some(ref path) => {
+            let mut expanded = String::new();
+            let mut rest = &path[..];
+            while let Some(start) = rest.find("${") {
+                expanded.push_str(&rest[..start]);
+                let end = try!(rest[start..].find('}').ok_or_else(|| {
+                    Error::InvalidField(field, format!("unclosed ${{ in {}", path))
+                }));
+                let name = &rest[start + 2..start + end];
+                expanded.push_str(&try!(env::var(name).map_err(|_| {
+                    Error::InvalidField(field,
+                                        format!("environment variable {} is not set", name))
+                })));
+                rest = &rest[start + end + 1..];
+            }
+            expanded.push_str(rest);
+            base.join(expanded).to_string_lossy().into_owned()
+        }
+        None => return Ok(()),
+    };
+    *path = Some(resolved);
+    Ok(())
+}
 
-/// A configurable structure for georeferencing points.
-#[derive(Debug)]
-pub struct Georeferencer {
+/// Builds a rotation from nine numbers in row-major order.
+fn rotation_from_rows(m: [f64; 9]) -> Rot3<f64> {
+    let mut rot3 = Rot3::new_identity(3);
+    for i in 0..3 {
+        rot3.set_col(i, Vec3::new(m[i], m[3 + i], m[6 + i]));
+    }
+    rot3
+}
+
+/// Returns a message describing why nine numbers in row-major order aren't a rotation, if they
+/// aren't.
+fn rotation_problem(m: [f64; 9]) -> Option<&'static str> {
+    if m.iter().any(|v| !v.is_finite()) {
+        return Some("matrix elements must be finite");
+    }
+    let row = |i: usize| [m[3 * i], m[3 * i + 1], m[3 * i + 2]];
+    let dot = |a: [f64; 3], b: [f64; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    for i in 0..3 {
+        for j in 0..3 {
+            let expected = if i == j { 1.0 } else { 0.0 };
+            if (dot(row(i), row(j)) - expected).abs() > ORTHONORMAL_TOLERANCE {
+                return Some("matrix must be orthonormal");
+            }
+        }
+    }
+    let (a, b, c) = (row(0), row(1), row(2));
+    let determinant = a[0] * (b[1] * c[2] - b[2] * c[1]) - a[1] * (b[0] * c[2] - b[2] * c[0]) +
+                      a[2] * (b[0] * c[1] - b[1] * c[0]);
+    if determinant < 0.0 {
+        return Some("matrix must be a rotation, not a reflection");
+    }
+    None
+}
+
+/// Splits a row-major homogeneous transform into its rotation, in row-major order, and its
+/// translation.
+fn split_transform(m: [f64; 16]) -> ([f64; 9], Vec3<f64>) {
+    ([m[0], m[1], m[2], m[4], m[5], m[6], m[8], m[9], m[10]], Vec3::new(m[3], m[7], m[11]))
+}
+
+/// Returns a message describing why sixteen numbers in row-major order aren't a rigid
+/// homogeneous transform, if they aren't.
+fn transform_problem(m: [f64; 16]) -> Option<&'static str> {
+    if m.iter().any(|v| !v.is_finite()) {
+        return Some("matrix elements must be finite");
+    }
+    if m[12..] != [0.0, 0.0, 0.0, 1.0] {
+        return Some("last row must be 0, 0, 0, 1");
+    }
+    rotation_problem(split_transform(m).0)
+}
+
+/// Returns the unit vector for a SOCS axis string, e.g. "-z".
+fn socs_axis(s: &str) -> Option<Vec3<f64>> {
+    match s {
+        "x" => Some(Vec3::x()),
+        "-x" => Some(-Vec3::x()),
+        "y" => Some(Vec3::y()),
+        "-y" => Some(-Vec3::y()),
+        "z" => Some(Vec3::z()),
+        "-z" => Some(-Vec3::z()),
+        _ => None,
+    }
+}
+
+/// A configurable structure for georeferencing points.
+#[derive(Clone, Debug)]
+pub struct Georeferencer {
     boresight_matrix: Rot3<f64>,
     chunk_size: usize,
+    config: GeorefConfig,
+    datum_shift: Option<Helmert>,
+    ellipsoid: Ellipsoid,
+    error_policy: ErrorPolicy,
+    filter: Filter,
+    flight_lines: Vec<FlightLine>,
+    frame: NavigationFrame,
+    geoid: Option<Arc<Geoid>>,
+    gimbal: Option<Arc<Gimbal>>,
+    ground_scale: Option<GroundScale>,
+    heading_offset: f64,
+    interpolation: Interpolation,
+    intrinsics: Option<Intrinsics>,
     lever_arm: Vec3<f64>,
     limit: Option<usize>,
+    local_frame: Option<LocalFrame>,
+    max_chunks_in_flight: usize,
+    orthophoto: Option<Arc<Orthophoto>>,
+    output_frame: OutputFrame,
+    point_source_id: Option<u16>,
+    polar: Option<Polar>,
+    pose_interval: Option<f64>,
+    range_correction: Option<RangeCorrection>,
+    return_time_offsets: Vec<f64>,
     rotation_order: RotationOrder,
+    scanners: Vec<Scanner>,
     socs_map: SocsMap,
+    threads: usize,
     time_offset: f64,
     utm_zone: u8,
+    write_scan_angle: bool,
+}
+
+/// One scanner of a multi-scanner rig.
+#[derive(Clone, Debug)]
+struct Scanner {
+    id: String,
+    point_source_id: u16,
+    georeferencer: Georeferencer,
 }
 
 impl Georeferencer {
@@ -144,63 +1258,2965 @@ impl Georeferencer {
     /// let georeferencer = Georeferencer::new(config);
     /// ```
     pub fn new(config: GeorefConfig) -> Result<Georeferencer> {
+        let mut scanners = Vec::new();
+        if let Some(ref scanner_configs) = config.scanner {
+            for (i, scanner) in scanner_configs.iter().enumerate() {
+                scanners.push(Scanner {
+                    id: scanner.id.clone(),
+                    point_source_id: scanner.point_source_id.unwrap_or(i as u16 + 1),
+                    georeferencer: try!(Georeferencer::new(try!(config.clone()
+                                                                      .for_scanner(&scanner.id)))),
+                });
+            }
+        }
         let rotation_order = try!(RotationOrder::new(config.rotation_order[0].as_ref(),
                                                      config.rotation_order[1].as_ref(),
                                                      config.rotation_order[2].as_ref()));
+        if let Some(problem) = config.boresight.problem() {
+            return Err(Error::InvalidField("boresight", problem.to_string()));
+        }
+        if let Some(problem) = config.mounting_problem() {
+            return Err(Error::InvalidField("mounting", problem.to_string()));
+        }
+        let (boresight_matrix, lever_arm) = match config.mounting {
+            Some(mounting) => {
+                let (rotation, translation) = split_transform(mounting);
+                (rotation_from_rows(rotation), translation)
+            }
+            None => (config.boresight.into_rot3(&rotation_order), config.lever_arm),
+        };
+        let (output_frame, utm_zone) = match (config.output_crs, config.output_frame) {
+            (Some(_), Some(_)) => {
+                return Err(Error::InvalidField("output_crs",
+                                               "cannot be combined with output_frame"
+                                                   .to_string()))
+            }
+            (Some(crs), None) => {
+                let (output_frame, utm_zone) = try!(parse_crs(&crs));
+                (output_frame, utm_zone.unwrap_or(config.utm_zone))
+            }
+            (None, Some(output_frame)) => (try!(output_frame.parse()), config.utm_zone),
+            (None, None) => (OutputFrame::Utm, config.utm_zone),
+        };
+        let ellipsoid = match config.ellipsoid {
+            Some(ref ellipsoid) => try!(Ellipsoid::new(ellipsoid)),
+            None => Ellipsoid::default(),
+        };
+        let local_frame = if output_frame == OutputFrame::Local {
+            Some(try!(local_frame(config.local_origin, ellipsoid)))
+        } else {
+            None
+        };
+        let mut interpolation = match config.interpolation {
+            Some(ref interpolation) => try!(interpolation.to_interpolation()),
+            None => Interpolation::default(),
+        };
+        if config.max_interpolation_gap.map_or(false, |max_gap| !(max_gap > 0.0)) {
+            return Err(Error::InvalidField("max_interpolation_gap",
+                                           "must be greater than zero".to_string()));
+        }
+        interpolation.max_gap = config.max_interpolation_gap;
+        interpolation.rotation_order = rotation_order;
+        let heading_offset = config.heading_offset.unwrap_or(0.0);
+        if !heading_offset.is_finite() {
+            return Err(Error::InvalidField("heading_offset", "must be finite".to_string()));
+        }
+        let filter = match config.filter {
+            Some(ref filter) => try!(Filter::new(filter)),
+            None => Filter::default(),
+        };
+        let intrinsics = match config.intrinsics {
+            Some(ref intrinsics) => Some(try!(Intrinsics::new(intrinsics))),
+            None => None,
+        };
+        let polar = match config.polar {
+            Some(ref polar) => Some(try!(Polar::new(polar))),
+            None => None,
+        };
+        let range_correction = match config.range_correction {
+            Some(ref range_correction) => Some(try!(RangeCorrection::new(range_correction))),
+            None => None,
+        };
+        let geoid = match config.geoid {
+            Some(_) if output_frame == OutputFrame::Ecef => {
+                return Err(Error::InvalidField("geoid",
+                                               "cannot be used with ECEF output".to_string()))
+            }
+            Some(_) if output_frame == OutputFrame::Local => {
+                return Err(Error::InvalidField("geoid",
+                                               "cannot be used with local output".to_string()))
+            }
+            Some(_) if output_frame.is_platform() => {
+                return Err(Error::InvalidField("geoid",
+                                               "cannot be used with sensor or body output"
+                                                   .to_string()))
+            }
+            Some(ref path) => Some(Arc::new(try!(Geoid::from_path(path)))),
+            None => None,
+        };
+        let gimbal = match config.gimbal {
+            Some(ref gimbal) => Some(Arc::new(try!(Gimbal::new(gimbal)))),
+            None => None,
+        };
+        let ground_scale = match config.ground_scale {
+            Some(_) if output_frame != OutputFrame::Utm => {
+                return Err(Error::InvalidField("ground_scale",
+                                               "can only be used with UTM output".to_string()))
+            }
+            Some(ref ground_scale) => {
+                Some(try!(GroundScale::new(ground_scale, utm_zone, ellipsoid)))
+            }
+            None => None,
+        };
+        let time_system_offset = match config.time_system {
+            Some(ref time_system) => try!(time_system.offset()),
+            None => 0.0,
+        };
+        if output_frame == OutputFrame::Utm {
+            debug!("projecting into UTM zone {}", utm_zone);
+        }
+        let orthophoto = match config.orthophoto {
+            Some(_) if output_frame == OutputFrame::Ecef => {
+                return Err(Error::InvalidField("orthophoto",
+                                               "cannot be used with ECEF output".to_string()))
+            }
+            Some(_) if output_frame.is_platform() => {
+                return Err(Error::InvalidField("orthophoto",
+                                               "cannot be used with sensor or body output"
+                                                   .to_string()))
+            }
+            Some(ref path) => Some(Arc::new(try!(Orthophoto::from_path(path)))),
+            None => None,
+        };
         Ok(Georeferencer {
-            boresight_matrix: rotation_order.rot3(config.boresight.roll,
-                                                  config.boresight.pitch,
-                                                  config.boresight.yaw),
+            boresight_matrix: boresight_matrix,
             chunk_size: config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE),
-            lever_arm: config.lever_arm,
+            config: config.clone(),
+            datum_shift: match config.datum_shift {
+                Some(ref datum_shift) => Some(try!(Helmert::new(datum_shift))),
+                None => None,
+            },
+            ellipsoid: ellipsoid,
+            error_policy: match config.error_policy {
+                Some(ref error_policy) => try!(error_policy.parse()),
+                None => Default::default(),
+            },
+            filter: filter,
+            flight_lines: Vec::new(),
+            frame: match config.frame {
+                Some(ref frame) => try!(frame.parse()),
+                None => Default::default(),
+            },
+            geoid: geoid,
+            gimbal: gimbal,
+            ground_scale: ground_scale,
+            heading_offset: heading_offset.to_radians(),
+            interpolation: interpolation,
+            intrinsics: intrinsics,
+            lever_arm: lever_arm,
             limit: config.limit,
+            local_frame: local_frame,
+            max_chunks_in_flight: config.max_chunks_in_flight
+                                        .unwrap_or(2 * config.threads.unwrap_or(1)),
+            orthophoto: orthophoto,
+            output_frame: output_frame,
+            point_source_id: config.point_source_id,
+            polar: polar,
+            pose_interval: config.pose_interval,
+            range_correction: range_correction,
+            return_time_offsets: config.return_time_offsets.clone().unwrap_or(Vec::new()),
             rotation_order: rotation_order,
+            scanners: scanners,
             socs_map: try!(SocsMap::new(config.socs_map)),
-            time_offset: config.time_offset.unwrap_or(0.0),
-            utm_zone: config.utm_zone,
+            threads: config.threads.unwrap_or(1),
+            time_offset: config.time_offset.unwrap_or(0.0) + time_system_offset,
+            utm_zone: utm_zone,
+            write_scan_angle: config.write_scan_angle.unwrap_or(false),
         })
     }
 
+    /// Sets the point source id written to every point, or `None` to keep the source's.
+    ///
+    /// This also sets the point source id of every scanner's georeferencer, which is handy for
+    /// numbering the files of a batch. Points merged from several channels still get their
+    /// scanner's point source id.
+    pub fn set_point_source_id(&mut self, point_source_id: Option<u16>) {
+        self.point_source_id = point_source_id;
+        for scanner in &mut self.scanners {
+            scanner.georeferencer.set_point_source_id(point_source_id);
+        }
+    }
+
+    /// Sets the flight lines used to tag and split points.
+    ///
+    /// Once set, each point in a line gets the line's number as its point source id, unless a
+    /// point source id is configured. Lines usually come from `Trajectory::flight_lines`. This
+    /// also sets the flight lines of every scanner's georeferencer.
+    pub fn set_flight_lines(&mut self, flight_lines: Vec<FlightLine>) {
+        for scanner in &mut self.scanners {
+            scanner.georeferencer.set_flight_lines(flight_lines.clone());
+        }
+        self.flight_lines = flight_lines;
+    }
+
+    /// Projects a trajectory's records into this georeferencer's UTM zone, if that's where points
+    /// are going.
+    ///
+    /// Points are then placed with interpolated UTM positions instead of being projected one at a
+    /// time. See `ImuGnss::project_utm`. Trajectories are left alone for other output frames, or
+    /// when there's a datum shift, since those points are projected after leaving ECEF.
+    pub fn project_trajectory(&self, imu_gnss: &mut ImuGnss) {
+        if self.output_frame == OutputFrame::Utm && self.datum_shift.is_none() {
+            debug!("projecting {} trajectory records into UTM zone {}",
+                   imu_gnss.len(),
+                   self.utm_zone);
+            imu_gnss.project_utm(self.utm_zone, self.ellipsoid);
+        }
+    }
+
+    /// Returns the georeferencer for one scanner of a multi-scanner rig, if the configuration
+    /// has a scanner with that id.
+    pub fn scanner(&self, id: &str) -> Option<&Georeferencer> {
+        self.scanners
+            .iter()
+            .find(|scanner| scanner.id == id)
+            .map(|scanner| &scanner.georeferencer)
+    }
+
+    /// Returns the combined grid-to-ground factor applied to UTM output, if any.
+    pub fn ground_scale_factor(&self) -> Option<f64> {
+        self.ground_scale.map(|ground_scale| ground_scale.factor())
+    }
+
+    /// Returns the configuration this georeferencer was built from.
+    ///
+    /// Changes made afterwards, e.g. with `set_flight_lines`, aren't reflected.
+    pub fn config(&self) -> &GeorefConfig {
+        &self.config
+    }
+
+    /// Returns the number of points read from the source at a time.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns the most chunks that may be in flight between reading and writing.
+    pub fn max_chunks_in_flight(&self) -> usize {
+        self.max_chunks_in_flight
+    }
+
+    /// Returns the OGC well-known text of the output coordinate reference system.
+    ///
+    /// Returns `None` if the output can't be described as one of the WGS84 systems that the WKT
+    /// names, i.e. UTM, geographic, or ECEF output on another ellipsoid, through a datum shift, or
+    /// scaled to the ground. Set the WKT yourself in those cases.
+    pub fn output_wkt(&self) -> Option<String> {
+        self.output_wkt_in_zone(self.utm_zone)
+    }
+
+    /// Returns the OGC well-known text of the output coordinate reference system in a UTM zone,
+    /// for points that are split by zone.
+    ///
+    /// See `output_wkt` for when this is `None`.
+    pub fn output_wkt_in_zone(&self, utm_zone: u8) -> Option<String> {
+        match self.output_frame {
+            OutputFrame::Utm | OutputFrame::Ecef | OutputFrame::Geographic => {
+                if self.ellipsoid != Ellipsoid::wgs84() || self.datum_shift.is_some() ||
+                   self.ground_scale.is_some() {
+                    return None;
+                }
+            }
+            _ => {}
+        }
+        let code = self.config.output_crs.as_ref().and_then(|crs| crs_code(crs));
+        match (self.output_frame, code) {
+            (OutputFrame::Geographic, Some(4979)) => Some(OutputFrame::geographic_3d_wkt()),
+            (OutputFrame::Utm, Some(32701...32760)) => {
+                Some(OutputFrame::southern_utm_wkt(utm_zone))
+            }
+            _ => Some(self.output_frame.wkt(utm_zone)),
+        }
+    }
+
     /// Georeference a point cloud.
-    pub fn georeference(&self,
-                        source: &mut pabst::Source,
-                        interpolator: &mut pos::Interpolator,
-                        sink: &mut pabst::Sink)
-                        -> Result<()> {
-        let mut npoints = 0;
-        loop {
-            let points = match try!(source.source(self.chunk_size)) {
+    ///
+    /// The source and sink can be pabst's, or anything else that implements `PointSource` and
+    /// `PointSink`, e.g. a vector of points that's already in memory.
+    ///
+    /// If more than one thread is configured, chunks are transformed on a pool of worker threads
+    /// while interpolation and I/O stay on the calling thread, so reading, transforming, and
+    /// writing overlap. At most `max_chunks_in_flight` chunks, by default two per thread, are in
+    /// flight at once, so memory use stays flat no matter how fast the source is. Points are
+    /// always written to the sink in the order they were read from the source.
+    ///
+    /// Only each point's x, y, and z are changed. All other attributes are passed through to the
+    /// sink untouched, though what the sink does with them is up to its format.
+    pub fn georeference<S, K>(&self,
+                              source: &mut S,
+                              trajectory: &Trajectory,
+                              sink: &mut K)
+                              -> Result<Summary>
+        where S: PointSource + ?Sized,
+              K: PointSink + ?Sized
+    {
+        self.georeference_with_progress(source, trajectory, sink, None, |_| {})
+    }
+
+    /// Georeference a point cloud, calling `progress` after each chunk is written.
+    ///
+    /// `points_total` is passed through to the progress reports, e.g. from a file header, since
+    /// sources don't generally know how many points they hold.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate georef;
+    /// # extern crate pabst;
+    /// # use georef::{Georeferencer, ImuGnss};
+    /// # fn f(georeferencer: Georeferencer,
+    /// #      source: &mut pabst::Source,
+    /// #      imu_gnss: &mut ImuGnss,
+    /// #      sink: &mut pabst::Sink) {
+    /// let report = |progress: &georef::Progress| {
+    ///     println!("{} of 1000 points", progress.points_processed);
+    /// };
+    /// georeferencer.georeference_with_progress(source, imu_gnss, sink, Some(1000), report)
+    ///              .unwrap();
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn georeference_with_progress<S, K, F>(&self,
+                                               source: &mut S,
+                                               trajectory: &Trajectory,
+                                               sink: &mut K,
+                                               points_total: Option<usize>,
+                                               mut progress: F)
+                                               -> Result<Summary>
+        where S: PointSource + ?Sized,
+              K: PointSink + ?Sized,
+              F: FnMut(&Progress)
+    {
+        let mut report = Progress { points_total: points_total, ..Default::default() };
+        if self.threads > 1 {
+            return self.georeference_parallel(source, trajectory, sink, report, progress);
+        }
+        let start = Instant::now();
+        let mut summary = Summary::default();
+        while let Some(points) = try!(self.next_chunk(source, &mut summary)) {
+            let (mut points, imu_gnss_points) = try!(self.prepare_chunk(points,
+                                                                        trajectory,
+                                                                        0,
+                                                                        &mut summary));
+            self.transform_chunk(&mut points, &imu_gnss_points, &mut summary);
+            let write_start = Instant::now();
+            for (point, imu_gnss_point) in points.iter().zip(&imu_gnss_points) {
+                if self.keep(point, &mut summary) {
+                    try!(sink.sink_with_record(point, imu_gnss_point));
+                }
+            }
+            summary.metrics.write += seconds_since(write_start);
+            report.points_processed = summary.points_processed();
+            report.chunks_done += 1;
+            progress(&report);
+        }
+        summary.metrics.total = seconds_since(start);
+        Ok(summary)
+    }
+
+    /// Returns an iterator over a point cloud's georeferenced points.
+    ///
+    /// Chunks are read from the source as the iterator needs them, so points can be filtered or
+    /// analyzed as they're georeferenced without going through a sink. Points that are skipped
+    /// or filtered never come out of the iterator, and the first error ends it. Everything runs
+    /// on the calling thread, whatever the number of threads configured.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::{GeoreferencerBuilder, ImuGnss, ImuGnssPoint, Point};
+    /// let georeferencer = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+    /// let imu_gnss = ImuGnss::new(vec![ImuGnssPoint { time: 0.0, ..Default::default() },
+    ///                                  ImuGnssPoint { time: 1.0, ..Default::default() }])
+    ///                    .unwrap();
+    /// let mut source = vec![Point { gps_time: Some(0.5), ..Default::default() }];
+    /// for point in georeferencer.georeference_iter(&mut source, &imu_gnss) {
+    ///     println!("{}", point.unwrap().x);
+    /// }
+    /// ```
+    pub fn georeference_iter<'a, S>(&'a self,
+                                    source: &'a mut S,
+                                    trajectory: &'a Trajectory)
+                                    -> GeoreferenceIter<'a, S>
+        where S: PointSource + ?Sized
+    {
+        GeoreferenceIter {
+            georeferencer: self,
+            source: source,
+            trajectory: trajectory,
+            points: VecDeque::new(),
+            summary: Summary::default(),
+            done: false,
+        }
+    }
+
+    /// Georeference several sources against one trajectory into one sink, e.g. the channels of
+    /// a multi-scanner rig.
+    ///
+    /// Each source is paired with the id of one of the configuration's scanners. Its points are
+    /// georeferenced with that scanner's mounting and given that scanner's point source id.
+    /// Points are merged across the sources by time, so the trajectory is read in order and
+    /// time-ordered sources make for time-ordered output. Everything runs on the calling thread.
+    pub fn georeference_channels<S, K>(&self,
+                                       channels: &mut [(&mut S, &str)],
+                                       trajectory: &Trajectory,
+                                       sink: &mut K)
+                                       -> Result<Summary>
+        where S: PointSource + ?Sized,
+              K: PointSink + ?Sized
+    {
+        let start = Instant::now();
+        let mut scanners = Vec::with_capacity(channels.len());
+        for &(_, id) in channels.iter() {
+            let scanner = self.scanners.iter().find(|scanner| scanner.id == id);
+            scanners.push(try!(scanner.ok_or_else(|| {
+                Error::InvalidField("scanner", format!("no scanner has id {}", id))
+            })));
+        }
+        let mut buffers: Vec<VecDeque<pabst::Point>> = channels.iter()
+                                                               .map(|_| VecDeque::new())
+                                                               .collect();
+        let mut exhausted = vec![false; channels.len()];
+        let time = |point: &pabst::Point| point.gps_time.unwrap_or(f64::NEG_INFINITY);
+        let mut summary = Summary::default();
+        while !self.limit_reached(&summary, 0) {
+            for (i, channel) in channels.iter_mut().enumerate() {
+                while buffers[i].is_empty() && !exhausted[i] {
+                    match try!(self.next_chunk(&mut *channel.0, &mut summary)) {
+                        Some(points) => buffers[i].extend(points),
+                        None => exhausted[i] = true,
+                    }
+                }
+            }
+            let mut next: Option<usize> = None;
+            for (i, buffer) in buffers.iter().enumerate() {
+                if let Some(point) = buffer.front() {
+                    if next.map_or(true, |j| time(point) < time(&buffers[j][0])) {
+                        next = Some(i);
+                    }
+                }
+            }
+            let i = match next {
+                Some(i) => i,
+                None => break,
+            };
+            // Everything in this channel up to the other channels' earliest point is next, so it
+            // goes through interpolation and the transform as one chunk.
+            let until = buffers.iter()
+                               .enumerate()
+                               .filter(|&(j, _)| j != i)
+                               .filter_map(|(_, buffer)| buffer.front())
+                               .map(&time)
+                               .fold(f64::INFINITY, f64::min);
+            let n = buffers[i].iter()
+                              .take(self.chunk_size)
+                              .take_while(|point| time(point) <= until)
+                              .count();
+            let points = buffers[i].drain(..n).collect();
+            let scanner = scanners[i];
+            let (mut points, imu_gnss_points) =
+                try!(scanner.georeferencer.prepare_chunk(points, trajectory, 0, &mut summary));
+            scanner.georeferencer.transform_chunk(&mut points, &imu_gnss_points, &mut summary);
+            let write_start = Instant::now();
+            for (point, imu_gnss_point) in points.iter_mut().zip(&imu_gnss_points) {
+                point.point_source_id = Some(scanner.point_source_id);
+                if scanner.georeferencer.keep(point, &mut summary) {
+                    try!(sink.sink_with_record(point, imu_gnss_point));
+                }
+            }
+            summary.metrics.write += seconds_since(write_start);
+        }
+        summary.metrics.total = seconds_since(start);
+        Ok(summary)
+    }
+
+    /// Georeference a single point.
+    ///
+    /// Returns false if the point was skipped because of the error policy, in which case it is
+    /// left untouched.
+    pub fn georeference_point(&self,
+                              point: &mut pabst::Point,
+                              trajectory: &Trajectory)
+                              -> Result<bool> {
+        match try!(self.pose(point, trajectory)) {
+            Interpolated::Pose(imu_gnss_point) => {
+                self.transform(point, &imu_gnss_point);
+                Ok(true)
+            }
+            Interpolated::Outside | Interpolated::InGap => Ok(false),
+        }
+    }
+
+    /// Georeference a single point with a pose that's already known, instead of interpolating one
+    /// from a trajectory.
+    ///
+    /// This is for simulations, tests, and poses computed elsewhere. The pose is used as is, at
+    /// the point's time, so neither the time offset nor the error policy applies. Its height is
+    /// ellipsoidal, as in a trajectory, so a configured geoid is still applied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate georef;
+    /// # extern crate pabst;
+    /// # fn main() {
+    /// use georef::{GeoreferencerBuilder, Pose};
+    /// let georeferencer = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+    /// let pose = Pose { latitude: 0.001, height: 100.0, ..Default::default() };
+    /// let mut point = pabst::Point { z: 5.0, gps_time: Some(0.5), ..Default::default() };
+    /// georeferencer.georeference_point_with_pose(&mut point, &pose).unwrap();
+    /// # }
+    /// ```
+    pub fn georeference_point_with_pose(&self,
+                                        point: &mut pabst::Point,
+                                        pose: &Pose)
+                                        -> Result<()> {
+        try!(self.check_geoid(pose));
+        self.transform(point, pose);
+        Ok(())
+    }
+
+    /// Georeference a slice of points in place.
+    ///
+    /// This is `georeference_point` for many points at once, without a source or sink, and with
+    /// the pose interval honored across the slice. Points that are skipped because of the error
+    /// policy are left untouched and counted in the summary. Neither the filter nor the limit is
+    /// applied, since points can't be removed from a slice, so every point that isn't skipped is
+    /// counted as written.
+    pub fn georeference_points(&self,
+                               points: &mut [pabst::Point],
+                               trajectory: &Trajectory)
+                               -> Result<Summary> {
+        let mut summary = Summary::default();
+        let mut cache = None;
+        for point in points.iter_mut() {
+            let interpolated = match self.pose_interval {
+                Some(interval) => {
+                    try!(self.pose_in_bucket(point, trajectory, interval, &mut cache))
+                }
+                None => try!(self.pose(point, trajectory)),
+            };
+            match interpolated {
+                Interpolated::Pose(imu_gnss_point) => {
+                    self.transform(point, &imu_gnss_point);
+                    include(&mut summary.bounds, point);
+                    summary.points_written += 1;
+                }
+                Interpolated::Outside => {
+                    summary.points_skipped += 1;
+                    summary.points_skipped_outside += 1;
+                }
+                Interpolated::InGap => {
+                    summary.points_skipped += 1;
+                    summary.points_skipped_in_gaps += 1;
+                }
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Maps a georeferenced coordinate back into the scanner's own coordinate system, e.g. for
+    /// simulating scans, computing residuals, or calibration loops.
+    ///
+    /// `world` is in the output frame and `time` is the point's gps time, before the time offset
+    /// is applied. The mounting, the trajectory, and the SOCS map are undone, but the intrinsic
+    /// calibration and range correction aren't, so the result is what the scanner measured after
+    /// them. The error policy isn't applied, so times outside of the trajectory are an error, and
+    /// so is a datum shift, which can't be undone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate georef;
+    /// # extern crate nalgebra;
+    /// # extern crate pabst;
+    /// # fn main() {
+    /// use georef::{GeoreferencerBuilder, ImuGnss, ImuGnssPoint};
+    /// use nalgebra::Vec3;
+    /// let georeferencer = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+    /// let imu_gnss = ImuGnss::new(vec![ImuGnssPoint { time: 0.0, ..Default::default() },
+    ///                                  ImuGnssPoint { time: 1.0, ..Default::default() }])
+    ///                    .unwrap();
+    /// let mut point = pabst::Point { x: 10.0, gps_time: Some(0.5), ..Default::default() };
+    /// georeferencer.georeference_point(&mut point, &imu_gnss).unwrap();
+    /// let socs = georeferencer.inverse_point(Vec3::new(point.x, point.y, point.z),
+    ///                                        0.5,
+    ///                                        &imu_gnss)
+    ///                         .unwrap();
+    /// assert!((socs.x - 10.0).abs() < 1e-6);
+    /// # }
+    /// ```
+    pub fn inverse_point(&self,
+                         world: Vec3<f64>,
+                         time: f64,
+                         trajectory: &Trajectory)
+                         -> Result<Vec3<f64>> {
+        if self.datum_shift.is_some() {
+            return Err(Error::InvalidField("datum_shift",
+                                           "cannot be undone by inverse georeferencing"
+                                               .to_string()));
+        }
+        let mut imu_gnss_point = try!(trajectory.pose_at(time + self.time_offset,
+                                                         &self.interpolation));
+        self.apply_heading_offset(&mut imu_gnss_point);
+        try!(self.check_geoid(&imu_gnss_point));
+        // Multiplying a vector by a rotation on the right applies the rotation's inverse.
+        let body = match self.output_frame {
+            OutputFrame::Sensor => world + self.lever_arm,
+            OutputFrame::Body => world,
+            OutputFrame::Utm => {
+                let p = self.ground_scale.map_or(world, |ground_scale| ground_scale.unapply(world));
+                let pos = UtmPoint::from_latlon(&imu_gnss_point, self.utm_zone, self.ellipsoid);
+                let mut offset = p - pos.location();
+                offset.z += self.undulation_at_offset(&imu_gnss_point, offset);
+                offset * pos.rotation_matrix(&self.rotation_order, self.frame)
+            }
+            _ => {
+                let p = match self.output_frame {
+                    OutputFrame::Geographic => {
+                        let (latitude, longitude) = (world.y.to_radians(), world.x.to_radians());
+                        let height = world.z +
+                                     self.undulation(&imu_gnss_point, latitude, longitude);
+                        let (x, y, z) = self.ellipsoid.to_ecef(latitude, longitude, height);
+                        Vec3::new(x, y, z)
+                    }
+                    OutputFrame::Local => {
+                        self.local_frame.map_or(world, |frame| frame.unproject(world))
+                    }
+                    _ => world,
+                };
+                let pos = EcefPoint::from_latlon(&imu_gnss_point, self.ellipsoid);
+                (p - pos.location()) * pos.rotation_matrix(&self.rotation_order, self.frame)
+            }
+        };
+        let sensor = match self.gimbal {
+            Some(ref gimbal) if self.output_frame != OutputFrame::Sensor => {
+                (body - self.lever_arm) * gimbal.rotation_at(imu_gnss_point.time)
+            }
+            _ => body - self.lever_arm,
+        };
+        let scanner = sensor * self.boresight_matrix;
+        Ok(self.socs_map.rotation_matrix * scanner)
+    }
+
+    /// Georeference a point cloud, projecting each point into its own UTM zone.
+    ///
+    /// A point's zone is the natural zone of the IMU/GNSS position at that point's time, so flights
+    /// that cross zone boundaries are never projected far outside of a zone. The configured UTM
+    /// zone is ignored. `open_sink` is called once for each zone the first time a point falls into
+    /// it, and the sinks are returned, ordered by zone, so they can be closed.
+    pub fn georeference_by_zone<S, K, F>(&self,
+                                         source: &mut S,
+                                         trajectory: &Trajectory,
+                                         open_sink: F)
+                                         -> Result<(Vec<(u8, Box<K>)>, Summary)>
+        where S: PointSource + ?Sized,
+              K: PointSink + ?Sized,
+              F: FnMut(u8) -> Result<Box<K>>
+    {
+        if self.output_frame != OutputFrame::Utm {
+            return Err(Error::InvalidField("output_frame",
+                                           "points can only be split by zone for UTM output"
+                                               .to_string()));
+        }
+        if self.ground_scale.is_some() {
+            return Err(Error::InvalidField("ground_scale",
+                                           "cannot be used when points are split by zone"
+                                               .to_string()));
+        }
+        self.georeference_split(source,
+                                trajectory,
+                                |point, imu_gnss_point| {
+                                    let zone = natural_utm_zone(imu_gnss_point.latitude,
+                                                                imu_gnss_point.longitude);
+                                    self.transform_in_zone(point, imu_gnss_point, zone);
+                                    Some(zone)
+                                },
+                                open_sink)
+    }
+
+    /// Georeference a point cloud, writing each flight line to its own sink.
+    ///
+    /// The flight lines must have been set with `set_flight_lines`. Points that aren't in any
+    /// line, e.g. those scanned during turns, are counted as filtered. `open_sink` is called with
+    /// a line's number the first time a point falls into that line, and the sinks are returned,
+    /// ordered by line, so they can be closed.
+    pub fn georeference_by_flight_line<S, K, F>(&self,
+                                                source: &mut S,
+                                                trajectory: &Trajectory,
+                                                open_sink: F)
+                                                -> Result<(Vec<(u16, Box<K>)>, Summary)>
+        where S: PointSource + ?Sized,
+              K: PointSink + ?Sized,
+              F: FnMut(u16) -> Result<Box<K>>
+    {
+        if self.flight_lines.is_empty() {
+            return Err(Error::InvalidField("flight_lines",
+                                           "no flight lines to split points by".to_string()));
+        }
+        self.georeference_split(source,
+                                trajectory,
+                                |point, imu_gnss_point| {
+                                    self.transform(point, imu_gnss_point);
+                                    flight_line::line_at(&self.flight_lines, imu_gnss_point.time)
+                                        .map(|line| line.number)
+                                },
+                                open_sink)
+    }
+
+    /// Georeference a point cloud in parts of `part_size` source points, each written to its own
+    /// sink, so a long run can be resumed.
+    ///
+    /// Part `i` holds whatever is written from the source's points `i * part_size` up to
+    /// `(i + 1) * part_size`. `open_sink` is called with a part's index when its first chunk is
+    /// ready, and `close_sink` once the part is done, so the caller can close the sink and
+    /// record that the part is finished. To resume after a failure, move the source past the
+    /// points of the finished parts, e.g. with `PointSource::skip`, and pass the number of
+    /// finished parts as `first_part` so the parts keep their indices.
+    ///
+    /// The summary only covers the parts georeferenced by this call, and the limit and the
+    /// filter's thinning count from its first point. Parts are written on the calling thread,
+    /// whatever the number of threads configured.
+    pub fn georeference_in_parts<S, K, F, C>(&self,
+                                             source: &mut S,
+                                             trajectory: &Trajectory,
+                                             part_size: usize,
+                                             first_part: usize,
+                                             mut open_sink: F,
+                                             mut close_sink: C)
+                                             -> Result<Summary>
+        where S: PointSource + ?Sized,
+              K: PointSink + ?Sized,
+              F: FnMut(usize) -> Result<Box<K>>,
+              C: FnMut(usize, Box<K>) -> Result<()>
+    {
+        if part_size == 0 {
+            return Err(Error::InvalidField("part_size", "must be greater than zero".to_string()));
+        }
+        let start = Instant::now();
+        let mut summary = Summary::default();
+        let (mut part, mut read) = (first_part, 0);
+        let mut sink = None;
+        while !self.limit_reached(&summary, 0) {
+            let read_start = Instant::now();
+            let points = match try!(source.source(cmp::min(self.chunk_size, part_size - read))) {
                 Some(points) => points,
                 None => break,
             };
+            summary.metrics.read += seconds_since(read_start);
+            read += points.len();
+            let (mut points, imu_gnss_points) = try!(self.prepare_chunk(points,
+                                                                        trajectory,
+                                                                        0,
+                                                                        &mut summary));
+            self.transform_chunk(&mut points, &imu_gnss_points, &mut summary);
+            if sink.is_none() {
+                sink = Some(try!(open_sink(part)));
+            }
+            let write_start = Instant::now();
+            if let Some(ref mut sink) = sink {
+                for (point, imu_gnss_point) in points.iter().zip(&imu_gnss_points) {
+                    if self.keep(point, &mut summary) {
+                        try!(sink.sink_with_record(point, imu_gnss_point));
+                    }
+                }
+            }
+            summary.metrics.write += seconds_since(write_start);
+            if read >= part_size {
+                try!(close_sink(part, sink.take().unwrap()));
+                part += 1;
+                read = 0;
+            }
+        }
+        if let Some(sink) = sink.take() {
+            try!(close_sink(part, sink));
+        }
+        summary.metrics.total = seconds_since(start);
+        Ok(summary)
+    }
+
+    /// Georeference a point cloud into several sinks.
+    ///
+    /// `transform` georeferences a point and returns the key of its sink, or `None` to drop it.
+    fn georeference_split<S, K, Q, T, F>(&self,
+                                         source: &mut S,
+                                         trajectory: &Trajectory,
+                                         mut transform: T,
+                                         mut open_sink: F)
+                                         -> Result<(Vec<(Q, Box<K>)>, Summary)>
+        where S: PointSource + ?Sized,
+              K: PointSink + ?Sized,
+              Q: Copy + Ord,
+              T: FnMut(&mut pabst::Point, &ImuGnssPoint) -> Option<Q>,
+              F: FnMut(Q) -> Result<Box<K>>
+    {
+        let start = Instant::now();
+        let mut sinks = BTreeMap::new();
+        let mut summary = Summary::default();
+        while let Some(points) = try!(self.next_chunk(source, &mut summary)) {
+            let (mut points, imu_gnss_points) = try!(self.prepare_chunk(points,
+                                                                        trajectory,
+                                                                        0,
+                                                                        &mut summary));
+            let transform_start = Instant::now();
+            let keys: Vec<Option<Q>> = points.iter_mut()
+                                             .zip(&imu_gnss_points)
+                                             .map(|(point, imu_gnss_point)| {
+                                                 transform(point, imu_gnss_point)
+                                             })
+                                             .collect();
+            summary.metrics.transform += seconds_since(transform_start);
+            let write_start = Instant::now();
+            for ((point, key), imu_gnss_point) in points.iter().zip(keys).zip(&imu_gnss_points) {
+                let key = match key {
+                    Some(key) => key,
+                    None => {
+                        summary.points_filtered += 1;
+                        continue;
+                    }
+                };
+                if !self.keep(point, &mut summary) {
+                    continue;
+                }
+                if !sinks.contains_key(&key) {
+                    let _ = sinks.insert(key, try!(open_sink(key)));
+                }
+                try!(sinks.get_mut(&key).unwrap().sink_with_record(point, imu_gnss_point));
+            }
+            summary.metrics.write += seconds_since(write_start);
+        }
+        summary.metrics.total = seconds_since(start);
+        Ok((sinks.into_iter().collect(), summary))
+    }
+
+    /// Checks how much of a point cloud's time span a trajectory covers, without interpolating.
+    ///
+    /// Every point is read, and the time of every `sample_interval`th point is compared against
+    /// the trajectory's time bounds, which is much faster than a dry run. Gaps aren't detected. If
+    /// the trajectory's time bounds aren't known, every sampled point counts as covered.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # extern crate georef;
+    /// # extern crate pabst;
+    /// # fn main() {
+    /// use georef::{GeoreferencerBuilder, ImuGnss};
+    /// let georeferencer = GeoreferencerBuilder::new().utm_zone(6).build().unwrap();
+    /// let mut source = pabst::open_file_source("points.las", None).unwrap();
+    /// let trajectory = ImuGnss::new(Vec::new()).unwrap();
+    /// let coverage = georeferencer.coverage(&mut *source, &trajectory, 100).unwrap();
+    /// println!("{:.1}% covered", 100.0 * coverage.fraction());
+    /// # }
+    /// ```
+    pub fn coverage<S>(&self,
+                       source: &mut S,
+                       trajectory: &Trajectory,
+                       sample_interval: usize)
+                       -> Result<Coverage>
+        where S: PointSource + ?Sized
+    {
+        let sample_interval = cmp::max(sample_interval, 1);
+        let mut coverage = Coverage {
+            trajectory_time_bounds: trajectory.time_bounds(),
+            ..Default::default()
+        };
+        let mut index = 0;
+        while let Some(points) = try!(source.source(self.chunk_size)) {
+            for point in points {
+                if index % sample_interval == 0 {
+                    let time = try!(self.point_time(&point));
+                    coverage.points_sampled += 1;
+                    if coverage.trajectory_time_bounds
+                               .map_or(true, |(start, end)| time >= start && time <= end) {
+                        coverage.points_covered += 1;
+                    }
+                    coverage.point_time_bounds = Some(match coverage.point_time_bounds {
+                        Some((start, end)) => (start.min(time), end.max(time)),
+                        None => (time, time),
+                    });
+                }
+                index += 1;
+            }
+        }
+        Ok(coverage)
+    }
+
+    /// Returns what this georeferencer does with points outside of the trajectory.
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+
+    /// Estimates what georeferencing a point cloud would do, without writing anything.
+    ///
+    /// Every point is read, and every `sample_interval`th point is georeferenced. The error policy
+    /// is honored, so e.g. clamped points count as covered. Any error other than a point falling
+    /// outside of or in a gap in the trajectory is returned.
+    pub fn dry_run<S>(&self,
+                      source: &mut S,
+                      trajectory: &Trajectory,
+                      sample_interval: usize)
+                      -> Result<DryRun>
+        where S: PointSource + ?Sized
+    {
+        let sample_interval = cmp::max(sample_interval, 1);
+        let mut dry_run = DryRun::default();
+        while let Some(points) = try!(source.source(self.chunk_size)) {
             for mut point in points {
-                try!(self.georeference_point(&mut point, interpolator));
-                try!(sink.sink(&point));
-                npoints += 1;
-                if let Some(limit) = self.limit {
-                    if npoints >= limit {
-                        return Ok(());
+                if dry_run.points % sample_interval == 0 {
+                    dry_run.points_sampled += 1;
+                    match self.pose(&point, trajectory) {
+                        Ok(Interpolated::Pose(imu_gnss_point)) => {
+                            dry_run.points_covered += 1;
+                            let socs = self.scanner_vector(&point);
+                            let kept = self.filter.keeps_source(&point) &&
+                                       self.filter.keeps_socs(socs, self.frame);
+                            self.transform(&mut point, &imu_gnss_point);
+                            if kept && self.filter.keeps_output(&point) {
+                                include(&mut dry_run.bounds, &point);
+                            }
+                        }
+                        Ok(Interpolated::Outside) |
+                        Ok(Interpolated::InGap) |
+                        Err(Error::OutsideOfImuGnssRecords) |
+                        Err(Error::TrajectoryGap(_, _)) => {}
+                        Err(err) => return Err(err),
                     }
                 }
+                dry_run.points += 1;
+            }
+        }
+        Ok(dry_run)
+    }
+
+    fn georeference_parallel<S, K, F>(&self,
+                                      source: &mut S,
+                                      trajectory: &Trajectory,
+                                      sink: &mut K,
+                                      mut report: Progress,
+                                      mut progress: F)
+                                      -> Result<Summary>
+        where S: PointSource + ?Sized,
+              K: PointSink + ?Sized,
+              F: FnMut(&Progress)
+    {
+        // Sources and sinks are borrowed and aren't `Send`, so they stay on this thread. The
+        // bounded channels can hold every in-flight chunk, so neither side ever blocks the other
+        // for good.
+        let start = Instant::now();
+        let max_in_flight = self.max_chunks_in_flight;
+        let georeferencer = Arc::new(self.clone());
+        let (job_sender, job_receiver) = mpsc::sync_channel(max_in_flight);
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, result_receiver) = mpsc::sync_channel(max_in_flight);
+        let handles: Vec<_> = (0..self.threads)
+                                  .map(|_| {
+                                      spawn_worker(georeferencer.clone(),
+                                                   job_receiver.clone(),
+                                                   result_sender.clone())
+                                  })
+                                  .collect();
+        drop(result_sender);
+
+        let mut pending = BTreeMap::new();
+        // The number of points processed once each in-flight chunk is written, and its length.
+        let mut processed = VecDeque::new();
+        let mut summary = Summary::default();
+        let (mut nchunks, mut nwritten, mut in_flight) = (0, 0, 0);
+        while !self.limit_reached(&summary, in_flight) {
+            let points = match try!(self.next_chunk(source, &mut summary)) {
+                Some(points) => points,
+                None => break,
+            };
+            let (points, imu_gnss_points) = try!(self.prepare_chunk(points,
+                                                                    trajectory,
+                                                                    in_flight,
+                                                                    &mut summary));
+            in_flight += points.len();
+            processed.push_back((summary.points_processed() + in_flight, points.len()));
+            try!(job_sender.send((nchunks, points, imu_gnss_points))
+                           .map_err(|_| Error::WorkerThread));
+            nchunks += 1;
+            while nchunks - nwritten >= max_in_flight {
+                try!(self.write_chunk(&result_receiver,
+                                      &mut pending,
+                                      nwritten,
+                                      sink,
+                                      &mut summary));
+                nwritten += 1;
+                let (points_processed, len) = processed.pop_front().unwrap();
+                in_flight -= len;
+                report.points_processed = points_processed;
+                report.chunks_done = nwritten;
+                progress(&report);
+            }
+        }
+        while nwritten < nchunks {
+            try!(self.write_chunk(&result_receiver,
+                                  &mut pending,
+                                  nwritten,
+                                  sink,
+                                  &mut summary));
+            nwritten += 1;
+            report.points_processed = processed.pop_front().unwrap().0;
+            report.chunks_done = nwritten;
+            progress(&report);
+        }
+        drop(job_sender);
+        for handle in handles {
+            try!(handle.join().map_err(|_| Error::WorkerThread));
+        }
+        summary.metrics.total = seconds_since(start);
+        Ok(summary)
+    }
+
+    /// Writes the chunk with the given index to the sink, buffering any chunks that arrive early.
+    fn write_chunk<K>(&self,
+                      results: &Receiver<Done>,
+                      pending: &mut BTreeMap<usize, (Vec<pabst::Point>, Vec<ImuGnssPoint>)>,
+                      index: usize,
+                      sink: &mut K,
+                      summary: &mut Summary)
+                      -> Result<()>
+        where K: PointSink + ?Sized
+    {
+        while !pending.contains_key(&index) {
+            let done = try!(results.recv().map_err(|_| Error::WorkerThread));
+            let (i, points, imu_gnss_points, seconds) = done;
+            summary.metrics.transform += seconds;
+            let _ = pending.insert(i, (points, imu_gnss_points));
+        }
+        let start = Instant::now();
+        let (points, imu_gnss_points) = pending.remove(&index).unwrap();
+        for (point, imu_gnss_point) in points.iter().zip(&imu_gnss_points) {
+            if self.keep(point, summary) {
+                try!(sink.sink_with_record(point, imu_gnss_point));
             }
         }
+        summary.metrics.write += seconds_since(start);
         Ok(())
     }
 
-    /// Georeference a single point.
-    pub fn georeference_point(&self,
-                              point: &mut pabst::Point,
-                              interpolator: &mut pos::Interpolator)
-                              -> Result<()> {
+    /// Transforms a chunk of points on this thread, timing it.
+    fn transform_chunk(&self,
+                       points: &mut [pabst::Point],
+                       imu_gnss_points: &[ImuGnssPoint],
+                       summary: &mut Summary) {
+        let start = Instant::now();
+        for (point, imu_gnss_point) in points.iter_mut().zip(imu_gnss_points) {
+            self.transform(point, imu_gnss_point);
+        }
+        summary.metrics.transform += seconds_since(start);
+    }
+
+    /// Applies the filter to a georeferenced point, returning true if it should be written.
+    ///
+    /// This is where points are counted as written, and kept points grow the summary's bounds.
+    fn keep(&self, point: &pabst::Point, summary: &mut Summary) -> bool {
+        if self.filter.keeps_output(point) {
+            summary.points_written += 1;
+            include(&mut summary.bounds, point);
+            true
+        } else {
+            summary.points_filtered += 1;
+            false
+        }
+    }
+
+    /// Reads the next chunk of points from the source, or returns `None` once the source is
+    /// exhausted or the point limit has been reached.
+    fn next_chunk<S>(&self,
+                     source: &mut S,
+                     summary: &mut Summary)
+                     -> Result<Option<Vec<pabst::Point>>>
+        where S: PointSource + ?Sized
+    {
+        if self.limit_reached(summary, 0) {
+            debug!("stopping after {} points, the limit", summary.points_written);
+            return Ok(None);
+        }
+        let start = Instant::now();
+        let points = try!(source.source(self.chunk_size));
+        summary.metrics.read += seconds_since(start);
+        if let Some(ref points) = points {
+            debug!("read a chunk of {} points", points.len());
+        }
+        Ok(points)
+    }
+
+    /// Interpolates a record for each point in a chunk, dropping skipped points and any points
+    /// past the limit.
+    ///
+    /// Kept points aren't counted until they're written, so `in_flight` is the number of points
+    /// prepared earlier that haven't been written or filtered yet. The limit and the filter's
+    /// thinning count them as written.
+    fn prepare_chunk(&self,
+                     points: Vec<pabst::Point>,
+                     trajectory: &Trajectory,
+                     in_flight: usize,
+                     summary: &mut Summary)
+                     -> Result<(Vec<pabst::Point>, Vec<ImuGnssPoint>)> {
+        let start = Instant::now();
+        let mut kept = Vec::with_capacity(points.len());
+        let mut imu_gnss_points = Vec::with_capacity(points.len());
+        let mut cache = None;
+        for point in points {
+            let pending = in_flight + kept.len();
+            if self.limit_reached(summary, pending) {
+                break;
+            }
+            if !(self.filter.keeps_index(summary.points_processed() + pending) &&
+                 self.filter.keeps_source(&point) &&
+                 self.filter.keeps_socs(self.scanner_vector(&point), self.frame)) {
+                summary.points_filtered += 1;
+                continue;
+            }
+            let interpolated = match self.pose_interval {
+                Some(interval) => {
+                    try!(self.pose_in_bucket(&point, trajectory, interval, &mut cache))
+                }
+                None => try!(self.pose(&point, trajectory)),
+            };
+            match interpolated {
+                Interpolated::Pose(imu_gnss_point) => {
+                    kept.push(point);
+                    imu_gnss_points.push(imu_gnss_point);
+                }
+                Interpolated::Outside => {
+                    summary.points_skipped += 1;
+                    summary.points_skipped_outside += 1;
+                }
+                Interpolated::InGap => {
+                    summary.points_skipped += 1;
+                    summary.points_skipped_in_gaps += 1;
+                }
+            }
+        }
+        summary.metrics.interpolate += seconds_since(start);
+        Ok((kept, imu_gnss_points))
+    }
+
+    /// Returns true once the points written, and the `pending` points that will be, reach the
+    /// limit.
+    fn limit_reached(&self, summary: &Summary, pending: usize) -> bool {
+        self.limit.map_or(false, |limit| summary.points_written + pending >= limit)
+    }
+
+    /// Returns a point's time on the trajectory's clock, with the time offsets applied.
+    fn point_time(&self, point: &pabst::Point) -> Result<f64> {
         let time = try!(point.gps_time.ok_or(Error::MissingGpsTime)) + self.time_offset;
-        let pos = try!(interpolator.interpolate(time));
-        let pos = UtmPoint::from_latlon(&pos, self.utm_zone);
-        let p = pos.rotation_matrix(&self.rotation_order) *
-                (self.boresight_matrix * self.socs_map.vec3(&point) + self.lever_arm) +
-                pos.location();
-        point.x = p.x;
-        point.y = p.y;
-        point.z = p.z;
-        Ok(())
+        let return_time_offset = point.return_number
+                                      .and_then(|n| (n as usize).checked_sub(1))
+                                      .and_then(|i| self.return_time_offsets.get(i))
+                                      .cloned()
+                                      .unwrap_or(0.0);
+        Ok(time + return_time_offset)
+    }
+
+    /// Interpolates the IMU/GNSS record for a point, applying the error policy.
+    fn pose(&self, point: &pabst::Point, trajectory: &Trajectory) -> Result<Interpolated> {
+        let time = try!(self.point_time(point));
+        self.pose_at(time, trajectory)
+    }
+
+    /// Returns the IMU/GNSS record at the middle of a point's time bucket, reusing the last one
+    /// if the point is in the same bucket.
+    ///
+    /// Falls back to the record at the point's own time if the middle of the bucket can't be
+    /// interpolated, e.g. at the ends of the trajectory.
+    fn pose_in_bucket(&self,
+                      point: &pabst::Point,
+                      trajectory: &Trajectory,
+                      interval: f64,
+                      cache: &mut Option<(i64, ImuGnssPoint)>)
+                      -> Result<Interpolated> {
+        let time = try!(self.point_time(point));
+        let bucket = (time / interval).floor() as i64;
+        if let Some((cached, imu_gnss_point)) = *cache {
+            if cached == bucket {
+                return Ok(Interpolated::Pose(imu_gnss_point));
+            }
+        }
+        match self.pose_at((bucket as f64 + 0.5) * interval, trajectory) {
+            Ok(Interpolated::Pose(imu_gnss_point)) => {
+                *cache = Some((bucket, imu_gnss_point));
+                Ok(Interpolated::Pose(imu_gnss_point))
+            }
+            _ => {
+                trace!("pose bucket {} has no pose, interpolating at {}", bucket, time);
+                self.pose_at(time, trajectory)
+            }
+        }
+    }
+
+    /// Interpolates the IMU/GNSS record at a time, applying the error policy.
+    fn pose_at(&self, time: f64, trajectory: &Trajectory) -> Result<Interpolated> {
+        let mut imu_gnss_point = match trajectory.pose_at(time, &self.interpolation) {
+            Ok(imu_gnss_point) => imu_gnss_point,
+            Err(Error::OutsideOfImuGnssRecords) => {
+                match (self.error_policy, trajectory.time_bounds()) {
+                    (ErrorPolicy::Skip, _) => {
+                        trace!("skipping point at {}: outside of the trajectory", time);
+                        return Ok(Interpolated::Outside);
+                    }
+                    (ErrorPolicy::ClampToNearest, Some((start, end))) => {
+                        trace!("clamping point at {} to the trajectory, {} to {}",
+                               time,
+                               start,
+                               end);
+                        try!(trajectory.pose_at(time.max(start).min(end), &self.interpolation))
+                    }
+                    (ErrorPolicy::ClampToNearest, None) => {
+                        return Err(Error::InvalidField("error_policy",
+                                                       "clamp needs a trajectory that reports its \
+                                                        time bounds"
+                                                           .to_string()));
+                    }
+                    _ => return Err(Error::OutsideOfImuGnssRecords),
+                }
+            }
+            Err(Error::TrajectoryGap(start, end)) if self.error_policy == ErrorPolicy::Skip => {
+                trace!("skipping point at {}: in a trajectory gap from {} to {}",
+                       time,
+                       start,
+                       end);
+                return Ok(Interpolated::InGap);
+            }
+            Err(err) => return Err(err),
+        };
+        self.apply_heading_offset(&mut imu_gnss_point);
+        try!(self.check_geoid(&imu_gnss_point));
+        Ok(Interpolated::Pose(imu_gnss_point))
+    }
+
+    /// Adds the heading offset to a trajectory pose's yaw.
+    ///
+    /// A quaternion attitude is turned by the rotation order's own yaw rotation, as it is by the
+    /// meridian convergence, and its roll, pitch, and yaw are recomputed from it.
+    fn apply_heading_offset(&self, pose: &mut Pose) {
+        if self.heading_offset != 0.0 {
+            match pose.orientation {
+                Some(orientation) => {
+                    let turned = self.rotation_order.rot3(0.0, 0.0, self.heading_offset) *
+                                 orientation.to_rot3();
+                    let orientation = Quaternion::from_rot3(&turned);
+                    let (roll, pitch, yaw) = orientation.rpy();
+                    pose.roll = roll;
+                    pose.pitch = pitch;
+                    pose.yaw = yaw;
+                    pose.orientation = Some(orientation);
+                }
+                None => pose.yaw += self.heading_offset,
+            }
+        }
+    }
+
+    /// Checks that a pose is on the geoid grid, if there's a geoid.
+    fn check_geoid(&self, pose: &Pose) -> Result<()> {
+        if let Some(ref geoid) = self.geoid {
+            let _ = try!(geoid.undulation(pose.latitude, pose.longitude));
+        }
+        Ok(())
+    }
+
+    /// Returns the geoid undulation, in meters, at a point's latitude and longitude in radians,
+    /// or zero if there's no geoid.
+    ///
+    /// A point measured from a pose near the edge of a regional grid can land just off of it, so
+    /// it gets its pose's undulation, which `check_geoid` has already looked up.
+    fn undulation(&self, pose: &Pose, latitude: f64, longitude: f64) -> f64 {
+        match self.geoid {
+            Some(ref geoid) => {
+                geoid.undulation(latitude, longitude)
+                     .or_else(|_| geoid.undulation(pose.latitude, pose.longitude))
+                     .unwrap_or(0.0)
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Returns the geoid undulation at a point offset from its pose along UTM grid axes.
+    fn undulation_at_offset(&self, pose: &Pose, offset: Vec3<f64>) -> f64 {
+        if self.geoid.is_none() {
+            return 0.0;
+        }
+        // Grid north is a few degrees off of true north at most, which moves a point tens of
+        // meters over a scanner's range, far less than the spacing of a geoid grid.
+        let latitude = pose.latitude + offset.y / EARTH_RADIUS;
+        let longitude = pose.longitude + offset.x / (EARTH_RADIUS * latitude.cos());
+        self.undulation(pose, latitude, longitude)
+    }
+
+    /// Returns a point's coordinates in the scanner's own coordinate system, after the intrinsic
+    /// calibration and range correction, rotated by the SOCS map.
+    fn scanner_vector(&self, point: &pabst::Point) -> Vec3<f64> {
+        let mut socs = Vec3::new(point.x, point.y, point.z);
+        if let Some(ref polar) = self.polar {
+            socs = polar.to_socs(socs);
+        }
+        if let Some(ref intrinsics) = self.intrinsics {
+            socs = intrinsics.apply(socs);
+        }
+        if let Some(ref range_correction) = self.range_correction {
+            socs = range_correction.apply(socs);
+        }
+        self.socs_map.rotate(socs)
+    }
+
+    fn transform(&self, point: &mut pabst::Point, imu_gnss_point: &ImuGnssPoint) {
+        self.transform_in_zone(point, imu_gnss_point, self.utm_zone)
+    }
+
+    fn transform_in_zone(&self,
+                         point: &mut pabst::Point,
+                         imu_gnss_point: &ImuGnssPoint,
+                         utm_zone: u8) {
+        let scanner = self.scanner_vector(point);
+        if let Some(point_source_id) = self.point_source_id {
+            point.point_source_id = Some(point_source_id);
+        } else if let Some(line) = flight_line::line_at(&self.flight_lines, imu_gnss_point.time) {
+            point.point_source_id = Some(line.number);
+        }
+        if self.write_scan_angle {
+            let angle = scan_angle(scanner, self.frame);
+            point.scan_angle_rank = Some(angle.round().max(-90.0).min(90.0) as i8);
+        }
+        let sensor = self.boresight_matrix * scanner;
+        let body = self.gimbal.as_ref().map_or(sensor, |gimbal| {
+            gimbal.rotation_at(imu_gnss_point.time) * sensor
+        }) + self.lever_arm;
+        let p = match self.output_frame {
+            OutputFrame::Sensor => sensor,
+            OutputFrame::Body => body,
+            OutputFrame::Utm if self.datum_shift.is_none() => {
+                let pos = UtmPoint::from_latlon(imu_gnss_point, utm_zone, self.ellipsoid);
+                let offset = pos.rotation_matrix(&self.rotation_order, self.frame) * body;
+                let mut p = offset + pos.location();
+                p.z -= self.undulation_at_offset(imu_gnss_point, offset);
+                self.ground_scale.map_or(p, |ground_scale| ground_scale.apply(p))
+            }
+            _ => {
+                // A datum shift works on earth-centered coordinates, so UTM output takes the long
+                // way around: to ECEF, through the shift, and back to geodetic for projection.
+                // The trajectory is on WGS84 before a shift and on the output ellipsoid without
+                // one.
+                let source = if self.datum_shift.is_some() {
+                    Ellipsoid::wgs84()
+                } else {
+                    self.ellipsoid
+                };
+                let pos = EcefPoint::from_latlon(imu_gnss_point, source);
+                let p = pos.rotation_matrix(&self.rotation_order, self.frame) * body +
+                        pos.location();
+                let p = self.datum_shift.map_or(p, |datum_shift| datum_shift.apply(p));
+                match self.output_frame {
+                    OutputFrame::Utm => {
+                        let (latitude, longitude, height) =
+                            self.ellipsoid.to_geodetic(p.x, p.y, p.z);
+                        let (northing, easting, _) =
+                            self.ellipsoid.to_utm(latitude, longitude, utm_zone);
+                        let height = height -
+                                     self.undulation(imu_gnss_point, latitude, longitude);
+                        let p = Vec3::new(easting, northing, height);
+                        self.ground_scale.map_or(p, |ground_scale| ground_scale.apply(p))
+                    }
+                    OutputFrame::Geographic => {
+                        let (latitude, longitude, height) =
+                            self.ellipsoid.to_geodetic(p.x, p.y, p.z);
+                        let height = height -
+                                     self.undulation(imu_gnss_point, latitude, longitude);
+                        Vec3::new(longitude.to_degrees(), latitude.to_degrees(), height)
+                    }
+                    OutputFrame::Local => self.local_frame.map_or(p, |frame| frame.project(p)),
+                    _ => p,
+                }
+            }
+        };
+        set_coordinates(point, p);
+        if let Some(ref orthophoto) = self.orthophoto {
+            if let Some(rgb) = orthophoto.rgb(point.x, point.y) {
+                // Scale 8-bit samples to the full 16-bit range that LAS colors use.
+                point.color = Some(pabst::Color {
+                    red: rgb[0] as u16 * 257,
+                    green: rgb[1] as u16 * 257,
+                    blue: rgb[2] as u16 * 257,
+                });
+            }
+        }
+    }
+}
+
+/// An iterator over georeferenced points, from `Georeferencer::georeference_iter`.
+pub struct GeoreferenceIter<'a, S: PointSource + ?Sized + 'a> {
+    georeferencer: &'a Georeferencer,
+    source: &'a mut S,
+    trajectory: &'a Trajectory,
+    points: VecDeque<pabst::Point>,
+    summary: Summary,
+    done: bool,
+}
+
+impl<'a, S: PointSource + ?Sized> GeoreferenceIter<'a, S> {
+    /// Returns the statistics of the points read so far.
+    ///
+    /// Points are counted as written once their chunk has been read, even if they haven't come
+    /// out of the iterator yet.
+    pub fn summary(&self) -> &Summary {
+        &self.summary
+    }
+
+    fn next_chunk(&mut self) -> Result<()> {
+        let georeferencer = self.georeferencer;
+        match try!(georeferencer.next_chunk(self.source, &mut self.summary)) {
+            Some(points) => {
+                let summary = &mut self.summary;
+                let (mut points, imu_gnss_points) =
+                    try!(georeferencer.prepare_chunk(points, self.trajectory, 0, summary));
+                georeferencer.transform_chunk(&mut points, &imu_gnss_points, summary);
+                for point in points {
+                    if georeferencer.keep(&point, summary) {
+                        self.points.push_back(point);
+                    }
+                }
+            }
+            None => self.done = true,
+        }
+        Ok(())
+    }
+}
+
+impl<'a, S: PointSource + ?Sized> Iterator for GeoreferenceIter<'a, S> {
+    type Item = Result<pabst::Point>;
+
+    fn next(&mut self) -> Option<Result<pabst::Point>> {
+        loop {
+            if let Some(point) = self.points.pop_front() {
+                return Some(Ok(point));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.next_chunk() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+impl<'a, S: PointSource + ?Sized> fmt::Debug for GeoreferenceIter<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GeoreferenceIter")
+         .field("points", &self.points.len())
+         .field("summary", &self.summary)
+         .field("done", &self.done)
+         .finish()
+    }
+}
+
+/// Moves a point to its georeferenced coordinates.
+///
+/// Apart from point source ids, scan angles, and colors, if asked for, this is the only place a
+/// georeferencer changes a point, so every other attribute, e.g. intensity, return number,
+/// classification, gps time, and any extra bytes, reaches the sink exactly as the source produced
+/// it.
+fn set_coordinates(point: &mut pabst::Point, p: Vec3<f64>) {
+    point.x = p.x;
+    point.y = p.y;
+    point.z = p.z;
+}
+
+/// Builds a `Georeferencer` one field at a time.
+///
+/// The rotation order defaults to `r3(yaw) r2(pitch) r1(roll)` and the SOCS map defaults to the
+/// identity. All fields are validated when the georeferencer is built.
+///
+/// # Examples
+///
+/// ```
+/// use georef::georef::GeoreferencerBuilder;
+/// let georeferencer = GeoreferencerBuilder::new()
+///                         .utm_zone(6)
+///                         .lever_arm(0.1, 0.2, -0.3)
+///                         .rotation_order("r3(-yaw)", "r1(pitch)", "r2(roll)")
+///                         .build()
+///                         .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct GeoreferencerBuilder {
+    attitude_interpolation: Option<AttitudeInterpolation>,
+    config: GeorefConfig,
+    error_policy: Option<ErrorPolicy>,
+    output_frame: Option<OutputFrame>,
+    position_interpolation: Option<PositionInterpolation>,
+}
+
+impl GeoreferencerBuilder {
+    /// Creates a new builder with default values.
+    pub fn new() -> GeoreferencerBuilder {
+        GeoreferencerBuilder {
+            attitude_interpolation: None,
+            error_policy: None,
+            output_frame: None,
+            position_interpolation: None,
+            config: GeorefConfig {
+                rotation_order: ["r3(yaw)".to_string(),
+                                 "r2(pitch)".to_string(),
+                                 "r1(roll)".to_string()],
+                socs_map: SocsStringMap {
+                    x: "x".to_string(),
+                    y: "y".to_string(),
+                    z: "z".to_string(),
+                },
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Sets the boresight angles, in radians.
+    pub fn boresight(mut self, roll: f64, pitch: f64, yaw: f64) -> GeoreferencerBuilder {
+        self.config.boresight = Rpy {
+            roll: roll,
+            pitch: pitch,
+            yaw: yaw,
+            matrix: None,
+        };
+        self
+    }
+
+    /// Sets the boresight as a rotation matrix, in row-major order.
+    pub fn boresight_matrix(mut self, matrix: [f64; 9]) -> GeoreferencerBuilder {
+        self.config.boresight = Rpy { matrix: Some(matrix), ..Default::default() };
+        self
+    }
+
+    /// Sets the size of each processing chunk.
+    pub fn chunk_size(mut self, chunk_size: usize) -> GeoreferencerBuilder {
+        self.config.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Sets how attitude is interpolated.
+    pub fn attitude_interpolation(mut self,
+                                  attitude: AttitudeInterpolation)
+                                  -> GeoreferencerBuilder {
+        self.attitude_interpolation = Some(attitude);
+        self
+    }
+
+    /// Sets how position is interpolated.
+    pub fn position_interpolation(mut self,
+                                  position: PositionInterpolation)
+                                  -> GeoreferencerBuilder {
+        self.position_interpolation = Some(position);
+        self
+    }
+
+    /// Sets the navigation frame that the IMU/GNSS attitude rotates body vectors into.
+    pub fn frame(mut self, frame: NavigationFrame) -> GeoreferencerBuilder {
+        let frame = match frame {
+            NavigationFrame::Enu => "ENU",
+            NavigationFrame::Ned => "NED",
+        };
+        self.config.frame = Some(frame.to_string());
+        self
+    }
+
+    /// Sets the ellipsoid that output is on, by its semi-major axis, in
+    /// meters, and inverse flattening.
+    pub fn ellipsoid(mut self,
+                     semi_major_axis: f64,
+                     inverse_flattening: f64)
+                     -> GeoreferencerBuilder {
+        self.config.ellipsoid = Some(EllipsoidConfig {
+            name: None,
+            semi_major_axis: Some(semi_major_axis),
+            inverse_flattening: Some(inverse_flattening),
+        });
+        self
+    }
+
+    /// Sets what to do with points outside of the IMU/GNSS records.
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> GeoreferencerBuilder {
+        self.error_policy = Some(error_policy);
+        self
+    }
+
+    /// Sets the constant correction added to every trajectory record's yaw, in degrees.
+    pub fn heading_offset(mut self, heading_offset: f64) -> GeoreferencerBuilder {
+        self.config.heading_offset = Some(heading_offset);
+        self
+    }
+
+    /// Sets the lever arm, in meters.
+    pub fn lever_arm(mut self, x: f64, y: f64, z: f64) -> GeoreferencerBuilder {
+        self.config.lever_arm = Vec3::new(x, y, z);
+        self
+    }
+
+    /// Sets the scanner-to-body transform, a homogeneous 4x4 matrix in row-major order.
+    ///
+    /// The boresight, lever arm, and SOCS map must be left at the identity.
+    pub fn mounting(mut self, mounting: [f64; 16]) -> GeoreferencerBuilder {
+        self.config.mounting = Some(mounting);
+        self
+    }
+
+    /// Sets the origin of the local tangent plane, as a latitude and longitude on the output
+    /// ellipsoid, in degrees, and an ellipsoidal height, for local output.
+    pub fn local_origin(mut self,
+                        latitude: f64,
+                        longitude: f64,
+                        height: f64)
+                        -> GeoreferencerBuilder {
+        self.config.local_origin = Some(LocalOrigin {
+            latitude: latitude,
+            longitude: longitude,
+            height: height,
+        });
+        self
+    }
+
+    /// Sets the largest gap, in seconds, between IMU/GNSS records that points may be
+    /// interpolated across.
+    pub fn max_interpolation_gap(mut self, max_gap: f64) -> GeoreferencerBuilder {
+        self.config.max_interpolation_gap = Some(max_gap);
+        self
+    }
+
+    /// Sets the length, in seconds, of the time buckets that share one interpolated pose.
+    pub fn pose_interval(mut self, interval: f64) -> GeoreferencerBuilder {
+        self.config.pose_interval = Some(interval);
+        self
+    }
+
+    /// Sets whether each point's scan angle is written to its scan angle rank.
+    pub fn write_scan_angle(mut self, write_scan_angle: bool) -> GeoreferencerBuilder {
+        self.config.write_scan_angle = Some(write_scan_angle);
+        self
+    }
+
+    /// Sets the point source id written to every point.
+    pub fn point_source_id(mut self, point_source_id: u16) -> GeoreferencerBuilder {
+        self.config.point_source_id = Some(point_source_id);
+        self
+    }
+
+    /// Limits the number of points written out.
+    pub fn limit(mut self, limit: usize) -> GeoreferencerBuilder {
+        self.config.limit = Some(limit);
+        self
+    }
+
+    /// Sets the time offsets applied to each point by return number, in seconds.
+    pub fn return_time_offsets(mut self, return_time_offsets: Vec<f64>) -> GeoreferencerBuilder {
+        self.config.return_time_offsets = Some(return_time_offsets);
+        self
+    }
+
+    /// Sets the rotation order, e.g. `("r3(yaw)", "r2(pitch)", "r1(roll)")`.
+    pub fn rotation_order(mut self,
+                          first: &str,
+                          second: &str,
+                          third: &str)
+                          -> GeoreferencerBuilder {
+        self.config.rotation_order = [first.to_string(), second.to_string(), third.to_string()];
+        self
+    }
+
+    /// Sets the coordinate frame of the output points.
+    pub fn output_frame(mut self, output_frame: OutputFrame) -> GeoreferencerBuilder {
+        self.output_frame = Some(output_frame);
+        self
+    }
+
+    /// Sets the SOCS map, e.g. `("-z", "x", "y")`.
+    pub fn socs_map(mut self, x: &str, y: &str, z: &str) -> GeoreferencerBuilder {
+        self.config.socs_map = SocsStringMap {
+            x: x.to_string(),
+            y: y.to_string(),
+            z: z.to_string(),
+        };
+        self
+    }
+
+    /// Sets the most chunks that may be in flight at once when more than one thread is used.
+    pub fn max_chunks_in_flight(mut self, max_chunks_in_flight: usize) -> GeoreferencerBuilder {
+        self.config.max_chunks_in_flight = Some(max_chunks_in_flight);
+        self
+    }
+
+    /// Sets the number of worker threads.
+    pub fn threads(mut self, threads: usize) -> GeoreferencerBuilder {
+        self.config.threads = Some(threads);
+        self
+    }
+
+    /// Sets the time offset applied to each point, in seconds.
+    pub fn time_offset(mut self, time_offset: f64) -> GeoreferencerBuilder {
+        self.config.time_offset = Some(time_offset);
+        self
+    }
+
+    /// Sets the UTM zone of the output points.
+    pub fn utm_zone(mut self, utm_zone: u8) -> GeoreferencerBuilder {
+        self.config.utm_zone = utm_zone;
+        self
+    }
+
+    /// Validates every field and builds the georeferencer.
+    ///
+    /// Errors name the offending field.
+    pub fn build(self) -> Result<Georeferencer> {
+        let output_frame = self.output_frame.unwrap_or(OutputFrame::Utm);
+        if output_frame == OutputFrame::Utm &&
+           (self.config.utm_zone < 1 || self.config.utm_zone > 60) {
+            return Err(Error::InvalidField("utm_zone",
+                                           format!("{} is not between 1 and 60",
+                                                   self.config.utm_zone)));
+        }
+        if self.config.chunk_size == Some(0) {
+            return Err(Error::InvalidField("chunk_size", "must be greater than zero".to_string()));
+        }
+        if self.config.max_interpolation_gap.map_or(false, |max_gap| !(max_gap > 0.0)) {
+            return Err(Error::InvalidField("max_interpolation_gap",
+                                           "must be greater than zero".to_string()));
+        }
+        if self.config.pose_interval.map_or(false, |interval| !(interval > 0.0)) {
+            return Err(Error::InvalidField("pose_interval",
+                                           "must be greater than zero".to_string()));
+        }
+        if self.config.threads == Some(0) {
+            return Err(Error::InvalidField("threads", "must be greater than zero".to_string()));
+        }
+        if self.config.max_chunks_in_flight == Some(0) {
+            return Err(Error::InvalidField("max_chunks_in_flight",
+                                           "must be greater than zero".to_string()));
+        }
+        {
+            let order = &self.config.rotation_order;
+            if let Err(err) = RotationOrder::new(&order[0], &order[1], &order[2]) {
+                return Err(Error::InvalidField("rotation_order", err.to_string()));
+            }
+        }
+        let local_origin = self.config.local_origin;
+        let mut georeferencer = try!(Georeferencer::new(self.config).map_err(|err| {
+            match err {
+                Error::SocsMap(_) => Error::InvalidField("socs_map", err.to_string()),
+                _ => err,
+            }
+        }));
+        if let Some(attitude) = self.attitude_interpolation {
+            georeferencer.interpolation.attitude = attitude;
+        }
+        if let Some(position) = self.position_interpolation {
+            georeferencer.interpolation.position = position;
+        }
+        if let Some(error_policy) = self.error_policy {
+            georeferencer.error_policy = error_policy;
+        }
+        if output_frame == OutputFrame::Local && georeferencer.local_frame.is_none() {
+            georeferencer.local_frame = Some(try!(local_frame(local_origin,
+                                                              georeferencer.ellipsoid)));
+        }
+        georeferencer.output_frame = output_frame;
+        Ok(georeferencer)
+    }
+}
+
+impl Default for GeoreferencerBuilder {
+    fn default() -> GeoreferencerBuilder {
+        GeoreferencerBuilder::new()
+    }
+}
+
+type Job = (usize, Vec<pabst::Point>, Vec<ImuGnssPoint>);
+
+/// A transformed chunk's index, points, and their IMU/GNSS records, and how long the transform
+/// took, in seconds.
+type Done = (usize, Vec<pabst::Point>, Vec<ImuGnssPoint>, f64);
+
+/// Spawns a worker that transforms chunks of points until the job channel is closed.
+fn spawn_worker(georeferencer: Arc<Georeferencer>,
+                jobs: Arc<Mutex<Receiver<Job>>>,
+                results: SyncSender<Done>)
+                -> JoinHandle<()> {
+    thread::spawn(move || {
+        loop {
+            let job = jobs.lock().unwrap().recv();
+            let (index, mut points, imu_gnss_points) = match job {
+                Ok(job) => job,
+                Err(_) => break,
+            };
+            let start = Instant::now();
+            for (point, imu_gnss_point) in points.iter_mut().zip(&imu_gnss_points) {
+                georeferencer.transform(point, imu_gnss_point);
+            }
+            if results.send((index, points, imu_gnss_points, seconds_since(start))).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::thread;
+
+    use pabst;
+
+    use Result;
+    use ellipsoid::{Ellipsoid, EllipsoidConfig};
+    use error::Error;
+    use flight_line::FlightLine;
+    use geoid::Geoid;
+    use imu_gnss::{ImuGnss, ImuGnssPoint, Quaternion};
+    use point::{NavigationFrame, OutputFrame};
+    use stream::PointSource;
+    use trajectory::{AttitudeInterpolation, Interpolation, Pose, Trajectory};
+
+    fn point(time: f64) -> pabst::Point {
+        pabst::Point { gps_time: Some(time), ..Default::default() }
+    }
+
+    fn imu_gnss() -> ImuGnss {
+        ImuGnss::new(vec![ImuGnssPoint { time: 0.0, ..Default::default() },
+                          ImuGnssPoint { time: 1.0, ..Default::default() }])
+            .unwrap()
+    }
+
+    #[test]
+    fn builder_defaults() {
+        assert!(GeoreferencerBuilder::new().utm_zone(6).build().is_ok());
+    }
+
+    #[test]
+    fn bounds_tiles() {
+        let bounds = Bounds {
+            min_x: -1.0,
+            max_x: 1.0,
+            min_y: 2000.0,
+            max_y: 2000.0,
+            ..Default::default()
+        };
+        assert_eq!((2, 1), bounds.tiles(1000.0));
+        assert_eq!((5, 1), bounds.tiles(0.5));
+    }
+
+    #[test]
+    fn config_to_toml() {
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(6)
+                                .threads(4)
+                                .time_offset(0.5)
+                                .build()
+                                .unwrap();
+        let table = georeferencer.config().to_toml();
+        let config = GeorefConfig::from_toml(table.clone()).unwrap();
+        assert_eq!(6, config.utm_zone);
+        assert_eq!(Some(4), config.threads);
+        assert_eq!(Some(0.5), config.time_offset);
+        assert!(config.chunk_size.is_none());
+        assert!(table.lookup("chunk_size").is_none());
+        assert!(Georeferencer::new(config).is_ok());
+    }
+
+    #[test]
+    fn builder_ecef_ignores_utm_zone() {
+        assert!(GeoreferencerBuilder::new().output_frame(OutputFrame::Ecef).build().is_ok());
+    }
+
+    #[test]
+    fn builder_field_errors() {
+        let field = |builder: GeoreferencerBuilder| {
+            match builder.build() {
+                Err(Error::InvalidField(field, _)) => field,
+                other => panic!("unexpected result: {:?}", other),
+            }
+        };
+        assert_eq!("utm_zone", field(GeoreferencerBuilder::new()));
+        assert_eq!("utm_zone", field(GeoreferencerBuilder::new().utm_zone(61)));
+        assert_eq!("chunk_size",
+                   field(GeoreferencerBuilder::new().utm_zone(6).chunk_size(0)));
+        assert_eq!("rotation_order",
+                   field(GeoreferencerBuilder::new()
+                             .utm_zone(6)
+                             .rotation_order("r3(yaw)", "r2(pitch)", "r4(roll)")));
+        assert_eq!("socs_map",
+                   field(GeoreferencerBuilder::new().utm_zone(6).socs_map("x", "y", "w")));
+    }
+
+    #[test]
+    fn validate() {
+        let fields = |config: GeorefConfig| -> Vec<&'static str> {
+            config.validate()
+                  .into_iter()
+                  .map(|err| {
+                      match err {
+                          Error::InvalidField(field, _) => field,
+                          other => panic!("unexpected error: {:?}", other),
+                      }
+                  })
+                  .collect()
+        };
+        assert_eq!(vec!["utm_zone", "rotation_order", "socs_map"],
+                   fields(GeorefConfig::default()));
+        let valid = || GeoreferencerBuilder::new().utm_zone(6).config;
+        assert!(fields(valid()).is_empty());
+        assert!(fields(GeorefConfig { output_crs: Some("EPSG:32606".to_string()),
+                                      utm_zone: 0,
+                                      ..valid() })
+                    .is_empty());
+        let mut config = valid();
+        config.socs_map = SocsStringMap {
+            x: "x".to_string(),
+            y: "x".to_string(),
+            z: "z".to_string(),
+        };
+        config.lever_arm = Vec3::new(0.0, 0.0, 1000.0);
+        config.threads = Some(0);
+        config.max_chunks_in_flight = Some(0);
+        config.error_policy = Some("lenient".to_string());
+        assert_eq!(vec!["error_policy", "socs_map", "lever_arm", "threads", "max_chunks_in_flight"],
+                   fields(config));
+    }
+
+    #[test]
+    fn data_config() {
+        let mut s = String::new();
+        let _ = File::open("data/config.toml").unwrap().read_to_string(&mut s).unwrap();
+        let mut table = toml::Parser::new(&s).parse().unwrap();
+        let config = GeorefConfig::from_toml(table.remove("georef").unwrap()).unwrap();
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn boresight_matrix() {
+        let matrix = [0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(6)
+                                .boresight_matrix(matrix)
+                                .build()
+                                .unwrap();
+        assert_eq!(Vec3::new(0.0, 1.0, 0.0),
+                   georeferencer.boresight_matrix * Vec3::new(1.0, 0.0, 0.0));
+
+        let s = r#"{
+            "boresight": {"matrix": [0, -1, 0, 1, 0, 0, 0, 0, 1]},
+            "lever_arm": {"x": 0.0, "y": 0.0, "z": 0.0},
+            "socs_map": {"x": "x", "y": "y", "z": "z"},
+            "rotation_order": ["r3(yaw)", "r2(pitch)", "r1(roll)"],
+            "utm_zone": 6
+        }"#;
+        let config = GeorefConfig::from_json(s).unwrap();
+        assert_eq!(Some(matrix), config.boresight.matrix);
+        let encoded = json::encode(&config).unwrap();
+        assert_eq!(Some(matrix), GeorefConfig::from_json(&encoded).unwrap().boresight.matrix);
+        let both = encoded.replace("\"matrix\"", "\"roll\":0.0,\"matrix\"");
+        assert!(GeorefConfig::from_json(&both).is_err());
+
+        let mut config = GeoreferencerBuilder::new().utm_zone(6).config;
+        config.boresight.matrix = Some([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, -1.0]);
+        assert_eq!(1, config.validate().len());
+        config.boresight.matrix = Some([0.995, -0.09983, 0.0, 0.09983, 0.995, 0.0, 0.0, 0.0, 1.0]);
+        assert!(config.validate().is_empty());
+        config.boresight.matrix = Some([1.0, 0.1, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(1, config.validate().len());
+        assert!(Georeferencer::new(config).is_err());
+    }
+
+    #[test]
+    fn mounting() {
+        let mounting = [0.0, -1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 2.0, 0.0, 0.0, 1.0, 3.0, 0.0, 0.0,
+                        0.0, 1.0];
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(6)
+                                .mounting(mounting)
+                                .build()
+                                .unwrap();
+        assert_eq!(Vec3::new(1.0, 3.0, 3.0),
+                   georeferencer.boresight_matrix * Vec3::new(1.0, 0.0, 0.0) +
+                   georeferencer.lever_arm);
+        assert!(GeoreferencerBuilder::new()
+                    .utm_zone(6)
+                    .mounting(mounting)
+                    .lever_arm(0.1, 0.0, 0.0)
+                    .build()
+                    .is_err());
+        let mut config = GeoreferencerBuilder::new().utm_zone(6).mounting(mounting).config;
+        assert!(config.validate().is_empty());
+        config.mounting.as_mut().unwrap()[15] = 2.0;
+        assert_eq!(1, config.validate().len());
+        config.mounting.as_mut().unwrap()[15] = 1.0;
+        config.lever_arm = Vec3::new(0.1, 0.0, 0.0);
+        assert_eq!(1, config.validate().len());
+    }
+
+    #[test]
+    fn navigation_frame() {
+        let imu_gnss = imu_gnss();
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(31)
+                                .frame(NavigationFrame::Ned)
+                                .build()
+                                .unwrap();
+        let mut georeferenced = pabst::Point { z: 10.0, ..point(0.5) };
+        assert!(georeferencer.georeference_point(&mut georeferenced, &imu_gnss).unwrap());
+        assert!((georeferenced.z + 10.0).abs() < 1e-9);
+        let mut config = GeoreferencerBuilder::new().utm_zone(31).config;
+        config.frame = Some("NWU".to_string());
+        assert_eq!(1, config.validate().len());
+        assert!(Georeferencer::new(config).is_err());
+    }
+
+    #[test]
+    fn in_memory() {
+        for &(threads, max_chunks_in_flight) in &[(1, 1), (2, 4), (4, 1)] {
+            let georeferencer = GeoreferencerBuilder::new()
+                                    .utm_zone(31)
+                                    .chunk_size(2)
+                                    .threads(threads)
+                                    .max_chunks_in_flight(max_chunks_in_flight)
+                                    .error_policy(ErrorPolicy::Skip)
+                                    .build()
+                                    .unwrap();
+            let mut source = vec![point(0.25), point(0.5), point(0.75), point(2.0)];
+            let mut sink: Vec<pabst::Point> = Vec::new();
+            let summary = georeferencer.georeference(&mut source, &imu_gnss(), &mut sink)
+                                       .unwrap();
+            assert_eq!(3, summary.points_written);
+            assert!(source.is_empty());
+            assert_eq!(vec![Some(0.25), Some(0.5), Some(0.75)],
+                       sink.iter().map(|p| p.gps_time).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn threads_keep_order() {
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(31)
+                                .chunk_size(7)
+                                .threads(4)
+                                .build()
+                                .unwrap();
+        let times: Vec<_> = (0..1000).map(|i| Some(i as f64 / 1000.0)).collect();
+        let mut source: Vec<_> = times.iter().map(|time| point(time.unwrap())).collect();
+        let mut sink: Vec<pabst::Point> = Vec::new();
+        let summary = georeferencer.georeference(&mut source, &imu_gnss(), &mut sink).unwrap();
+        assert_eq!(1000, summary.points_written);
+        assert_eq!(times, sink.iter().map(|p| p.gps_time).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parts() {
+        let georeferencer = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+        let source = || vec![point(0.1), point(0.2), point(0.3), point(0.4), point(0.5)];
+        let times = |points: &Vec<pabst::Point>| {
+            points.iter().map(|p| p.gps_time.unwrap()).collect::<Vec<_>>()
+        };
+        let mut parts = Vec::new();
+        let summary = georeferencer.georeference_in_parts(&mut source(),
+                                                          &imu_gnss(),
+                                                          2,
+                                                          0,
+                                                          |_| Ok(Box::new(Vec::new())),
+                                                          |i, sink: Box<Vec<pabst::Point>>| {
+                                                              parts.push((i, times(&sink)));
+                                                              Ok(())
+                                                          })
+                                   .unwrap();
+        assert_eq!(5, summary.points_written);
+        assert_eq!(vec![(0, vec![0.1, 0.2]), (1, vec![0.3, 0.4]), (2, vec![0.5])], parts);
+
+        let mut resumed = source();
+        assert_eq!(2, resumed.skip(2).unwrap());
+        let mut parts = Vec::new();
+        let summary = georeferencer.georeference_in_parts(&mut resumed,
+                                                          &imu_gnss(),
+                                                          2,
+                                                          1,
+                                                          |_| Ok(Box::new(Vec::new())),
+                                                          |i, sink: Box<Vec<pabst::Point>>| {
+                                                              parts.push((i, times(&sink)));
+                                                              Ok(())
+                                                          })
+                                   .unwrap();
+        assert_eq!(3, summary.points_written);
+        assert_eq!(vec![(1, vec![0.3, 0.4]), (2, vec![0.5])], parts);
+
+        assert!(georeferencer.georeference_in_parts(&mut source(),
+                                                    &imu_gnss(),
+                                                    0,
+                                                    0,
+                                                    |_| Ok(Box::new(Vec::new())),
+                                                    |_, _: Box<Vec<pabst::Point>>| Ok(()))
+                             .is_err());
+    }
+
+    #[test]
+    fn metrics() {
+        for &threads in &[1, 2] {
+            let georeferencer = GeoreferencerBuilder::new()
+                                    .utm_zone(31)
+                                    .chunk_size(2)
+                                    .threads(threads)
+                                    .build()
+                                    .unwrap();
+            let mut source = vec![point(0.25), point(0.5), point(0.75)];
+            let mut sink: Vec<pabst::Point> = Vec::new();
+            let summary = georeferencer.georeference(&mut source, &imu_gnss(), &mut sink)
+                                       .unwrap();
+            let metrics = summary.metrics;
+            assert!(metrics.total > 0.0);
+            assert!(summary.points_per_second().unwrap() > 0.0);
+            if threads == 1 {
+                assert!(metrics.read + metrics.interpolate + metrics.transform + metrics.write <=
+                        metrics.total);
+            }
+        }
+        assert!(Summary::default().points_per_second().is_none());
+    }
+
+    #[test]
+    fn iterator() {
+        let imu_gnss = imu_gnss();
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(31)
+                                .chunk_size(2)
+                                .error_policy(ErrorPolicy::Skip)
+                                .build()
+                                .unwrap();
+        let mut source = vec![point(0.25), point(2.0), point(0.75)];
+        {
+            let mut points = georeferencer.georeference_iter(&mut source, &imu_gnss);
+            assert_eq!(Some(0.25), points.next().unwrap().unwrap().gps_time);
+            assert_eq!(1, points.summary().points_skipped);
+            assert_eq!(Some(0.75), points.next().unwrap().unwrap().gps_time);
+            assert!(points.next().is_none());
+            assert_eq!(2, points.summary().points_written);
+        }
+        let strict = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+        let mut source = vec![point(2.0), point(0.5)];
+        let mut points = strict.georeference_iter(&mut source, &imu_gnss);
+        assert!(points.next().unwrap().is_err());
+        assert!(points.next().is_none());
+    }
+
+    #[test]
+    fn slice() {
+        let imu_gnss = imu_gnss();
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(31)
+                                .error_policy(ErrorPolicy::Skip)
+                                .build()
+                                .unwrap();
+        let mut points = vec![pabst::Point { z: 1.0, ..point(0.25) },
+                              pabst::Point { z: 1.0, ..point(2.0) },
+                              pabst::Point { z: 1.0, ..point(0.75) }];
+        let summary = georeferencer.georeference_points(&mut points, &imu_gnss).unwrap();
+        assert_eq!(2, summary.points_written);
+        assert_eq!(1, summary.points_skipped_outside);
+        assert_eq!(pabst::Point { z: 1.0, ..point(2.0) }, points[1]);
+        assert!(points[0].x > 100000.0);
+        assert!(points[2].x > 100000.0);
+        assert!(summary.bounds.is_some());
+    }
+
+    #[test]
+    fn return_time_offsets() {
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint { time: 0.0, ..Default::default() },
+                                         ImuGnssPoint {
+                                             time: 1.0,
+                                             height: 10.0,
+                                             ..Default::default()
+                                         }])
+                           .unwrap();
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(31)
+                                .time_offset(0.25)
+                                .return_time_offsets(vec![0.0, 0.5])
+                                .build()
+                                .unwrap();
+        let mut first = pabst::Point { return_number: Some(1), ..point(0.0) };
+        let mut second = pabst::Point { return_number: Some(2), ..point(0.0) };
+        let mut third = pabst::Point { return_number: Some(3), ..point(0.0) };
+        for returned in vec![&mut first, &mut second, &mut third] {
+            assert!(georeferencer.georeference_point(returned, &imu_gnss).unwrap());
+        }
+        assert!((first.z - 2.5).abs() < 1e-9);
+        assert!((second.z - 7.5).abs() < 1e-9);
+        assert!((third.z - 2.5).abs() < 1e-9);
+        let mut config = GeoreferencerBuilder::new().utm_zone(31).config;
+        config.return_time_offsets = Some(vec![0.0, ::std::f64::NAN]);
+        assert_eq!(1, config.validate().len());
+    }
+
+    #[test]
+    fn local_output() {
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 0.0,
+                                             latitude: 0.1,
+                                             longitude: 0.2,
+                                             height: 100.0,
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 1.0,
+                                             latitude: 0.1,
+                                             longitude: 0.2,
+                                             height: 100.0,
+                                             ..Default::default()
+                                         }])
+                       .unwrap();
+        let georeferencer = GeoreferencerBuilder::new()
+                                .output_frame(OutputFrame::Local)
+                                .local_origin(0.1f64.to_degrees(), 0.2f64.to_degrees(), 90.0)
+                                .build()
+                                .unwrap();
+        let mut georeferenced = pabst::Point { z: 5.0, ..point(0.5) };
+        assert!(georeferencer.georeference_point(&mut georeferenced, &imu_gnss).unwrap());
+        assert!(georeferenced.x.abs() < 1e-6);
+        assert!(georeferenced.y.abs() < 1e-6);
+        assert!((georeferenced.z - 15.0).abs() < 1e-6);
+        assert!(GeoreferencerBuilder::new().output_frame(OutputFrame::Local).build().is_err());
+    }
+
+    #[test]
+    fn inverse_point() {
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 0.0,
+                                             latitude: 0.8,
+                                             longitude: 0.05,
+                                             height: 100.0,
+                                             roll: 0.1,
+                                             pitch: -0.2,
+                                             yaw: 1.0,
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 1.0,
+                                             latitude: 0.8,
+                                             longitude: 0.05,
+                                             height: 110.0,
+                                             roll: 0.1,
+                                             pitch: -0.2,
+                                             yaw: 1.1,
+                                             ..Default::default()
+                                         }])
+                       .unwrap();
+        for &output_frame in &[OutputFrame::Utm,
+                               OutputFrame::Ecef,
+                               OutputFrame::Geographic,
+                               OutputFrame::Sensor,
+                               OutputFrame::Body] {
+            let georeferencer = GeoreferencerBuilder::new()
+                                    .utm_zone(31)
+                                    .output_frame(output_frame)
+                                    .boresight(0.01, 0.02, 0.03)
+                                    .lever_arm(0.1, 0.2, -0.3)
+                                    .socs_map("-y", "x", "z")
+                                    .time_offset(0.25)
+                                    .build()
+                                    .unwrap();
+            let mut point = pabst::Point { x: 10.0, y: -2.0, z: 5.0, ..point(0.5) };
+            assert!(georeferencer.georeference_point(&mut point, &imu_gnss).unwrap());
+            let socs = georeferencer.inverse_point(Vec3::new(point.x, point.y, point.z),
+                                                   0.5,
+                                                   &imu_gnss)
+                                    .unwrap();
+            assert!((socs.x - 10.0).abs() < 1e-6, "{:?}: {:?}", output_frame, socs);
+            assert!((socs.y + 2.0).abs() < 1e-6, "{:?}: {:?}", output_frame, socs);
+            assert!((socs.z - 5.0).abs() < 1e-6, "{:?}: {:?}", output_frame, socs);
+        }
+    }
+
+    #[test]
+    fn sensor_and_body_output() {
+        let georeference = |output_frame| {
+            let georeferencer = GeoreferencerBuilder::new()
+                                    .output_frame(output_frame)
+                                    .lever_arm(1.0, 2.0, 3.0)
+                                    .build()
+                                    .unwrap();
+            let mut point = pabst::Point { x: 5.0, ..point(0.5) };
+            assert!(georeferencer.georeference_point(&mut point, &imu_gnss()).unwrap());
+            (point.x, point.y, point.z)
+        };
+        assert_eq!((5.0, 0.0, 0.0), georeference(OutputFrame::Sensor));
+        assert_eq!((6.0, 2.0, 3.0), georeference(OutputFrame::Body));
+        let mut config = GeorefConfig::default();
+        config.output_frame = Some("body".to_string());
+        config.geoid = Some("geoid.grd".to_string());
+        assert!(config.validate().iter().any(|err| {
+            match *err {
+                Error::InvalidField("geoid", _) => true,
+                _ => false,
+            }
+        }));
+    }
+
+    #[test]
+    fn datum_shift() {
+        let imu_gnss = imu_gnss();
+        let mut config = GeoreferencerBuilder::new().utm_zone(31).config;
+        config.datum_shift = Some(HelmertConfig {
+            translation: [10.0, 0.0, 0.0],
+            ..Default::default()
+        });
+        let georeferencer = Georeferencer::new(config.clone()).unwrap();
+        let unshifted = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+        let mut original = pabst::Point { x: 1.0, y: 2.0, z: 3.0, ..point(0.5) };
+        let mut shifted = original.clone();
+        assert!(unshifted.georeference_point(&mut original, &imu_gnss).unwrap());
+        assert!(georeferencer.georeference_point(&mut shifted, &imu_gnss).unwrap());
+        // The trajectory is on the equator at the prime meridian, where ECEF x is straight up. The
+        // shifted point is projected from geodetic coordinates, so it picks up the UTM scale.
+        assert!((shifted.x - original.x).abs() < 1e-2);
+        assert!((shifted.y - original.y).abs() < 1e-2);
+        assert!((shifted.z - original.z - 10.0).abs() < 1e-3);
+        config.datum_shift = Some(HelmertConfig {
+            scale_rate: Some(1.0),
+            ..Default::default()
+        });
+        assert!(Georeferencer::new(config).is_err());
+    }
+
+    #[test]
+    fn geoid() {
+        // The undulation is 1000 m at the prime meridian and rises a meter every thousandth of a
+        // degree east, so a point a kilometer east of the trajectory is nine meters higher.
+        let grid = "-1 1 -1 1 1 1\n0 1000 2000\n0 1000 2000\n0 1000 2000\n";
+        let geoid = Arc::new(Geoid::from_reader(grid.as_bytes()).unwrap());
+        let imu_gnss = imu_gnss();
+        let expected = 1000.0 + 1000.0 * (1000.0 / 6_378_137f64).to_degrees();
+        for &output_frame in &[OutputFrame::Utm, OutputFrame::Geographic] {
+            let mut georeferencer = GeoreferencerBuilder::new()
+                                        .utm_zone(31)
+                                        .output_frame(output_frame)
+                                        .build()
+                                        .unwrap();
+            let mut ellipsoidal = pabst::Point { x: 1000.0, ..point(0.5) };
+            assert!(georeferencer.georeference_point(&mut ellipsoidal, &imu_gnss).unwrap());
+            georeferencer.geoid = Some(geoid.clone());
+            let mut orthometric = pabst::Point { x: 1000.0, ..point(0.5) };
+            assert!(georeferencer.georeference_point(&mut orthometric, &imu_gnss).unwrap());
+            let undulation = ellipsoidal.z - orthometric.z;
+            assert!((undulation - expected).abs() < 0.1,
+                    "{:?}: {}",
+                    output_frame,
+                    undulation);
+            let socs = georeferencer.inverse_point(Vec3::new(orthometric.x,
+                                                             orthometric.y,
+                                                             orthometric.z),
+                                                   0.5,
+                                                   &imu_gnss)
+                                    .unwrap();
+            assert!((socs.x - 1000.0).abs() < 1e-6, "{:?}: {:?}", output_frame, socs);
+            assert!(socs.z.abs() < 1e-6, "{:?}: {:?}", output_frame, socs);
+        }
+    }
+
+    #[test]
+    fn ellipsoid() {
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 0.0,
+                                             latitude: 0.8,
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 1.0,
+                                             latitude: 0.8,
+                                             ..Default::default()
+                                         }])
+                       .unwrap();
+        let wgs84 = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+        let clarke = GeoreferencerBuilder::new()
+                         .utm_zone(31)
+                         .ellipsoid(6378206.4, 294.978698214)
+                         .build()
+                         .unwrap();
+        let mut on_wgs84 = point(0.5);
+        let mut on_clarke = point(0.5);
+        assert!(wgs84.georeference_point(&mut on_wgs84, &imu_gnss).unwrap());
+        assert!(clarke.georeference_point(&mut on_clarke, &imu_gnss).unwrap());
+        assert!((on_wgs84.y - on_clarke.y).abs() > 10.0);
+        let flattened = GeoreferencerBuilder::new().utm_zone(31).ellipsoid(6378137.0, 0.5);
+        assert!(flattened.build().is_err());
+    }
+
+    #[test]
+    fn ellipsoid_ecef() {
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 0.0,
+                                             latitude: 0.8,
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 1.0,
+                                             latitude: 0.8,
+                                             ..Default::default()
+                                         }])
+                       .unwrap();
+        let clarke = GeoreferencerBuilder::new()
+                         .output_frame(OutputFrame::Ecef)
+                         .ellipsoid(6378206.4, 294.978698214)
+                         .build()
+                         .unwrap();
+        let mut p = point(0.5);
+        assert!(clarke.georeference_point(&mut p, &imu_gnss).unwrap());
+        let ellipsoid = Ellipsoid::new(&EllipsoidConfig {
+                            name: None,
+                            semi_major_axis: Some(6378206.4),
+                            inverse_flattening: Some(294.978698214),
+                        })
+                            .unwrap();
+        let (x, y, z) = ellipsoid.to_ecef(0.8, 0.0, 0.0);
+        assert!((p.x - x).abs() < 1e-6);
+        assert!((p.y - y).abs() < 1e-6);
+        assert!((p.z - z).abs() < 1e-6);
+        let (wgs84_x, _, wgs84_z) = Ellipsoid::wgs84().to_ecef(0.8, 0.0, 0.0);
+        assert!((p.x - wgs84_x).abs() > 10.0 || (p.z - wgs84_z).abs() > 10.0);
+    }
+
+    #[test]
+    fn output_wkt() {
+        let wkt = GeoreferencerBuilder::new().utm_zone(6).build().unwrap().output_wkt().unwrap();
+        assert!(wkt.starts_with("PROJCS[\"WGS 84 / UTM zone 6N\""));
+        let clarke = GeoreferencerBuilder::new()
+                         .utm_zone(6)
+                         .ellipsoid(6378206.4, 294.978698214)
+                         .build()
+                         .unwrap();
+        assert_eq!(None, clarke.output_wkt());
+        assert_eq!(None, clarke.output_wkt_in_zone(7));
+        let mut config = GeoreferencerBuilder::new().utm_zone(6).config;
+        config.output_crs = Some("EPSG:4979".to_string());
+        let wkt = Georeferencer::new(config).unwrap().output_wkt().unwrap();
+        assert!(wkt.ends_with("AUTHORITY[\"EPSG\",\"4979\"]]"));
+        let mut config = GeoreferencerBuilder::new().config;
+        config.output_crs = Some("EPSG:32706".to_string());
+        let wkt = Georeferencer::new(config).unwrap().output_wkt().unwrap();
+        assert!(wkt.starts_with("PROJCS[\"WGS 84 / UTM zone 6S\""));
+    }
+
+    #[test]
+    fn quaternion_orientation() {
+        let record = |time: f64, orientation: Option<Quaternion>| {
+            ImuGnssPoint {
+                time: time,
+                latitude: 0.8,
+                longitude: 0.1,
+                roll: 0.1,
+                pitch: -0.2,
+                yaw: 2.5,
+                orientation: orientation,
+                ..Default::default()
+            }
+        };
+        let orientation = Some(Quaternion::from_rpy(0.1, -0.2, 2.5));
+        let euler = ImuGnss::new(vec![record(0.0, None), record(1.0, None)]).unwrap();
+        let quaternion = ImuGnss::new(vec![record(0.0, orientation), record(1.0, orientation)])
+                             .unwrap();
+        for &output_frame in &[OutputFrame::Utm, OutputFrame::Ecef] {
+            let georeferencer = GeoreferencerBuilder::new()
+                                    .utm_zone(31)
+                                    .output_frame(output_frame)
+                                    .build()
+                                    .unwrap();
+            let mut from_euler = pabst::Point { x: 10.0, y: 20.0, z: 30.0, ..point(0.5) };
+            let mut from_quaternion = from_euler.clone();
+            assert!(georeferencer.georeference_point(&mut from_euler, &euler).unwrap());
+            assert!(georeferencer.georeference_point(&mut from_quaternion, &quaternion)
+                                 .unwrap());
+            assert!((from_euler.x - from_quaternion.x).abs() < 1e-6);
+            assert!((from_euler.y - from_quaternion.y).abs() < 1e-6);
+            assert!((from_euler.z - from_quaternion.z).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn slerp_matches_linear() {
+        // West of zone 6's central meridian, so the meridian convergence is a few degrees, with
+        // the rotation order from data/config.toml, whose yaw turns clockwise.
+        let record = |time: f64, yaw: f64| {
+            ImuGnssPoint {
+                time: time,
+                latitude: 61f64.to_radians(),
+                longitude: -150f64.to_radians(),
+                roll: 0.1,
+                pitch: -0.2,
+                yaw: yaw,
+                ..Default::default()
+            }
+        };
+        let imu_gnss = ImuGnss::new(vec![record(0.0, 2.5), record(1.0, 2.6)]).unwrap();
+        let georeference = |attitude| {
+            let georeferencer = GeoreferencerBuilder::new()
+                                    .utm_zone(6)
+                                    .rotation_order("r3(-yaw)", "r1(pitch)", "r2(roll)")
+                                    .attitude_interpolation(attitude)
+                                    .heading_offset(5.0)
+                                    .build()
+                                    .unwrap();
+            let mut point = pabst::Point { x: 10.0, y: 20.0, z: 30.0, ..point(0.0) };
+            assert!(georeferencer.georeference_point(&mut point, &imu_gnss).unwrap());
+            point
+        };
+        let linear = georeference(AttitudeInterpolation::Linear);
+        let slerp = georeference(AttitudeInterpolation::Slerp);
+        assert!((linear.x - slerp.x).abs() < 1e-6, "{} {}", linear.x, slerp.x);
+        assert!((linear.y - slerp.y).abs() < 1e-6, "{} {}", linear.y, slerp.y);
+        assert!((linear.z - slerp.z).abs() < 1e-6, "{} {}", linear.z, slerp.z);
+    }
+
+    #[test]
+    fn ground_scale() {
+        let imu_gnss = imu_gnss();
+        let mut config = GeoreferencerBuilder::new().utm_zone(31).config;
+        config.ground_scale = Some(GroundScaleConfig {
+            latitude: 0.0,
+            longitude: 0.0,
+            height: 0.0,
+            factor: Some(0.5),
+        });
+        let georeferencer = Georeferencer::new(config.clone()).unwrap();
+        assert_eq!(Some(0.5), georeferencer.ground_scale_factor());
+        let grid = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+        let mut on_grid = pabst::Point { x: 10.0, ..point(0.5) };
+        let mut on_ground = on_grid.clone();
+        assert!(grid.georeference_point(&mut on_grid, &imu_gnss).unwrap());
+        assert!(georeferencer.georeference_point(&mut on_ground, &imu_gnss).unwrap());
+        // The trajectory is at the origin, so points are scaled away from it.
+        let origin = on_grid.x - 10.0;
+        assert!((on_ground.x - (origin + 20.0)).abs() < 1e-6);
+        config.output_frame = Some("ecef".to_string());
+        assert!(Georeferencer::new(config).is_err());
+    }
+
+    #[test]
+    fn scanners() {
+        let config = GeorefConfig {
+            scanner: Some(vec![ScannerConfig {
+                                   lever_arm: Some(Vec3::new(1.0, 2.0, 3.0)),
+                                   ..ScannerConfig::new("left")
+                               },
+                               ScannerConfig {
+                                   intrinsics: Some(IntrinsicsConfig {
+                                       range_scale: Some(1.001),
+                                       ..Default::default()
+                                   }),
+                                   ..ScannerConfig::new("right")
+                               }]),
+            ..GeoreferencerBuilder::new().utm_zone(6).time_offset(0.5).config
+        };
+        assert_eq!(vec!["left".to_string(), "right".to_string()], config.scanner_ids());
+        let left = config.clone().for_scanner("left").unwrap();
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0), left.lever_arm);
+        assert_eq!(Some(0.5), left.time_offset);
+        assert!(left.scanner.is_none());
+        let georeferencer = Georeferencer::new(config.clone()).unwrap();
+        assert_eq!(1, georeferencer.scanners[0].point_source_id);
+        assert_eq!(2, georeferencer.scanners[1].point_source_id);
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0),
+                   georeferencer.scanner("left").unwrap().lever_arm);
+        assert!(georeferencer.scanner("center").is_none());
+        let right = config.clone().for_scanner("right").unwrap();
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0), right.lever_arm);
+        assert_eq!(Some(1.001), right.intrinsics.and_then(|intrinsics| intrinsics.range_scale));
+        assert!(left.intrinsics.is_none());
+        assert!(config.clone().for_scanner("center").is_err());
+        assert!(config.validate().is_empty());
+
+        let mut config = config;
+        config.scanner = Some(vec![ScannerConfig {
+                                       lever_arm: Some(Vec3::new(0.0, 0.0, 1000.0)),
+                                       ..ScannerConfig::new("left")
+                                   },
+                                   ScannerConfig::new("left")]);
+        let errors = config.validate();
+        assert_eq!(2, errors.len());
+        match errors[0] {
+            Error::InvalidField("lever_arm", ref message) => {
+                assert!(message.starts_with("scanner left: "))
+            }
+            ref other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn channels() {
+        let config = GeorefConfig {
+            scanner: Some(vec![ScannerConfig::new("left"), ScannerConfig::new("right")]),
+            ..GeoreferencerBuilder::new().utm_zone(31).config
+        };
+        let georeferencer = Georeferencer::new(config).unwrap();
+        let mut left = vec![point(0.1), point(0.4), point(0.5)];
+        let mut right = vec![point(0.2), point(0.3), point(0.6)];
+        let mut sink: Vec<pabst::Point> = Vec::new();
+        let summary = georeferencer.georeference_channels(&mut [(&mut left, "left"),
+                                                                (&mut right, "right")],
+                                                          &imu_gnss(),
+                                                          &mut sink)
+                                   .unwrap();
+        assert_eq!(6, summary.points_written);
+        assert!(summary.metrics.total >= summary.metrics.write);
+        assert_eq!(vec![Some(0.1), Some(0.2), Some(0.3), Some(0.4), Some(0.5), Some(0.6)],
+                   sink.iter().map(|p| p.gps_time).collect::<Vec<_>>());
+        assert_eq!(vec![Some(1), Some(2), Some(2), Some(1), Some(1), Some(2)],
+                   sink.iter().map(|p| p.point_source_id).collect::<Vec<_>>());
+        let mut center = vec![point(0.1)];
+        assert!(georeferencer.georeference_channels(&mut [(&mut center, "center")],
+                                                    &imu_gnss(),
+                                                    &mut sink)
+                             .is_err());
+    }
+
+    #[test]
+    fn bounds() {
+        let point = |x, y, z| pabst::Point { x: x, y: y, z: z, ..Default::default() };
+        let mut bounds = Bounds::new(&point(1.0, 2.0, 3.0));
+        bounds.grow(&point(-1.0, 5.0, 3.0));
+        assert_eq!(Bounds {
+                       min_x: -1.0,
+                       min_y: 2.0,
+                       min_z: 3.0,
+                       max_x: 1.0,
+                       max_y: 5.0,
+                       max_z: 3.0,
+                   },
+                   bounds);
+    }
+
+    #[test]
+    fn error_policies() {
+        let imu_gnss = imu_gnss();
+        let builder = || GeoreferencerBuilder::new().utm_zone(31);
+        let strict = builder().build().unwrap();
+        assert!(strict.georeference_point(&mut point(2.0), &imu_gnss).is_err());
+        let skip = builder().error_policy(ErrorPolicy::Skip).build().unwrap();
+        let mut skipped = point(2.0);
+        assert!(!skip.georeference_point(&mut skipped, &imu_gnss).unwrap());
+        assert_eq!(point(2.0), skipped);
+        let clamp = builder().error_policy(ErrorPolicy::ClampToNearest).build().unwrap();
+        assert!(clamp.georeference_point(&mut point(2.0), &imu_gnss).unwrap());
+        assert!(clamp.georeference_point(&mut point(-1.0), &imu_gnss).unwrap());
+    }
+
+    /// A trajectory that doesn't report its time bounds.
+    struct Unbounded(ImuGnss);
+
+    impl Trajectory for Unbounded {
+        fn pose_at(&self, time: f64, interpolation: &Interpolation) -> Result<Pose> {
+            self.0.pose_at(time, interpolation)
+        }
+    }
+
+    #[test]
+    fn clamp_needs_time_bounds() {
+        let unbounded = Unbounded(imu_gnss());
+        let clamp = GeoreferencerBuilder::new()
+                        .utm_zone(31)
+                        .error_policy(ErrorPolicy::ClampToNearest)
+                        .build()
+                        .unwrap();
+        assert!(clamp.georeference_point(&mut point(0.5), &unbounded).unwrap());
+        match clamp.georeference_point(&mut point(2.0), &unbounded) {
+            Err(Error::InvalidField("error_policy", _)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn attributes_pass_through() {
+        let imu_gnss = imu_gnss();
+        for &output_frame in &[OutputFrame::Utm, OutputFrame::Ecef, OutputFrame::Geographic] {
+            let georeferencer = GeoreferencerBuilder::new()
+                                    .utm_zone(31)
+                                    .output_frame(output_frame)
+                                    .lever_arm(1.0, 2.0, 3.0)
+                                    .build()
+                                    .unwrap();
+            let original = pabst::Point {
+                x: 10.0,
+                y: 20.0,
+                z: 30.0,
+                intensity: Some(1234),
+                ..point(0.5)
+            };
+            let mut georeferenced = original.clone();
+            assert!(georeferencer.georeference_point(&mut georeferenced, &imu_gnss).unwrap());
+            assert!(georeferenced.x != original.x);
+            let restored = pabst::Point {
+                x: original.x,
+                y: original.y,
+                z: original.z,
+                ..georeferenced
+            };
+            assert_eq!(original, restored);
+        }
+    }
+
+    #[test]
+    fn coverage_fraction() {
+        assert_eq!(1.0, Coverage::default().fraction());
+        let coverage = Coverage {
+            points_sampled: 4,
+            points_covered: 1,
+            ..Default::default()
+        };
+        assert_eq!(0.25, coverage.fraction());
+    }
+
+    #[test]
+    fn time_system() {
+        let imu_gnss = imu_gnss();
+        let config = GeorefConfig {
+            time_system: Some(TimeSystemConfig {
+                point_time: Some("standard".to_string()),
+                trajectory_time: Some("adjusted".to_string()),
+                gps_week: None,
+            }),
+            ..GeoreferencerBuilder::new().utm_zone(31).config
+        };
+        let georeferencer = Georeferencer::new(config).unwrap();
+        assert!(georeferencer.georeference_point(&mut point(1e9 + 0.5), &imu_gnss).unwrap());
+        let georeferencer = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+        assert!(georeferencer.georeference_point(&mut point(1e9 + 0.5), &imu_gnss).is_err());
+    }
+
+    #[test]
+    fn point_source_id() {
+        let imu_gnss = imu_gnss();
+        let mut georeferencer = GeoreferencerBuilder::new()
+                                    .utm_zone(31)
+                                    .point_source_id(3)
+                                    .build()
+                                    .unwrap();
+        let original = pabst::Point { point_source_id: Some(1), ..point(0.5) };
+        let mut georeferenced = original.clone();
+        assert!(georeferencer.georeference_point(&mut georeferenced, &imu_gnss).unwrap());
+        assert_eq!(Some(3), georeferenced.point_source_id);
+        georeferencer.set_point_source_id(None);
+        let mut georeferenced = original.clone();
+        assert!(georeferencer.georeference_point(&mut georeferenced, &imu_gnss).unwrap());
+        assert_eq!(Some(1), georeferenced.point_source_id);
+    }
+
+    #[test]
+    fn flight_lines() {
+        let imu_gnss = imu_gnss();
+        let mut georeferencer = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+        georeferencer.set_flight_lines(vec![FlightLine { number: 2, start: 0.25, end: 0.75 }]);
+        let mut georeferenced = point(0.5);
+        assert!(georeferencer.georeference_point(&mut georeferenced, &imu_gnss).unwrap());
+        assert_eq!(Some(2), georeferenced.point_source_id);
+        let mut georeferenced = point(0.9);
+        assert!(georeferencer.georeference_point(&mut georeferenced, &imu_gnss).unwrap());
+        assert_eq!(None, georeferenced.point_source_id);
+    }
+
+    #[test]
+    fn write_scan_angle() {
+        let imu_gnss = imu_gnss();
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(31)
+                                .write_scan_angle(true)
+                                .build()
+                                .unwrap();
+        let mut point = pabst::Point { y: 10.0, z: -10.0, ..point(0.5) };
+        assert!(georeferencer.georeference_point(&mut point, &imu_gnss).unwrap());
+        assert_eq!(Some(-45), point.scan_angle_rank);
+    }
+
+    #[test]
+    fn pose_interval() {
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint { time: 0.0, ..Default::default() },
+                                         ImuGnssPoint {
+                                             time: 1.0,
+                                             latitude: 0.001,
+                                             ..Default::default()
+                                         }])
+                           .unwrap();
+        let georeferencer = GeoreferencerBuilder::new()
+                                .utm_zone(31)
+                                .pose_interval(0.5)
+                                .build()
+                                .unwrap();
+        let (points, imu_gnss_points) = georeferencer.prepare_chunk(vec![point(0.1),
+                                                                         point(0.4),
+                                                                         point(0.6),
+                                                                         point(1.0)],
+                                                                    &imu_gnss,
+                                                                    0,
+                                                                    &mut Summary::default())
+                                                     .unwrap();
+        assert_eq!(4, points.len());
+        assert_eq!(imu_gnss_points[0], imu_gnss_points[1]);
+        assert!((imu_gnss_points[0].latitude - 0.00025).abs() < 1e-12);
+        assert!((imu_gnss_points[2].latitude - 0.00075).abs() < 1e-12);
+        // The middle of the last bucket is past the end of the trajectory.
+        assert!((imu_gnss_points[3].latitude - 0.001).abs() < 1e-12);
+        assert!(GeoreferencerBuilder::new().utm_zone(31).pose_interval(0.0).build().is_err());
+    }
+
+    #[test]
+    fn filter() {
+        let config = GeorefConfig {
+            filter: Some(FilterConfig { bounds: Some([0.0, 0.0, 1.0, 1.0]), ..Default::default() }),
+            ..GeoreferencerBuilder::new().utm_zone(31).config
+        };
+        let georeferencer = Georeferencer::new(config).unwrap();
+        let mut summary = Summary::default();
+        assert!(georeferencer.keep(&pabst::Point { x: 0.5, y: 0.5, ..Default::default() },
+                                   &mut summary));
+        assert!(!georeferencer.keep(&pabst::Point { x: 2.0, y: 0.5, ..Default::default() },
+                                    &mut summary));
+        assert_eq!(1, summary.points_written);
+        assert_eq!(1, summary.points_filtered);
+        assert_eq!(2, summary.points_processed());
+        assert_eq!(0.5, summary.bounds.unwrap().max_x);
+
+        let config = GeorefConfig {
+            filter: Some(FilterConfig { max_range: Some(5.0), ..Default::default() }),
+            ..GeoreferencerBuilder::new().utm_zone(31).config
+        };
+        let georeferencer = Georeferencer::new(config).unwrap();
+        let far = pabst::Point { x: 10.0, ..point(0.5) };
+        let mut summary = Summary::default();
+        let (points, _) = georeferencer.prepare_chunk(vec![point(0.5), far],
+                                                      &imu_gnss(),
+                                                      0,
+                                                      &mut summary)
+                                       .unwrap();
+        assert_eq!(1, points.len());
+        assert_eq!(0, summary.points_written);
+        assert_eq!(1, summary.points_filtered);
+    }
+
+    #[test]
+    fn max_interpolation_gap() {
+        let imu_gnss = imu_gnss();
+        let builder = || GeoreferencerBuilder::new().utm_zone(31).max_interpolation_gap(0.5);
+        let strict = builder().build().unwrap();
+        assert!(strict.georeference_point(&mut point(0.5), &imu_gnss).is_err());
+        let skip = builder().error_policy(ErrorPolicy::Skip).build().unwrap();
+        assert!(!skip.georeference_point(&mut point(0.5), &imu_gnss).unwrap());
+        let clamp = builder().error_policy(ErrorPolicy::ClampToNearest).build().unwrap();
+        assert!(clamp.georeference_point(&mut point(0.5), &imu_gnss).is_err());
+        let wide = GeoreferencerBuilder::new().utm_zone(31).max_interpolation_gap(1.0).build();
+        assert!(wide.unwrap().georeference_point(&mut point(0.5), &imu_gnss).unwrap());
+        for &max_gap in &[0.0, -1.0, f64::NAN] {
+            let mut config = GeoreferencerBuilder::new().utm_zone(31).config;
+            config.max_interpolation_gap = Some(max_gap);
+            assert!(Georeferencer::new(config).is_err());
+        }
+    }
+
+    #[test]
+    fn georeference_point_with_pose() {
+        let georeferencer = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+        let imu_gnss = imu_gnss();
+        let pose = imu_gnss.pose_at(0.5, &Default::default()).unwrap();
+        let mut from_trajectory = pabst::Point { x: 3.0, z: -2.0, ..point(0.5) };
+        let mut from_pose = pabst::Point { x: 3.0, z: -2.0, ..point(0.5) };
+        assert!(georeferencer.georeference_point(&mut from_trajectory, &imu_gnss).unwrap());
+        georeferencer.georeference_point_with_pose(&mut from_pose, &pose).unwrap();
+        assert_eq!(from_trajectory.x, from_pose.x);
+        assert_eq!(from_trajectory.y, from_pose.y);
+        assert_eq!(from_trajectory.z, from_pose.z);
+    }
+
+    #[test]
+    fn heading_offset() {
+        let imu_gnss = imu_gnss();
+        let builder = || GeoreferencerBuilder::new().utm_zone(31);
+        let mut turned = pabst::Point { x: 10.0, ..point(0.5) };
+        let mut corrected = pabst::Point { x: 10.0, ..point(0.5) };
+        let pose = imu_gnss.pose_at(0.5, &Default::default()).unwrap();
+        let pose = Pose { yaw: 30f64.to_radians(), ..pose };
+        builder().build().unwrap().georeference_point_with_pose(&mut turned, &pose).unwrap();
+        assert!(builder()
+                    .heading_offset(30.0)
+                    .build()
+                    .unwrap()
+                    .georeference_point(&mut corrected, &imu_gnss)
+                    .unwrap());
+        assert!((turned.x - corrected.x).abs() < 1e-9);
+        assert!((turned.y - corrected.y).abs() < 1e-9);
+        assert!(builder().heading_offset(::std::f64::NAN).build().is_err());
+    }
+
+    #[test]
+    fn shared_trajectory() {
+        let georeferencer = Arc::new(GeoreferencerBuilder::new().utm_zone(31).build().unwrap());
+        let imu_gnss = Arc::new(imu_gnss());
+        let handles: Vec<_> = (0..4)
+                                  .map(|i| {
+                                      let georeferencer = georeferencer.clone();
+                                      let imu_gnss = imu_gnss.clone();
+                                      thread::spawn(move || {
+                                          let mut point = point(i as f64 / 4.0);
+                                          georeferencer.georeference_point(&mut point, &*imu_gnss)
+                                                       .unwrap()
+                                      })
+                                  })
+                                  .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn resolve_paths() {
+        let mut config = GeorefConfig {
+            geoid: Some("/absolute/egm96.grd".to_string()),
+            orthophoto: Some("${GEOREF_TEST_UNSET_VARIABLE}/ortho.tif".to_string()),
+            ..Default::default()
+        };
+        assert!(config.resolve_paths(Path::new("/projects")).is_err());
+        config.orthophoto = Some("${HOME/ortho.tif".to_string());
+        assert!(config.resolve_paths(Path::new("/projects")).is_err());
+        config.orthophoto = None;
+        config.resolve_paths(Path::new("/projects")).unwrap();
+        assert_eq!("/absolute/egm96.grd", config.geoid.unwrap());
+    }
+
+    #[test]
+    fn polar() {
+        let imu_gnss = imu_gnss();
+        let mut config = GeoreferencerBuilder::new().utm_zone(31).config;
+        config.polar = Some(PolarConfig {
+            model: Some("profile".to_string()),
+            ..Default::default()
+        });
+        let mut measured = pabst::Point { x: 10.0, y: 90.0, z: 7.0, ..point(0.5) };
+        let mut cartesian = pabst::Point { y: 10.0, ..point(0.5) };
+        assert!(Georeferencer::new(config.clone())
+                    .unwrap()
+                    .georeference_point(&mut measured, &imu_gnss)
+                    .unwrap());
+        config.polar = None;
+        assert!(Georeferencer::new(config)
+                    .unwrap()
+                    .georeference_point(&mut cartesian, &imu_gnss)
+                    .unwrap());
+        assert!((measured.x - cartesian.x).abs() < 1e-6);
+        assert!((measured.y - cartesian.y).abs() < 1e-6);
+        assert!((measured.z - cartesian.z).abs() < 1e-6);
     }
 }
@@ -0,0 +1,154 @@
+//! Angle logs from stabilized scanner mounts.
+//!
+//! A stabilized, or gimbal, mount turns the scanner relative to the body to hold it level, and
+//! logs its own encoder angles as it goes. Those angles belong in the rotation chain between the
+//! boresight and the body frame: the boresight is then the scanner's rotation relative to the
+//! mount at zero angles, and the lever arm is measured to the mount's center of rotation.
+//!
+//! A mount log is delimited text, separated by commas or whitespace, with a time, roll, and pitch
+//! on each line, and a yaw for three-axis mounts. Blank lines and lines that start with `#` are
+//! skipped. Times must increase, and are in the trajectory's time system. The angles rotate the
+//! mount frame into the body frame as the z-y-x sequence, and are interpolated linearly between
+//! records; times before or after the log get its first or last angles. Mounts are configured in
+//! the `[georef]` table:
+//!
+//! ```toml
+//! gimbal = { path = "mount.csv", angle_units = "degrees" }
+//! ```
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use nalgebra::Rot3;
+
+use Result;
+use error::Error;
+use imu_gnss::Quaternion;
+
+/// A decodable description of a stabilized mount's angle log.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct GimbalConfig {
+    /// The path to the angle log.
+    pub path: String,
+    /// The units of the angles, either "degrees" (the default) or "radians".
+    pub angle_units: Option<String>,
+}
+
+/// A stabilized mount's angles over time.
+#[derive(Debug)]
+pub struct Gimbal {
+    times: Vec<f64>,
+    angles: Vec<[f64; 3]>,
+}
+
+impl Gimbal {
+    /// Reads the angle log that a config names.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::gimbal::{Gimbal, GimbalConfig};
+    /// let config = GimbalConfig { path: "mount.csv".to_string(), ..Default::default() };
+    /// let gimbal = Gimbal::new(&config).unwrap();
+    /// ```
+    pub fn new(config: &GimbalConfig) -> Result<Gimbal> {
+        Gimbal::from_reader(BufReader::new(try!(File::open(&config.path))), config)
+    }
+
+    /// Reads an angle log, with the units in a config.
+    pub fn from_reader<R: BufRead>(reader: R, config: &GimbalConfig) -> Result<Gimbal> {
+        let angle_factor = match config.angle_units.as_ref().map(|s| s.as_ref()) {
+            None | Some("degrees") => 1f64.to_radians(),
+            Some("radians") => 1.0,
+            Some(s) => return Err(Error::Gimbal(format!("unknown angle units: {}", s))),
+        };
+        let (mut times, mut angles) = (Vec::new(), Vec::new());
+        for (i, line) in reader.lines().enumerate() {
+            let line = try!(line);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut values = Vec::new();
+            for field in line.split(|c: char| c == ',' || c.is_whitespace())
+                             .filter(|field| !field.is_empty()) {
+                values.push(try!(field.parse::<f64>().map_err(|_| {
+                    Error::Gimbal(format!("line {}: could not parse {} as a number", i + 1, field))
+                })));
+            }
+            if values.len() != 3 && values.len() != 4 {
+                return Err(Error::Gimbal(format!("line {}: expected a time, roll, pitch, and \
+                                                  optionally a yaw, found {} values",
+                                                 i + 1,
+                                                 values.len())));
+            }
+            if times.last().map_or(false, |&last| !(values[0] > last)) {
+                return Err(Error::Gimbal(format!("line {}: times do not increase", i + 1)));
+            }
+            times.push(values[0]);
+            angles.push([values[1] * angle_factor,
+                         values[2] * angle_factor,
+                         values.get(3).map_or(0.0, |yaw| yaw * angle_factor)]);
+        }
+        if times.is_empty() {
+            return Err(Error::Gimbal("the angle log has no records".to_string()));
+        }
+        Ok(Gimbal {
+            times: times,
+            angles: angles,
+        })
+    }
+
+    /// Returns the roll, pitch, and yaw of the mount at a time, in radians.
+    pub fn angles_at(&self, time: f64) -> [f64; 3] {
+        let n = self.times.len();
+        if !(time > self.times[0]) {
+            return self.angles[0];
+        }
+        if !(time < self.times[n - 1]) {
+            return self.angles[n - 1];
+        }
+        let i = match self.times.binary_search_by(|t| t.partial_cmp(&time).unwrap()) {
+            Ok(i) => return self.angles[i],
+            Err(i) => i - 1,
+        };
+        let factor = (time - self.times[i]) / (self.times[i + 1] - self.times[i]);
+        let (a, b) = (self.angles[i], self.angles[i + 1]);
+        [a[0] + (b[0] - a[0]) * factor,
+         a[1] + (b[1] - a[1]) * factor,
+         a[2] + (b[2] - a[2]) * factor]
+    }
+
+    /// Returns the rotation from the mount frame into the body frame at a time.
+    pub fn rotation_at(&self, time: f64) -> Rot3<f64> {
+        let angles = self.angles_at(time);
+        Quaternion::from_rpy(angles[0], angles[1], angles[2]).to_rot3()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_and_interpolate() {
+        let text = "# time roll pitch\n1.0, 2.0, -4.0\n\n2.0 4.0 0.0\n";
+        let gimbal = Gimbal::from_reader(text.as_bytes(), &GimbalConfig::default()).unwrap();
+        let angles = gimbal.angles_at(1.5);
+        assert!((angles[0] - 3f64.to_radians()).abs() < 1e-12);
+        assert!((angles[1] + 2f64.to_radians()).abs() < 1e-12);
+        assert_eq!(0.0, angles[2]);
+        assert_eq!(gimbal.angles_at(0.0), gimbal.angles_at(1.0));
+        assert_eq!(gimbal.angles_at(3.0), gimbal.angles_at(2.0));
+    }
+
+    #[test]
+    fn bad_logs() {
+        let config = GimbalConfig::default();
+        assert!(Gimbal::from_reader("".as_bytes(), &config).is_err());
+        assert!(Gimbal::from_reader("1,2\n".as_bytes(), &config).is_err());
+        assert!(Gimbal::from_reader("2,0,0\n1,0,0\n".as_bytes(), &config).is_err());
+        let config = GimbalConfig { angle_units: Some("gradians".to_string()), ..config };
+        assert!(Gimbal::from_reader("1,0,0\n".as_bytes(), &config).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Grid-to-ground scaling of UTM output.
+//!
+//! UTM distances are grid distances: they're stretched or shrunk by the projection's point scale
+//! factor, and measured on the ellipsoid rather than at the height of the project. Surveyors often
+//! want ground distances instead. The combined factor is the point scale factor times the
+//! elevation factor, `R / (R + h)`, where `R` is the ellipsoid's Gaussian mean radius. It is
+//! computed once, at a project origin, and every point's easting and northing are scaled about
+//! that origin:
+//!
+//! ```text
+//! ground = origin + (grid - origin) / combined factor
+//! ```
+//!
+//! Heights aren't scaled. A known combined factor can be given instead of the computed one:
+//!
+//! ```toml
+//! [georef.ground_scale]
+//! latitude = 61.2
+//! longitude = -149.9
+//! height = 100.0
+//! ```
+
+use nalgebra::Vec3;
+
+use Result;
+use ellipsoid::Ellipsoid;
+use error::Error;
+
+/// A decodable description of grid-to-ground scaling.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct GroundScaleConfig {
+    /// The latitude of the project origin, in degrees.
+    pub latitude: f64,
+    /// The longitude of the project origin, in degrees.
+    pub longitude: f64,
+    /// The ellipsoidal height of the project origin, in meters.
+    pub height: f64,
+    /// The combined factor to use instead of the one computed at the origin.
+    pub factor: Option<f64>,
+}
+
+impl GroundScaleConfig {
+    /// Returns a message for an origin off the globe, a height that isn't finite, and a factor that
+    /// isn't positive.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(self.latitude.abs() <= 90.0) {
+            problems.push("latitude must be between -90 and 90".to_string());
+        }
+        if !(self.longitude.abs() <= 180.0) {
+            problems.push("longitude must be between -180 and 180".to_string());
+        }
+        if !self.height.is_finite() {
+            problems.push("height must be finite".to_string());
+        }
+        if self.factor.map_or(false, |factor| !(factor > 0.0 && factor.is_finite())) {
+            problems.push("factor must be greater than zero".to_string());
+        }
+        problems
+    }
+}
+
+/// Grid-to-ground scaling, ready to be applied to UTM points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GroundScale {
+    easting: f64,
+    northing: f64,
+    factor: f64,
+}
+
+impl GroundScale {
+    /// Creates a grid-to-ground scaling from its configuration, for points in a UTM zone
+    /// projected on an ellipsoid.
+    pub fn new(config: &GroundScaleConfig,
+               utm_zone: u8,
+               ellipsoid: Ellipsoid)
+               -> Result<GroundScale> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("ground_scale", problem));
+        }
+        let (latitude, longitude) = (config.latitude.to_radians(), config.longitude.to_radians());
+        let (northing, easting, _) = ellipsoid.to_utm(latitude, longitude, utm_zone);
+        Ok(GroundScale {
+            easting: easting,
+            northing: northing,
+            factor: config.factor.unwrap_or_else(|| {
+                ellipsoid.utm_scale_factor(latitude, longitude, utm_zone) *
+                ellipsoid.elevation_factor(latitude, config.height)
+            }),
+        })
+    }
+
+    /// Returns the combined factor, grid distance over ground distance.
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    /// Scales a UTM point from grid to ground distances.
+    pub fn apply(&self, p: Vec3<f64>) -> Vec3<f64> {
+        Vec3::new(self.easting + (p.x - self.easting) / self.factor,
+                  self.northing + (p.y - self.northing) / self.factor,
+                  p.z)
+    }
+
+    /// Scales a UTM point from ground back to grid distances, undoing `apply`.
+    pub fn unapply(&self, p: Vec3<f64>) -> Vec3<f64> {
+        Vec3::new(self.easting + (p.x - self.easting) * self.factor,
+                  self.northing + (p.y - self.northing) * self.factor,
+                  p.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::Vec3;
+
+    use ellipsoid::Ellipsoid;
+
+    fn config() -> GroundScaleConfig {
+        GroundScaleConfig {
+            latitude: 0.0,
+            longitude: 3.0,
+            height: 0.0,
+            factor: None,
+        }
+    }
+
+    fn scale(config: GroundScaleConfig) -> GroundScale {
+        GroundScale::new(&config, 31, Ellipsoid::wgs84()).unwrap()
+    }
+
+    #[test]
+    fn factor() {
+        // On the central meridian at the ellipsoid, only the projection's scale applies.
+        assert!((scale(config()).factor() - 0.9996).abs() < 1e-9);
+        let high = scale(GroundScaleConfig { height: 1000.0, ..config() });
+        assert!((high.factor() - 0.9996 * 0.99984).abs() < 1e-5);
+        let east = scale(GroundScaleConfig { longitude: 6.0, ..config() });
+        assert!(east.factor() > 1.0);
+    }
+
+    #[test]
+    fn apply() {
+        let halved = scale(GroundScaleConfig { factor: Some(0.5), ..config() });
+        let p = halved.apply(Vec3::new(500100.0, 10.0, 42.0));
+        assert!((p.x - 500200.0).abs() < 1e-6);
+        assert!((p.y - 20.0).abs() < 1e-6);
+        assert_eq!(42.0, p.z);
+    }
+
+    #[test]
+    fn problems() {
+        let config = GroundScaleConfig {
+            latitude: 91.0,
+            factor: Some(0.0),
+            ..config()
+        };
+        assert_eq!(vec!["latitude must be between -90 and 90".to_string(),
+                        "factor must be greater than zero".to_string()],
+                   config.problems());
+        assert!(GroundScale::new(&config, 31, Ellipsoid::wgs84()).is_err());
+    }
+}
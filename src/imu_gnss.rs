@@ -0,0 +1,1573 @@
+//! IMU/GNSS trajectory records and interpolation.
+
+use std::cmp::Ordering;
+use std::f64;
+use std::f64::consts::PI;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::u16;
+
+use nalgebra::{Col, Eye, Rot3, Vec3};
+use pos;
+
+use Result;
+use ellipsoid::Ellipsoid;
+use error::Error;
+use flight_line::{FlightLine, FlightLineConfig};
+use outlier::OutlierConfig;
+use point::natural_utm_zone;
+use smoothing::{self, SmoothingConfig};
+use trajectory::{AttitudeInterpolation, Interpolation, Pose, PositionInterpolation, Trajectory,
+                 TrajectoryStats};
+
+/// The shortest step over which heading rates are measured, in seconds.
+const HEADING_RATE_STEP: f64 = 1.0;
+/// How far apart, in meters, merged trajectories' overlapping records may be.
+const MAX_MERGE_DISAGREEMENT: f64 = 0.1;
+/// How far apart, in degrees, merged trajectories' overlapping attitudes may be.
+const MAX_MERGE_ATTITUDE_DISAGREEMENT: f64 = 0.1;
+/// The longest interval, in seconds, that an overlapping record is compared across.
+const MAX_MERGE_COMPARISON_INTERVAL: f64 = 1.0;
+/// The mean radius of the earth, in meters, for measuring short distances between records.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// The estimated standard deviations of an IMU/GNSS record.
+///
+/// Positions are in meters and attitudes are in radians.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Accuracy {
+    /// The north position standard deviation.
+    pub north: f64,
+    /// The east position standard deviation.
+    pub east: f64,
+    /// The down position standard deviation.
+    pub down: f64,
+    /// The roll standard deviation.
+    pub roll: f64,
+    /// The pitch standard deviation.
+    pub pitch: f64,
+    /// The heading standard deviation.
+    pub heading: f64,
+}
+
+impl Accuracy {
+    /// Linearly interpolates between this accuracy and another.
+    pub fn interpolate(&self, other: &Accuracy, factor: f64) -> Accuracy {
+        let lerp = |a: f64, b: f64| a + (b - a) * factor;
+        Accuracy {
+            north: lerp(self.north, other.north),
+            east: lerp(self.east, other.east),
+            down: lerp(self.down, other.down),
+            roll: lerp(self.roll, other.roll),
+            pitch: lerp(self.pitch, other.pitch),
+            heading: lerp(self.heading, other.heading),
+        }
+    }
+}
+
+/// A record's position, projected into a UTM zone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UtmProjection {
+    /// The UTM zone.
+    pub utm_zone: u8,
+    /// The ellipsoid the position was projected on.
+    pub ellipsoid: Ellipsoid,
+    /// The northing, in meters.
+    pub northing: f64,
+    /// The easting, in meters.
+    pub easting: f64,
+    /// The meridian convergence, in radians.
+    pub meridian_convergence: f64,
+}
+
+impl UtmProjection {
+    /// Linearly interpolates between this projection and another.
+    ///
+    /// Returns `None` if they're in different zones or on different ellipsoids.
+    pub fn interpolate(&self, other: &UtmProjection, factor: f64) -> Option<UtmProjection> {
+        if self.utm_zone != other.utm_zone || self.ellipsoid != other.ellipsoid {
+            return None;
+        }
+        let lerp = |a: f64, b: f64| a + (b - a) * factor;
+        Some(UtmProjection {
+            utm_zone: self.utm_zone,
+            ellipsoid: self.ellipsoid,
+            northing: lerp(self.northing, other.northing),
+            easting: lerp(self.easting, other.easting),
+            meridian_convergence: lerp(self.meridian_convergence, other.meridian_convergence),
+        })
+    }
+}
+
+/// A single IMU/GNSS record.
+///
+/// Angles are in radians and the height is ellipsoidal, in meters.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ImuGnssPoint {
+    /// The gps time of this record.
+    pub time: f64,
+    /// The latitude, in radians.
+    pub latitude: f64,
+    /// The longitude, in radians.
+    pub longitude: f64,
+    /// The ellipsoidal height, in meters.
+    pub height: f64,
+    /// The roll, in radians.
+    pub roll: f64,
+    /// The pitch, in radians.
+    pub pitch: f64,
+    /// The yaw, in radians.
+    pub yaw: f64,
+    /// The rates of change of roll, pitch, and yaw, in radians per second, if known.
+    ///
+    /// These are used by Hermite attitude interpolation.
+    pub attitude_rate: Option<[f64; 3]>,
+    /// The attitude as a unit quaternion, if the trajectory provides one.
+    ///
+    /// When set, this is used instead of roll, pitch, and yaw, and the configured rotation order
+    /// is ignored. Roll, pitch, and yaw should still describe the same rotation, as the z-y-x
+    /// sequence, so statistics and flight lines work as usual.
+    pub orientation: Option<Quaternion>,
+    /// The estimated accuracy of this record, if known.
+    pub accuracy: Option<Accuracy>,
+    /// This record's position projected into a UTM zone, if it's been projected.
+    ///
+    /// See `ImuGnss::project_utm`. This must describe the same position as the latitude and
+    /// longitude.
+    pub utm: Option<UtmProjection>,
+}
+
+impl ImuGnssPoint {
+    /// Interpolates between this record and another at the given time.
+    fn interpolate(&self,
+                   other: &ImuGnssPoint,
+                   time: f64,
+                   interpolation: &Interpolation)
+                   -> ImuGnssPoint {
+        let factor = (time - self.time) / (other.time - self.time);
+        let lerp = |a: f64, b: f64| a + (b - a) * factor;
+        let orientation = match (self.orientation, other.orientation, interpolation.attitude) {
+            (Some(a), Some(b), _) => Some(a.slerp(&b, factor)),
+            (_, _, AttitudeInterpolation::Slerp) => {
+                let order = &interpolation.rotation_order;
+                let quaternion = |point: &ImuGnssPoint| {
+                    point.orientation.unwrap_or_else(|| {
+                        Quaternion::from_rot3(&order.rot3(point.roll, point.pitch, point.yaw))
+                    })
+                };
+                Some(quaternion(self).slerp(&quaternion(other), factor))
+            }
+            _ => None,
+        };
+        let (roll, pitch, yaw) = match (orientation, interpolation.attitude) {
+            (Some(orientation), _) => orientation.rpy(),
+            (None, AttitudeInterpolation::Hermite) if self.attitude_rate.is_some() &&
+                                                      other.attitude_rate.is_some() => {
+                let (a, b) = (self.attitude_rate.unwrap(), other.attitude_rate.unwrap());
+                let duration = other.time - self.time;
+                let hermite = |start: f64, end: f64, i: usize| {
+                    hermite(start, end, a[i] * duration, b[i] * duration, factor)
+                };
+                (hermite(self.roll, self.roll + angle_difference(self.roll, other.roll), 0),
+                 hermite(self.pitch, other.pitch, 1),
+                 hermite(self.yaw, self.yaw + angle_difference(self.yaw, other.yaw), 2))
+            }
+            (None, _) => {
+                if interpolation.attitude == AttitudeInterpolation::Hermite {
+                    trace!("no attitude rates at {}, interpolating attitude linearly", time);
+                }
+                (lerp_angle(self.roll, other.roll, factor),
+                 lerp(self.pitch, other.pitch),
+                 lerp_angle(self.yaw, other.yaw, factor))
+            }
+        };
+        ImuGnssPoint {
+            time: time,
+            latitude: lerp(self.latitude, other.latitude),
+            longitude: lerp_angle(self.longitude, other.longitude, factor),
+            height: lerp(self.height, other.height),
+            roll: roll,
+            pitch: pitch,
+            yaw: yaw,
+            attitude_rate: match (self.attitude_rate, other.attitude_rate) {
+                (Some(a), Some(b)) => Some([lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])]),
+                _ => None,
+            },
+            orientation: orientation,
+            accuracy: match (self.accuracy, other.accuracy) {
+                (Some(a), Some(b)) => Some(a.interpolate(&b, factor)),
+                _ => None,
+            },
+            utm: match (self.utm, other.utm) {
+                (Some(a), Some(b)) => a.interpolate(&b, factor),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Linearly interpolates between two angles, in radians, along the shorter way around the circle.
+///
+/// This keeps e.g. a heading that crosses from 359° to 1° from sweeping back through 180°.
+fn lerp_angle(a: f64, b: f64, factor: f64) -> f64 {
+    a + angle_difference(a, b) * factor
+}
+
+/// Interpolates between two values with a cubic Hermite spline, given their tangents scaled to
+/// the interval, at a factor between zero and one.
+fn hermite(start: f64, end: f64, start_tangent: f64, end_tangent: f64, factor: f64) -> f64 {
+    let (t2, t3) = (factor * factor, factor * factor * factor);
+    (2.0 * t3 - 3.0 * t2 + 1.0) * start + (t3 - 2.0 * t2 + factor) * start_tangent +
+    (-2.0 * t3 + 3.0 * t2) * end + (t3 - t2) * end_tangent
+}
+
+/// Interpolates a value at `time`, between `times[1]` and `times[2]`, with a Catmull-Rom spline.
+///
+/// The tangents at the inner knots are the slopes between their neighbors, which handles unevenly
+/// spaced records. The outer knots may repeat the inner ones at the ends of a trajectory.
+fn catmull_rom(times: [f64; 4], values: [f64; 4], time: f64) -> f64 {
+    let slope = |a: usize, b: usize| (values[b] - values[a]) / (times[b] - times[a]);
+    let (m1, m2) = (slope(0, 2), slope(1, 3));
+    let dt = times[2] - times[1];
+    let s = (time - times[1]) / dt;
+    let (s2, s3) = (s * s, s * s * s);
+    (2.0 * s3 - 3.0 * s2 + 1.0) * values[1] + (s3 - 2.0 * s2 + s) * dt * m1 +
+    (-2.0 * s3 + 3.0 * s2) * values[2] + (s3 - s2) * dt * m2
+}
+
+/// Returns the signed difference from one angle to another, in radians, between -π and π.
+fn angle_difference(a: f64, b: f64) -> f64 {
+    let mut difference = (b - a) % (2.0 * PI);
+    if difference > PI {
+        difference -= 2.0 * PI;
+    } else if difference < -PI {
+        difference += 2.0 * PI;
+    }
+    difference
+}
+
+/// Returns the approximate distance between two records, in meters, for records close together.
+fn distance(a: &ImuGnssPoint, b: &ImuGnssPoint) -> f64 {
+    let north = (b.latitude - a.latitude) * EARTH_RADIUS;
+    let east = angle_difference(a.longitude, b.longitude) * EARTH_RADIUS * a.latitude.cos();
+    let up = b.height - a.height;
+    (north * north + east * east + up * up).sqrt()
+}
+
+/// Returns merged records interpolated at a time they overlap, or `None` if the records around
+/// it are too far apart to compare against.
+fn overlapping(points: &[ImuGnssPoint], time: f64) -> Option<ImuGnssPoint> {
+    let search = points.binary_search_by(|p| p.time.partial_cmp(&time).unwrap_or(Ordering::Equal));
+    let i = match search {
+        Ok(i) => return Some(points[i]),
+        Err(i) => i,
+    };
+    if i == 0 || i == points.len() ||
+       points[i].time - points[i - 1].time > MAX_MERGE_COMPARISON_INTERVAL {
+        return None;
+    }
+    Some(points[i - 1].interpolate(&points[i], time, &Interpolation::default()))
+}
+
+/// A unit quaternion that rotates body vectors into the navigation frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    /// The scalar part.
+    pub w: f64,
+    /// The x part.
+    pub x: f64,
+    /// The y part.
+    pub y: f64,
+    /// The z part.
+    pub z: f64,
+}
+
+impl Quaternion {
+    /// Creates a unit quaternion from its scalar and vector parts, normalizing them.
+    ///
+    /// Returns `None` if the parts have no length or aren't finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::Quaternion;
+    /// let q = Quaternion::new(2.0, 0.0, 0.0, 0.0).unwrap();
+    /// assert_eq!(1.0, q.w);
+    /// assert!(Quaternion::new(0.0, 0.0, 0.0, 0.0).is_none());
+    /// ```
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Option<Quaternion> {
+        let norm = (w * w + x * x + y * y + z * z).sqrt();
+        if norm > 0.0 && norm.is_finite() {
+            Some(Quaternion {
+                w: w / norm,
+                x: x / norm,
+                y: y / norm,
+                z: z / norm,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Creates a quaternion from a z-y-x rotation sequence.
+    pub fn from_rpy(roll: f64, pitch: f64, yaw: f64) -> Quaternion {
+        let (sr, cr) = (roll / 2.0).sin_cos();
+        let (sp, cp) = (pitch / 2.0).sin_cos();
+        let (sy, cy) = (yaw / 2.0).sin_cos();
+        Quaternion {
+            w: cr * cp * cy + sr * sp * sy,
+            x: sr * cp * cy - cr * sp * sy,
+            y: cr * sp * cy + sr * cp * sy,
+            z: cr * cp * sy - sr * sp * cy,
+        }
+    }
+
+    /// Creates a quaternion from a rotation matrix.
+    pub fn from_rot3(rot3: &Rot3<f64>) -> Quaternion {
+        let x = *rot3 * Vec3::new(1.0, 0.0, 0.0);
+        let y = *rot3 * Vec3::new(0.0, 1.0, 0.0);
+        let z = *rot3 * Vec3::new(0.0, 0.0, 1.0);
+        let trace = x.x + y.y + z.z;
+        // Dividing by the largest of the four parts keeps this stable near half turns.
+        let (w, qx, qy, qz) = if trace > 0.0 {
+            let s = 2.0 * (1.0 + trace).sqrt();
+            (s / 4.0, (y.z - z.y) / s, (z.x - x.z) / s, (x.y - y.x) / s)
+        } else if x.x > y.y && x.x > z.z {
+            let s = 2.0 * (1.0 + x.x - y.y - z.z).sqrt();
+            ((y.z - z.y) / s, s / 4.0, (y.x + x.y) / s, (z.x + x.z) / s)
+        } else if y.y > z.z {
+            let s = 2.0 * (1.0 + y.y - x.x - z.z).sqrt();
+            ((z.x - x.z) / s, (y.x + x.y) / s, s / 4.0, (z.y + y.z) / s)
+        } else {
+            let s = 2.0 * (1.0 + z.z - x.x - y.y).sqrt();
+            ((x.y - y.x) / s, (z.x + x.z) / s, (z.y + y.z) / s, s / 4.0)
+        };
+        Quaternion {
+            w: w,
+            x: qx,
+            y: qy,
+            z: qz,
+        }
+    }
+
+    /// Returns the roll, pitch, and yaw of the z-y-x rotation sequence for this quaternion.
+    pub fn rpy(&self) -> (f64, f64, f64) {
+        let (w, x, y, z) = (self.w, self.x, self.y, self.z);
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let pitch = (2.0 * (w * y - z * x)).max(-1.0).min(1.0).asin();
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+        (roll, pitch, yaw)
+    }
+
+    /// Returns the rotation matrix of this quaternion.
+    pub fn to_rot3(&self) -> Rot3<f64> {
+        let (w, x, y, z) = (self.w, self.x, self.y, self.z);
+        let mut rot3 = Rot3::new_identity(3);
+        rot3.set_col(0,
+                     Vec3::new(1.0 - 2.0 * (y * y + z * z),
+                               2.0 * (x * y + w * z),
+                               2.0 * (x * z - w * y)));
+        rot3.set_col(1,
+                     Vec3::new(2.0 * (x * y - w * z),
+                               1.0 - 2.0 * (x * x + z * z),
+                               2.0 * (y * z + w * x)));
+        rot3.set_col(2,
+                     Vec3::new(2.0 * (x * z + w * y),
+                               2.0 * (y * z - w * x),
+                               1.0 - 2.0 * (x * x + y * y)));
+        rot3
+    }
+
+    /// Spherically interpolates between this quaternion and another.
+    pub fn slerp(&self, other: &Quaternion, factor: f64) -> Quaternion {
+        let mut other = *other;
+        let mut dot = self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z;
+        if dot < 0.0 {
+            other = Quaternion {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            };
+            dot = -dot;
+        }
+        let (a, b) = if dot > 0.9995 {
+            (1.0 - factor, factor)
+        } else {
+            let theta = dot.acos();
+            (((1.0 - factor) * theta).sin() / theta.sin(),
+             (factor * theta).sin() / theta.sin())
+        };
+        let q = Quaternion {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        };
+        let norm = (q.w * q.w + q.x * q.x + q.y * q.y + q.z * q.z).sqrt();
+        Quaternion {
+            w: q.w / norm,
+            x: q.x / norm,
+            y: q.y / norm,
+            z: q.z / norm,
+        }
+    }
+}
+
+/// What to do with trajectory records whose times don't increase monotonically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepairPolicy {
+    /// Refuse the trajectory with an error.
+    Error,
+    /// Sort the records by time, then drop any that repeat an earlier record's time.
+    Sort,
+    /// Drop every record that isn't later than the last record kept, without sorting.
+    DropDuplicates,
+}
+
+impl Default for RepairPolicy {
+    fn default() -> RepairPolicy {
+        RepairPolicy::Error
+    }
+}
+
+impl FromStr for RepairPolicy {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<RepairPolicy> {
+        match s {
+            "error" => Ok(RepairPolicy::Error),
+            "sort" => Ok(RepairPolicy::Sort),
+            "drop_duplicates" => Ok(RepairPolicy::DropDuplicates),
+            _ => Err(Error::InvalidField("repair", format!("unknown repair policy: {}", s))),
+        }
+    }
+}
+
+/// How many records were affected when a trajectory was repaired.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Repair {
+    /// The number of records that were earlier than the record before them, and were sorted.
+    pub reordered: usize,
+    /// The number of records that were dropped.
+    pub dropped: usize,
+}
+
+impl Repair {
+    /// Returns true if no records were affected.
+    pub fn is_empty(&self) -> bool {
+        self.reordered == 0 && self.dropped == 0
+    }
+}
+
+/// An in-memory IMU/GNSS trajectory.
+///
+/// Trajectories are `Send` and `Sync`, so one can be shared by threads that georeference
+/// different points.
+#[derive(Debug)]
+pub struct ImuGnss {
+    points: Vec<ImuGnssPoint>,
+    /// The index of the last lookup, which is a hint for the next one and so needn't be exact.
+    index: AtomicUsize,
+}
+
+impl ImuGnss {
+    /// Creates a new trajectory from a vector of records.
+    ///
+    /// The records' times must increase monotonically.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::imu_gnss::{ImuGnss, ImuGnssPoint};
+    /// let points = vec![ImuGnssPoint { time: 1.0, ..Default::default() },
+    ///                   ImuGnssPoint { time: 2.0, ..Default::default() }];
+    /// let imu_gnss = ImuGnss::new(points).unwrap();
+    /// ```
+    pub fn new(points: Vec<ImuGnssPoint>) -> Result<ImuGnss> {
+        if points.windows(2).any(|w| w[1].time <= w[0].time) {
+            return Err(Error::NonmonotonicImuGnssRecords);
+        }
+        Ok(ImuGnss {
+            points: points,
+            index: AtomicUsize::new(0),
+        })
+    }
+
+    /// Creates a new trajectory from a vector of records, repairing records whose times don't
+    /// increase monotonically, e.g. a single duplicated epoch, according to a policy.
+    ///
+    /// Returns the trajectory and how many records were affected. Under `RepairPolicy::Error`
+    /// this is the same as `ImuGnss::new`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::imu_gnss::{ImuGnss, ImuGnssPoint, RepairPolicy};
+    /// let points = vec![ImuGnssPoint { time: 1.0, ..Default::default() },
+    ///                   ImuGnssPoint { time: 2.0, ..Default::default() },
+    ///                   ImuGnssPoint { time: 2.0, ..Default::default() },
+    ///                   ImuGnssPoint { time: 3.0, ..Default::default() }];
+    /// let (imu_gnss, repair) = ImuGnss::sanitize(points, RepairPolicy::DropDuplicates).unwrap();
+    /// assert_eq!(3, imu_gnss.len());
+    /// assert_eq!(1, repair.dropped);
+    /// ```
+    pub fn sanitize(mut points: Vec<ImuGnssPoint>,
+                    policy: RepairPolicy)
+                    -> Result<(ImuGnss, Repair)> {
+        let mut repair = Repair::default();
+        if policy == RepairPolicy::Error {
+            return ImuGnss::new(points).map(|imu_gnss| (imu_gnss, repair));
+        }
+        if policy == RepairPolicy::Sort {
+            repair.reordered = points.windows(2).filter(|w| w[1].time < w[0].time).count();
+            points.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+        }
+        let n = points.len();
+        let mut last = f64::NEG_INFINITY;
+        points.retain(|point| {
+            let keep = point.time > last;
+            if keep {
+                last = point.time;
+            }
+            keep
+        });
+        repair.dropped = n - points.len();
+        if !repair.is_empty() {
+            warn!("repaired trajectory records out of time order: {} reordered, {} dropped",
+                  repair.reordered,
+                  repair.dropped);
+        }
+        ImuGnss::new(points).map(|imu_gnss| (imu_gnss, repair))
+    }
+
+    /// Reads every record of a `pos` source, e.g. a .pos file, into memory, repairing records out
+    /// of time order according to a policy.
+    ///
+    /// Unlike a `pos::Interpolator`, the trajectory has time bounds and statistics, and honors
+    /// every interpolation option and error policy.
+    pub fn from_pos_source(source: &mut pos::Source,
+                           policy: RepairPolicy)
+                           -> Result<(ImuGnss, Repair)> {
+        let mut points = Vec::new();
+        while let Some(point) = try!(source.source()) {
+            points.push(ImuGnssPoint {
+                time: point.time,
+                latitude: point.latitude.0,
+                longitude: point.longitude.0,
+                height: point.altitude,
+                roll: point.roll.0,
+                pitch: point.pitch.0,
+                yaw: point.yaw.0,
+                ..Default::default()
+            });
+        }
+        ImuGnss::sanitize(points, policy)
+    }
+
+    /// Merges trajectories, e.g. from a mission split across several files, into one.
+    ///
+    /// The trajectories are ordered by their first records. Where they overlap, the earlier
+    /// trajectory's records are kept and the later one's are dropped, but each dropped record must
+    /// be within 10 cm and a tenth of a degree of the kept records interpolated at its time, so
+    /// re-initialized solutions that disagree aren't silently stitched together. Records are only
+    /// compared where the kept records are at most a second apart. Gaps between trajectories are
+    /// left for georeferencing to handle, like any other gap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::imu_gnss::{ImuGnss, ImuGnssPoint};
+    /// let trajectory = |times: Vec<f64>| {
+    ///     ImuGnss::new(times.into_iter()
+    ///                       .map(|time| ImuGnssPoint { time: time, ..Default::default() })
+    ///                       .collect())
+    ///         .unwrap()
+    /// };
+    /// let first = trajectory(vec![0.0, 1.0, 2.0]);
+    /// let second = trajectory(vec![2.0, 3.0, 4.0]);
+    /// let imu_gnss = ImuGnss::merge(vec![second, first]).unwrap();
+    /// assert_eq!(5, imu_gnss.len());
+    /// ```
+    pub fn merge(trajectories: Vec<ImuGnss>) -> Result<ImuGnss> {
+        let mut trajectories: Vec<ImuGnss> = trajectories.into_iter()
+                                                         .filter(|t| !t.is_empty())
+                                                         .collect();
+        trajectories.sort_by(|a, b| {
+            a.points[0].time.partial_cmp(&b.points[0].time).unwrap_or(Ordering::Equal)
+        });
+        let mut points: Vec<ImuGnssPoint> = Vec::new();
+        let mut dropped = 0;
+        for trajectory in trajectories {
+            for point in trajectory.points {
+                let end = points.last().map(|last| last.time);
+                if end.map_or(true, |end| point.time > end) {
+                    if let Some(end) = end {
+                        debug!("joining trajectories across {} s", point.time - end);
+                    }
+                    points.push(point);
+                    continue;
+                }
+                if let Some(kept) = overlapping(&points, point.time) {
+                    let distance = distance(&kept, &point);
+                    if distance > MAX_MERGE_DISAGREEMENT {
+                        return Err(Error::DisagreeingImuGnssRecords(point.time, distance));
+                    }
+                    let angle = [(kept.roll, point.roll),
+                                 (kept.pitch, point.pitch),
+                                 (kept.yaw, point.yaw)]
+                                    .iter()
+                                    .map(|&(a, b)| angle_difference(a, b).abs().to_degrees())
+                                    .fold(0.0, f64::max);
+                    if angle > MAX_MERGE_ATTITUDE_DISAGREEMENT {
+                        return Err(Error::DisagreeingImuGnssAttitudes(point.time, angle));
+                    }
+                }
+                dropped += 1;
+            }
+        }
+        debug!("merged trajectories into {} records, dropping {} overlapping records",
+               points.len(),
+               dropped);
+        ImuGnss::new(points)
+    }
+
+    /// Returns this trajectory's records.
+    pub fn points(&self) -> &[ImuGnssPoint] {
+        &self.points
+    }
+
+    /// Returns the number of records.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns true if there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Returns the times of the first and last records, or `None` if there are fewer than two.
+    pub fn time_bounds(&self) -> Option<(f64, f64)> {
+        match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) if self.points.len() > 1 => Some((first.time, last.time)),
+            _ => None,
+        }
+    }
+
+    /// Returns the record rate, in hertz, from the median time between records, or `None` if
+    /// there are fewer than two records.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::imu_gnss::{ImuGnss, ImuGnssPoint};
+    /// let points = (0..200).map(|i| ImuGnssPoint { time: i as f64 / 200.0, ..Default::default() })
+    ///                      .collect();
+    /// let imu_gnss = ImuGnss::new(points).unwrap();
+    /// assert!((imu_gnss.sample_rate().unwrap() - 200.0).abs() < 1e-6);
+    /// ```
+    pub fn sample_rate(&self) -> Option<f64> {
+        self.median_interval().map(|interval| 1.0 / interval)
+    }
+
+    /// Returns statistics about this trajectory's records, or `None` if there are fewer than two.
+    pub fn stats(&self) -> Option<TrajectoryStats> {
+        let (time_bounds, interval) = match (self.time_bounds(), self.median_interval()) {
+            (Some(time_bounds), Some(interval)) => (time_bounds, interval),
+            _ => return None,
+        };
+        Some(TrajectoryStats {
+            records: self.points.len(),
+            time_bounds: time_bounds,
+            duration: time_bounds.1 - time_bounds.0,
+            sample_rate: 1.0 / interval,
+            gaps: self.points
+                      .windows(2)
+                      .filter(|w| w[1].time - w[0].time > 2.0 * interval)
+                      .map(|w| (w[0].time, w[1].time))
+                      .collect(),
+            roll_bounds: extremes(self.points.iter().map(|point| point.roll)),
+            pitch_bounds: extremes(self.points.iter().map(|point| point.pitch)),
+            latitude_bounds: extremes(self.points.iter().map(|point| point.latitude)),
+            longitude_bounds: extremes(self.points.iter().map(|point| point.longitude)),
+            height_bounds: extremes(self.points.iter().map(|point| point.height)),
+            utm_zones: {
+                let mut zones: Vec<u8> = self.points
+                                             .iter()
+                                             .map(|point| {
+                                                 natural_utm_zone(point.latitude, point.longitude)
+                                             })
+                                             .collect();
+                zones.sort();
+                zones.dedup();
+                zones
+            },
+        })
+    }
+
+    /// Downsamples this trajectory to roughly the given rate, in hertz.
+    ///
+    /// A record is kept if at least `1 / rate` seconds have passed since the last kept record, or
+    /// if the roll, pitch, or yaw have changed by more than `max_attitude_change` radians since
+    /// then, so that quick maneuvers keep their full resolution. The first and last records are
+    /// always kept. The rate must be greater than zero, and the attitude change zero or greater.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::imu_gnss::{ImuGnss, ImuGnssPoint};
+    /// let points = (0..200).map(|i| ImuGnssPoint { time: i as f64 / 200.0, ..Default::default() })
+    ///                      .collect();
+    /// let imu_gnss = ImuGnss::new(points).unwrap().decimate(50.0, 0.01).unwrap();
+    /// assert_eq!(51, imu_gnss.points().len());
+    /// ```
+    pub fn decimate(self, rate: f64, max_attitude_change: f64) -> Result<ImuGnss> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(Error::InvalidField("rate", "must be greater than zero".to_string()));
+        }
+        if !(max_attitude_change >= 0.0) {
+            return Err(Error::InvalidField("max_attitude_change",
+                                           "must be zero or greater".to_string()));
+        }
+        let interval = 1.0 / rate;
+        let n = self.points.len();
+        let mut points: Vec<ImuGnssPoint> = Vec::new();
+        for (i, point) in self.points.into_iter().enumerate() {
+            let keep = match points.last() {
+                Some(last) => {
+                    i + 1 == n || point.time - last.time >= interval - 1e-9 ||
+                    angle_difference(last.roll, point.roll).abs() > max_attitude_change ||
+                    angle_difference(last.pitch, point.pitch).abs() > max_attitude_change ||
+                    angle_difference(last.yaw, point.yaw).abs() > max_attitude_change
+                }
+                None => true,
+            };
+            if keep {
+                points.push(point);
+            }
+        }
+        debug!("decimated {} records to {}", n, points.len());
+        Ok(ImuGnss {
+            points: points,
+            index: AtomicUsize::new(0),
+        })
+    }
+
+    /// Projects every record into a UTM zone.
+    ///
+    /// UTM positions are then interpolated between records, instead of projecting each
+    /// interpolated position. At typical record rates the difference is well under a millimeter,
+    /// and georeferencing skips a full projection for every point. Catmull-Rom position
+    /// interpolation still projects each point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::ellipsoid::Ellipsoid;
+    /// use georef::imu_gnss::{ImuGnss, ImuGnssPoint};
+    /// let points = vec![ImuGnssPoint { time: 1.0, ..Default::default() },
+    ///                   ImuGnssPoint { time: 2.0, ..Default::default() }];
+    /// let mut imu_gnss = ImuGnss::new(points).unwrap();
+    /// imu_gnss.project_utm(31, Ellipsoid::wgs84());
+    /// assert!(imu_gnss.points()[0].utm.is_some());
+    /// ```
+    pub fn project_utm(&mut self, utm_zone: u8, ellipsoid: Ellipsoid) {
+        for point in &mut self.points {
+            let (northing, easting, meridian_convergence) =
+                ellipsoid.to_utm(point.latitude, point.longitude, utm_zone);
+            point.utm = Some(UtmProjection {
+                utm_zone: utm_zone,
+                ellipsoid: ellipsoid,
+                northing: northing,
+                easting: easting,
+                meridian_convergence: meridian_convergence,
+            });
+        }
+    }
+
+    /// Smooths the positions and attitudes of this trajectory.
+    ///
+    /// See the `smoothing` module for the methods. Longitudes and angles are unwrapped before
+    /// they're smoothed, so windows that cross the antimeridian or a heading of 180° aren't
+    /// dragged the wrong way around. Quaternion attitudes are rebuilt from the smoothed roll,
+    /// pitch, and yaw; times, attitude rates, and accuracies are kept as they are. UTM
+    /// projections are dropped, since they no longer match the positions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::imu_gnss::{ImuGnss, ImuGnssPoint};
+    /// use georef::smoothing::SmoothingConfig;
+    /// let points = (0..10).map(|i| ImuGnssPoint { time: i as f64, ..Default::default() })
+    ///                     .collect();
+    /// let config = SmoothingConfig { window: 5, ..Default::default() };
+    /// let imu_gnss = ImuGnss::new(points).unwrap().smooth(&config).unwrap();
+    /// ```
+    pub fn smooth(self, config: &SmoothingConfig) -> Result<ImuGnss> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("smoothing", problem));
+        }
+        debug!("smoothing {} records with a window of {}", self.points.len(), config.window);
+        let mut points = self.points;
+        let (latitudes, longitudes, heights, rolls, pitches, yaws) = {
+            let smooth = |value: &Fn(&ImuGnssPoint) -> f64| {
+                smoothing::smooth(&points.iter().map(|p| value(p)).collect::<Vec<_>>(), config)
+            };
+            let smooth_angle = |value: &Fn(&ImuGnssPoint) -> f64| {
+                let mut unwrapped: Vec<f64> = Vec::with_capacity(points.len());
+                for point in &points {
+                    let angle = match unwrapped.last() {
+                        Some(&last) => last + angle_difference(last, value(point)),
+                        None => value(point),
+                    };
+                    unwrapped.push(angle);
+                }
+                smoothing::smooth(&unwrapped, config)
+            };
+            (smooth(&|p| p.latitude),
+             smooth_angle(&|p| p.longitude),
+             smooth(&|p| p.height),
+             smooth_angle(&|p| p.roll),
+             smooth(&|p| p.pitch),
+             smooth_angle(&|p| p.yaw))
+        };
+        // Map each smoothed angle back onto the branch its record was on.
+        let rewrap = |original: f64, smoothed: f64| original + angle_difference(original, smoothed);
+        for (i, point) in points.iter_mut().enumerate() {
+            point.latitude = latitudes[i];
+            point.longitude = rewrap(point.longitude, longitudes[i]);
+            point.height = heights[i];
+            point.roll = rewrap(point.roll, rolls[i]);
+            point.pitch = pitches[i];
+            point.yaw = rewrap(point.yaw, yaws[i]);
+            if point.orientation.is_some() {
+                point.orientation = Some(Quaternion::from_rpy(point.roll, point.pitch, point.yaw));
+            }
+            point.utm = None;
+        }
+        Ok(ImuGnss {
+            points: points,
+            index: AtomicUsize::new(0),
+        })
+    }
+
+    /// Drops isolated records that jump implausibly far from their neighbors.
+    ///
+    /// See the `outlier` module for how outliers are found. Each dropped record is logged as a
+    /// warning.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::imu_gnss::{ImuGnss, ImuGnssPoint};
+    /// use georef::outlier::OutlierConfig;
+    /// let mut points: Vec<ImuGnssPoint> =
+    ///     (0..10).map(|i| ImuGnssPoint { time: i as f64, ..Default::default() }).collect();
+    /// points[5].height = 1000.0;
+    /// let imu_gnss = ImuGnss::new(points).unwrap().reject_outliers(&OutlierConfig::default());
+    /// assert_eq!(9, imu_gnss.unwrap().len());
+    /// ```
+    pub fn reject_outliers(self, config: &OutlierConfig) -> Result<ImuGnss> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("outliers", problem));
+        }
+        let (max_speed, max_attitude_rate) = (config.max_speed(), config.max_attitude_rate());
+        let jumps = |a: &ImuGnssPoint, b: &ImuGnssPoint| {
+            let duration = b.time - a.time;
+            distance(a, b) / duration > max_speed ||
+            angle_difference(a.roll, b.roll).abs() / duration > max_attitude_rate ||
+            angle_difference(a.pitch, b.pitch).abs() / duration > max_attitude_rate ||
+            angle_difference(a.yaw, b.yaw).abs() / duration > max_attitude_rate
+        };
+        let n = self.points.len();
+        let mut points: Vec<ImuGnssPoint> = Vec::with_capacity(n);
+        for (i, point) in self.points.iter().enumerate() {
+            let outlier = match (points.last(), self.points.get(i + 1)) {
+                (Some(last), Some(next)) => jumps(last, point) && jumps(point, next),
+                (Some(last), None) => jumps(last, point),
+                // The first record has only one neighbor, so it's an outlier only if that
+                // neighbor agrees with the one after it.
+                (None, Some(next)) => {
+                    jumps(point, next) &&
+                    self.points.get(i + 2).map_or(false, |after| !jumps(next, after))
+                }
+                (None, None) => false,
+            };
+            if outlier {
+                warn!("dropping trajectory outlier at {}", point.time);
+            } else {
+                points.push(*point);
+            }
+        }
+        debug!("dropped {} of {} records as outliers", n - points.len(), n);
+        Ok(ImuGnss {
+            points: points,
+            index: AtomicUsize::new(0),
+        })
+    }
+
+    /// Finds the flight lines in this trajectory.
+    ///
+    /// See the `flight_line` module for how lines are found.
+    pub fn flight_lines(&self, config: &FlightLineConfig) -> Vec<FlightLine> {
+        let mut samples: Vec<&ImuGnssPoint> = Vec::new();
+        for point in &self.points {
+            if samples.last().map_or(true, |last| point.time - last.time >= HEADING_RATE_STEP) {
+                samples.push(point);
+            }
+        }
+        let mut lines = Vec::new();
+        let mut start = None;
+        for w in samples.windows(2) {
+            let rate = angle_difference(w[0].yaw, w[1].yaw).abs() / (w[1].time - w[0].time);
+            if rate <= config.max_heading_rate() {
+                if start.is_none() {
+                    start = Some(w[0].time);
+                }
+            } else if let Some(start) = start.take() {
+                push_line(&mut lines, start, w[0].time, config);
+            }
+        }
+        if let (Some(start), Some(last)) = (start, samples.last()) {
+            push_line(&mut lines, start, last.time, config);
+        }
+        lines
+    }
+
+    fn median_interval(&self) -> Option<f64> {
+        let mut intervals: Vec<f64> = self.points
+                                          .windows(2)
+                                          .map(|w| w[1].time - w[0].time)
+                                          .collect();
+        if intervals.is_empty() {
+            return None;
+        }
+        // Times increase monotonically, so every interval is positive and finite.
+        intervals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        Some(intervals[intervals.len() / 2])
+    }
+
+    /// Returns the index of the record at or immediately before the given time.
+    ///
+    /// The previous lookup is tried first, since point times usually increase. Threads share
+    /// the hint, so it's read and written without ordering; a stale hint only costs a search.
+    fn bracket(&self, time: f64) -> Result<usize> {
+        let n = self.points.len();
+        if n < 2 || !(time >= self.points[0].time && time <= self.points[n - 1].time) {
+            return Err(Error::OutsideOfImuGnssRecords);
+        }
+        let i = self.index.load(AtomicOrdering::Relaxed);
+        if i + 1 < n && self.points[i].time <= time && time <= self.points[i + 1].time {
+            return Ok(i);
+        }
+        let (mut low, mut high) = (0, n - 1);
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            if self.points[mid].time <= time {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        self.index.store(low, AtomicOrdering::Relaxed);
+        Ok(low)
+    }
+}
+
+impl Trajectory for ImuGnss {
+    fn pose_at(&self, time: f64, interpolation: &Interpolation) -> Result<Pose> {
+        let i = try!(self.bracket(time));
+        let (start, end) = (self.points[i].time, self.points[i + 1].time);
+        if interpolation.max_gap.map_or(false, |max_gap| end - start > max_gap) {
+            return Err(Error::TrajectoryGap(start, end));
+        }
+        let mut pose = self.points[i].interpolate(&self.points[i + 1], time, interpolation);
+        if interpolation.position == PositionInterpolation::CatmullRom {
+            let knots = [&self.points[i.saturating_sub(1)],
+                         &self.points[i],
+                         &self.points[i + 1],
+                         &self.points[(i + 2).min(self.points.len() - 1)]];
+            let times = [knots[0].time, knots[1].time, knots[2].time, knots[3].time];
+            // Unwrap the longitudes around the bracketing record so the spline doesn't sweep
+            // around the globe at the antimeridian.
+            let longitude = knots[1].longitude;
+            let unwrap = |point: &ImuGnssPoint| {
+                longitude + angle_difference(longitude, point.longitude)
+            };
+            pose.latitude = catmull_rom(times,
+                                        [knots[0].latitude,
+                                         knots[1].latitude,
+                                         knots[2].latitude,
+                                         knots[3].latitude],
+                                        time);
+            pose.longitude = catmull_rom(times,
+                                         [unwrap(knots[0]),
+                                          longitude,
+                                          unwrap(knots[2]),
+                                          unwrap(knots[3])],
+                                         time);
+            pose.height = catmull_rom(times,
+                                      [knots[0].height,
+                                       knots[1].height,
+                                       knots[2].height,
+                                       knots[3].height],
+                                      time);
+            pose.utm = None;
+        }
+        Ok(pose)
+    }
+
+    fn time_bounds(&self) -> Option<(f64, f64)> {
+        ImuGnss::time_bounds(self)
+    }
+
+    fn stats(&self) -> Option<TrajectoryStats> {
+        ImuGnss::stats(self)
+    }
+
+    fn flight_lines(&self, config: &FlightLineConfig) -> Option<Vec<FlightLine>> {
+        Some(ImuGnss::flight_lines(self, config))
+    }
+}
+
+/// Adds a flight line from `start` to `end`, if it is long enough.
+fn push_line(lines: &mut Vec<FlightLine>, start: f64, end: f64, config: &FlightLineConfig) {
+    if end - start >= config.min_duration() && lines.len() < u16::MAX as usize {
+        let number = lines.len() as u16 + 1;
+        lines.push(FlightLine {
+            number: number,
+            start: start,
+            end: end,
+        });
+    }
+}
+
+/// Returns the smallest and largest values.
+fn extremes<I: Iterator<Item = f64>>(values: I) -> (f64, f64) {
+    values.fold((f64::INFINITY, f64::NEG_INFINITY),
+                |(min, max), value| (min.min(value), max.max(value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::{Norm, Vec3};
+
+    use error::Error;
+    use outlier::OutlierConfig;
+    use rotation::RotationOrder;
+    use trajectory::{AttitudeInterpolation, Interpolation, PositionInterpolation, Trajectory};
+
+    fn imu_gnss() -> ImuGnss {
+        ImuGnss::new(vec![ImuGnssPoint { time: 1.0, height: 10.0, ..Default::default() },
+                          ImuGnssPoint { time: 2.0, height: 20.0, ..Default::default() },
+                          ImuGnssPoint { time: 3.0, height: 40.0, ..Default::default() }])
+            .unwrap()
+    }
+
+    #[test]
+    fn stats() {
+        let mut points = Vec::new();
+        for i in 0..10 {
+            points.push(ImuGnssPoint {
+                time: if i < 9 { i as f64 * 0.1 } else { 1.5 },
+                roll: i as f64 * 0.25,
+                pitch: i as f64 * -0.5,
+                longitude: i as f64 * 0.02,
+                height: 100.0 - i as f64,
+                ..Default::default()
+            });
+        }
+        let imu_gnss = ImuGnss::new(points).unwrap();
+        assert_eq!(10, imu_gnss.len());
+        assert_eq!(Some((0.0, 1.5)), imu_gnss.time_bounds());
+        assert!((imu_gnss.sample_rate().unwrap() - 10.0).abs() < 1e-9);
+        let stats = imu_gnss.stats().unwrap();
+        assert_eq!(10, stats.records);
+        assert_eq!(1.5, stats.duration);
+        assert_eq!(1, stats.gaps.len());
+        assert_eq!(1.5, stats.gaps[0].1);
+        assert_eq!((0.0, 2.25), stats.roll_bounds);
+        assert_eq!((-4.5, 0.0), stats.pitch_bounds);
+        assert_eq!((0.0, 0.0), stats.latitude_bounds);
+        assert_eq!((0.0, 0.18), stats.longitude_bounds);
+        assert_eq!((91.0, 100.0), stats.height_bounds);
+        assert_eq!(vec![31, 32], stats.utm_zones);
+        assert!(ImuGnss::new(Vec::new()).unwrap().stats().is_none());
+    }
+
+    #[test]
+    fn flight_lines() {
+        // A minute-long line heading north, a 20 second turn, and another line heading south.
+        let mut points = Vec::new();
+        for i in 0..1000 {
+            let time = i as f64 * 0.1;
+            let yaw = if time < 60.0 {
+                0.0
+            } else if time < 80.0 {
+                (time - 60.0) / 20.0 * ::std::f64::consts::PI
+            } else {
+                ::std::f64::consts::PI
+            };
+            points.push(ImuGnssPoint { time: time, yaw: yaw, ..Default::default() });
+        }
+        let imu_gnss = ImuGnss::new(points).unwrap();
+        let lines = imu_gnss.flight_lines(&Default::default());
+        assert_eq!(2, lines.len());
+        assert_eq!((1, 0.0), (lines[0].number, lines[0].start));
+        assert!((lines[0].end - 60.0).abs() < 1.01);
+        assert!((lines[1].start - 80.0).abs() < 1.01);
+        assert_eq!(2, lines[1].number);
+        let config = FlightLineConfig { min_duration: Some(30.0), ..Default::default() };
+        assert_eq!(1, imu_gnss.flight_lines(&config).len());
+    }
+
+    #[test]
+    fn nonmonotonic() {
+        assert!(ImuGnss::new(vec![ImuGnssPoint { time: 2.0, ..Default::default() },
+                                  ImuGnssPoint { time: 1.0, ..Default::default() }])
+                    .is_err());
+    }
+
+    #[test]
+    fn sanitize() {
+        let points = || {
+            [1.0, 3.0, 2.0, 3.0, 4.0]
+                .iter()
+                .map(|&time| ImuGnssPoint { time: time, ..Default::default() })
+                .collect::<Vec<_>>()
+        };
+        assert!(ImuGnss::sanitize(points(), RepairPolicy::Error).is_err());
+        let (imu_gnss, repair) = ImuGnss::sanitize(points(), RepairPolicy::Sort).unwrap();
+        assert_eq!(vec![1.0, 2.0, 3.0, 4.0],
+                   imu_gnss.points().iter().map(|p| p.time).collect::<Vec<_>>());
+        assert_eq!(Repair { reordered: 1, dropped: 1 }, repair);
+        let (imu_gnss, repair) = ImuGnss::sanitize(points(), RepairPolicy::DropDuplicates)
+                                     .unwrap();
+        assert_eq!(vec![1.0, 3.0, 4.0],
+                   imu_gnss.points().iter().map(|p| p.time).collect::<Vec<_>>());
+        assert_eq!(Repair { reordered: 0, dropped: 2 }, repair);
+        assert_eq!(RepairPolicy::Sort, "sort".parse().unwrap());
+        assert!("shuffle".parse::<RepairPolicy>().is_err());
+    }
+
+    #[test]
+    fn interpolate() {
+        let imu_gnss = imu_gnss();
+        let interpolation = Default::default();
+        assert_eq!(15.0, imu_gnss.pose_at(1.5, &interpolation).unwrap().height);
+        assert_eq!(30.0, imu_gnss.pose_at(2.5, &interpolation).unwrap().height);
+        assert_eq!(10.0, imu_gnss.pose_at(1.0, &interpolation).unwrap().height);
+        assert_eq!(40.0, imu_gnss.pose_at(3.0, &interpolation).unwrap().height);
+        assert_eq!(15.0, imu_gnss.pose_at(1.5, &interpolation).unwrap().height);
+    }
+
+    #[test]
+    fn height_precision() {
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 0.0,
+                                             height: 1234.5678901,
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 1.0,
+                                             height: 1234.5678903,
+                                             ..Default::default()
+                                         }])
+                           .unwrap();
+        let height = imu_gnss.pose_at(0.5, &Default::default()).unwrap().height;
+        assert!((height - 1234.5678902).abs() < 1e-9);
+    }
+
+    #[test]
+    fn outside() {
+        let imu_gnss = imu_gnss();
+        assert!(imu_gnss.pose_at(0.5, &Default::default()).is_err());
+        assert!(imu_gnss.pose_at(3.5, &Default::default()).is_err());
+    }
+
+    #[test]
+    fn max_gap() {
+        let imu_gnss = imu_gnss();
+        let interpolation = Interpolation { max_gap: Some(1.5), ..Default::default() };
+        assert!(imu_gnss.pose_at(1.5, &interpolation).is_ok());
+        match imu_gnss.pose_at(2.5, &Interpolation { max_gap: Some(0.5), ..interpolation }) {
+            Err(Error::TrajectoryGap(start, end)) => assert_eq!((2.0, 3.0), (start, end)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn heading_wraparound() {
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 1.0,
+                                             yaw: 179f64.to_radians(),
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 2.0,
+                                             yaw: -177f64.to_radians(),
+                                             ..Default::default()
+                                         }])
+                           .unwrap();
+        let yaw = imu_gnss.pose_at(1.5, &Default::default()).unwrap().yaw;
+        assert!((yaw - 181f64.to_radians()).abs() < 1e-12);
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 1.0,
+                                             yaw: 359f64.to_radians(),
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 2.0,
+                                             yaw: 3f64.to_radians(),
+                                             ..Default::default()
+                                         }])
+                           .unwrap();
+        let yaw = imu_gnss.pose_at(1.5, &Default::default()).unwrap().yaw;
+        assert!((yaw - 361f64.to_radians()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn slerp() {
+        let interpolation = Interpolation {
+            attitude: AttitudeInterpolation::Slerp,
+            ..Default::default()
+        };
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 1.0,
+                                             yaw: 0.2,
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 2.0,
+                                             yaw: 0.4,
+                                             ..Default::default()
+                                         }])
+                           .unwrap();
+        let point = imu_gnss.pose_at(1.5, &interpolation).unwrap();
+        assert!((point.yaw - 0.3).abs() < 1e-12);
+        assert!(point.roll.abs() < 1e-12);
+        assert!(point.pitch.abs() < 1e-12);
+    }
+
+    #[test]
+    fn slerp_rotation_order() {
+        let rotation_order = RotationOrder::new("r1(roll)", "r2(pitch)", "r3(yaw)").unwrap();
+        let interpolation = Interpolation {
+            attitude: AttitudeInterpolation::Slerp,
+            rotation_order: rotation_order,
+            ..Default::default()
+        };
+        let record = |time, yaw| {
+            ImuGnssPoint {
+                time: time,
+                roll: 0.3,
+                pitch: -0.4,
+                yaw: yaw,
+                ..Default::default()
+            }
+        };
+        let imu_gnss = ImuGnss::new(vec![record(1.0, 0.2), record(2.0, 1.2)]).unwrap();
+        let pose = imu_gnss.pose_at(1.5, &interpolation).unwrap();
+        // Only the last rotation in the sequence changes, so halfway is halfway through it.
+        let expected = rotation_order.rot3(0.3, -0.4, 0.7);
+        let actual = pose.orientation.unwrap().to_rot3();
+        for v in &[Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+            let difference = expected * *v - actual * *v;
+            assert!(difference.norm() < 1e-12, "{:?}", difference);
+        }
+    }
+
+    #[test]
+    fn interpolate_accuracy() {
+        let accuracy = |north| Some(Accuracy { north: north, ..Default::default() });
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 1.0,
+                                             accuracy: accuracy(0.1),
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 2.0,
+                                             accuracy: accuracy(0.3),
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint { time: 3.0, ..Default::default() }])
+                           .unwrap();
+        let north = imu_gnss.pose_at(1.5, &Default::default()).unwrap().accuracy.unwrap().north;
+        assert!((north - 0.2).abs() < 1e-12);
+        assert!(imu_gnss.pose_at(2.5, &Default::default()).unwrap().accuracy.is_none());
+    }
+
+    #[test]
+    fn project_utm() {
+        // Two records a second apart, about 50 m apart, in zone 31.
+        let points = vec![ImuGnssPoint {
+                              time: 0.0,
+                              latitude: 0.7,
+                              longitude: 0.05,
+                              ..Default::default()
+                          },
+                          ImuGnssPoint {
+                              time: 1.0,
+                              latitude: 0.700005,
+                              longitude: 0.050006,
+                              ..Default::default()
+                          }];
+        let ellipsoid = Ellipsoid::wgs84();
+        let mut imu_gnss = ImuGnss::new(points).unwrap();
+        imu_gnss.project_utm(31, ellipsoid);
+        let pose = imu_gnss.pose_at(0.3, &Default::default()).unwrap();
+        let utm = pose.utm.unwrap();
+        let (northing, easting, convergence) = ellipsoid.to_utm(pose.latitude, pose.longitude, 31);
+        assert!((utm.northing - northing).abs() < 1e-3);
+        assert!((utm.easting - easting).abs() < 1e-3);
+        assert!((utm.meridian_convergence - convergence).abs() < 1e-9);
+
+        let interpolation = Interpolation {
+            position: PositionInterpolation::CatmullRom,
+            ..Default::default()
+        };
+        assert!(imu_gnss.pose_at(0.3, &interpolation).unwrap().utm.is_none());
+        let config = SmoothingConfig { window: 3, ..Default::default() };
+        assert!(imu_gnss.smooth(&config).unwrap().points()[0].utm.is_none());
+    }
+
+    #[test]
+    fn merge() {
+        let trajectory = |times: &[f64], height: f64| {
+            ImuGnss::new(times.iter()
+                              .map(|&time| {
+                                  ImuGnssPoint {
+                                      time: time,
+                                      height: height,
+                                      ..Default::default()
+                                  }
+                              })
+                              .collect())
+                .unwrap()
+        };
+        let imu_gnss = ImuGnss::merge(vec![trajectory(&[3.0, 4.0, 5.0], 1.05),
+                                           trajectory(&[1.0, 2.0, 3.0, 3.5], 1.0),
+                                           ImuGnss::new(Vec::new()).unwrap()])
+                           .unwrap();
+        let times: Vec<f64> = imu_gnss.points().iter().map(|point| point.time).collect();
+        assert_eq!(vec![1.0, 2.0, 3.0, 3.5, 4.0, 5.0], times);
+        assert_eq!(1.0, imu_gnss.points()[2].height);
+
+        match ImuGnss::merge(vec![trajectory(&[1.0, 2.0], 1.0), trajectory(&[2.0, 3.0], 2.0)]) {
+            Err(Error::DisagreeingImuGnssRecords(time, distance)) => {
+                assert_eq!(2.0, time);
+                assert!((distance - 1.0).abs() < 1e-9);
+            }
+            other => panic!("expected disagreeing records, got {:?}", other),
+        }
+        let tilted = |times: &[f64], roll: f64| {
+            ImuGnss::new(times.iter()
+                              .map(|&time| {
+                                  ImuGnssPoint {
+                                      time: time,
+                                      roll: roll,
+                                      ..Default::default()
+                                  }
+                              })
+                              .collect())
+                .unwrap()
+        };
+        let apart = vec![trajectory(&[1.0, 2.0, 3.0], 1.0), trajectory(&[2.5, 4.0], 1.5)];
+        match ImuGnss::merge(apart) {
+            Err(Error::DisagreeingImuGnssRecords(time, distance)) => {
+                assert_eq!(2.5, time);
+                assert!((distance - 0.5).abs() < 1e-9);
+            }
+            other => panic!("expected disagreeing records, got {:?}", other),
+        }
+        let level = tilted(&[1.0, 2.0, 3.0], 0.0);
+        match ImuGnss::merge(vec![level, tilted(&[2.5, 4.0], 1f64.to_radians())]) {
+            Err(Error::DisagreeingImuGnssAttitudes(time, angle)) => {
+                assert_eq!(2.5, time);
+                assert!((angle - 1.0).abs() < 1e-9);
+            }
+            other => panic!("expected disagreeing attitudes, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decimate() {
+        let points = (0..10)
+                         .map(|i| {
+                             ImuGnssPoint {
+                                 time: i as f64 * 0.1,
+                                 roll: if i == 3 { 0.5 } else { 0.0 },
+                                 ..Default::default()
+                             }
+                         })
+                         .collect();
+        let imu_gnss = ImuGnss::new(points).unwrap().decimate(2.5, 0.1).unwrap();
+        let times: Vec<f64> = imu_gnss.points().iter().map(|p| p.time).collect();
+        assert_eq!(5, times.len());
+        assert_eq!(0.0, times[0]);
+        assert!((times[1] - 0.3).abs() < 1e-12);
+        assert!((times[2] - 0.4).abs() < 1e-12);
+        assert!((times[3] - 0.8).abs() < 1e-12);
+        assert!((times[4] - 0.9).abs() < 1e-12);
+        for &(rate, max_change) in &[(0.0, 0.1), (-1.0, 0.1), (f64::NAN, 0.1), (1.0, -0.1)] {
+            assert!(imu_gnss().decimate(rate, max_change).is_err());
+        }
+    }
+
+    #[test]
+    fn smooth() {
+        let points = (0..7)
+                         .map(|i| {
+                             ImuGnssPoint {
+                                 time: i as f64,
+                                 height: if i == 3 { 10.0 } else { 0.0 },
+                                 yaw: if i % 2 == 0 { PI - 0.1 } else { -PI + 0.1 },
+                                 ..Default::default()
+                             }
+                         })
+                         .collect();
+        let config = SmoothingConfig { window: 5, ..Default::default() };
+        let imu_gnss = ImuGnss::new(points).unwrap().smooth(&config).unwrap();
+        let points = imu_gnss.points();
+        assert_eq!(7, points.len());
+        assert_eq!(3.0, points[3].time);
+        assert!((points[3].height - 2.0).abs() < 1e-12);
+        assert_eq!(0.0, points[0].height);
+        // Headings that straddle 180° average to 180°, not to zero.
+        assert!(angle_difference(points[3].yaw, PI).abs() < 0.05);
+        let config = SmoothingConfig { window: 4, ..Default::default() };
+        assert!(ImuGnss::new(vec![]).unwrap().smooth(&config).is_err());
+    }
+
+    #[test]
+    fn reject_outliers() {
+        let mut points: Vec<ImuGnssPoint> = (0..10)
+                                                .map(|i| {
+                                                    ImuGnssPoint {
+                                                        time: i as f64 * 0.1,
+                                                        ..Default::default()
+                                                    }
+                                                })
+                                                .collect();
+        points[0].height = 50.0;
+        points[4].yaw = 1.0;
+        // A step, rather than a spike, is kept.
+        for point in &mut points[7..] {
+            point.latitude = 0.001;
+        }
+        let config = OutlierConfig::default();
+        let imu_gnss = ImuGnss::new(points).unwrap().reject_outliers(&config).unwrap();
+        assert_eq!(vec![1, 2, 3, 5, 6, 7, 8, 9],
+                   imu_gnss.points()
+                           .iter()
+                           .map(|p| (p.time * 10.0).round() as i32)
+                           .collect::<Vec<_>>());
+        let config = OutlierConfig { max_speed: Some(0.0), ..Default::default() };
+        assert!(imu_gnss.reject_outliers(&config).is_err());
+    }
+
+    #[test]
+    fn catmull_rom_position() {
+        let points = (0..5)
+                         .map(|i| {
+                             let time = i as f64;
+                             ImuGnssPoint {
+                                 time: time,
+                                 height: time * time,
+                                 ..Default::default()
+                             }
+                         })
+                         .collect();
+        let imu_gnss = ImuGnss::new(points).unwrap();
+        let interpolation = Interpolation {
+            position: PositionInterpolation::CatmullRom,
+            ..Default::default()
+        };
+        let height = imu_gnss.pose_at(1.5, &interpolation).unwrap().height;
+        assert!((height - 2.25).abs() < 1e-12);
+        assert_eq!(2.5, imu_gnss.pose_at(1.5, &Default::default()).unwrap().height);
+        assert_eq!(9.0, imu_gnss.pose_at(3.0, &interpolation).unwrap().height);
+        assert!(imu_gnss.pose_at(0.5, &interpolation).unwrap().height.is_finite());
+        assert!(imu_gnss.pose_at(3.5, &interpolation).unwrap().height.is_finite());
+    }
+
+    #[test]
+    fn hermite_attitude() {
+        let record = |time: f64, yaw: f64, attitude_rate: Option<[f64; 3]>| {
+            ImuGnssPoint {
+                time: time,
+                yaw: yaw,
+                attitude_rate: attitude_rate,
+                ..Default::default()
+            }
+        };
+        let interpolation = Interpolation {
+            attitude: AttitudeInterpolation::Hermite,
+            ..Default::default()
+        };
+        let imu_gnss = ImuGnss::new(vec![record(0.0, 0.0, Some([0.0; 3])),
+                                         record(2.0, 1.0, Some([0.0; 3])),
+                                         record(4.0, 2.0, None)])
+                           .unwrap();
+        // Starting and stopping at rest, the yaw lags behind a straight line early on.
+        let yaw = imu_gnss.pose_at(0.5, &interpolation).unwrap().yaw;
+        assert!((yaw - 0.15625).abs() < 1e-12);
+        assert!((imu_gnss.pose_at(1.0, &interpolation).unwrap().yaw - 0.5).abs() < 1e-12);
+        assert_eq!(0.25, imu_gnss.pose_at(0.5, &Default::default()).unwrap().yaw);
+        // Without rates at both ends, it's linear.
+        assert_eq!(1.25, imu_gnss.pose_at(2.5, &interpolation).unwrap().yaw);
+        assert_eq!(AttitudeInterpolation::Hermite, "hermite".parse().unwrap());
+    }
+
+    #[test]
+    fn quaternion_orientation() {
+        let orientation = |yaw: f64| {
+            let (sin, cos) = (yaw / 2.0).sin_cos();
+            Quaternion::new(cos, 0.0, 0.0, sin)
+        };
+        let imu_gnss = ImuGnss::new(vec![ImuGnssPoint {
+                                             time: 0.0,
+                                             orientation: orientation(0.0),
+                                             ..Default::default()
+                                         },
+                                         ImuGnssPoint {
+                                             time: 1.0,
+                                             yaw: 1.0,
+                                             orientation: orientation(1.0),
+                                             ..Default::default()
+                                         }])
+                       .unwrap();
+        // The quaternions are interpolated directly, whatever the attitude interpolation.
+        let pose = imu_gnss.pose_at(0.25, &Default::default()).unwrap();
+        let expected = orientation(0.25).unwrap();
+        let actual = pose.orientation.unwrap();
+        assert!((expected.w - actual.w).abs() < 1e-12);
+        assert!((expected.z - actual.z).abs() < 1e-12);
+        assert!((pose.yaw - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn quaternion_rot3() {
+        let (roll, pitch, yaw) = (0.1, -0.2, 2.5);
+        let expected = RotationOrder::default().rot3(roll, pitch, yaw);
+        let actual = Quaternion::from_rpy(roll, pitch, yaw).to_rot3();
+        let converted = Quaternion::from_rot3(&expected).to_rot3();
+        for v in &[Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+            let difference = expected * *v - actual * *v;
+            assert!(difference.x.abs() < 1e-12);
+            assert!(difference.y.abs() < 1e-12);
+            assert!(difference.z.abs() < 1e-12);
+            assert!((expected * *v - converted * *v).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn quaternion_roundtrip() {
+        let (roll, pitch, yaw) = Quaternion::from_rpy(0.1, -0.2, 2.5).rpy();
+        assert!((roll - 0.1).abs() < 1e-12);
+        assert!((pitch + 0.2).abs() < 1e-12);
+        assert!((yaw - 2.5).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,184 @@
+//! Intrinsic calibration of the scanner.
+//!
+//! Instrument calibration reports describe errors in the scanner's own measurements, as opposed to
+//! how it is mounted. Each point is converted from the scanner's own coordinate system (SOCS) into
+//! a range, a horizontal angle, `atan2(y, x)`, and a vertical angle above the x-y plane. Then:
+//!
+//! - the range is multiplied by `range_scale`,
+//! - `horizontal_offset` is added to the horizontal angle,
+//! - `vertical_offset` is added to the vertical angle, along with a mirror wobble term. The wobble
+//!   is periodic in the horizontal angle, which is the mirror's rotation angle for a rotating
+//!   mirror: `sum(wobble_sine[k - 1] * sin(k * h) + wobble_cosine[k - 1] * cos(k * h))` for
+//!   harmonics k = 1, 2, ...
+//!
+//! Angles are in radians.
+//!
+//! ```toml
+//! [georef.intrinsics]
+//! range_scale = 1.00002
+//! horizontal_offset = 0.0001
+//! vertical_offset = -0.0002
+//! wobble_sine = [0.00005]
+//! wobble_cosine = [-0.00003]
+//! ```
+
+use std::result;
+
+use nalgebra::{Norm, Vec3};
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+
+/// A decodable description of a scanner's intrinsic calibration.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct IntrinsicsConfig {
+    /// A factor that every range is multiplied by. Defaults to one.
+    pub range_scale: Option<f64>,
+    /// An offset added to every horizontal angle, in radians. Defaults to zero.
+    pub horizontal_offset: Option<f64>,
+    /// An offset added to every vertical angle, in radians. Defaults to zero.
+    pub vertical_offset: Option<f64>,
+    /// The sine coefficients of the mirror wobble, in radians, one per harmonic.
+    pub wobble_sine: Option<Vec<f64>>,
+    /// The cosine coefficients of the mirror wobble, in radians, one per harmonic.
+    pub wobble_cosine: Option<Vec<f64>>,
+}
+
+impl IntrinsicsConfig {
+    /// Creates a new intrinsics config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<IntrinsicsConfig, toml::DecodeError> {
+        IntrinsicsConfig::decode(&mut toml::Decoder::new(table))
+    }
+
+    /// Returns a message for a range scale that isn't positive and for each offset or set of
+    /// wobble coefficients that isn't finite.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.range_scale.map_or(false, |scale| !(scale > 0.0 && scale.is_finite())) {
+            problems.push("range_scale must be greater than zero".to_string());
+        }
+        for &(name, offset) in &[("horizontal_offset", self.horizontal_offset),
+                                 ("vertical_offset", self.vertical_offset)] {
+            if offset.map_or(false, |offset| !offset.is_finite()) {
+                problems.push(format!("{} must be finite", name));
+            }
+        }
+        for &(name, coefficients) in &[("wobble_sine", &self.wobble_sine),
+                                       ("wobble_cosine", &self.wobble_cosine)] {
+            if coefficients.as_ref().map_or(false, |c| c.iter().any(|c| !c.is_finite())) {
+                problems.push(format!("{} coefficients must be finite", name));
+            }
+        }
+        problems
+    }
+}
+
+/// A scanner's intrinsic calibration, ready to be applied to points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Intrinsics {
+    range_scale: f64,
+    horizontal_offset: f64,
+    vertical_offset: f64,
+    wobble_sine: Vec<f64>,
+    wobble_cosine: Vec<f64>,
+}
+
+impl Intrinsics {
+    /// Creates intrinsics from their configuration.
+    pub fn new(config: &IntrinsicsConfig) -> Result<Intrinsics> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("intrinsics", problem));
+        }
+        Ok(Intrinsics {
+            range_scale: config.range_scale.unwrap_or(1.0),
+            horizontal_offset: config.horizontal_offset.unwrap_or(0.0),
+            vertical_offset: config.vertical_offset.unwrap_or(0.0),
+            wobble_sine: config.wobble_sine.clone().unwrap_or(Vec::new()),
+            wobble_cosine: config.wobble_cosine.clone().unwrap_or(Vec::new()),
+        })
+    }
+
+    /// Applies the calibration to a point in the scanner's own coordinate system.
+    pub fn apply(&self, point: Vec3<f64>) -> Vec3<f64> {
+        let range = point.norm();
+        if range == 0.0 {
+            return point;
+        }
+        let horizontal = point.y.atan2(point.x);
+        let vertical = point.z.atan2((point.x * point.x + point.y * point.y).sqrt());
+        let wobble = self.wobble_sine
+                         .iter()
+                         .enumerate()
+                         .map(|(k, s)| s * ((k + 1) as f64 * horizontal).sin())
+                         .chain(self.wobble_cosine
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(k, c)| c * ((k + 1) as f64 * horizontal).cos()))
+                         .fold(0.0, |sum, term| sum + term);
+        let range = range * self.range_scale;
+        let horizontal = horizontal + self.horizontal_offset;
+        let vertical = vertical + self.vertical_offset + wobble;
+        Vec3::new(range * vertical.cos() * horizontal.cos(),
+                  range * vertical.cos() * horizontal.sin(),
+                  range * vertical.sin())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f64::consts::FRAC_PI_2;
+
+    use nalgebra::{Norm, Vec3};
+
+    fn close(a: Vec3<f64>, b: Vec3<f64>) -> bool {
+        (a - b).norm() < 1e-9
+    }
+
+    #[test]
+    fn identity() {
+        let intrinsics = Intrinsics::new(&Default::default()).unwrap();
+        let point = Vec3::new(1.0, -2.0, 3.0);
+        assert!(close(point, intrinsics.apply(point)));
+    }
+
+    #[test]
+    fn offsets_and_scale() {
+        let intrinsics = Intrinsics::new(&IntrinsicsConfig {
+                             range_scale: Some(2.0),
+                             horizontal_offset: Some(FRAC_PI_2),
+                             vertical_offset: Some(FRAC_PI_2),
+                             ..Default::default()
+                         })
+                             .unwrap();
+        assert!(close(Vec3::new(0.0, 0.0, 2.0), intrinsics.apply(Vec3::new(1.0, 0.0, 0.0))));
+    }
+
+    #[test]
+    fn wobble() {
+        let intrinsics = Intrinsics::new(&IntrinsicsConfig {
+                             wobble_sine: Some(vec![0.0, 0.1]),
+                             wobble_cosine: Some(vec![0.2]),
+                             ..Default::default()
+                         })
+                             .unwrap();
+        // At a horizontal angle of zero, only the first cosine term contributes.
+        let point = intrinsics.apply(Vec3::new(10.0, 0.0, 0.0));
+        assert!((point.z.atan2(point.x) - 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn problems() {
+        let config = IntrinsicsConfig {
+            range_scale: Some(-1.0),
+            wobble_cosine: Some(vec![::std::f64::NAN]),
+            ..Default::default()
+        };
+        assert_eq!(vec!["range_scale must be greater than zero".to_string(),
+                        "wobble_cosine coefficients must be finite".to_string()],
+                   config.problems());
+    }
+}
@@ -0,0 +1,80 @@
+//! Read JSON configuration.
+//!
+//! JSON objects are converted to toml tables, so JSON configs are decoded with the same structs as
+//! toml ones and can be passed on to pabst sources and sinks.
+
+use std::result;
+
+use rustc_serialize::json::Json;
+use toml;
+
+use Result;
+use convert::{self, Node, ToToml};
+use error::Error;
+
+/// Parses a JSON object into a toml table.
+///
+/// Nulls are dropped, since toml has no equivalent.
+///
+/// # Examples
+///
+/// ```
+/// use georef::json;
+/// let table = json::to_table(r#"{"georef": {"utm_zone": 6}}"#).unwrap();
+/// assert!(table.contains_key("georef"));
+/// ```
+pub fn to_table(s: &str) -> Result<toml::Table> {
+    let json = try!(Json::from_str(s).map_err(|err| Error::Json(err.to_string())));
+    match try!(convert::to_toml(json).map_err(Error::Json)) {
+        Some(toml::Value::Table(table)) => Ok(table),
+        _ => Err(Error::Json("document is not an object".to_string())),
+    }
+}
+
+impl ToToml for Json {
+    fn node(self) -> result::Result<Node<Json>, String> {
+        Ok(match self {
+            Json::I64(n) => Node::Scalar(toml::Value::Integer(n)),
+            Json::U64(n) => {
+                if n > i64::max_value() as u64 {
+                    return Err(format!("{} is too large for a config value", n));
+                }
+                Node::Scalar(toml::Value::Integer(n as i64))
+            }
+            Json::F64(n) => Node::Scalar(toml::Value::Float(n)),
+            Json::String(s) => Node::Scalar(toml::Value::String(s)),
+            Json::Boolean(b) => Node::Scalar(toml::Value::Boolean(b)),
+            Json::Array(array) => Node::Array(array),
+            Json::Object(object) => Node::Table(object.into_iter().collect()),
+            Json::Null => Node::Null,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use toml;
+
+    #[test]
+    fn scalars() {
+        let s = r#"{"a": 1, "b": 1.5, "c": "text", "d": true, "e": null, "f": [1, 2]}"#;
+        let table = to_table(s).unwrap();
+        assert_eq!(Some(&toml::Value::Integer(1)), table.get("a"));
+        assert_eq!(Some(&toml::Value::Float(1.5)), table.get("b"));
+        assert_eq!(Some(&toml::Value::String("text".to_string())), table.get("c"));
+        assert_eq!(Some(&toml::Value::Boolean(true)), table.get("d"));
+        assert!(!table.contains_key("e"));
+        assert_eq!(Some(&toml::Value::Array(vec![toml::Value::Integer(1),
+                                                 toml::Value::Integer(2)])),
+                   table.get("f"));
+    }
+
+    #[test]
+    fn not_an_object() {
+        assert!(to_table("[1, 2]").is_err());
+        assert!(to_table(r#"{"a": [1, null]}"#).is_err());
+        assert!(to_table(r#"{"a": 18446744073709551615}"#).is_err());
+    }
+}
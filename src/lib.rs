@@ -1,23 +1,90 @@
 //! Georeference point cloud data.
 
-#![deny(fat_ptr_transmutes, missing_copy_implementations, missing_debug_implementations, missing_docs, trivial_casts, trivial_numeric_casts, unused_extern_crates, unused_import_braces, unused_qualifications, unused_results, variant_size_differences)]
+#![deny(fat_ptr_transmutes,
+        missing_copy_implementations,
+        missing_debug_implementations,
+        missing_docs,
+        trivial_casts,
+        trivial_numeric_casts,
+        unused_extern_crates,
+        unused_import_braces,
+        unused_qualifications,
+        unused_results,
+        variant_size_differences)]
 
+extern crate byteorder;
+#[macro_use]
+extern crate log;
 extern crate nalgebra;
 extern crate pabst;
 extern crate pos;
 extern crate rustc_serialize;
 extern crate toml;
 extern crate utm;
+#[cfg(feature = "yaml")]
+extern crate yaml_rust;
 
+mod convert;
 mod point;
 mod rotation;
+pub mod csv;
+pub mod datum;
+pub mod ellipsoid;
 pub mod error;
+pub mod filter;
+pub mod flight_line;
+pub mod geoid;
 pub mod georef;
+pub mod gimbal;
+pub mod ground;
+pub mod imu_gnss;
+pub mod intrinsics;
+pub mod json;
+pub mod navigation;
+pub mod orthophoto;
+pub mod outlier;
+pub mod ply;
+pub mod polar;
+pub mod range;
+pub mod rosbag;
+pub mod sbet;
+pub mod setups;
+pub mod smoothing;
+pub mod stream;
+pub mod text;
+pub mod time;
+pub mod trajectory;
+pub mod window;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 
 pub use error::Error;
-pub use georef::{GeorefConfig, Georeferencer};
+pub use georef::{Bounds, Coverage, DryRun, ErrorPolicy, GeorefConfig, GeoreferenceIter,
+                 Georeferencer, GeoreferencerBuilder, Metrics, Progress, Summary};
+pub use imu_gnss::{Accuracy, ImuGnss, ImuGnssPoint, Quaternion, Repair, RepairPolicy,
+                   UtmProjection};
+pub use point::{NavigationFrame, OutputFrame};
+pub use rotation::RotationOrder;
+pub use stream::{Point, PointSink, PointSource};
+pub use trajectory::{AttitudeInterpolation, Interpolation, Pose, PositionInterpolation, Trajectory,
+                     TrajectoryStats};
 
 use std::result;
 
 /// Our custom result type.
 pub type Result<T> = result::Result<T, Error>;
+
+/// Returns the optional cargo features that this build was compiled with, e.g. `["rxp", "yaml"]`.
+///
+/// LAS and the other formats built into pabst are always available, but RXP sources need the
+/// `rxp` feature and YAML configs need the `yaml` feature, which is on by default.
+pub fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "rxp") {
+        features.push("rxp");
+    }
+    if cfg!(feature = "yaml") {
+        features.push("yaml");
+    }
+    features
+}
@@ -0,0 +1,2110 @@
+//! Georeference point clouds from the command line.
+
+extern crate byteorder;
+extern crate docopt;
+extern crate env_logger;
+extern crate georef;
+extern crate libc;
+#[macro_use]
+extern crate log;
+extern crate nalgebra;
+extern crate pabst;
+extern crate pos;
+extern crate rustc_serialize;
+extern crate toml;
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::u16;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use docopt::Docopt;
+use env_logger::LogBuilder;
+use log::LogLevelFilter;
+use georef::{ErrorPolicy, GeorefConfig, Georeferencer, ImuGnss, ImuGnssPoint, Point, PointSink,
+             PointSource, Progress, RepairPolicy, Summary, Trajectory};
+use georef::{csv, sbet};
+#[cfg(feature = "yaml")]
+use georef::yaml;
+use georef::csv::CsvConfig;
+use georef::filter::FilterConfig;
+use georef::flight_line::FlightLineConfig;
+use georef::outlier::OutlierConfig;
+use georef::ply::{PlyConfig, PlySink};
+use georef::rosbag::{self, RosbagSource, RosbagSourceConfig, RosbagTrajectoryConfig};
+use georef::setups::{Setups, SetupsConfig};
+use georef::smoothing::SmoothingConfig;
+use georef::text::{TextConfig, TextSink};
+use georef::window::WindowedTrajectory;
+use nalgebra::Vec3;
+use pabst::{open_file_sink, open_file_source};
+use rustc_serialize::json::{self, Json};
+
+const USAGE: &'static str = "
+Georeference point clouds.
+
+Usage:
+    georef run [options] <config> <infile> <imu-gnss> <outfile>
+    georef batch [options] <config> <imu-gnss> <outdir> [<infiles>...]
+    georef info [options] <imu-gnss>
+    georef bounds [options] <config> <infile> <imu-gnss>
+    georef validate [--error-format <fmt>] <config> [<infile> <imu-gnss>]
+    georef config init [--error-format <fmt>] [<file>]
+    georef (-h | --help)
+    georef --version
+
+Commands:
+    run                 Georeference a point cloud.
+    batch               Georeference many point clouds against the same config
+                        and trajectory, writing them into <outdir>.
+    info                Print the time span, record rate, gaps, roll and pitch
+                        ranges, position extent, and UTM zones of an IMU/GNSS
+                        trajectory, to sanity-check it before processing.
+    bounds              Estimate the output bounds of a point cloud by
+                        georeferencing a sample of its points, e.g. with
+                        --sample-interval 10000, for planning tiles or
+                        checking coordinates before a full run.
+    validate            Check a config file, reporting every problem at once,
+                        and, if they're given, that the trajectory can be read
+                        and that the point cloud opens and has GPS times,
+                        without writing anything.
+    config init         Write a commented default config file to <file>, or to
+                        standard output if no file is given.
+
+Options:
+    -h --help           Show this screen.
+    --version           Show version and the optional features, e.g. rxp,
+                        that georef was built with.
+    --error-format <fmt>
+                        Print errors as text or, for scripts and schedulers,
+                        as json [default: text].
+
+Run, batch, and bounds options:
+    --split-utm-zones   Project each point into its own UTM zone, writing one output
+                        file per zone, e.g. out_zone06.las and out_zone07.las.
+    --split-flight-lines
+                        Write one output file per flight line, e.g. out_line01.las
+                        and out_line02.las, dropping points scanned in turns.
+    --utm-zone <zone>   Override the config's utm_zone.
+    --time-offset <s>   Override the config's time_offset, in seconds.
+    --lever-arm <xyz>   Override the config's lever_arm, as meters x,y,z,
+                        e.g. --lever-arm=0.1,0.2,-0.3. This also replaces
+                        the lever_arm of every [[georef.scanner]] table.
+    --limit <n>         Override the config's limit on points written.
+    --scanner <id>      Use the mounting of the config's [[georef.scanner]] table
+                        with this id. In batch, a manifest line can instead
+                        name its file's scanner after a tab.
+    --report <file>     Write a JSON report of the input and output files, point
+                        counts, output bounds, processing times, grid-to-ground
+                        factor, and effective config to this file.
+    --check-coverage    Before georeferencing, check that the trajectory's time
+                        span covers a sample of each input's points. Uncovered
+                        points are an error with the strict error policy, and
+                        a warning otherwise. In batch, every input is checked
+                        before any is georeferenced.
+    --sample-interval <n>
+                        Sample every nth point in a dry run or coverage check
+                        [default: 100].
+    --print-config      Print the effective config, after the command-line
+                        overrides, as TOML and exit without georeferencing.
+                        Options that aren't printed take their defaults.
+    --trajectory-window <s>
+                        Read an SBET trajectory as the points need it, holding
+                        only the records within this many seconds of the
+                        latest point time instead of all of them. Points
+                        should be roughly in time order, and flight lines,
+                        --smrmsg, [outliers], and [smoothing] aren't available.
+
+Bounds options:
+    --tile-size <m>     Also count the tiles of this size, aligned to multiples
+                        of it, that the estimated bounds touch.
+
+Run options:
+    --dry-run           Read the input and georeference a sample of its points,
+                        reporting the point count, trajectory coverage, and
+                        expected output bounds, without writing anything.
+    --input-format <ext>
+                        The format of standard input when <infile> is -
+                        [default: las].
+    --output-format <ext>
+                        The format of standard output when <outfile> is -
+                        [default: las].
+    --checkpoint <file> Write the output in parts of --part-size input points,
+                        e.g. out_part001.las and out_part002.las, recording
+                        each finished part in this file so an interrupted run
+                        can be resumed.
+    --part-size <n>     The number of input points in each part of a
+                        checkpointed run [default: 10000000].
+    --resume            Resume a checkpointed run, skipping to the input point
+                        after the parts it finished and carrying on with the
+                        next.
+
+Batch options:
+    --manifest <file>   Also read input files from this file, one per line.
+    --name-template <t>
+                        Name each output file by replacing {stem} and {name} in
+                        this template with the input's file stem and name
+                        [default: {stem}.las]. Two inputs that would get the
+                        same output name, e.g. a/1.las and b/1.las, are an
+                        error.
+    --point-source-ids  Number the input files from 1, in order, and write each
+                        file's number as its points' point source id, so strips
+                        can be told apart.
+
+Run, batch, info, and bounds options:
+    --smrmsg <file>     Attach the accuracy in this smrmsg file to an SBET
+                        trajectory.
+    --config <file>     Read a delimited text trajectory's [trajectory] table
+                        from this config file (info only; run uses <config>).
+
+The IMU/GNSS file format is chosen by extension: .pos files are read as POS
+files, .sbet or .out files are read as Applanix SBET files, and .csv or .txt
+files are read as delimited text, with columns and units described by the
+config file's [trajectory] table. Inertial Explorer ASCII profile exports
+can be read as delimited text; its binary output is not supported.
+
+Scans from a tripod, or from a stop-and-go platform, can use a .toml file of
+[[setup]] tables as the IMU/GNSS file instead of a trajectory. Each setup has
+a latitude, longitude, height, roll, pitch, and heading, in degrees and
+ellipsoidal meters, that hold from its start to its stop gps time, and each
+point gets the pose of the setup it was scanned in. Points scanned between
+setups are outside of the trajectory. A single setup without start and stop
+times is a static scan.
+
+ROS 1 bags (.bag) can be both the <infile> and the IMU/GNSS file. Points are
+read from sensor_msgs/PointCloud2 messages, on the [source] table's topic if
+it's set, and the trajectory from nav_msgs/Odometry messages, on the
+[trajectory] table's topic if it's set. The odometry must be in an
+east-north-up frame, anchored at the [trajectory] table's origin, e.g.
+origin = { latitude = 61.2, longitude = -149.9, height = 100.0 }. Bags with
+compressed chunks must be decompressed with rosbag decompress first.
+
+Trajectory records must increase in time. Set repair = \"sort\" or repair =
+\"drop_duplicates\" in the config file's [trajectory] table to repair a
+trajectory with a duplicated epoch or records out of order instead of
+refusing it.
+
+A mission split across several trajectory files, e.g. at a GPS week boundary,
+can be given as one <imu-gnss> of comma-separated paths, e.g. a.pos,b.pos.
+The files are read into memory and merged in time order. Where they overlap,
+the earlier file's records are kept, and records at the same time that are
+more than 10 cm apart are an error.
+
+LAS output files are written as LAS 1.4 with the output coordinate reference
+system as WKT, unless the [sink] table sets version or wkt. With another
+ellipsoid, a datum shift, or a ground scale, the output isn't a WGS84 system,
+so no WKT is written unless [sink] sets one. PLY output files are written by
+georef, with their format (binary or ascii), properties (x, y, z, time,
+intensity), and coordinate type (double, or float relative to an offset) set
+by the [sink] table. Delimited text output files (.csv, .tsv, or .txt) are
+also written by georef, with their columns, delimiter, precision, and header
+set by the [sink] table; .tsv files are tab-delimited by default.
+
+PLY and delimited text outputs can include the trajectory's estimated
+accuracy at each point, in meters, as the sigma_north, sigma_east, and
+sigma_up properties or columns, e.g. for quality-control maps. They're NaN
+where the trajectory has no accuracy, e.g. an SBET without --smrmsg. For
+looking into artifacts that follow the platform's motion, they can also
+include the roll, pitch, and heading, in degrees, and the trajectory_height
+that each point was georeferenced with. LAS outputs can't hold these, since
+pabst doesn't write extra bytes.
+
+Points outside of the config file's [filter] bounds, given in the output
+frame, outside of its min_range and max_range from the scanner, or beyond its
+max_scan_angle from nadir, are dropped before they are written. The [filter]
+table's keep_every_nth or keep_fraction thin the output for quick previews,
+and its returns and classifications keep only some returns or classes.
+
+If the config file has a [flight_lines] table, or --split-flight-lines is
+given, the trajectory is split into flight lines at its turns, and each point
+in a line is tagged with the line's number as its point source id. Flight
+lines can only be found in IMU/GNSS trajectories that are read into memory,
+e.g. SBET and delimited text, not POS files.
+
+A progress bar is drawn on standard error when both standard output and
+standard error are terminals. Its ETA is only shown for LAS input.
+
+Warnings and notes, e.g. about skipped points, are logged to standard error.
+Set RUST_LOG=debug for details like the UTM zone and chunk sizes, or
+RUST_LOG=trace for why each point was skipped or clamped.
+
+Errors are printed to standard error. With --error-format json, an error is
+printed as one JSON object with its kind, message, exit code, and, if they
+are known, the input file and gps time it's about, e.g.
+{\"kind\": \"point\", \"message\": \"...\", \"file\": \"in.las\", \"time\": 1.5, ...}.
+Each kind of error exits with its own code:
+
+    1  other errors, e.g. bad arguments
+    2  config: a bad config value, or a file it names, e.g. a geoid grid
+    3  trajectory: the IMU/GNSS trajectory can't be read or used
+    4  format: a point cloud or output file can't be read or written
+    5  point: a point can't be georeferenced, e.g. it's in a trajectory gap
+    6  io: an IO error
+    7  internal: e.g. a worker thread exited unexpectedly
+
+Use - as run's <infile> or <outfile> to read from standard input or write to
+standard output, e.g. in a pipeline. The data are spooled through temporary
+files, so reading starts once standard input is closed.
+
+Config files with a .json extension are read as JSON, with each table as an
+object, e.g. {\"georef\": {...}, \"sink\": {...}}. Config files with a .yaml or
+.yml extension are read as YAML, with each table as a mapping. All others are
+read as TOML. The files a config names, e.g. its geoid, orthophoto, gimbal log,
+and leap second table, may use ${VAR} environment variables, and relative
+paths are relative to the config file's directory.
+
+Some integrations are optional cargo features: RXP input needs the rxp
+feature, and YAML config files need the yaml feature, which is on by default.
+georef --version lists the features that this build has.
+";
+
+const DEFAULT_CONFIG: &'static str = r#"# A georef configuration file. Edit the values marked TODO.
+
+# Options for the point source, passed through to pabst. Which options are supported depends on
+# the input format; e.g. RXP sources can sync their timestamps to the GNSS pulse-per-second. For
+# .bag input, topic picks the point cloud topic, e.g. topic = "/velodyne_points".
+[source]
+sync_to_pps = true
+
+[georef]
+# The UTM zone of the output points. TODO: set to your project's zone.
+utm_zone = 6
+
+# The scanner's own coordinate system (SOCS) axes, in terms of the IMU's axes. Each value is one of
+# x, y, z, -x, -y, or -z, and each IMU axis must be used once.
+socs_map = { x = "x", y = "y", z = "z" }
+
+# The rotation between the scanner and the IMU, in radians. TODO: set from your boresight
+# calibration. A rotation matrix, in row-major order, can be given instead, e.g.
+# boresight = { matrix = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0] }
+boresight = { roll = 0.0, pitch = 0.0, yaw = 0.0 }
+
+# The offset from the IMU to the scanner, in meters, in the IMU's frame. TODO: set from your
+# installation survey.
+lever_arm = { x = 0.0, y = 0.0, z = 0.0 }
+
+# A scanner-to-body transform, as a homogeneous 4x4 matrix in row-major order, can replace the
+# socs_map, boresight, and lever_arm, which must then be left at the identity.
+# mounting = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+
+# The order in which roll, pitch, and yaw rotations are applied. Each entry is r1, r2, or r3 (about
+# the x, y, or z axis) around an optionally negated angle.
+rotation_order = ["r3(yaw)", "r2(pitch)", "r1(roll)"]
+
+# The navigation frame that the attitude rotates into, "ENU" (the default) or "NED". Use "NED" for
+# trajectories whose body z axis points down and whose heading is clockwise from north.
+# frame = "NED"
+
+# Degrees added to every trajectory yaw, e.g. the magnetic declination of a magnetometer-derived
+# heading, positive east in the NED frame, or a known convergence offset.
+# heading_offset = 0.0
+
+# Seconds added to each point's time, for skew between the scanner and GNSS clocks.
+# time_offset = 0.0
+
+# Seconds added to each point's time by return number, on top of time_offset, for scanners that
+# timestamp later returns with some latency. The first value is for first returns, and so on.
+# return_time_offsets = [0.0, 0.000002, 0.000004]
+
+# The GPS time systems of the points and the trajectory, each "week" (seconds of week),
+# "standard", "adjusted" (standard minus 1e9), or "utc" (Unix time). Converting week seconds
+# needs the GPS week, which is also used to look up leap seconds for UTC. Set leap_seconds or
+# leap_seconds_file (an IERS leap-seconds.list) to override the built-in leap second table.
+# time_system = { point_time = "week", trajectory_time = "adjusted", gps_week = 1890 }
+
+# The output frame, "utm", "ecef", "geographic", or "local", or an output CRS. Use one or the
+# other. Only WGS84 output CRSs are supported: EPSG:4978 (ECEF), EPSG:4326 or EPSG:4979
+# (geographic), and EPSG:32601 through EPSG:32660 or EPSG:32701 through EPSG:32760 (northern or
+# southern UTM zones); other systems, e.g. state plane, are rejected. Local output is east,
+# north, and up in meters from local_origin, given in degrees and meters above the ellipsoid.
+# For debugging, "sensor" and "body" stop after the boresight or the lever arm, before the
+# trajectory is applied.
+# output_frame = "utm"
+# output_crs = "EPSG:32606"
+# local_origin = { latitude = 61.2, longitude = -149.9, height = 100.0 }
+
+# A geoid grid, for orthometric output heights.
+# geoid = "WW15MGH.GRD"
+
+# The angle log of a stabilized mount, with a time, roll, pitch, and optional yaw on each line. The
+# mount's angles rotate the scanner into the body after the boresight, and the lever arm is
+# measured to the mount's center of rotation. Angles are "degrees" (the default) or "radians".
+# gimbal = { path = "mount.csv", angle_units = "degrees" }
+
+# Scale UTM output from grid to ground distances about a project origin, in degrees and meters
+# above the ellipsoid. The combined factor is computed at the origin unless factor is given.
+# ground_scale = { latitude = 61.2, longitude = -149.9, height = 100.0 }
+
+# The ellipsoid that output is on, by name ("WGS84" or "GRS80") or as a semi-major axis
+# and inverse flattening. LAS output only gets a WKT on WGS84, so set [sink] wkt for others.
+# ellipsoid = { name = "GRS80" }
+
+# A Helmert datum shift applied before projection, e.g. ITRF2014 to NAD83(2011), roughly, below.
+# Use the published parameters. Translations are in meters, rotations in milliarcseconds, and
+# scale in parts per billion. With rates, which are per year, set reference_epoch and the
+# decimal-year epoch of the data.
+# datum_shift = { translation = [1.0, -1.9, -0.5], rotation = [26.8, -0.4, 10.9], scale = 0.4 }
+
+# What to do with points outside of the trajectory: "strict", "skip", or "clamp".
+# error_policy = "strict"
+
+# The longest trajectory gap, in seconds, to interpolate across.
+# max_interpolation_gap = 0.1
+
+# Attitude interpolation ("linear", "slerp", or "hermite", which uses SBET angular rates) and
+# position interpolation ("linear" or "catmull-rom").
+# interpolation = { attitude = "linear", position = "linear" }
+
+# The scanner's intrinsic calibration, in radians: encoder offsets, a range scale, and mirror
+# wobble coefficients by harmonic. Scanner tables can override it.
+# intrinsics = { range_scale = 1.0, horizontal_offset = 0.0, vertical_offset = 0.0 }
+
+# For sources that deliver raw measurements, the scanner geometry that turns a range in x and
+# encoder angles in y and z into scanner coordinates: "spherical" (horizontal and vertical angles)
+# or "profile" (one angle, for line scanners). Angles are "degrees", "radians", or "counts".
+# Scanner tables can override it.
+# polar = { model = "profile", angle_units = "counts", counts_per_revolution = 36000.0 }
+
+# Range corrections in the scanner's frame: a constant offset in meters, a scale factor, and an
+# atmospheric correction from the survey's temperature (degrees C) and pressure (hPa).
+# range_correction = { offset = 0.0, scale = 1.0, temperature = 12.0, pressure = 1013.25 }
+
+# An uncompressed 8-bit RGB GeoTIFF, in the output coordinate reference system, to color points.
+# LAS output only keeps colors in point formats that have them.
+# orthophoto = "ortho.tif"
+
+# Write each point's scan angle, from nadir towards the scanner's +y axis, to the LAS scan angle.
+# write_scan_angle = false
+
+# Write this point source id to every point, e.g. to number flight lines.
+# point_source_id = 1
+
+# Reuse one interpolated pose for every point in each bucket of this many seconds. Faster, but
+# each pose can be off by half a bucket of motion, so leave unset for UAVs and other fast turners.
+# pose_interval = 0.001
+
+# Parallelism and memory use.
+# threads = 1
+# chunk_size = 1000
+
+# With more than one thread, points are still written in the order they were read. Chunks that
+# finish early wait in a reorder buffer; this caps how many chunks are held at once (default two
+# per thread).
+# max_chunks_in_flight = 8
+
+# Stop after this many points have been written.
+# limit = 1000000
+
+# The scanners on a multi-scanner rig. Each can override boresight, lever_arm, socs_map,
+# time_offset, return_time_offsets, intrinsics, and polar; pick one with --scanner or in a batch
+# manifest. --lever-arm replaces every scanner's lever_arm.
+# [[georef.scanner]]
+# id = "left"
+# lever_arm = { x = 0.1, y = -0.5, z = -0.2 }
+
+# Drop points outside of an area of interest, as [min_x, min_y, max_x, max_y] in the output
+# frame.
+# [filter]
+# bounds = [500000.0, 6700000.0, 501000.0, 6701000.0]
+# Drop points closer to or farther from the scanner than these ranges, in meters.
+# min_range = 2.0
+# max_range = 800.0
+# Drop points whose scan angle is farther than this from nadir, in degrees.
+# max_scan_angle = 30.0
+# Thin the output for a quick preview, by keeping every nth point or a fraction of the points.
+# keep_every_nth = 10
+# keep_fraction = 0.1
+# Keep only "first" or "last" returns, and only points with these classification codes.
+# returns = "last"
+# classifications = [1, 2]
+
+# Split the mission into flight lines at turns, where the heading changes faster than
+# max_heading_rate, in degrees per second. Lines shorter than min_duration, in seconds, are
+# dropped. Each point in a line gets the line's number as its point source id, unless
+# point_source_id is set, and --split-flight-lines writes each line to its own file.
+# [flight_lines]
+# max_heading_rate = 2.0
+# min_duration = 10.0
+
+# Drop isolated trajectory records that jump away from both neighbors faster than max_speed, in
+# meters per second, or max_attitude_rate, in degrees per second.
+# [outliers]
+# max_speed = 150.0
+# max_attitude_rate = 180.0
+
+# Smooth trajectories before interpolation, with a "moving_average" or "savitzky_golay" filter
+# over an odd window of records. Records are treated as evenly spaced.
+# [smoothing]
+# method = "savitzky_golay"
+# window = 21
+# order = 2
+
+# The columns and units of .csv and .txt trajectories. Ignored for other formats, except repair.
+[trajectory]
+delimiter = ","
+skip_lines = 1
+angle_units = "degrees"
+height_units = "meters"
+# Set to "omega_phi_kappa" for photogrammetric attitudes, in the roll, pitch, and heading columns.
+angle_convention = "roll_pitch_heading"
+# The attitude columns to negate as they're read, for IMUs whose positive directions differ.
+# negate = ["pitch", "heading"]
+columns = { time = 0, latitude = 1, longitude = 2, height = 3, roll = 4, pitch = 5, heading = 6 }
+# Set to "sort" or "drop_duplicates" to repair records out of time order instead of refusing them.
+repair = "error"
+# For .bag trajectories, the odometry topic and the origin of its east-north-up frame.
+# topic = "/odometry/filtered"
+# origin = { latitude = 61.2, longitude = -149.9, height = 100.0 }
+
+# Options for the point sink, passed through to pabst, e.g. LAS offsets and scale factors. For
+# .ply output, use format = "binary" or "ascii" and e.g. properties = ["x", "y", "z", "time"], and
+# coordinates = "float" to halve the size of x, y, and z, which are then written relative to an
+# offset, e.g. offset = [500000.0, 6000000.0, 0.0], or to the first point. For .csv, .tsv, or
+# .txt output, use e.g. columns = ["x", "y", "z", "intensity"] and precision = 3.
+[sink]
+auto_offsets = true
+scale_factors = { x = 0.01, y = 0.01, z = 0.01 }
+"#;
+
+/// The number of points that `validate` reads from a point cloud to check for GPS times.
+const VALIDATE_POINTS: usize = 1000;
+
+#[derive(Debug, RustcDecodable)]
+struct Args {
+    cmd_batch: bool,
+    cmd_bounds: bool,
+    cmd_config: bool,
+    cmd_init: bool,
+    cmd_info: bool,
+    cmd_run: bool,
+    cmd_validate: bool,
+    arg_config: String,
+    arg_file: Option<String>,
+    arg_infile: String,
+    arg_infiles: Vec<String>,
+    arg_imu_gnss: String,
+    arg_outdir: String,
+    arg_outfile: String,
+    flag_check_coverage: bool,
+    flag_checkpoint: Option<String>,
+    flag_config: Option<String>,
+    flag_dry_run: bool,
+    flag_error_format: String,
+    flag_input_format: String,
+    flag_lever_arm: Option<String>,
+    flag_limit: Option<usize>,
+    flag_manifest: Option<String>,
+    flag_name_template: String,
+    flag_output_format: String,
+    flag_part_size: usize,
+    flag_point_source_ids: bool,
+    flag_print_config: bool,
+    flag_report: Option<String>,
+    flag_resume: bool,
+    flag_sample_interval: usize,
+    flag_scanner: Option<String>,
+    flag_split_flight_lines: bool,
+    flag_split_utm_zones: bool,
+    flag_smrmsg: Option<String>,
+    flag_tile_size: Option<f64>,
+    flag_time_offset: Option<f64>,
+    flag_trajectory_window: Option<f64>,
+    flag_utm_zone: Option<u8>,
+}
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+                         .and_then(|d| {
+                             d.version(Some(version())).decode()
+                         })
+                         .unwrap_or_else(|e| e.exit());
+    init_logger();
+    let result = if args.cmd_run {
+        run(&args)
+    } else if args.cmd_batch {
+        batch(&args)
+    } else if args.cmd_info {
+        info(&args)
+    } else if args.cmd_bounds {
+        bounds(&args)
+    } else if args.cmd_validate {
+        validate(&args)
+    } else {
+        config_init(&args)
+    };
+    if let Err(err) = result {
+        process::exit(report_error(err, &args.flag_error_format));
+    }
+}
+
+/// An error while georeferencing one input file, so the file can be reported with the error.
+#[derive(Debug)]
+struct FileError {
+    file: String,
+    error: Box<Error>,
+}
+
+impl Error for FileError {
+    fn description(&self) -> &str {
+        self.error.description()
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&*self.error)
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.error)
+    }
+}
+
+/// Tags an error with the input file it came from.
+fn in_file(file: &str, error: Box<Error>) -> Box<Error> {
+    Box::new(FileError {
+        file: file.to_string(),
+        error: error,
+    })
+}
+
+/// An error as printed by `--error-format json`.
+#[derive(RustcEncodable)]
+struct ErrorReport {
+    kind: String,
+    message: String,
+    exit_code: i32,
+    file: Option<String>,
+    time: Option<f64>,
+}
+
+/// Prints an error to standard error in the given format and returns the exit code for its kind.
+fn report_error(err: Box<Error>, format: &str) -> i32 {
+    let (file, err) = match err.downcast::<FileError>() {
+        Ok(file_error) => {
+            let file_error = *file_error;
+            (Some(file_error.file), file_error.error)
+        }
+        Err(err) => (None, err),
+    };
+    let (kind, time) = match err.downcast_ref::<georef::Error>() {
+        Some(err) => (err.kind(), err.time()),
+        None if err.is::<io::Error>() => ("io", None),
+        None => ("other", None),
+    };
+    let exit_code = match kind {
+        "config" => 2,
+        "trajectory" => 3,
+        "format" => 4,
+        "point" => 5,
+        "io" => 6,
+        "internal" => 7,
+        _ => 1,
+    };
+    let message = match file {
+        Some(ref file) => format!("{}: {}", file, err),
+        None => err.to_string(),
+    };
+    if format == "json" {
+        let report = ErrorReport {
+            kind: kind.to_string(),
+            message: err.to_string(),
+            exit_code: exit_code,
+            file: file,
+            time: time,
+        };
+        match json::encode(&report) {
+            Ok(s) => {
+                let _ = writeln!(io::stderr(), "{}", s);
+            }
+            Err(_) => {
+                let _ = writeln!(io::stderr(), "error: {}", message);
+            }
+        }
+    } else {
+        if format != "text" {
+            let _ = writeln!(io::stderr(),
+                             "warning: unknown error format {}, using text",
+                             format);
+        }
+        let _ = writeln!(io::stderr(), "error: {}", message);
+    }
+    exit_code
+}
+
+/// Returns the version and the optional features that georef was built with, for --version.
+fn version() -> String {
+    let features = georef::features();
+    format!("{}\nfeatures: {}",
+            env!("CARGO_PKG_VERSION"),
+            if features.is_empty() {
+                "none".to_string()
+            } else {
+                features.join(", ")
+            })
+}
+
+/// Logs warnings and info to stderr, e.g. `warn: skipped 3 points`, unless RUST_LOG asks for
+/// something else, e.g. `RUST_LOG=debug`.
+fn init_logger() {
+    let mut builder = LogBuilder::new();
+    let _ = builder.format(|record| {
+                       format!("{}: {}",
+                               record.level().to_string().to_lowercase(),
+                               record.args())
+                   })
+                   .filter(None, LogLevelFilter::Info);
+    if let Ok(rust_log) = env::var("RUST_LOG") {
+        let _ = builder.parse(&rust_log);
+    }
+    if let Err(err) = builder.init() {
+        let _ = writeln!(io::stderr(), "error: could not start logging: {}", err);
+    }
+}
+
+/// Everything loaded from the config file and trajectory, which is reused across input files.
+struct Setup {
+    config_problems: Vec<georef::Error>,
+    config_json: Json,
+    georeferencer: Georeferencer,
+    trajectory: Box<Trajectory>,
+    source_config: Option<toml::Value>,
+    sink_config: Option<toml::Value>,
+}
+
+fn setup(args: &Args) -> Result<Setup, Box<Error>> {
+    let mut config = try!(read_config(&args.arg_config));
+    let mut georef_config = match config.remove("georef") {
+        Some(table) => try!(GeorefConfig::from_toml(table)),
+        None => return Err(config_error("config file has no [georef] table")),
+    };
+    try!(georef_config.resolve_paths(config_dir(&args.arg_config)));
+    if let Some(table) = config.remove("filter") {
+        if georef_config.filter.is_some() {
+            return Err(config_error("the filter is set in both [filter] and [georef]"));
+        }
+        georef_config.filter = Some(try!(FilterConfig::from_toml(table)));
+    }
+    let georef_config = try!(apply_overrides(georef_config, args));
+    let outliers = match config.remove("outliers") {
+        Some(table) => Some(try!(OutlierConfig::from_toml(table))),
+        None => None,
+    };
+    let smoothing = match config.remove("smoothing") {
+        Some(table) => Some(try!(SmoothingConfig::from_toml(table))),
+        None => None,
+    };
+    let config_problems = georef_config.validate();
+    let config_json = try!(Json::from_str(&try!(json::encode(&georef_config))));
+    let georeferencer = try!(Georeferencer::new(georef_config));
+    let trajectory = match args.flag_trajectory_window {
+        Some(window) => {
+            if args.flag_smrmsg.is_some() || outliers.is_some() || smoothing.is_some() {
+                return Err("--trajectory-window cannot be used with --smrmsg, [outliers], or \
+                            [smoothing]"
+                               .into());
+            }
+            try!(windowed_trajectory(&args.arg_imu_gnss, window))
+        }
+        None => {
+            try!(imu_gnss_from_path(&args.arg_imu_gnss,
+                                    args.flag_smrmsg.as_ref(),
+                                    config.remove("trajectory"),
+                                    outliers,
+                                    smoothing,
+                                    Some(&georeferencer)))
+        }
+    };
+    let mut setup = Setup {
+        config_problems: config_problems,
+        config_json: config_json,
+        georeferencer: georeferencer,
+        trajectory: trajectory,
+        source_config: config.remove("source"),
+        sink_config: config.remove("sink"),
+    };
+    let flight_line_config = match config.remove("flight_lines") {
+        Some(table) => Some(try!(FlightLineConfig::from_toml(table))),
+        None if args.flag_split_flight_lines => Some(FlightLineConfig::default()),
+        None => None,
+    };
+    if let Some(flight_line_config) = flight_line_config {
+        if let Some(problem) = flight_line_config.problems().into_iter().next() {
+            return Err(config_error(&format!("invalid [flight_lines] table: {}", problem)));
+        }
+        let flight_lines = try!(setup.trajectory
+                                     .flight_lines(&flight_line_config)
+                                     .ok_or("flight lines cannot be found in this trajectory"));
+        info!("found {} flight lines", flight_lines.len());
+        setup.georeferencer.set_flight_lines(flight_lines);
+    }
+    Ok(setup)
+}
+
+fn run(args: &Args) -> Result<(), Box<Error>> {
+    let mut setup = try!(setup(args));
+    if args.flag_print_config {
+        print_config(&setup);
+        return Ok(());
+    }
+    let stdin = if args.arg_infile == "-" {
+        let (file, mut spool) = try!(TempFile::create("stdin", &args.flag_input_format));
+        let _ = try!(io::copy(&mut io::stdin(), &mut spool));
+        Some(file)
+    } else {
+        None
+    };
+    let stdout = if args.arg_outfile == "-" {
+        if args.flag_split_utm_zones || args.flag_split_flight_lines {
+            return Err("output cannot be split when writing to standard output".into());
+        }
+        Some(try!(TempFile::create("stdout", &args.flag_output_format)).0)
+    } else {
+        None
+    };
+    let infile = stdin.as_ref().map_or(args.arg_infile.clone(), |file| file.path());
+    let scanner = args.flag_scanner.as_ref().map(|s| s.as_ref());
+    if args.flag_dry_run {
+        return dry_run(&mut setup, &infile, scanner, args.flag_sample_interval);
+    }
+    if args.flag_check_coverage {
+        try!(check_coverage(&setup, &infile, scanner, args.flag_sample_interval)
+                 .map_err(|err| in_file(&args.arg_infile, err)));
+    }
+    let outfile = stdout.as_ref().map_or(args.arg_outfile.clone(), |file| file.path());
+    let start = Instant::now();
+    let summary = match args.flag_checkpoint {
+        Some(ref checkpoint) => {
+            if stdin.is_some() || stdout.is_some() {
+                return Err("checkpointed runs cannot use standard input or output".into());
+            }
+            if try!(split(args)) != Split::None {
+                return Err("checkpointed runs cannot be split".into());
+            }
+            try!(georeference_in_parts(&mut setup,
+                                       &infile,
+                                       &outfile,
+                                       scanner,
+                                       checkpoint,
+                                       args.flag_part_size,
+                                       args.flag_resume)
+                     .map_err(|err| in_file(&args.arg_infile, err)))
+        }
+        None if args.flag_resume => return Err("--resume needs --checkpoint".into()),
+        None => {
+            try!(georeference_file(&mut setup, &infile, &outfile, scanner, try!(split(args)))
+                     .map_err(|err| in_file(&args.arg_infile, err)))
+        }
+    };
+    let file_report = FileReport {
+        infile: args.arg_infile.clone(),
+        outfile: args.arg_outfile.clone(),
+        seconds: seconds(start.elapsed()),
+        summary: summary,
+    };
+    if let Some(ref file) = stdout {
+        let _ = try!(io::copy(&mut try!(File::open(&file.0)), &mut io::stdout()));
+    }
+    report_summary(&summary);
+    if let Some(ref path) = args.flag_report {
+        try!(write_report(path, &setup, vec![file_report], start));
+    }
+    Ok(())
+}
+
+/// A machine-readable record of a run or batch, written with `--report`.
+#[derive(Debug, RustcEncodable)]
+struct Report {
+    config: Json,
+    files: Vec<FileReport>,
+    ground_scale_factor: Option<f64>,
+    seconds: f64,
+}
+
+/// A machine-readable record of one georeferenced file.
+#[derive(Debug, RustcEncodable)]
+struct FileReport {
+    infile: String,
+    outfile: String,
+    seconds: f64,
+    summary: Summary,
+}
+
+/// Prints the [georef] table that the georeferencer was built from, and the [source] and [sink]
+/// tables, as TOML.
+fn print_config(setup: &Setup) {
+    let mut table = toml::Table::new();
+    let _ = table.insert("georef".to_string(), setup.georeferencer.config().to_toml());
+    if let Some(ref source_config) = setup.source_config {
+        let _ = table.insert("source".to_string(), source_config.clone());
+    }
+    if let Some(ref sink_config) = setup.sink_config {
+        let _ = table.insert("sink".to_string(), sink_config.clone());
+    }
+    println!("# The effective configuration of georef {}.", env!("CARGO_PKG_VERSION"));
+    print!("{}", toml::Value::Table(table));
+}
+
+fn write_report(path: &str,
+                setup: &Setup,
+                files: Vec<FileReport>,
+                start: Instant)
+                -> Result<(), Box<Error>> {
+    let report = Report {
+        config: setup.config_json.clone(),
+        files: files,
+        ground_scale_factor: setup.georeferencer.ground_scale_factor(),
+        seconds: seconds(start.elapsed()),
+    };
+    let mut file = try!(File::create(path));
+    try!(writeln!(file, "{}", json::as_pretty_json(&report)));
+    Ok(())
+}
+
+fn bounds(args: &Args) -> Result<(), Box<Error>> {
+    if args.flag_tile_size.map_or(false, |size| !(size > 0.0)) {
+        return Err("--tile-size must be greater than zero".into());
+    }
+    let mut setup = try!(setup(args));
+    let mut source = try!(open_source(&args.arg_infile, setup.source_config.clone()));
+    let scanner = args.flag_scanner.as_ref().map(|s| s.as_ref());
+    let georeferencer = try!(select_georeferencer(&setup.georeferencer, scanner));
+    let dry_run = try!(georeferencer.dry_run(&mut *source,
+                                             &*setup.trajectory,
+                                             args.flag_sample_interval));
+    println!("sampled {} of {} points, {} covered by the trajectory",
+             dry_run.points_sampled,
+             dry_run.points,
+             dry_run.points_covered);
+    let bounds = try!(dry_run.bounds
+                             .ok_or("no sampled points are covered by the trajectory"));
+    println!("x: {:.3} to {:.3} ({:.3} across)",
+             bounds.min_x,
+             bounds.max_x,
+             bounds.max_x - bounds.min_x);
+    println!("y: {:.3} to {:.3} ({:.3} across)",
+             bounds.min_y,
+             bounds.max_y,
+             bounds.max_y - bounds.min_y);
+    println!("z: {:.3} to {:.3} ({:.3} across)",
+             bounds.min_z,
+             bounds.max_z,
+             bounds.max_z - bounds.min_z);
+    if let Some(tile_size) = args.flag_tile_size {
+        let (columns, rows) = bounds.tiles(tile_size);
+        println!("tiles of {}: {} columns by {} rows, {} in all",
+                 tile_size,
+                 columns,
+                 rows,
+                 columns * rows);
+    }
+    Ok(())
+}
+
+fn dry_run(setup: &mut Setup,
+           infile: &str,
+           scanner: Option<&str>,
+           sample_interval: usize)
+           -> Result<(), Box<Error>> {
+    for problem in &setup.config_problems {
+        println!("config problem: {}", problem);
+    }
+    if let Some((start, end)) = setup.trajectory.time_bounds() {
+        println!("trajectory: {} to {} ({} s)", start, end, end - start);
+    }
+    let mut source = try!(open_source(infile, setup.source_config.clone()));
+    let georeferencer = try!(select_georeferencer(&setup.georeferencer, scanner));
+    let dry_run = try!(georeferencer.dry_run(&mut *source,
+                                             &*setup.trajectory,
+                                             sample_interval));
+    println!("points: {}", dry_run.points);
+    if dry_run.points_sampled > 0 {
+        println!("trajectory coverage: {:.1}% of {} sampled points",
+                 100.0 * dry_run.points_covered as f64 / dry_run.points_sampled as f64,
+                 dry_run.points_sampled);
+    }
+    match dry_run.bounds {
+        Some(bounds) => {
+            println!("expected bounds: x {} to {}, y {} to {}, z {} to {}",
+                     bounds.min_x,
+                     bounds.max_x,
+                     bounds.min_y,
+                     bounds.max_y,
+                     bounds.min_z,
+                     bounds.max_z)
+        }
+        None => println!("expected bounds: none, no sampled points are covered by the trajectory"),
+    }
+    Ok(())
+}
+
+/// Checks that the trajectory covers a sample of a point cloud's times.
+///
+/// With the strict error policy, uncovered points would stop the run partway through, so they are
+/// an error up front. Otherwise, the coverage is a warning.
+fn check_coverage(setup: &Setup,
+                  infile: &str,
+                  scanner: Option<&str>,
+                  sample_interval: usize)
+                  -> Result<(), Box<Error>> {
+    let georeferencer = try!(select_georeferencer(&setup.georeferencer, scanner));
+    let mut source = try!(open_source(infile, setup.source_config.clone()));
+    let coverage = try!(georeferencer.coverage(&mut *source, &*setup.trajectory, sample_interval));
+    if coverage.points_covered == coverage.points_sampled {
+        return Ok(());
+    }
+    let mut message = format!("the trajectory covers {:.1}% of {} sampled points in {}",
+                              100.0 * coverage.fraction(),
+                              coverage.points_sampled,
+                              infile);
+    if let (Some(points), Some(trajectory)) = (coverage.point_time_bounds,
+                                               coverage.trajectory_time_bounds) {
+        message.push_str(&format!(" (points span {} to {}, the trajectory {} to {})",
+                                  points.0,
+                                  points.1,
+                                  trajectory.0,
+                                  trajectory.1));
+    }
+    if georeferencer.error_policy() == ErrorPolicy::Strict {
+        Err(message.into())
+    } else {
+        warn!("{}", message);
+        Ok(())
+    }
+}
+
+/// A temporary file that is removed when dropped.
+///
+/// pabst opens sources and sinks by path, and picks their formats by extension, so standard input
+/// and output are spooled through temporary files.
+struct TempFile(PathBuf);
+
+/// Counts the temporary files this process has created, so their names never repeat.
+static TEMP_FILES: AtomicUsize = ATOMIC_USIZE_INIT;
+
+impl TempFile {
+    /// Creates a new, empty temporary file, named by the process id, a counter, and the time.
+    ///
+    /// The file is created exclusively, so an existing file, e.g. another user's, is never
+    /// reused or overwritten.
+    fn create(name: &str, extension: &str) -> io::Result<(TempFile, File)> {
+        let nanos = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64)
+                        .unwrap_or(0);
+        let path = env::temp_dir().join(format!("georef-{}-{}-{}-{}.{}",
+                                                name,
+                                                unsafe { libc::getpid() },
+                                                TEMP_FILES.fetch_add(1, Ordering::SeqCst),
+                                                nanos,
+                                                extension));
+        let file = try!(OpenOptions::new().write(true).create_new(true).open(&path));
+        Ok((TempFile(path), file))
+    }
+
+    fn path(&self) -> String {
+        self.0.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn batch(args: &Args) -> Result<(), Box<Error>> {
+    let mut infiles = Vec::new();
+    for infile in &args.arg_infiles {
+        infiles.push((infile.clone(), args.flag_scanner.clone()));
+    }
+    if let Some(ref manifest) = args.flag_manifest {
+        let mut s = String::new();
+        let _ = try!(try!(File::open(manifest)).read_to_string(&mut s));
+        for line in s.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(2, '\t');
+            let infile = fields.next().unwrap_or("").trim().to_string();
+            let scanner = match fields.next().map(|s| s.trim()) {
+                Some(scanner) if !scanner.is_empty() => Some(scanner.to_string()),
+                _ => args.flag_scanner.clone(),
+            };
+            infiles.push((infile, scanner));
+        }
+    }
+    if infiles.is_empty() {
+        return Err("no input files were given".into());
+    }
+    let mut outfiles: HashMap<String, &str> = HashMap::new();
+    for &(ref infile, _) in &infiles {
+        let name = output_name(&args.flag_name_template, infile);
+        if let Some(other) = outfiles.insert(name.clone(), infile) {
+            return Err(format!("{} and {} would both be written to {}; rename one of them or \
+                                use a --name-template that tells them apart",
+                               other,
+                               infile,
+                               name)
+                           .into());
+        }
+    }
+    try!(fs::create_dir_all(&args.arg_outdir));
+    let mut setup = try!(setup(args));
+    if args.flag_print_config {
+        print_config(&setup);
+        return Ok(());
+    }
+    if args.flag_check_coverage {
+        for &(ref infile, ref scanner) in &infiles {
+            try!(check_coverage(&setup,
+                                infile,
+                                scanner.as_ref().map(|s| s.as_ref()),
+                                args.flag_sample_interval)
+                     .map_err(|err| in_file(infile, err)));
+        }
+    }
+    if args.flag_point_source_ids && infiles.len() > u16::MAX as usize {
+        return Err(format!("--point-source-ids can number at most {} files", u16::MAX).into());
+    }
+    let start = Instant::now();
+    let mut file_reports = Vec::new();
+    for (i, &(ref infile, ref scanner)) in infiles.iter().enumerate() {
+        if args.flag_point_source_ids {
+            setup.georeferencer.set_point_source_id(Some(i as u16 + 1));
+        }
+        let outfile = Path::new(&args.arg_outdir).join(output_name(&args.flag_name_template,
+                                                                   infile));
+        if outfile.exists() &&
+           try!(fs::canonicalize(&outfile)) == try!(fs::canonicalize(infile)) {
+            return Err(format!("output file {} would overwrite its input", outfile.display())
+                           .into());
+        }
+        let outfile = outfile.to_string_lossy().into_owned();
+        println!("{} -> {}", infile, outfile);
+        let file_start = Instant::now();
+        let summary = try!(georeference_file(&mut setup,
+                                             infile,
+                                             &outfile,
+                                             scanner.as_ref().map(|s| s.as_ref()),
+                                             try!(split(args)))
+                               .map_err(|err| in_file(infile, err)));
+        report_summary(&summary);
+        file_reports.push(FileReport {
+            infile: infile.clone(),
+            outfile: outfile,
+            seconds: seconds(file_start.elapsed()),
+            summary: summary,
+        });
+    }
+    if let Some(ref path) = args.flag_report {
+        try!(write_report(path, &setup, file_reports, start));
+    }
+    Ok(())
+}
+
+/// Returns the georeferencer for a scanner id, or the top-level one if there is no id.
+fn select_georeferencer<'a>(georeferencer: &'a Georeferencer,
+                            scanner: Option<&str>)
+                            -> Result<&'a Georeferencer, Box<Error>> {
+    match scanner {
+        Some(id) => {
+            georeferencer.scanner(id)
+                         .ok_or_else(|| format!("the config has no scanner with id {}", id).into())
+        }
+        None => Ok(georeferencer),
+    }
+}
+
+/// Fills in a batch output file name template.
+///
+/// `{stem}` is replaced with the input file's name without its extension, and `{name}` with the
+/// input file's full name.
+fn output_name(template: &str, infile: &str) -> String {
+    let path = Path::new(infile);
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    template.replace("{stem}", stem).replace("{name}", name)
+}
+
+/// How a run's output is split into files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Split {
+    None,
+    UtmZones,
+    FlightLines,
+}
+
+fn split(args: &Args) -> Result<Split, Box<Error>> {
+    match (args.flag_split_utm_zones, args.flag_split_flight_lines) {
+        (true, true) => Err("--split-utm-zones and --split-flight-lines cannot be combined".into()),
+        (true, false) => Ok(Split::UtmZones),
+        (false, true) => Ok(Split::FlightLines),
+        (false, false) => Ok(Split::None),
+    }
+}
+
+fn georeference_file(setup: &mut Setup,
+                     infile: &str,
+                     outfile: &str,
+                     scanner: Option<&str>,
+                     split: Split)
+                     -> Result<Summary, Box<Error>> {
+    let georeferencer = try!(select_georeferencer(&setup.georeferencer, scanner));
+    let sink_config = &setup.sink_config;
+    if split == Split::UtmZones {
+        let mut source = try!(open_source(infile, setup.source_config.clone()));
+        let open_sink = |zone| {
+            let path = suffixed_path(outfile, &format!("zone{:02}", zone));
+            let wkt = georeferencer.output_wkt_in_zone(zone);
+            let config = las_sink_config(&path, sink_config, wkt);
+            open_sink(&path, config)
+        };
+        let (sinks, summary) = try!(georeferencer.georeference_by_zone(&mut *source,
+                                                                       &*setup.trajectory,
+                                                                       open_sink));
+        for (_, sink) in sinks {
+            try!(sink.close());
+        }
+        Ok(summary)
+    } else if split == Split::FlightLines {
+        let mut source = try!(open_source(infile, setup.source_config.clone()));
+        let wkt = georeferencer.output_wkt();
+        let open_sink = |line| {
+            let path = suffixed_path(outfile, &format!("line{:02}", line));
+            let config = las_sink_config(&path, sink_config, wkt.clone());
+            open_sink(&path, config)
+        };
+        let (sinks, summary) =
+            try!(georeferencer.georeference_by_flight_line(&mut *source,
+                                                           &*setup.trajectory,
+                                                           open_sink));
+        for (_, sink) in sinks {
+            try!(sink.close());
+        }
+        Ok(summary)
+    } else {
+        // The file is read and written on threads of their own, so I/O overlaps with
+        // georeferencing.
+        let chunk_size = georeferencer.chunk_size();
+        let bound = georeferencer.max_chunks_in_flight();
+        let mut source = try!(ReaderThread::spawn(infile,
+                                                  setup.source_config.clone(),
+                                                  chunk_size,
+                                                  bound));
+        let config = las_sink_config(outfile, sink_config, georeferencer.output_wkt());
+        let mut sink = try!(WriterThread::spawn(outfile, config, chunk_size, bound));
+        let mut progress_bar = if is_terminal() {
+            Some(ProgressBar::new())
+        } else {
+            None
+        };
+        let summary = {
+            let report = |progress: &Progress| {
+                if let Some(ref mut progress_bar) = progress_bar {
+                    progress_bar.draw(progress);
+                }
+            };
+            try!(georeferencer.georeference_with_progress(&mut source,
+                                                          &*setup.trajectory,
+                                                          &mut sink,
+                                                          las_point_count(infile),
+                                                          report))
+        };
+        if let Some(ref mut progress_bar) = progress_bar {
+            progress_bar.finish();
+        }
+        try!(sink.close());
+        Ok(summary)
+    }
+}
+
+/// The finished parts of a checkpointed run, written with `--checkpoint`.
+#[derive(Debug, RustcDecodable, RustcEncodable)]
+struct Checkpoint {
+    infile: String,
+    part_size: usize,
+    parts_done: usize,
+    next_chunk: usize,
+}
+
+/// Georeferences a file in parts, recording each part in the checkpoint file once its output
+/// file is closed.
+fn georeference_in_parts(setup: &mut Setup,
+                         infile: &str,
+                         outfile: &str,
+                         scanner: Option<&str>,
+                         checkpoint: &str,
+                         part_size: usize,
+                         resume: bool)
+                         -> Result<Summary, Box<Error>> {
+    let (first_part, next_chunk) = if resume {
+        let mut json = String::new();
+        let _ = try!(try!(File::open(checkpoint)).read_to_string(&mut json));
+        let previous: Checkpoint = try!(json::decode(&json));
+        if previous.infile != infile || previous.part_size != part_size {
+            return Err(format!("checkpoint {} is for {} in parts of {} points, not {} in parts \
+                                of {} points",
+                               checkpoint,
+                               previous.infile,
+                               previous.part_size,
+                               infile,
+                               part_size)
+                           .into());
+        }
+        info!("resuming after {} finished parts at input point {}",
+              previous.parts_done,
+              previous.next_chunk);
+        (previous.parts_done, previous.next_chunk)
+    } else {
+        (0, 0)
+    };
+    let georeferencer = try!(select_georeferencer(&setup.georeferencer, scanner));
+    let mut source = try!(open_source(infile, setup.source_config.clone()));
+    // The last finished part may have held the end of the input, so only a skip that falls short
+    // of the last part's first point means the input has changed.
+    let skipped = try!(source.skip(next_chunk));
+    if skipped < next_chunk && skipped <= next_chunk.saturating_sub(part_size) {
+        return Err(format!("checkpoint {} resumes at point {} of {}, which only has {} points",
+                           checkpoint,
+                           next_chunk,
+                           infile,
+                           skipped)
+                       .into());
+    }
+    let sink_config = &setup.sink_config;
+    let wkt = georeferencer.output_wkt();
+    let open_sink = |part: usize| {
+        let path = suffixed_path(outfile, &format!("part{:03}", part + 1));
+        let config = las_sink_config(&path, sink_config, wkt.clone());
+        open_sink(&path, config)
+    };
+    let close_sink = |part: usize, sink: Box<OutputSink>| {
+        try!(sink.close());
+        let checkpoint_record = Checkpoint {
+            infile: infile.to_string(),
+            part_size: part_size,
+            parts_done: part + 1,
+            next_chunk: (part + 1) * part_size,
+        };
+        write_checkpoint(checkpoint, &checkpoint_record).map_err(georef::Error::from)
+    };
+    Ok(try!(georeferencer.georeference_in_parts(&mut *source,
+                                                &*setup.trajectory,
+                                                part_size,
+                                                first_part,
+                                                open_sink,
+                                                close_sink)))
+}
+
+/// Writes a checkpoint next to its path and then moves it into place, so a crash never leaves a
+/// half-written checkpoint behind.
+fn write_checkpoint(path: &str, checkpoint: &Checkpoint) -> io::Result<()> {
+    let temporary = format!("{}.tmp", path);
+    {
+        let mut file = try!(File::create(&temporary));
+        try!(writeln!(file, "{}", json::as_pretty_json(checkpoint)));
+        try!(file.sync_all());
+    }
+    fs::rename(&temporary, path)
+}
+
+/// Returns true if both standard output and standard error are terminals.
+#[cfg(unix)]
+fn is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) != 0 && libc::isatty(libc::STDERR_FILENO) != 0 }
+}
+
+#[cfg(not(unix))]
+fn is_terminal() -> bool {
+    false
+}
+
+/// Reads the number of points from a LAS file's header, if the file is a LAS file.
+fn las_point_count(path: &str) -> Option<usize> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return None,
+    };
+    let mut header = [0; 255];
+    if file.read_exact(&mut header).is_err() || &header[0..4] != b"LASF" {
+        return None;
+    }
+    let minor_version = header[25];
+    let legacy = (&header[107..111]).read_u32::<LittleEndian>().unwrap_or(0) as u64;
+    let count = if legacy == 0 && minor_version >= 4 {
+        (&header[247..255]).read_u64::<LittleEndian>().unwrap_or(0)
+    } else {
+        legacy
+    };
+    Some(count as usize)
+}
+
+/// A progress bar with a point rate and ETA, drawn on standard error.
+struct ProgressBar {
+    start: Instant,
+    last_draw: Option<Instant>,
+}
+
+impl ProgressBar {
+    fn new() -> ProgressBar {
+        ProgressBar {
+            start: Instant::now(),
+            last_draw: None,
+        }
+    }
+
+    /// Redraws the bar, at most ten times a second.
+    fn draw(&mut self, progress: &Progress) {
+        if self.last_draw.map_or(false, |last_draw| seconds(last_draw.elapsed()) < 0.1) {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+        let elapsed = seconds(self.start.elapsed());
+        let rate = progress.points_processed as f64 / elapsed.max(1e-9);
+        let line = match progress.points_total {
+            Some(total) if total > 0 => {
+                let fraction = (progress.points_processed as f64 / total as f64).min(1.0);
+                let filled = (fraction * 30.0).round() as usize;
+                let remaining = total.saturating_sub(progress.points_processed) as f64 / rate;
+                format!("[{}{}] {:3.0}% {} points, {:.0} points/s, ETA {}",
+                        repeat("#", filled),
+                        repeat("-", 30 - filled),
+                        fraction * 100.0,
+                        progress.points_processed,
+                        rate,
+                        hms(remaining))
+            }
+            _ => format!("{} points, {:.0} points/s", progress.points_processed, rate),
+        };
+        let _ = write!(io::stderr(), "\r{}\x1b[K", line);
+    }
+
+    fn finish(&mut self) {
+        let _ = writeln!(io::stderr(), "");
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+fn repeat(s: &str, n: usize) -> String {
+    (0..n).map(|_| s).collect()
+}
+
+/// Formats a number of seconds as hours, minutes, and seconds.
+fn hms(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "--:--:--".to_string();
+    }
+    let seconds = seconds.round() as u64;
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Applies any command-line overrides to a config.
+fn apply_overrides(mut config: GeorefConfig, args: &Args) -> Result<GeorefConfig, Box<Error>> {
+    if let Some(utm_zone) = args.flag_utm_zone {
+        config.utm_zone = utm_zone;
+    }
+    if let Some(time_offset) = args.flag_time_offset {
+        config.time_offset = Some(time_offset);
+    }
+    if let Some(ref lever_arm) = args.flag_lever_arm {
+        let values = try!(lever_arm.split(',')
+                                   .map(|s| s.trim().parse::<f64>())
+                                   .collect::<Result<Vec<_>, _>>());
+        if values.len() != 3 {
+            return Err(format!("--lever-arm needs three values, got {}", lever_arm).into());
+        }
+        config.lever_arm = Vec3::new(values[0], values[1], values[2]);
+        if let Some(ref mut scanners) = config.scanner {
+            for scanner in scanners.iter_mut() {
+                scanner.lever_arm = None;
+            }
+        }
+    }
+    if let Some(limit) = args.flag_limit {
+        config.limit = Some(limit);
+    }
+    Ok(config)
+}
+
+fn info(args: &Args) -> Result<(), Box<Error>> {
+    let trajectory_config = match args.flag_config {
+        Some(ref path) => try!(read_config(path)).remove("trajectory"),
+        None => None,
+    };
+    let trajectory = try!(imu_gnss_from_path(&args.arg_imu_gnss,
+                                             args.flag_smrmsg.as_ref(),
+                                             trajectory_config,
+                                             None,
+                                             None,
+                                             None));
+    match trajectory.time_bounds() {
+        Some((start, end)) => {
+            println!("start time: {}", start);
+            println!("end time: {}", end);
+            println!("duration: {} s", end - start);
+        }
+        None => println!("time span: unknown"),
+    }
+    if let Some(stats) = trajectory.stats() {
+        println!("records: {}", stats.records);
+        println!("sample rate: {:.1} Hz", stats.sample_rate);
+        println!("gaps: {}", stats.gaps.len());
+        for &(start, end) in &stats.gaps {
+            println!("    {} to {} ({} s)", start, end, end - start);
+        }
+        println!("roll: {:.3} to {:.3} degrees",
+                 stats.roll_bounds.0.to_degrees(),
+                 stats.roll_bounds.1.to_degrees());
+        println!("pitch: {:.3} to {:.3} degrees",
+                 stats.pitch_bounds.0.to_degrees(),
+                 stats.pitch_bounds.1.to_degrees());
+        println!("latitude: {:.6} to {:.6} degrees",
+                 stats.latitude_bounds.0.to_degrees(),
+                 stats.latitude_bounds.1.to_degrees());
+        println!("longitude: {:.6} to {:.6} degrees",
+                 stats.longitude_bounds.0.to_degrees(),
+                 stats.longitude_bounds.1.to_degrees());
+        println!("height: {:.3} to {:.3} m", stats.height_bounds.0, stats.height_bounds.1);
+        println!("utm zones: {}",
+                 stats.utm_zones
+                      .iter()
+                      .map(|zone| zone.to_string())
+                      .collect::<Vec<_>>()
+                      .join(", "));
+    }
+    Ok(())
+}
+
+fn config_init(args: &Args) -> Result<(), Box<Error>> {
+    match args.arg_file {
+        Some(ref path) => {
+            if Path::new(path).exists() {
+                return Err(format!("{} already exists", path).into());
+            }
+            try!(try!(File::create(path)).write_all(DEFAULT_CONFIG.as_bytes()));
+        }
+        None => print!("{}", DEFAULT_CONFIG),
+    }
+    Ok(())
+}
+
+fn validate(args: &Args) -> Result<(), Box<Error>> {
+    let mut config = try!(read_config(&args.arg_config));
+    let trajectory_config = config.get("trajectory").cloned();
+    let source_config = config.get("source").cloned();
+    let mut problems = match config.remove("georef") {
+        Some(table) => {
+            let mut georef_config = try!(GeorefConfig::from_toml(table));
+            try!(georef_config.resolve_paths(config_dir(&args.arg_config)));
+            georef_config.validate()
+                         .into_iter()
+                         .map(|err| err.to_string())
+                         .collect()
+        }
+        None => vec!["config file has no [georef] table".to_string()],
+    };
+    if let Some(table) = config.remove("trajectory") {
+        match CsvConfig::from_toml(table) {
+            Ok(CsvConfig { repair: Some(ref repair), .. }) => {
+                if let Err(err) = repair.parse::<RepairPolicy>() {
+                    problems.push(format!("invalid [trajectory] table: {}", err));
+                }
+            }
+            Ok(_) => {}
+            Err(err) => problems.push(format!("invalid [trajectory] table: {}", err)),
+        }
+    }
+    if let Some(table) = config.remove("flight_lines") {
+        match FlightLineConfig::from_toml(table) {
+            Ok(config) => {
+                problems.extend(config.problems()
+                                      .into_iter()
+                                      .map(|problem| format!("invalid [flight_lines] table: {}",
+                                                             problem)))
+            }
+            Err(err) => problems.push(format!("invalid [flight_lines] table: {}", err)),
+        }
+    }
+    if let Some(table) = config.remove("outliers") {
+        match OutlierConfig::from_toml(table) {
+            Ok(config) => {
+                problems.extend(config.problems()
+                                      .into_iter()
+                                      .map(|problem| format!("invalid [outliers] table: {}",
+                                                             problem)))
+            }
+            Err(err) => problems.push(format!("invalid [outliers] table: {}", err)),
+        }
+    }
+    if let Some(table) = config.remove("smoothing") {
+        match SmoothingConfig::from_toml(table) {
+            Ok(config) => {
+                problems.extend(config.problems()
+                                      .into_iter()
+                                      .map(|problem| format!("invalid [smoothing] table: {}",
+                                                             problem)))
+            }
+            Err(err) => problems.push(format!("invalid [smoothing] table: {}", err)),
+        }
+    }
+    if problems.is_empty() {
+        println!("pass: {} is valid", args.arg_config);
+    } else {
+        for problem in &problems {
+            println!("fail: {}: {}", args.arg_config, problem);
+        }
+    }
+    if !args.arg_imu_gnss.is_empty() {
+        match validate_trajectory(&args.arg_imu_gnss, trajectory_config) {
+            Ok(message) => println!("pass: {}: {}", args.arg_imu_gnss, message),
+            Err(message) => {
+                println!("fail: {}: {}", args.arg_imu_gnss, message);
+                problems.push(message);
+            }
+        }
+    }
+    if !args.arg_infile.is_empty() {
+        match validate_source(&args.arg_infile, source_config) {
+            Ok(message) => println!("pass: {}: {}", args.arg_infile, message),
+            Err(message) => {
+                println!("fail: {}: {}", args.arg_infile, message);
+                problems.push(message);
+            }
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(config_error(&format!("found {} problems", problems.len())))
+    }
+}
+
+/// Reads a trajectory, describing it or what went wrong and how to fix it.
+fn validate_trajectory(path: &str, config: Option<toml::Value>) -> Result<String, String> {
+    let trajectory = match imu_gnss_from_path(path, None, config, None, None, None) {
+        Ok(trajectory) => trajectory,
+        Err(err) => {
+            return Err(format!("could not read the trajectory ({}); check that its extension \
+                                is .sbet, .out, .pos, .csv, .txt, .bag, or .toml for setups \
+                                and, for delimited text, that the [trajectory] table's columns \
+                                and units match the file",
+                               err))
+        }
+    };
+    match trajectory.time_bounds() {
+        Some((start, end)) => {
+            Ok(format!("trajectory is readable and in time order, from {} to {}", start, end))
+        }
+        None => {
+            Err("the trajectory has fewer than two records, so no point can be interpolated"
+                    .to_string())
+        }
+    }
+}
+
+/// Opens a point cloud and reads its first points, describing them or what went wrong and how to
+/// fix it.
+fn validate_source(path: &str, config: Option<toml::Value>) -> Result<String, String> {
+    let mut source = try!(open_source(path, config).map_err(|err| {
+        format!("could not open the point cloud ({}); check the path, the extension, and the \
+                 [source] table",
+                err)
+    }));
+    let points = match source.source(VALIDATE_POINTS) {
+        Ok(Some(points)) => points,
+        Ok(None) => return Err("the point cloud has no points".to_string()),
+        Err(err) => return Err(format!("could not read points ({})", err)),
+    };
+    let missing = points.iter().filter(|point| point.gps_time.is_none()).count();
+    if missing > 0 {
+        return Err(format!("{} of the first {} points have no GPS time, which is needed to \
+                            find their poses; for LAS, use a point format that stores GPS \
+                            time, e.g. 1 or 3",
+                           missing,
+                           points.len()));
+    }
+    Ok(format!("point cloud opens and its first {} points have GPS times; run with \
+                --check-coverage to check them against the trajectory",
+               points.len()))
+}
+
+#[cfg(feature = "yaml")]
+fn yaml_config(s: &str) -> Result<toml::Table, Box<Error>> {
+    Ok(try!(yaml::to_table(s)))
+}
+
+#[cfg(not(feature = "yaml"))]
+fn yaml_config(_: &str) -> Result<toml::Table, Box<Error>> {
+    Err(config_error("YAML config files need georef built with the yaml feature"))
+}
+
+/// Returns a config error, which exits with the config code, for a problem with a whole table or
+/// file rather than one field.
+fn config_error(message: &str) -> Box<Error> {
+    Box::new(georef::Error::Config(message.to_string()))
+}
+
+/// Returns the directory that a config file's relative paths are relative to.
+fn config_dir(path: &str) -> &Path {
+    Path::new(path).parent().unwrap_or(Path::new(""))
+}
+
+/// Reads a config file into a toml table, so every format shares the same decoding structs.
+///
+/// Files with a .json extension are read as JSON, files with a .yaml or .yml extension are read as
+/// YAML, and everything else is read as TOML.
+fn read_config(path: &str) -> Result<toml::Table, Box<Error>> {
+    let mut s = String::new();
+    let _ = try!(try!(File::open(path)).read_to_string(&mut s));
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("yaml") | Some("yml") => yaml_config(&s),
+        Some("json") => Ok(try!(georef::json::to_table(&s))),
+        _ => {
+            toml::Parser::new(&s)
+                .parse()
+                .ok_or_else(|| config_error(&format!("could not parse config file {}", path)))
+        }
+    }
+}
+
+fn report_summary(summary: &Summary) {
+    if summary.points_skipped > 0 {
+        warn!("skipped {} points outside of and {} points in gaps in the IMU/GNSS records",
+              summary.points_skipped_outside,
+              summary.points_skipped_in_gaps);
+    }
+    if summary.points_filtered > 0 {
+        info!("filtered out {} points", summary.points_filtered);
+    }
+    if let Some(points_per_second) = summary.points_per_second() {
+        let metrics = &summary.metrics;
+        info!("{:.0} points per second; read {:.2} s, interpolate {:.2} s, transform {:.2} s, \
+               write {:.2} s",
+              points_per_second,
+              metrics.read,
+              metrics.interpolate,
+              metrics.transform,
+              metrics.write);
+    }
+}
+
+/// Opens a source by extension: .bag files are read by georef, and all others by pabst.
+fn open_source(path: &str, config: Option<toml::Value>) -> Result<Box<PointSource>, georef::Error> {
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("bag") => {
+            let config = match config {
+                Some(config) => {
+                    try!(RosbagSourceConfig::from_toml(config)
+                             .map_err(|err| georef::Error::Rosbag(err.to_string())))
+                }
+                None => RosbagSourceConfig::default(),
+            };
+            Ok(Box::new(try!(RosbagSource::from_path(path, &config))))
+        }
+        _ => Ok(Box::new(try!(open_file_source(path, config)))),
+    }
+}
+
+/// Opens a sink by extension: .ply, .csv, .tsv, and .txt files are written by georef, and all
+/// others by pabst.
+fn open_sink(path: &str, config: Option<toml::Value>) -> Result<Box<OutputSink>, georef::Error> {
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("ply") => {
+            let config = match config {
+                Some(config) => {
+                    try!(PlyConfig::from_toml(config)
+                             .map_err(|err| georef::Error::Ply(err.to_string())))
+                }
+                None => PlyConfig::default(),
+            };
+            Ok(Box::new(try!(PlySink::from_path(path, &config))))
+        }
+        Some(extension @ "csv") | Some(extension @ "tsv") | Some(extension @ "txt") => {
+            let mut config = match config {
+                Some(config) => {
+                    try!(TextConfig::from_toml(config)
+                             .map_err(|err| georef::Error::Text(err.to_string())))
+                }
+                None => TextConfig::default(),
+            };
+            if extension == "tsv" && config.delimiter.is_none() {
+                config.delimiter = Some("tab".to_string());
+            }
+            Ok(Box::new(try!(TextSink::from_path(path, &config))))
+        }
+        _ => Ok(Box::new(try!(open_file_sink(path, config)))),
+    }
+}
+
+/// An output file, which is closed once every point is written.
+///
+/// georef's own sinks are written as `PointSink`s, so they get each point's trajectory record,
+/// e.g. for its accuracy.
+trait OutputSink: PointSink {
+    /// Finishes writing the file.
+    fn close(self: Box<Self>) -> Result<(), georef::Error>;
+}
+
+impl OutputSink for Box<pabst::Sink> {
+    fn close(self: Box<Self>) -> Result<(), georef::Error> {
+        (*self).close_sink().map_err(georef::Error::from)
+    }
+}
+
+impl OutputSink for PlySink<BufWriter<File>> {
+    fn close(self: Box<Self>) -> Result<(), georef::Error> {
+        let _ = try!((*self).finish());
+        Ok(())
+    }
+}
+
+impl OutputSink for TextSink<BufWriter<File>> {
+    fn close(self: Box<Self>) -> Result<(), georef::Error> {
+        let _ = try!((*self).finish());
+        Ok(())
+    }
+}
+
+/// A source that's opened and read on a thread of its own, which hands chunks of points over a
+/// bounded channel.
+struct ReaderThread {
+    receiver: Receiver<Result<Vec<Point>, georef::Error>>,
+    points: Vec<Point>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ReaderThread {
+    /// Opens a source on a new thread, which reads ahead by at most `bound` chunks of
+    /// `chunk_size` points.
+    fn spawn(path: &str,
+             config: Option<toml::Value>,
+             chunk_size: usize,
+             bound: usize)
+             -> Result<ReaderThread, georef::Error> {
+        let (opened_sender, opened_receiver) = mpsc::channel();
+        let (sender, receiver) = mpsc::sync_channel(bound);
+        let path = path.to_string();
+        let handle = thread::spawn(move || {
+            let mut source = match open_source(&path, config) {
+                Ok(source) => source,
+                Err(err) => {
+                    let _ = opened_sender.send(Err(err));
+                    return;
+                }
+            };
+            let _ = opened_sender.send(Ok(()));
+            loop {
+                let chunk = match source.source(chunk_size) {
+                    Ok(Some(points)) => Ok(points),
+                    Ok(None) => return,
+                    Err(err) => Err(err),
+                };
+                let failed = chunk.is_err();
+                // The receiver hangs up when georeferencing stops early, e.g. at the limit.
+                if sender.send(chunk).is_err() || failed {
+                    return;
+                }
+            }
+        });
+        try!(try!(opened_receiver.recv().map_err(|_| georef::Error::WorkerThread)));
+        Ok(ReaderThread {
+            receiver: receiver,
+            points: Vec::new(),
+            handle: Some(handle),
+        })
+    }
+}
+
+impl PointSource for ReaderThread {
+    fn source(&mut self, want: usize) -> Result<Option<Vec<Point>>, georef::Error> {
+        while self.points.is_empty() {
+            match self.receiver.recv() {
+                Ok(chunk) => self.points = try!(chunk),
+                Err(_) => {
+                    // The thread hung up, so the source is exhausted unless it panicked.
+                    if let Some(handle) = self.handle.take() {
+                        try!(handle.join().map_err(|_| georef::Error::WorkerThread));
+                    }
+                    break;
+                }
+            }
+        }
+        PointSource::source(&mut self.points, want)
+    }
+}
+
+/// A sink that's opened and written on a thread of its own, which is handed chunks of points
+/// over a bounded channel.
+struct WriterThread {
+    sender: Option<SyncSender<Vec<(Point, Option<ImuGnssPoint>)>>>,
+    points: Vec<(Point, Option<ImuGnssPoint>)>,
+    chunk_size: usize,
+    handle: Option<JoinHandle<Result<(), georef::Error>>>,
+}
+
+impl WriterThread {
+    /// Opens a sink on a new thread, which falls behind by at most `bound` chunks of `chunk_size`
+    /// points.
+    fn spawn(path: &str,
+             config: Option<toml::Value>,
+             chunk_size: usize,
+             bound: usize)
+             -> Result<WriterThread, georef::Error> {
+        let (opened_sender, opened_receiver) = mpsc::channel();
+        let (sender, receiver) = mpsc::sync_channel(bound);
+        let path = path.to_string();
+        let handle = thread::spawn(move || {
+            let mut sink = match open_sink(&path, config) {
+                Ok(sink) => sink,
+                Err(err) => {
+                    let _ = opened_sender.send(Err(err));
+                    return Ok(());
+                }
+            };
+            let _ = opened_sender.send(Ok(()));
+            for points in receiver {
+                for (point, record) in points {
+                    match record {
+                        Some(ref record) => try!(sink.sink_with_record(&point, record)),
+                        None => try!(sink.sink(&point)),
+                    }
+                }
+            }
+            sink.close()
+        });
+        try!(try!(opened_receiver.recv().map_err(|_| georef::Error::WorkerThread)));
+        Ok(WriterThread {
+            sender: Some(sender),
+            points: Vec::with_capacity(chunk_size),
+            chunk_size: chunk_size,
+            handle: Some(handle),
+        })
+    }
+
+    /// Hands the buffered points to the thread, which only hangs up early if writing failed.
+    fn flush(&mut self) -> Result<(), georef::Error> {
+        if self.points.is_empty() {
+            return Ok(());
+        }
+        let points = mem::replace(&mut self.points, Vec::with_capacity(self.chunk_size));
+        let sent = match self.sender {
+            Some(ref sender) => sender.send(points).is_ok(),
+            None => false,
+        };
+        if sent {
+            Ok(())
+        } else {
+            self.join()
+        }
+    }
+
+    /// Waits for the thread to finish writing and returns its result.
+    fn join(&mut self) -> Result<(), georef::Error> {
+        self.sender = None;
+        match self.handle.take().map(|handle| handle.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) | None => Err(georef::Error::WorkerThread),
+        }
+    }
+
+    /// Writes the last points and closes the file.
+    fn close(mut self) -> Result<(), georef::Error> {
+        try!(self.flush());
+        self.join()
+    }
+
+    /// Buffers a point, handing the buffer to the thread once it holds a chunk.
+    fn push(&mut self, point: &Point, record: Option<ImuGnssPoint>) -> Result<(), georef::Error> {
+        self.points.push((point.clone(), record));
+        if self.points.len() >= self.chunk_size {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl PointSink for WriterThread {
+    fn sink(&mut self, point: &Point) -> Result<(), georef::Error> {
+        self.push(point, None)
+    }
+
+    fn sink_with_record(&mut self,
+                        point: &Point,
+                        record: &ImuGnssPoint)
+                        -> Result<(), georef::Error> {
+        self.push(point, Some(*record))
+    }
+}
+
+/// Asks for a LAS 1.4 file with the output coordinate reference system as WKT, if the path is a
+/// LAS file and the sink config doesn't already set them.
+fn las_sink_config(path: &str,
+                   config: &Option<toml::Value>,
+                   wkt: Option<String>)
+                   -> Option<toml::Value> {
+    let is_las = Path::new(path)
+                     .extension()
+                     .and_then(|e| e.to_str())
+                     .map_or(false, |e| e.eq_ignore_ascii_case("las"));
+    if !is_las {
+        return config.clone();
+    }
+    let mut table = match *config {
+        Some(toml::Value::Table(ref table)) => table.clone(),
+        Some(_) => return config.clone(),
+        None => toml::Table::new(),
+    };
+    let _ = table.entry("version".to_string())
+                 .or_insert(toml::Value::String("1.4".to_string()));
+    if !table.contains_key("wkt") {
+        match wkt {
+            Some(wkt) => {
+                let _ = table.insert("wkt".to_string(), toml::Value::String(wkt));
+            }
+            None => {
+                warn!("not writing a coordinate reference system to {}: the ellipsoid, datum \
+                       shift, or ground scale isn't WGS84's, so set [sink] wkt to describe it",
+                      path)
+            }
+        }
+    }
+    Some(toml::Value::Table(table))
+}
+
+/// Inserts a suffix into a path, e.g. `out.las` becomes `out_zone06.las`.
+fn suffixed_path(path: &str, suffix: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let file_name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}_{}.{}", stem, suffix, extension),
+        None => format!("{}_{}", stem, suffix),
+    };
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
+
+/// Reads a trajectory, or merges several trajectories given as a comma-separated list of paths.
+fn imu_gnss_from_path(path: &str,
+                      smrmsg: Option<&String>,
+                      config: Option<toml::Value>,
+                      outliers: Option<OutlierConfig>,
+                      smoothing: Option<SmoothingConfig>,
+                      georeferencer: Option<&Georeferencer>)
+                      -> Result<Box<Trajectory>, Box<Error>> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str());
+    let imu_gnss = if path.contains(',') {
+        if smrmsg.is_some() {
+            return Err("--smrmsg can only be used with a single trajectory".into());
+        }
+        let mut trajectories = Vec::new();
+        for path in path.split(',').filter(|path| !path.is_empty()) {
+            trajectories.push(try!(read_imu_gnss(path, config.clone())));
+        }
+        try!(ImuGnss::merge(trajectories))
+    } else if let Some(smrmsg) = smrmsg {
+        match extension {
+            Some("sbet") | Some("out") => {
+                let mut smrmsg = try!(sbet::SmrmsgReader::from_path(smrmsg));
+                try!(try!(sbet::Reader::from_path(path)).read_imu_gnss_with_smrmsg(&mut smrmsg))
+            }
+            _ => return Err("--smrmsg can only be used with SBET trajectories".into()),
+        }
+    } else if extension == Some("toml") {
+        if outliers.is_some() || smoothing.is_some() {
+            return Err(config_error("[outliers] and [smoothing] cannot be used with setups"));
+        }
+        let table = toml::Value::Table(try!(read_config(path)));
+        let config = try!(SetupsConfig::from_toml(table));
+        return Ok(Box::new(try!(Setups::new(&config))));
+    } else {
+        try!(read_imu_gnss(path, config))
+    };
+    let imu_gnss = match outliers {
+        Some(outliers) => try!(imu_gnss.reject_outliers(&outliers)),
+        None => imu_gnss,
+    };
+    let mut imu_gnss = match smoothing {
+        Some(smoothing) => try!(imu_gnss.smooth(&smoothing)),
+        None => imu_gnss,
+    };
+    if let Some(georeferencer) = georeferencer {
+        georeferencer.project_trajectory(&mut imu_gnss);
+    }
+    Ok(Box::new(imu_gnss))
+}
+
+/// Opens an SBET trajectory that's read as it's needed, holding a window of records.
+fn windowed_trajectory(path: &str, window: f64) -> Result<Box<Trajectory>, Box<Error>> {
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("sbet") | Some("out") => {
+            let reader = try!(sbet::Reader::from_path(path));
+            let records = reader.map(|record| record.map(ImuGnssPoint::from));
+            Ok(Box::new(try!(WindowedTrajectory::new(records, window))))
+        }
+        _ => Err("--trajectory-window can only be used with SBET trajectories".into()),
+    }
+}
+
+/// Reads every record of a trajectory into memory, so it can be merged with others.
+fn read_imu_gnss(path: &str, config: Option<toml::Value>) -> Result<ImuGnss, Box<Error>> {
+    let repair = match config.as_ref().and_then(|table| table.lookup("repair")) {
+        Some(value) => {
+            match value.as_str() {
+                Some(repair) => try!(repair.parse::<RepairPolicy>()),
+                None => return Err(config_error("the trajectory's repair must be a string")),
+            }
+        }
+        None => RepairPolicy::default(),
+    };
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("pos") => {
+            let mut reader = try!(pos::pos::Reader::from_path(path));
+            Ok(try!(ImuGnss::from_pos_source(&mut reader, repair)).0)
+        }
+        Some("sbet") | Some("out") => {
+            let mut points: Vec<ImuGnssPoint> = Vec::new();
+            for record in try!(sbet::Reader::from_path(path)) {
+                points.push(try!(record).into());
+            }
+            Ok(try!(ImuGnss::sanitize(points, repair)).0)
+        }
+        Some("bag") => {
+            let config = match config {
+                Some(table) => try!(RosbagTrajectoryConfig::from_toml(table)),
+                None => {
+                    return Err(config_error("a ROS bag trajectory needs a [trajectory] origin"))
+                }
+            };
+            let mut reader = try!(rosbag::Reader::from_path(path));
+            Ok(try!(rosbag::read_imu_gnss(&mut reader, &config)))
+        }
+        Some("csv") | Some("txt") => {
+            let config = match config {
+                Some(table) => try!(CsvConfig::from_toml(table)),
+                None => CsvConfig::default(),
+            };
+            Ok(try!(csv::read_imu_gnss_from_path(path, &config)))
+        }
+        _ => {
+            Err(format!("unsupported IMU/GNSS file extension: {} (binary Inertial Explorer \
+                         output can't be read, so export it as an ASCII profile or SBET)",
+                        path)
+                    .into())
+        }
+    }
+}
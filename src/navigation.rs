@@ -0,0 +1,508 @@
+//! Smooth raw GNSS positions with IMU measurements into a trajectory.
+//!
+//! Most trajectories come out of dedicated post-processing software, but sometimes all that's on
+//! hand are the raw GNSS fixes and IMU samples. This module is a position smoother, not a full
+//! inertial navigation filter: its only states are position and velocity, which a forward Kalman
+//! filter and a backward Rauch-Tung-Striebel smoother estimate from the fixes and the IMU's
+//! accelerations. The attitude is open-loop dead reckoning from the gyros:
+//!
+//! - Attitude is integrated from the gyro rates, starting from a configured roll, pitch, and yaw.
+//! - Specific forces are rotated into north-east-down, normal gravity is added back, and the
+//!   resulting accelerations drive a position and velocity filter on each axis of a local frame
+//!   anchored at the first fix.
+//! - GNSS fixes update the positions, and the smoother carries every fix both forwards and
+//!   backwards in time.
+//!
+//! This is deliberately simple. Earth rotation and transport rate are ignored, there are no
+//! attitude or sensor bias states, and the GNSS fixes never correct the attitude, so its error
+//! grows with the gyro bias for as long as the collection lasts. It's only suitable for short
+//! collections with a navigation-grade IMU and a good initial alignment. Post-processed
+//! trajectories should be preferred when they're available.
+//!
+//! The attitudes rotate body vectors into north-east-down, so georeference against the result
+//! with `frame = "NED"` in the `[georef]` table. The default east-north-up frame would turn every
+//! point by the wrong rotation.
+
+use nalgebra::Vec3;
+
+use Result;
+use ellipsoid::Ellipsoid;
+use error::Error;
+use imu_gnss::{Accuracy, ImuGnss, ImuGnssPoint, Quaternion};
+use point::{LocalFrame, ecef_to_geodetic};
+
+/// A two-by-two matrix, in row-major order.
+type Matrix = [[f64; 2]; 2];
+
+/// A GNSS position fix.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GnssFix {
+    /// The gps time of this fix.
+    pub time: f64,
+    /// The latitude, in radians.
+    pub latitude: f64,
+    /// The longitude, in radians.
+    pub longitude: f64,
+    /// The ellipsoidal height, in meters.
+    pub height: f64,
+    /// The standard deviation of each position component, in meters, if known.
+    pub std_dev: Option<f64>,
+}
+
+/// An IMU measurement, in the body frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ImuSample {
+    /// The gps time of this sample.
+    pub time: f64,
+    /// The specific force along x, y, and z, in meters per second squared.
+    pub specific_force: [f64; 3],
+    /// The angular rate about x, y, and z, in radians per second.
+    pub angular_rate: [f64; 3],
+}
+
+/// A decodable description of how to fuse GNSS and IMU measurements.
+#[derive(Clone, Debug, RustcDecodable, RustcEncodable)]
+pub struct NavigationConfig {
+    /// The roll at the first IMU sample, in radians.
+    pub roll: f64,
+    /// The pitch at the first IMU sample, in radians.
+    pub pitch: f64,
+    /// The yaw at the first IMU sample, in radians.
+    pub yaw: f64,
+    /// The standard deviation of each fix's position components, in meters, for fixes that don't
+    /// have their own.
+    pub gnss_std_dev: f64,
+    /// The standard deviation of the initial velocity, in meters per second.
+    pub velocity_std_dev: f64,
+    /// The spectral density of the acceleration noise, in meters per second squared per root
+    /// hertz.
+    pub acceleration_noise: f64,
+}
+
+impl Default for NavigationConfig {
+    fn default() -> NavigationConfig {
+        NavigationConfig {
+            roll: 0.0,
+            pitch: 0.0,
+            yaw: 0.0,
+            gnss_std_dev: 0.05,
+            velocity_std_dev: 1.0,
+            acceleration_noise: 0.1,
+        }
+    }
+}
+
+impl NavigationConfig {
+    /// Returns a message for an initial attitude that isn't finite and for each standard deviation
+    /// or noise density that isn't positive.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(self.roll.is_finite() && self.pitch.is_finite() && self.yaw.is_finite()) {
+            problems.push("roll, pitch, and yaw must be finite".to_string());
+        }
+        let positive = |value: f64| value > 0.0 && value.is_finite();
+        if !positive(self.gnss_std_dev) {
+            problems.push("gnss_std_dev must be greater than zero".to_string());
+        }
+        if !positive(self.velocity_std_dev) {
+            problems.push("velocity_std_dev must be greater than zero".to_string());
+        }
+        if !positive(self.acceleration_noise) {
+            problems.push("acceleration_noise must be greater than zero".to_string());
+        }
+        problems
+    }
+}
+
+/// A moment at which the filter state is estimated, either an IMU sample, a GNSS fix, or both.
+#[derive(Clone, Copy, Debug)]
+struct Epoch {
+    time: f64,
+    /// The north, east, and down acceleration held from this epoch until the next.
+    acceleration: [f64; 3],
+    fix: Option<GnssFix>,
+    orientation: Option<Quaternion>,
+}
+
+/// The position and velocity of one axis, with their covariance.
+#[derive(Clone, Copy, Debug)]
+struct State {
+    x: [f64; 2],
+    p: Matrix,
+}
+
+/// Smooths GNSS fixes with IMU samples into a trajectory.
+///
+/// Both must be sorted by time. The trajectory has a record at every IMU sample between the first
+/// and last fixes, with position standard deviations from the smoother. Attitudes are integrated
+/// from the gyros alone and rotate into north-east-down, so the trajectory needs
+/// `NavigationFrame::Ned`. Attitude standard deviations aren't estimated and are left at zero.
+pub fn fuse(fixes: &[GnssFix],
+            samples: &[ImuSample],
+            config: &NavigationConfig)
+            -> Result<ImuGnss> {
+    if let Some(problem) = config.problems().into_iter().next() {
+        return Err(Error::InvalidField("navigation", problem));
+    }
+    if fixes.windows(2).any(|w| w[1].time <= w[0].time) ||
+       samples.windows(2).any(|w| w[1].time <= w[0].time) {
+        return Err(Error::Navigation("fixes and samples must increase in time".to_string()));
+    }
+    let (first, last) = match (fixes.first(), fixes.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Err(Error::Navigation("no gnss fixes".to_string())),
+    };
+    let samples: Vec<ImuSample> = samples.iter()
+                                         .filter(|s| s.time >= first.time && s.time <= last.time)
+                                         .cloned()
+                                         .collect();
+    if samples.len() < 2 {
+        return Err(Error::Navigation("fewer than two imu samples between the first and last gnss \
+                                      fixes"
+                                         .to_string()));
+    }
+    let frame = LocalFrame::new(first.latitude,
+                                first.longitude,
+                                first.height,
+                                Ellipsoid::wgs84());
+    let gravity = normal_gravity(first.latitude, first.height);
+    let epochs = epochs(fixes, &samples, config, gravity);
+
+    let variance = |fix: &GnssFix| fix.std_dev.unwrap_or(config.gnss_std_dev).powi(2);
+    let start = ned(&frame, &first);
+    let mut predicted: Vec<[State; 3]> = Vec::with_capacity(epochs.len());
+    let mut filtered: Vec<[State; 3]> = Vec::with_capacity(epochs.len());
+    for (i, epoch) in epochs.iter().enumerate() {
+        let mut states = if i == 0 {
+            let state = |position: f64| {
+                State {
+                    x: [position, 0.0],
+                    p: [[variance(&first), 0.0], [0.0, config.velocity_std_dev.powi(2)]],
+                }
+            };
+            [state(start[0]), state(start[1]), state(start[2])]
+        } else {
+            let previous = &epochs[i - 1];
+            let dt = epoch.time - previous.time;
+            let last = &filtered[i - 1];
+            [predict(&last[0], previous.acceleration[0], dt, config.acceleration_noise),
+             predict(&last[1], previous.acceleration[1], dt, config.acceleration_noise),
+             predict(&last[2], previous.acceleration[2], dt, config.acceleration_noise)]
+        };
+        predicted.push(states);
+        // The first epoch is the first fix, which the initial state already holds.
+        match epoch.fix {
+            Some(ref fix) if i > 0 => {
+                let position = ned(&frame, fix);
+                for axis in 0..3 {
+                    states[axis] = update(&states[axis], position[axis], variance(fix));
+                }
+            }
+            _ => {}
+        }
+        filtered.push(states);
+    }
+
+    let mut smoothed = filtered.clone();
+    for i in (0..epochs.len() - 1).rev() {
+        let dt = epochs[i + 1].time - epochs[i].time;
+        for axis in 0..3 {
+            smoothed[i][axis] = smooth(&filtered[i][axis],
+                                       &predicted[i + 1][axis],
+                                       &smoothed[i + 1][axis],
+                                       dt);
+        }
+    }
+
+    let points = epochs.iter()
+                       .zip(&smoothed)
+                       .filter_map(|(epoch, states)| {
+                           epoch.orientation.map(|orientation| {
+                               let enu = Vec3::new(states[1].x[0], states[0].x[0], -states[2].x[0]);
+                               let ecef = frame.unproject(enu);
+                               let (latitude, longitude, height) =
+                                   ecef_to_geodetic(ecef.x, ecef.y, ecef.z);
+                               let (roll, pitch, yaw) = orientation.rpy();
+                               ImuGnssPoint {
+                                   time: epoch.time,
+                                   latitude: latitude,
+                                   longitude: longitude,
+                                   height: height,
+                                   roll: roll,
+                                   pitch: pitch,
+                                   yaw: yaw,
+                                   attitude_rate: None,
+                                   orientation: None,
+                                   accuracy: Some(Accuracy {
+                                       north: states[0].p[0][0].sqrt(),
+                                       east: states[1].p[0][0].sqrt(),
+                                       down: states[2].p[0][0].sqrt(),
+                                       ..Default::default()
+                                   }),
+                                   utm: None,
+                               }
+                           })
+                       })
+                       .collect();
+    ImuGnss::new(points)
+}
+
+/// Merges fixes and samples into epochs, integrating the attitude along the way.
+fn epochs(fixes: &[GnssFix],
+          samples: &[ImuSample],
+          config: &NavigationConfig,
+          gravity: f64)
+          -> Vec<Epoch> {
+    let mut epochs = Vec::with_capacity(fixes.len() + samples.len());
+    let mut orientation = Quaternion::from_rpy(config.roll, config.pitch, config.yaw);
+    let mut held = acceleration(&orientation, &samples[0], gravity);
+    let (mut i, mut j) = (0, 0);
+    while i < fixes.len() || j < samples.len() {
+        let fix = fixes.get(i).cloned();
+        let sample = samples.get(j).cloned();
+        let time = match (fix, sample) {
+            (Some(fix), Some(sample)) => fix.time.min(sample.time),
+            (Some(fix), None) => fix.time,
+            (None, Some(sample)) => sample.time,
+            (None, None) => unreachable!(),
+        };
+        let fix = match fix {
+            Some(fix) if fix.time == time => Some(fix),
+            _ => None,
+        };
+        let sample = match sample {
+            Some(sample) if sample.time == time => Some(sample),
+            _ => None,
+        };
+        if fix.is_some() {
+            i += 1;
+        }
+        if let Some(ref sample) = sample {
+            if j > 0 {
+                orientation = rotate(&orientation, &samples[j - 1], sample.time);
+            }
+            held = acceleration(&orientation, sample, gravity);
+            j += 1;
+        }
+        epochs.push(Epoch {
+            time: time,
+            acceleration: held,
+            fix: fix,
+            orientation: sample.map(|_| orientation),
+        });
+    }
+    epochs
+}
+
+/// Advances an attitude by the angular rate of a sample, held until the given time.
+fn rotate(orientation: &Quaternion, sample: &ImuSample, time: f64) -> Quaternion {
+    let rate = sample.angular_rate;
+    let dt = time - sample.time;
+    let norm = (rate[0] * rate[0] + rate[1] * rate[1] + rate[2] * rate[2]).sqrt();
+    let angle = norm * dt;
+    if angle == 0.0 {
+        return *orientation;
+    }
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    let (x, y, z) = (rate[0] / norm * sin, rate[1] / norm * sin, rate[2] / norm * sin);
+    let q = orientation;
+    Quaternion::new(q.w * cos - q.x * x - q.y * y - q.z * z,
+                    q.w * x + q.x * cos + q.y * z - q.z * y,
+                    q.w * y - q.x * z + q.y * cos + q.z * x,
+                    q.w * z + q.x * y - q.y * x + q.z * cos)
+        .unwrap_or(*orientation)
+}
+
+/// Returns the north, east, and down acceleration of a sample.
+fn acceleration(orientation: &Quaternion, sample: &ImuSample, gravity: f64) -> [f64; 3] {
+    let f = sample.specific_force;
+    let a = orientation.to_rot3() * Vec3::new(f[0], f[1], f[2]);
+    [a.x, a.y, a.z + gravity]
+}
+
+/// Returns the north, east, and down position of a fix in the local frame.
+fn ned(frame: &LocalFrame, fix: &GnssFix) -> [f64; 3] {
+    let (x, y, z) = ::point::geodetic_to_ecef(fix.latitude, fix.longitude, fix.height);
+    let enu = frame.project(Vec3::new(x, y, z));
+    [enu.y, enu.x, -enu.z]
+}
+
+/// Returns the magnitude of normal gravity on the WGS84 ellipsoid, in meters per second squared,
+/// from Somigliana's formula with a free-air correction for height.
+fn normal_gravity(latitude: f64, height: f64) -> f64 {
+    let sin2 = latitude.sin().powi(2);
+    9.7803253359 * (1.0 + 0.00193185265241 * sin2) / (1.0 - 0.00669437999013 * sin2).sqrt() -
+    3.086e-6 * height
+}
+
+/// Predicts a state forwards in time under a constant acceleration.
+fn predict(state: &State, acceleration: f64, dt: f64, noise: f64) -> State {
+    let f = transition(dt);
+    let q = noise * noise;
+    let process = [[q * dt.powi(3) / 3.0, q * dt * dt / 2.0], [q * dt * dt / 2.0, q * dt]];
+    State {
+        x: [state.x[0] + state.x[1] * dt + acceleration * dt * dt / 2.0,
+            state.x[1] + acceleration * dt],
+        p: add(&multiply(&multiply(&f, &state.p), &transpose(&f)), &process),
+    }
+}
+
+/// Updates a state with a position measurement.
+fn update(state: &State, position: f64, variance: f64) -> State {
+    let p = state.p;
+    let innovation = p[0][0] + variance;
+    let gain = [p[0][0] / innovation, p[1][0] / innovation];
+    let residual = position - state.x[0];
+    State {
+        x: [state.x[0] + gain[0] * residual, state.x[1] + gain[1] * residual],
+        p: [[(1.0 - gain[0]) * p[0][0], (1.0 - gain[0]) * p[0][1]],
+            [p[1][0] - gain[1] * p[0][0], p[1][1] - gain[1] * p[0][1]]],
+    }
+}
+
+/// Smooths a filtered state with the smoothed state of the next epoch.
+fn smooth(filtered: &State, predicted: &State, next: &State, dt: f64) -> State {
+    let gain = multiply(&multiply(&filtered.p, &transpose(&transition(dt))),
+                        &inverse(&predicted.p));
+    let dx = [next.x[0] - predicted.x[0], next.x[1] - predicted.x[1]];
+    let dp = subtract(&next.p, &predicted.p);
+    State {
+        x: [filtered.x[0] + gain[0][0] * dx[0] + gain[0][1] * dx[1],
+            filtered.x[1] + gain[1][0] * dx[0] + gain[1][1] * dx[1]],
+        p: add(&filtered.p, &multiply(&multiply(&gain, &dp), &transpose(&gain))),
+    }
+}
+
+fn transition(dt: f64) -> Matrix {
+    [[1.0, dt], [0.0, 1.0]]
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [[a[0][0] * b[0][0] + a[0][1] * b[1][0], a[0][0] * b[0][1] + a[0][1] * b[1][1]],
+     [a[1][0] * b[0][0] + a[1][1] * b[1][0], a[1][0] * b[0][1] + a[1][1] * b[1][1]]]
+}
+
+fn transpose(a: &Matrix) -> Matrix {
+    [[a[0][0], a[1][0]], [a[0][1], a[1][1]]]
+}
+
+fn add(a: &Matrix, b: &Matrix) -> Matrix {
+    [[a[0][0] + b[0][0], a[0][1] + b[0][1]], [a[1][0] + b[1][0], a[1][1] + b[1][1]]]
+}
+
+fn subtract(a: &Matrix, b: &Matrix) -> Matrix {
+    [[a[0][0] - b[0][0], a[0][1] - b[0][1]], [a[1][0] - b[1][0], a[1][1] - b[1][1]]]
+}
+
+fn inverse(a: &Matrix) -> Matrix {
+    let determinant = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+    [[a[1][1] / determinant, -a[0][1] / determinant],
+     [-a[1][0] / determinant, a[0][0] / determinant]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::Vec3;
+
+    use ellipsoid::Ellipsoid;
+    use point::LocalFrame;
+
+    const LATITUDE: f64 = 0.9;
+    const LONGITUDE: f64 = -2.6;
+
+    /// Returns fixes every second and samples every hundredth of a second for ten seconds of level
+    /// flight due north, with a constant acceleration to the east.
+    fn measurements(east_acceleration: f64) -> (Vec<GnssFix>, Vec<ImuSample>) {
+        let frame = LocalFrame::new(LATITUDE, LONGITUDE, 100.0, Ellipsoid::wgs84());
+        let gravity = normal_gravity(LATITUDE, 100.0);
+        let fixes = (0..11)
+                        .map(|i| {
+                            let time = i as f64;
+                            let east = east_acceleration * time * time / 2.0;
+                            let ecef = frame.unproject(Vec3::new(east, 0.0, 0.0));
+                            let (latitude, longitude, height) =
+                                ::point::ecef_to_geodetic(ecef.x, ecef.y, ecef.z);
+                            GnssFix {
+                                time: time,
+                                latitude: latitude,
+                                longitude: longitude,
+                                height: height,
+                                std_dev: None,
+                            }
+                        })
+                        .collect();
+        let samples = (0..1001)
+                          .map(|i| {
+                              ImuSample {
+                                  time: i as f64 / 100.0,
+                                  specific_force: [0.0, east_acceleration, -gravity],
+                                  angular_rate: [0.0; 3],
+                              }
+                          })
+                          .collect();
+        (fixes, samples)
+    }
+
+    #[test]
+    fn stationary() {
+        let (fixes, samples) = measurements(0.0);
+        let imu_gnss = fuse(&fixes, &samples, &Default::default()).unwrap();
+        assert_eq!(1001, imu_gnss.len());
+        for point in imu_gnss.points() {
+            assert!((point.latitude - LATITUDE).abs() < 1e-9);
+            assert!((point.longitude - LONGITUDE).abs() < 1e-9);
+            assert!((point.height - 100.0).abs() < 1e-3);
+            assert!(point.roll.abs() < 1e-12);
+            assert!(point.yaw.abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn east_acceleration() {
+        let (fixes, samples) = measurements(0.5);
+        let imu_gnss = fuse(&fixes, &samples, &Default::default()).unwrap();
+        let frame = LocalFrame::new(LATITUDE, LONGITUDE, 100.0, Ellipsoid::wgs84());
+        for point in imu_gnss.points() {
+            let (x, y, z) =
+                ::point::geodetic_to_ecef(point.latitude, point.longitude, point.height);
+            let enu = frame.project(Vec3::new(x, y, z));
+            let expected = 0.5 * point.time * point.time / 2.0;
+            assert!((enu.x - expected).abs() < 1e-3);
+            assert!(enu.y.abs() < 1e-3);
+        }
+        // Between fixes, the smoother does better than any single fix.
+        let middle = imu_gnss.points()[550].accuracy.unwrap();
+        assert!(middle.east < 0.05);
+        assert!(middle.east > 0.0);
+    }
+
+    #[test]
+    fn rotation() {
+        let sample = ImuSample {
+            angular_rate: [0.0, 0.0, 0.1],
+            ..Default::default()
+        };
+        let q = rotate(&Quaternion::from_rpy(0.0, 0.0, 0.0), &sample, 2.0);
+        let (roll, pitch, yaw) = q.rpy();
+        assert!(roll.abs() < 1e-12);
+        assert!(pitch.abs() < 1e-12);
+        assert!((yaw - 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn errors() {
+        let (fixes, samples) = measurements(0.0);
+        let config = Default::default();
+        assert!(fuse(&[], &samples, &config).is_err());
+        assert!(fuse(&fixes, &samples[..1], &config).is_err());
+        let mut reversed = samples.clone();
+        reversed.reverse();
+        assert!(fuse(&fixes, &reversed, &config).is_err());
+        let config = NavigationConfig { gnss_std_dev: 0.0, ..Default::default() };
+        assert_eq!(vec!["gnss_std_dev must be greater than zero".to_string()],
+                   config.problems());
+        assert!(fuse(&fixes, &samples, &config).is_err());
+    }
+}
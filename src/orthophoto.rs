@@ -0,0 +1,323 @@
+//! Orthophotos for colorizing georeferenced points.
+//!
+//! Orthophotos are read from GeoTIFFs in the output coordinate reference system. Only baseline,
+//! uncompressed, strip-organized images with 8-bit RGB (or RGBA) pixels are supported, which is
+//! what most tools write when asked for an uncompressed GeoTIFF. The image is placed with its
+//! ModelTiepoint and ModelPixelScale tags; rotated images (ModelTransformation) are not supported.
+//!
+//! ```toml
+//! [georef]
+//! orthophoto = "ortho.tif"
+//! ```
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use Result;
+use error::Error;
+
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const STRIP_OFFSETS: u16 = 273;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const PLANAR_CONFIGURATION: u16 = 284;
+const MODEL_PIXEL_SCALE: u16 = 33550;
+const MODEL_TIEPOINT: u16 = 33922;
+
+/// An orthophoto, ready to be sampled.
+#[derive(Debug)]
+pub struct Orthophoto {
+    width: usize,
+    height: usize,
+    samples_per_pixel: usize,
+    /// The model coordinates of the upper left corner of the image.
+    origin: (f64, f64),
+    /// The size of a pixel in model coordinates.
+    scale: (f64, f64),
+    pixels: Vec<u8>,
+}
+
+impl Orthophoto {
+    /// Reads an orthophoto from a GeoTIFF at a path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::orthophoto::Orthophoto;
+    /// let orthophoto = Orthophoto::from_path("ortho.tif").unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Orthophoto> {
+        Orthophoto::from_reader(try!(File::open(path)))
+    }
+
+    /// Reads an orthophoto from a GeoTIFF in something that implements `Read`.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Orthophoto> {
+        let mut data = Vec::new();
+        let _ = try!(reader.read_to_end(&mut data));
+        let tiff = if data.starts_with(b"II") {
+            Tiff { data: &data, big_endian: false }
+        } else if data.starts_with(b"MM") {
+            Tiff { data: &data, big_endian: true }
+        } else {
+            return Err(error("not a TIFF file"));
+        };
+        if try!(tiff.u16(2)) != 42 {
+            return Err(error("not a TIFF file (BigTIFF is not supported)"));
+        }
+        let ifd = try!(tiff.u32(4)) as usize;
+        let mut entries = Vec::new();
+        for i in 0..try!(tiff.u16(ifd)) as usize {
+            entries.push(try!(tiff.entry(ifd + 2 + i * 12)));
+        }
+        let tag_values = |tag: u16| -> Result<Vec<f64>> {
+            match entries.iter().find(|entry| entry.tag == tag) {
+                Some(entry) => tiff.values(entry),
+                None => Err(Error::Orthophoto(format!("missing TIFF tag {}", tag))),
+            }
+        };
+        let optional = |tag: u16, default: f64| -> Result<f64> {
+            if entries.iter().any(|entry| entry.tag == tag) {
+                first(tag, try!(tag_values(tag)))
+            } else {
+                Ok(default)
+            }
+        };
+        let width = try!(first(IMAGE_WIDTH, try!(tag_values(IMAGE_WIDTH)))) as usize;
+        let height = try!(first(IMAGE_LENGTH, try!(tag_values(IMAGE_LENGTH)))) as usize;
+        let samples_per_pixel = try!(optional(SAMPLES_PER_PIXEL, 1.0)) as usize;
+        if try!(optional(COMPRESSION, 1.0)) != 1.0 {
+            return Err(error("only uncompressed images are supported"));
+        }
+        if try!(optional(PLANAR_CONFIGURATION, 1.0)) != 1.0 {
+            return Err(error("only chunky (interleaved) pixels are supported"));
+        }
+        let bits_per_sample = try!(tag_values(BITS_PER_SAMPLE));
+        if samples_per_pixel < 3 || bits_per_sample.iter().any(|&bits| bits != 8.0) {
+            return Err(error("only 8-bit RGB images are supported"));
+        }
+        let scale = try!(tag_values(MODEL_PIXEL_SCALE));
+        let tiepoint = try!(tag_values(MODEL_TIEPOINT));
+        if scale.len() < 2 || tiepoint.len() < 6 || !(scale[0] > 0.0 && scale[1] > 0.0) {
+            return Err(error("invalid ModelPixelScale or ModelTiepoint"));
+        }
+        let len = try!(width.checked_mul(height)
+                            .and_then(|len| len.checked_mul(samples_per_pixel))
+                            .ok_or(error("image is too large")));
+        if len > data.len() {
+            return Err(error("image has fewer pixels than its size"));
+        }
+        let offsets = try!(tag_values(STRIP_OFFSETS));
+        let byte_counts = try!(tag_values(STRIP_BYTE_COUNTS));
+        let mut pixels = Vec::with_capacity(len);
+        for (&offset, &byte_count) in offsets.iter().zip(&byte_counts) {
+            let start = offset as usize;
+            let strip = start.checked_add(byte_count as usize).and_then(|end| data.get(start..end));
+            pixels.extend_from_slice(try!(strip.ok_or(error("strip is truncated"))));
+        }
+        if pixels.len() < len {
+            return Err(error("image has fewer pixels than its size"));
+        }
+        Ok(Orthophoto {
+            width: width,
+            height: height,
+            samples_per_pixel: samples_per_pixel,
+            origin: (tiepoint[3] - tiepoint[0] * scale[0], tiepoint[4] + tiepoint[1] * scale[1]),
+            scale: (scale[0], scale[1]),
+            pixels: pixels,
+        })
+    }
+
+    /// Returns the red, green, and blue values of the pixel that contains a location, or `None`
+    /// if the location is outside of the image.
+    pub fn rgb(&self, x: f64, y: f64) -> Option<[u8; 3]> {
+        let col = ((x - self.origin.0) / self.scale.0).floor();
+        let row = ((self.origin.1 - y) / self.scale.1).floor();
+        if !(col >= 0.0 && row >= 0.0 && col < self.width as f64 && row < self.height as f64) {
+            return None;
+        }
+        let i = (row as usize * self.width + col as usize) * self.samples_per_pixel;
+        Some([self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]])
+    }
+}
+
+/// One entry of a TIFF image file directory.
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: usize,
+    /// The position of the value in the file, if it fits in the entry, or of its offset.
+    position: usize,
+}
+
+/// A TIFF file's bytes and byte order.
+#[derive(Clone, Copy, Debug)]
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn bytes(&self, position: usize, len: usize) -> Result<&'a [u8]> {
+        position.checked_add(len)
+                .and_then(|end| self.data.get(position..end))
+                .ok_or(error("file is truncated"))
+    }
+
+    fn u16(&self, position: usize) -> Result<u16> {
+        let bytes = try!(self.bytes(position, 2));
+        Ok(if self.big_endian {
+            BigEndian::read_u16(bytes)
+        } else {
+            LittleEndian::read_u16(bytes)
+        })
+    }
+
+    fn u32(&self, position: usize) -> Result<u32> {
+        let bytes = try!(self.bytes(position, 4));
+        Ok(if self.big_endian {
+            BigEndian::read_u32(bytes)
+        } else {
+            LittleEndian::read_u32(bytes)
+        })
+    }
+
+    fn f64(&self, position: usize) -> Result<f64> {
+        let bytes = try!(self.bytes(position, 8));
+        Ok(if self.big_endian {
+            BigEndian::read_f64(bytes)
+        } else {
+            LittleEndian::read_f64(bytes)
+        })
+    }
+
+    fn entry(&self, position: usize) -> Result<Entry> {
+        Ok(Entry {
+            tag: try!(self.u16(position)),
+            field_type: try!(self.u16(position + 2)),
+            count: try!(self.u32(position + 4)) as usize,
+            position: position + 8,
+        })
+    }
+
+    /// Reads the values of an entry of type BYTE, SHORT, LONG, or DOUBLE.
+    fn values(&self, entry: &Entry) -> Result<Vec<f64>> {
+        let size = match entry.field_type {
+            1 => 1,
+            3 => 2,
+            4 => 4,
+            12 => 8,
+            _ => {
+                return Err(Error::Orthophoto(format!("TIFF tag {} has unsupported type {}",
+                                                     entry.tag,
+                                                     entry.field_type)))
+            }
+        };
+        let len = try!(entry.count.checked_mul(size).ok_or(error("file is truncated")));
+        let start = if len <= 4 {
+            entry.position
+        } else {
+            try!(self.u32(entry.position)) as usize
+        };
+        // Checking that the values are all there keeps a corrupt count from allocating.
+        let _ = try!(self.bytes(start, len));
+        let mut values = Vec::with_capacity(entry.count);
+        for i in 0..entry.count {
+            let position = start + i * size;
+            values.push(match size {
+                1 => try!(self.bytes(position, 1))[0] as f64,
+                2 => try!(self.u16(position)) as f64,
+                4 => try!(self.u32(position)) as f64,
+                _ => try!(self.f64(position)),
+            });
+        }
+        Ok(values)
+    }
+}
+
+fn first(tag: u16, values: Vec<f64>) -> Result<f64> {
+    values.first().cloned().ok_or(Error::Orthophoto(format!("TIFF tag {} is empty", tag)))
+}
+
+fn error(s: &str) -> Error {
+    Error::Orthophoto(s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    /// Builds a little-endian GeoTIFF with 2x2 RGB pixels, each one meter square, with its upper
+    /// left corner at (100, 200).
+    fn geotiff() -> Vec<u8> {
+        // The header and directory take 122 bytes, followed by the bits per sample, the pixel
+        // scale, the tiepoint, and the pixels.
+        let entries = [(256, 3, 1, 2),
+                       (257, 3, 1, 2),
+                       (258, 3, 3, 122),
+                       (259, 3, 1, 1),
+                       (273, 4, 1, 200),
+                       (277, 3, 1, 3),
+                       (279, 4, 1, 12),
+                       (33550, 12, 3, 128),
+                       (33922, 12, 6, 152)];
+        let mut data = Vec::new();
+        data.extend_from_slice(b"II");
+        data.write_u16::<LittleEndian>(42).unwrap();
+        data.write_u32::<LittleEndian>(8).unwrap();
+        data.write_u16::<LittleEndian>(entries.len() as u16).unwrap();
+        for &(tag, field_type, count, value) in &entries {
+            data.write_u16::<LittleEndian>(tag).unwrap();
+            data.write_u16::<LittleEndian>(field_type).unwrap();
+            data.write_u32::<LittleEndian>(count).unwrap();
+            data.write_u32::<LittleEndian>(value).unwrap();
+        }
+        data.write_u32::<LittleEndian>(0).unwrap();
+        for _ in 0..3 {
+            data.write_u16::<LittleEndian>(8).unwrap();
+        }
+        for &value in &[1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 100.0, 200.0, 0.0] {
+            data.write_f64::<LittleEndian>(value).unwrap();
+        }
+        assert_eq!(200, data.len());
+        data.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        data
+    }
+
+    #[test]
+    fn rgb() {
+        let orthophoto = Orthophoto::from_reader(&geotiff()[..]).unwrap();
+        assert_eq!(Some([1, 2, 3]), orthophoto.rgb(100.5, 199.5));
+        assert_eq!(Some([4, 5, 6]), orthophoto.rgb(101.5, 199.5));
+        assert_eq!(Some([10, 11, 12]), orthophoto.rgb(101.9, 198.1));
+        assert_eq!(None, orthophoto.rgb(99.5, 199.5));
+        assert_eq!(None, orthophoto.rgb(100.5, 200.5));
+        assert_eq!(None, orthophoto.rgb(102.5, 199.5));
+    }
+
+    #[test]
+    fn not_a_tiff() {
+        assert!(Orthophoto::from_reader(&b"GIF89a"[..]).is_err());
+        assert!(Orthophoto::from_reader(&geotiff()[..20]).is_err());
+        assert!(Orthophoto::from_reader(&geotiff()[..206]).is_err());
+    }
+
+    #[test]
+    fn corrupt_sizes() {
+        let mut data = geotiff();
+        (&mut data[38..42]).write_u32::<LittleEndian>(u32::max_value()).unwrap();
+        assert!(Orthophoto::from_reader(&data[..]).is_err());
+        let mut data = geotiff();
+        (&mut data[18..20]).write_u16::<LittleEndian>(u16::max_value()).unwrap();
+        (&mut data[30..32]).write_u16::<LittleEndian>(u16::max_value()).unwrap();
+        assert!(Orthophoto::from_reader(&data[..]).is_err());
+    }
+}
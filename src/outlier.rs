@@ -0,0 +1,82 @@
+//! Reject trajectory outliers before a trajectory is interpolated.
+//!
+//! A single bad GNSS epoch, e.g. a multipath fix tens of meters off the flight path, bends every
+//! point scanned in the records around it, and nothing downstream notices. An outlier pass drops
+//! the isolated records that jump away from both of their neighbors faster than the platform
+//! could move or turn, and warns about each one. A real step in the solution, e.g. after a
+//! re-initialization, jumps away from only one neighbor, so it's kept. Outlier rejection is
+//! configured with the `[outliers]` table:
+//!
+//! ```toml
+//! [outliers]
+//! max_speed = 150.0
+//! max_attitude_rate = 180.0
+//! ```
+
+use std::result;
+
+use rustc_serialize::Decodable;
+use toml;
+
+/// The default largest plausible speed between records, in meters per second.
+const MAX_SPEED: f64 = 150.0;
+/// The default largest plausible attitude rate between records, in degrees per second.
+const MAX_ATTITUDE_RATE: f64 = 180.0;
+
+/// A decodable description of how to find trajectory outliers.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct OutlierConfig {
+    /// The largest plausible speed between records, in meters per second. Defaults to 150.
+    pub max_speed: Option<f64>,
+    /// The largest plausible roll, pitch, or heading rate between records, in degrees per second.
+    /// Defaults to 180.
+    pub max_attitude_rate: Option<f64>,
+}
+
+impl OutlierConfig {
+    /// Creates a new outlier config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<OutlierConfig, toml::DecodeError> {
+        OutlierConfig::decode(&mut toml::Decoder::new(table))
+    }
+
+    /// Returns a message for a maximum speed or attitude rate that isn't positive.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for &(name, value) in &[("max_speed", self.max_speed),
+                                ("max_attitude_rate", self.max_attitude_rate)] {
+            if value.map_or(false, |v| !(v > 0.0 && v.is_finite())) {
+                problems.push(format!("{} must be greater than zero", name));
+            }
+        }
+        problems
+    }
+
+    /// Returns the largest plausible speed between records, in meters per second.
+    pub fn max_speed(&self) -> f64 {
+        self.max_speed.unwrap_or(MAX_SPEED)
+    }
+
+    /// Returns the largest plausible attitude rate between records, in radians per second.
+    pub fn max_attitude_rate(&self) -> f64 {
+        self.max_attitude_rate.unwrap_or(MAX_ATTITUDE_RATE).to_radians()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problems() {
+        assert!(OutlierConfig::default().problems().is_empty());
+        let config = OutlierConfig {
+            max_speed: Some(0.0),
+            max_attitude_rate: Some(-1.0),
+        };
+        assert_eq!(vec!["max_speed must be greater than zero".to_string(),
+                        "max_attitude_rate must be greater than zero".to_string()],
+                   config.problems());
+        assert!((OutlierConfig::default().max_attitude_rate() - ::std::f64::consts::PI).abs() <
+                1e-12);
+    }
+}
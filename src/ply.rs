@@ -0,0 +1,426 @@
+//! Write georeferenced points to PLY files.
+//!
+//! PLY is read by most mesh tools. The format and the per-point properties are configurable,
+//! usually from the `[sink]` table:
+//!
+//! ```toml
+//! [sink]
+//! format = "binary"
+//! properties = ["x", "y", "z", "time", "intensity"]
+//! coordinates = "float"
+//! ```
+//!
+//! Coordinates and time are written as doubles and intensity as an unsigned short. Points without
+//! a time or an intensity are written with zero. The sigma_north, sigma_east, and sigma_up
+//! properties are the estimated accuracy of the trajectory at each point, written as doubles, and
+//! are NaN where the trajectory has no accuracy. The roll, pitch, heading, and trajectory_height
+//! properties are the trajectory's attitude, in degrees, and height at each point.
+//!
+//! Coordinates can instead be written as floats, which halves their size. A float only has about
+//! seven significant digits, so coordinates are written relative to an offset, which is recorded
+//! in the header as `comment offset <x> <y> <z>`. The offset can be configured, and otherwise
+//! it's the first point's coordinates rounded to the nearest kilometer, which keeps
+//! subcentimeter precision for points within a few tens of kilometers of it. Times are always
+//! written as doubles.
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::result;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use pabst;
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+use imu_gnss::ImuGnssPoint;
+use point::Attribute;
+use stream::PointSink;
+
+/// The width that the vertex count is padded to, so it can be filled in once all points are
+/// written.
+const COUNT_WIDTH: usize = 20;
+
+/// The width that an offset found from the first point is padded to.
+const OFFSET_WIDTH: usize = 3 * COUNT_WIDTH;
+
+/// The rounding of an offset found from the first point, in coordinate units.
+const OFFSET_ROUNDING: f64 = 1000.0;
+
+/// A decodable description of a PLY file.
+#[derive(Debug, Default, RustcDecodable)]
+pub struct PlyConfig {
+    /// Either "binary" (little-endian, the default) or "ascii".
+    pub format: Option<String>,
+    /// The per-point properties, in order, from "x", "y", "z", "time", "intensity",
+    /// "sigma_north", "sigma_east", "sigma_up", "roll", "pitch", "heading", and
+    /// "trajectory_height". Defaults to x, y, and z.
+    pub properties: Option<Vec<String>>,
+    /// The type of x, y, and z, either "double" (the default) or "float".
+    pub coordinates: Option<String>,
+    /// The x, y, and z offset that's subtracted from coordinates before they're written.
+    ///
+    /// Defaults to zero for doubles, and to the first point's coordinates rounded to the nearest
+    /// kilometer for floats.
+    pub offset: Option<Vec<f64>>,
+}
+
+impl PlyConfig {
+    /// Creates a new ply config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<PlyConfig, toml::DecodeError> {
+        PlyConfig::decode(&mut toml::Decoder::new(table))
+    }
+}
+
+/// Writes points to a PLY file.
+///
+/// The vertex count isn't known until every point is written, so it is filled in by `finish` or
+/// when the sink is closed.
+#[derive(Debug)]
+pub struct PlySink<W: Write + Seek> {
+    writer: W,
+    ascii: bool,
+    float: bool,
+    properties: Vec<Attribute>,
+    offset: Option<[f64; 3]>,
+    offset_position: Option<u64>,
+    count: u64,
+    count_position: u64,
+}
+
+impl PlySink<BufWriter<File>> {
+    /// Creates a PLY file at a path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::ply::{PlyConfig, PlySink};
+    /// let sink = PlySink::from_path("out.ply", &PlyConfig::default()).unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P,
+                                     config: &PlyConfig)
+                                     -> Result<PlySink<BufWriter<File>>> {
+        PlySink::new(BufWriter::new(try!(File::create(path))), config)
+    }
+}
+
+impl<W: Write + Seek> PlySink<W> {
+    /// Creates a new PLY sink and writes its header.
+    pub fn new(mut writer: W, config: &PlyConfig) -> Result<PlySink<W>> {
+        let ascii = match config.format.as_ref().map(|s| s.as_ref()) {
+            None | Some("binary") => false,
+            Some("ascii") => true,
+            Some(s) => return Err(Error::Ply(format!("unknown format: {}", s))),
+        };
+        let properties = match config.properties {
+            Some(ref properties) => {
+                let mut parsed = Vec::new();
+                for property in properties {
+                    parsed.push(try!(Attribute::from_name(property).ok_or_else(|| {
+                        Error::Ply(format!("unknown property: {}", property))
+                    })));
+                }
+                parsed
+            }
+            None => vec![Attribute::X, Attribute::Y, Attribute::Z],
+        };
+        if properties.is_empty() {
+            return Err(Error::Ply("no properties were given".to_string()));
+        }
+        let float = match config.coordinates.as_ref().map(|s| s.as_ref()) {
+            None | Some("double") => false,
+            Some("float") => true,
+            Some(s) => return Err(Error::Ply(format!("unknown coordinate type: {}", s))),
+        };
+        let offset = match config.offset {
+            Some(ref offset) if offset.len() == 3 => Some([offset[0], offset[1], offset[2]]),
+            Some(ref offset) => {
+                return Err(Error::Ply(format!("offset needs three values, got {}", offset.len())))
+            }
+            None if float => None,
+            None => Some([0.0; 3]),
+        };
+        try!(writeln!(writer, "ply"));
+        try!(writeln!(writer,
+                      "format {} 1.0",
+                      if ascii {
+                          "ascii"
+                      } else {
+                          "binary_little_endian"
+                      }));
+        try!(writeln!(writer, "comment written by georef"));
+        let offset_position = match offset {
+            Some(offset) if offset == [0.0; 3] => None,
+            Some(offset) => {
+                try!(writeln!(writer, "comment offset {} {} {}", offset[0], offset[1], offset[2]));
+                None
+            }
+            None => {
+                try!(write!(writer, "comment offset "));
+                let position = try!(writer.seek(SeekFrom::Current(0)));
+                try!(writeln!(writer, "{:<1$}", "", OFFSET_WIDTH));
+                Some(position)
+            }
+        };
+        try!(write!(writer, "element vertex "));
+        let count_position = try!(writer.seek(SeekFrom::Current(0)));
+        try!(writeln!(writer, "{:<1$}", 0, COUNT_WIDTH));
+        for property in &properties {
+            try!(writeln!(writer,
+                          "property {} {}",
+                          match *property {
+                              Attribute::Intensity => "ushort",
+                              Attribute::X | Attribute::Y | Attribute::Z if float => "float",
+                              _ => "double",
+                          },
+                          property.name()));
+        }
+        try!(writeln!(writer, "end_header"));
+        Ok(PlySink {
+            writer: writer,
+            ascii: ascii,
+            float: float,
+            properties: properties,
+            offset: offset,
+            offset_position: offset_position,
+            count: 0,
+            count_position: count_position,
+        })
+    }
+
+    /// Writes one point.
+    pub fn write_point(&mut self, point: &pabst::Point) -> io::Result<()> {
+        self.write_point_with_record(point, None)
+    }
+
+    /// Writes one point, taking the sigma and attitude properties from the trajectory record it
+    /// was georeferenced with.
+    pub fn write_point_with_record(&mut self,
+                                   point: &pabst::Point,
+                                   record: Option<&ImuGnssPoint>)
+                                   -> io::Result<()> {
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => {
+                // Adding zero turns a negative zero into zero, so it's written as "0".
+                let round = |value: f64| {
+                    (value / OFFSET_ROUNDING).round() * OFFSET_ROUNDING + 0.0
+                };
+                let offset = [round(point.x), round(point.y), round(point.z)];
+                self.offset = Some(offset);
+                offset
+            }
+        };
+        for (i, property) in self.properties.iter().enumerate() {
+            let coordinate = match *property {
+                Attribute::X => Some(0),
+                Attribute::Y => Some(1),
+                Attribute::Z => Some(2),
+                _ => None,
+            };
+            let value = property.value(point, record) - coordinate.map_or(0.0, |j| offset[j]);
+            let float = self.float && coordinate.is_some();
+            if self.ascii {
+                let separator = if i == 0 { "" } else { " " };
+                if float {
+                    try!(write!(self.writer, "{}{}", separator, value as f32));
+                } else {
+                    try!(write!(self.writer, "{}{}", separator, value));
+                }
+            } else if *property == Attribute::Intensity {
+                try!(self.writer.write_u16::<LittleEndian>(value as u16));
+            } else if float {
+                try!(self.writer.write_f32::<LittleEndian>(value as f32));
+            } else {
+                try!(self.writer.write_f64::<LittleEndian>(value));
+            }
+        }
+        if self.ascii {
+            try!(writeln!(self.writer, ""));
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Fills in the vertex count, and the offset if it came from the first point, and returns the
+    /// underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let end = try!(self.writer.seek(SeekFrom::Current(0)));
+        let _ = try!(self.writer.seek(SeekFrom::Start(self.count_position)));
+        try!(write!(self.writer, "{:<1$}", self.count, COUNT_WIDTH));
+        if let Some(position) = self.offset_position {
+            let offset = self.offset.unwrap_or([0.0; 3]);
+            let _ = try!(self.writer.seek(SeekFrom::Start(position)));
+            try!(write!(self.writer,
+                        "{:<1$}",
+                        format!("{} {} {}", offset[0], offset[1], offset[2]),
+                        OFFSET_WIDTH));
+        }
+        let _ = try!(self.writer.seek(SeekFrom::Start(end)));
+        try!(self.writer.flush());
+        Ok(self.writer)
+    }
+}
+
+impl<W: Debug + Write + Seek + 'static> pabst::Sink for PlySink<W> {
+    fn sink(&mut self, point: &pabst::Point) -> pabst::Result<()> {
+        try!(self.write_point(point));
+        Ok(())
+    }
+
+    fn close_sink(self: Box<Self>) -> pabst::Result<()> {
+        let _ = try!(self.finish());
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> PointSink for PlySink<W> {
+    fn sink(&mut self, point: &pabst::Point) -> Result<()> {
+        self.write_point(point).map_err(Error::from)
+    }
+
+    fn sink_with_record(&mut self, point: &pabst::Point, record: &ImuGnssPoint) -> Result<()> {
+        self.write_point_with_record(point, Some(record)).map_err(Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f64;
+    use std::io::Cursor;
+
+    use byteorder::{LittleEndian, ReadBytesExt};
+    use pabst;
+
+    fn point() -> pabst::Point {
+        pabst::Point {
+            x: 1.5,
+            y: 2.0,
+            z: -3.0,
+            gps_time: Some(10.0),
+            intensity: Some(42),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let config = PlyConfig {
+            format: Some("ascii".to_string()),
+            properties: Some(vec!["x".to_string(),
+                                  "y".to_string(),
+                                  "z".to_string(),
+                                  "time".to_string(),
+                                  "intensity".to_string()]),
+            ..Default::default()
+        };
+        let mut sink = PlySink::new(Cursor::new(Vec::new()), &config).unwrap();
+        sink.write_point(&point()).unwrap();
+        sink.write_point(&point()).unwrap();
+        let text = String::from_utf8(sink.finish().unwrap().into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!("format ascii 1.0", lines[1]);
+        assert_eq!("element vertex 2", lines[3].trim());
+        assert_eq!("property ushort intensity", lines[8]);
+        assert_eq!("end_header", lines[9]);
+        assert_eq!("1.5 2 -3 10 42", lines[10]);
+        assert_eq!(12, lines.len());
+    }
+
+    #[test]
+    fn attitude() {
+        let config = PlyConfig {
+            format: Some("ascii".to_string()),
+            properties: Some(vec!["roll".to_string(),
+                                  "pitch".to_string(),
+                                  "heading".to_string(),
+                                  "trajectory_height".to_string()]),
+            ..Default::default()
+        };
+        let record = ImuGnssPoint {
+            roll: f64::consts::PI / 2.0,
+            pitch: -f64::consts::PI,
+            yaw: f64::consts::PI / 4.0,
+            height: 100.0,
+            ..Default::default()
+        };
+        let mut sink = PlySink::new(Cursor::new(Vec::new()), &config).unwrap();
+        sink.sink_with_record(&point(), &record).unwrap();
+        sink.write_point(&point()).unwrap();
+        let text = String::from_utf8(sink.finish().unwrap().into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!("property double heading", lines[6]);
+        let values: Vec<f64> = lines[9].split(' ').map(|s| s.parse().unwrap()).collect();
+        for (expected, actual) in vec![90.0, -180.0, 45.0, 100.0].into_iter().zip(values) {
+            assert!((expected - actual).abs() < 1e-9);
+        }
+        assert_eq!("NaN NaN NaN NaN", lines[10]);
+    }
+
+    #[test]
+    fn binary() {
+        let mut sink = PlySink::new(Cursor::new(Vec::new()), &PlyConfig::default()).unwrap();
+        sink.write_point(&point()).unwrap();
+        let bytes = sink.finish().unwrap().into_inner();
+        let header_end = "end_header\n";
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        let body = text.find(header_end).unwrap() + header_end.len();
+        assert!(text.starts_with("ply\nformat binary_little_endian 1.0\n"));
+        assert_eq!(24, bytes.len() - body);
+    }
+
+    #[test]
+    fn float() {
+        let config = PlyConfig {
+            format: Some("ascii".to_string()),
+            properties: Some(vec!["x".to_string(), "y".to_string(), "time".to_string()]),
+            coordinates: Some("float".to_string()),
+            ..Default::default()
+        };
+        let mut sink = PlySink::new(Cursor::new(Vec::new()), &config).unwrap();
+        sink.write_point(&pabst::Point { x: 500_123.25, y: 6_000_499.5, ..point() }).unwrap();
+        sink.write_point(&pabst::Point { x: 499_999.0, ..point() }).unwrap();
+        let text = String::from_utf8(sink.finish().unwrap().into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!("comment offset 500000 6000000 0", lines[3].trim());
+        assert_eq!("property float x", lines[5]);
+        assert_eq!("property double time", lines[7]);
+        assert_eq!("123.25 499.5 10", lines[9]);
+        assert_eq!("-1 -5999998 10", lines[10]);
+
+        let config = PlyConfig {
+            coordinates: Some("float".to_string()),
+            offset: Some(vec![1.0, 2.0, 3.0]),
+            ..Default::default()
+        };
+        let mut sink = PlySink::new(Cursor::new(Vec::new()), &config).unwrap();
+        sink.write_point(&point()).unwrap();
+        let bytes = sink.finish().unwrap().into_inner();
+        let header_end = "end_header\n";
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        assert!(text.contains("comment offset 1 2 3\n"));
+        let body = text.find(header_end).unwrap() + header_end.len();
+        assert_eq!(12, bytes.len() - body);
+        assert_eq!(0.5, Cursor::new(&bytes[body..]).read_f32::<LittleEndian>().unwrap());
+    }
+
+    #[test]
+    fn bad_config() {
+        let config = PlyConfig {
+            format: Some("binary_big_endian".to_string()),
+            ..Default::default()
+        };
+        assert!(PlySink::new(Cursor::new(Vec::new()), &config).is_err());
+        let config = PlyConfig { properties: Some(vec!["red".to_string()]), ..Default::default() };
+        assert!(PlySink::new(Cursor::new(Vec::new()), &config).is_err());
+        let config = PlyConfig { coordinates: Some("half".to_string()), ..Default::default() };
+        assert!(PlySink::new(Cursor::new(Vec::new()), &config).is_err());
+        let config = PlyConfig { offset: Some(vec![1.0, 2.0]), ..Default::default() };
+        assert!(PlySink::new(Cursor::new(Vec::new()), &config).is_err());
+    }
+}
@@ -1,12 +1,276 @@
 //! Point management.
 
-use nalgebra::{Rot3, Vec3};
-use pos;
-use pos::{Accuracy, Radians};
-use utm;
+use std::f64;
+use std::str::FromStr;
 
+use nalgebra::{Col, Eye, Rot3, Vec3};
+use pabst;
+use pos::Radians;
+
+use {Error, Result};
+use ellipsoid::Ellipsoid;
+use imu_gnss::{ImuGnssPoint, Quaternion};
 use rotation::RotationOrder;
 
+/// The OGC WKT of the WGS84 datum, prime meridian, and angular unit.
+const WGS84_WKT: &'static str = "DATUM[\"WGS_1984\",SPHEROID[\"WGS 84\",6378137,298.257223563,\
+                                 AUTHORITY[\"EPSG\",\"7030\"]],AUTHORITY[\"EPSG\",\"6326\"]],\
+                                 PRIMEM[\"Greenwich\",0,AUTHORITY[\"EPSG\",\"8901\"]]";
+
+/// The coordinate frame of georeferenced points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFrame {
+    /// UTM easting, northing, and ellipsoidal height, in meters.
+    Utm,
+    /// Earth-centered, earth-fixed x, y, and z, in meters, for the output ellipsoid.
+    Ecef,
+    /// Longitude and latitude, in degrees, and ellipsoidal height, in meters, on the output
+    /// ellipsoid.
+    Geographic,
+    /// East, north, and up, in meters, in a local tangent plane anchored at an origin.
+    Local,
+    /// The scanner's coordinates rotated by the boresight into the IMU's axes, in meters, before
+    /// the lever arm and the trajectory are applied.
+    ///
+    /// This and the body frame are for debugging: if artifacts show up here, the scanner's
+    /// mounting is to blame, not the trajectory.
+    Sensor,
+    /// The scanner's coordinates in the IMU's body frame, in meters, after the boresight and the
+    /// lever arm but before the trajectory is applied.
+    Body,
+}
+
+impl Default for OutputFrame {
+    fn default() -> OutputFrame {
+        OutputFrame::Utm
+    }
+}
+
+impl OutputFrame {
+    /// Returns the OGC well-known text of this frame's coordinate reference system.
+    ///
+    /// The UTM zone is only used by the UTM frame. Heights are not described, so points with
+    /// geoid-corrected heights carry the same WKT as ellipsoidal ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::OutputFrame;
+    /// assert!(OutputFrame::Utm.wkt(6).starts_with("PROJCS[\"WGS 84 / UTM zone 6N\""));
+    /// ```
+    pub fn wkt(&self, utm_zone: u8) -> String {
+        match *self {
+            OutputFrame::Utm => utm_wkt(utm_zone, false),
+            OutputFrame::Ecef => {
+                format!("GEOCCS[\"WGS 84\",{},UNIT[\"metre\",1,AUTHORITY[\"EPSG\",\"9001\"]],\
+                         AXIS[\"Geocentric X\",OTHER],AXIS[\"Geocentric Y\",OTHER],\
+                         AXIS[\"Geocentric Z\",NORTH],AUTHORITY[\"EPSG\",\"4978\"]]",
+                        WGS84_WKT)
+            }
+            OutputFrame::Geographic => geographic_wkt(),
+            OutputFrame::Local => {
+                "LOCAL_CS[\"Local tangent plane\",LOCAL_DATUM[\"Local\",0],UNIT[\"metre\",1,\
+                 AUTHORITY[\"EPSG\",\"9001\"]],AXIS[\"Easting\",EAST],AXIS[\"Northing\",NORTH]]"
+                    .to_string()
+            }
+            OutputFrame::Sensor | OutputFrame::Body => {
+                "LOCAL_CS[\"IMU body frame\",LOCAL_DATUM[\"IMU\",0],UNIT[\"metre\",1,\
+                 AUTHORITY[\"EPSG\",\"9001\"]]]"
+                    .to_string()
+            }
+        }
+    }
+
+    /// Returns the OGC well-known text of WGS84 longitude, latitude, and ellipsoidal height, i.e.
+    /// the three-dimensional EPSG:4979 instead of EPSG:4326.
+    pub fn geographic_3d_wkt() -> String {
+        format!("GEOGCS[\"WGS 84\",{},UNIT[\"degree\",0.0174532925199433,\
+                 AUTHORITY[\"EPSG\",\"9122\"]],AXIS[\"Latitude\",NORTH],AXIS[\"Longitude\",EAST],\
+                 AXIS[\"Ellipsoidal height\",UP],AUTHORITY[\"EPSG\",\"4979\"]]",
+                WGS84_WKT)
+    }
+
+    /// Returns the OGC well-known text of a southern hemisphere WGS84 UTM zone, e.g. EPSG:32706
+    /// for zone 6, whose northings have a false northing of 10,000,000 meters.
+    pub fn southern_utm_wkt(utm_zone: u8) -> String {
+        utm_wkt(utm_zone, true)
+    }
+
+    /// Returns true for the sensor and body frames, which stop before the trajectory is applied.
+    pub fn is_platform(&self) -> bool {
+        *self == OutputFrame::Sensor || *self == OutputFrame::Body
+    }
+}
+
+/// Returns the OGC well-known text of WGS84 longitude and latitude, EPSG:4326.
+fn geographic_wkt() -> String {
+    format!("GEOGCS[\"WGS 84\",{},UNIT[\"degree\",0.0174532925199433,\
+             AUTHORITY[\"EPSG\",\"9122\"]],AUTHORITY[\"EPSG\",\"4326\"]]",
+            WGS84_WKT)
+}
+
+/// Returns the OGC well-known text of a WGS84 UTM zone in either hemisphere.
+fn utm_wkt(utm_zone: u8, south: bool) -> String {
+    let (hemisphere, false_northing, code) = if south {
+        ('S', 10000000, 32700 + utm_zone as u32)
+    } else {
+        ('N', 0, 32600 + utm_zone as u32)
+    };
+    format!("PROJCS[\"WGS 84 / UTM zone {}{}\",{},PROJECTION[\"Transverse_Mercator\"],\
+             PARAMETER[\"latitude_of_origin\",0],PARAMETER[\"central_meridian\",{}],\
+             PARAMETER[\"scale_factor\",0.9996],PARAMETER[\"false_easting\",500000],\
+             PARAMETER[\"false_northing\",{}],UNIT[\"metre\",1,AUTHORITY[\"EPSG\",\"9001\"]],\
+             AXIS[\"Easting\",EAST],AXIS[\"Northing\",NORTH],AUTHORITY[\"EPSG\",\"{}\"]]",
+            utm_zone,
+            hemisphere,
+            geographic_wkt(),
+            6 * utm_zone as i32 - 183,
+            false_northing,
+            code)
+}
+
+impl FromStr for OutputFrame {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<OutputFrame> {
+        match s {
+            "utm" => Ok(OutputFrame::Utm),
+            "ecef" => Ok(OutputFrame::Ecef),
+            "geographic" => Ok(OutputFrame::Geographic),
+            "local" => Ok(OutputFrame::Local),
+            "sensor" => Ok(OutputFrame::Sensor),
+            "body" => Ok(OutputFrame::Body),
+            _ => Err(Error::ParseOutputFrame(s.to_string())),
+        }
+    }
+}
+
+/// The navigation frame that an IMU/GNSS attitude rotates body vectors into.
+///
+/// Vendors differ: some report attitudes that rotate into east-north-up, others into
+/// north-east-down, with the body's z axis pointing down. Either way, points end up in
+/// east-north-up before they're projected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavigationFrame {
+    /// East, north, up.
+    Enu,
+    /// North, east, down.
+    Ned,
+}
+
+impl Default for NavigationFrame {
+    fn default() -> NavigationFrame {
+        NavigationFrame::Enu
+    }
+}
+
+impl NavigationFrame {
+    /// Returns the rotation from this frame into east-north-up.
+    pub fn to_enu(self) -> Rot3<f64> {
+        let mut rotation = Rot3::new_identity(3);
+        if self == NavigationFrame::Ned {
+            rotation.set_col(0, Vec3::new(0.0, 1.0, 0.0));
+            rotation.set_col(1, Vec3::new(1.0, 0.0, 0.0));
+            rotation.set_col(2, Vec3::new(0.0, 0.0, -1.0));
+        }
+        rotation
+    }
+}
+
+impl FromStr for NavigationFrame {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<NavigationFrame> {
+        match s {
+            "ENU" | "enu" => Ok(NavigationFrame::Enu),
+            "NED" | "ned" => Ok(NavigationFrame::Ned),
+            _ => Err(Error::ParseNavigationFrame(s.to_string())),
+        }
+    }
+}
+
+/// A per-point value that can be written by georef's own sinks.
+///
+/// The sigma attributes are the estimated accuracy of the trajectory record that a point was
+/// georeferenced with, in meters. Roll, pitch, heading, and trajectory height are that record's
+/// attitude, in degrees, and ellipsoidal height, in meters, for looking into artifacts that
+/// follow the platform's motion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attribute {
+    X,
+    Y,
+    Z,
+    Time,
+    Intensity,
+    SigmaNorth,
+    SigmaEast,
+    SigmaUp,
+    Roll,
+    Pitch,
+    Heading,
+    TrajectoryHeight,
+}
+
+impl Attribute {
+    /// Returns the attribute with this name, if there is one.
+    pub fn from_name(name: &str) -> Option<Attribute> {
+        match name {
+            "x" => Some(Attribute::X),
+            "y" => Some(Attribute::Y),
+            "z" => Some(Attribute::Z),
+            "time" => Some(Attribute::Time),
+            "intensity" => Some(Attribute::Intensity),
+            "sigma_north" => Some(Attribute::SigmaNorth),
+            "sigma_east" => Some(Attribute::SigmaEast),
+            "sigma_up" => Some(Attribute::SigmaUp),
+            "roll" => Some(Attribute::Roll),
+            "pitch" => Some(Attribute::Pitch),
+            "heading" => Some(Attribute::Heading),
+            "trajectory_height" => Some(Attribute::TrajectoryHeight),
+            _ => None,
+        }
+    }
+
+    /// Returns this attribute's name.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Attribute::X => "x",
+            Attribute::Y => "y",
+            Attribute::Z => "z",
+            Attribute::Time => "time",
+            Attribute::Intensity => "intensity",
+            Attribute::SigmaNorth => "sigma_north",
+            Attribute::SigmaEast => "sigma_east",
+            Attribute::SigmaUp => "sigma_up",
+            Attribute::Roll => "roll",
+            Attribute::Pitch => "pitch",
+            Attribute::Heading => "heading",
+            Attribute::TrajectoryHeight => "trajectory_height",
+        }
+    }
+
+    /// Returns this attribute's value for a point and the trajectory record it was georeferenced
+    /// with.
+    ///
+    /// Point attributes are zero if the point doesn't have them, and record attributes are NaN
+    /// if there's no record or, for the sigmas, it has no accuracy.
+    pub fn value(&self, point: &pabst::Point, record: Option<&ImuGnssPoint>) -> f64 {
+        let accuracy = record.and_then(|record| record.accuracy);
+        match *self {
+            Attribute::X => point.x,
+            Attribute::Y => point.y,
+            Attribute::Z => point.z,
+            Attribute::Time => point.gps_time.unwrap_or(0.0),
+            Attribute::Intensity => point.intensity.unwrap_or(0) as f64,
+            Attribute::SigmaNorth => accuracy.map_or(f64::NAN, |accuracy| accuracy.north),
+            Attribute::SigmaEast => accuracy.map_or(f64::NAN, |accuracy| accuracy.east),
+            Attribute::SigmaUp => accuracy.map_or(f64::NAN, |accuracy| accuracy.down),
+            Attribute::Roll => record.map_or(f64::NAN, |record| record.roll.to_degrees()),
+            Attribute::Pitch => record.map_or(f64::NAN, |record| record.pitch.to_degrees()),
+            Attribute::Heading => record.map_or(f64::NAN, |record| record.yaw.to_degrees()),
+            Attribute::TrajectoryHeight => record.map_or(f64::NAN, |record| record.height),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct UtmPoint {
     northing: f64,
@@ -15,27 +279,48 @@ pub struct UtmPoint {
     roll: Radians<f64>,
     pitch: Radians<f64>,
     yaw: Radians<f64>,
-    accuracy: Option<Accuracy>,
+    meridian_convergence: f64,
+    orientation: Option<Quaternion>,
 }
 
 impl UtmPoint {
-    /// Converts a pos point into a utm point.
-    pub fn from_latlon(point: &pos::Point, utm_zone: u8) -> UtmPoint {
-        let (northing, easting, meridian_convergence) = utm::radians_to_utm_wgs84(point.latitude.0, point.longitude.0, utm_zone);
+    /// Converts an IMU/GNSS point into a utm point, projected on an ellipsoid.
+    pub fn from_latlon(point: &ImuGnssPoint, utm_zone: u8, ellipsoid: Ellipsoid) -> UtmPoint {
+        let (northing, easting, meridian_convergence) = match point.utm {
+            Some(utm) if utm.utm_zone == utm_zone && utm.ellipsoid == ellipsoid => {
+                (utm.northing, utm.easting, utm.meridian_convergence)
+            }
+            _ => ellipsoid.to_utm(point.latitude, point.longitude, utm_zone),
+        };
         UtmPoint {
             northing: northing,
             easting: easting,
-            altitude: point.altitude,
-            roll: point.roll,
-            pitch: point.pitch,
-            yaw: point.yaw + Radians(meridian_convergence),
-            accuracy: point.accuracy,
+            altitude: point.height,
+            roll: Radians(point.roll),
+            pitch: Radians(point.pitch),
+            yaw: Radians(point.yaw + meridian_convergence),
+            meridian_convergence: meridian_convergence,
+            orientation: point.orientation,
         }
     }
 
     /// Returns the rotation matrix for this UTM point.
-    pub fn rotation_matrix(&self, rotation_order: &RotationOrder) -> Rot3<f64> {
-        rotation_order.rot3(self.roll.0, self.pitch.0, self.yaw.0)
+    ///
+    /// The attitude rotates into the navigation frame, which is then rotated into east-north-up.
+    /// A quaternion attitude is turned by the meridian convergence with the rotation order's own
+    /// yaw rotation, which is what adding the convergence to the yaw does when, as usual, the yaw
+    /// rotation comes first.
+    pub fn rotation_matrix(&self,
+                           rotation_order: &RotationOrder,
+                           frame: NavigationFrame)
+                           -> Rot3<f64> {
+        match self.orientation {
+            Some(orientation) => {
+                frame.to_enu() * rotation_order.rot3(0.0, 0.0, self.meridian_convergence) *
+                orientation.to_rot3()
+            }
+            None => frame.to_enu() * rotation_order.rot3(self.roll.0, self.pitch.0, self.yaw.0),
+        }
     }
 
     /// Returns this point's location as a vec3.
@@ -44,13 +329,185 @@ impl UtmPoint {
     }
 }
 
+/// Converts a coordinate reference system string into an output frame and UTM zone.
+///
+/// Only the WGS84 systems that have a built-in output frame are supported: `EPSG:4978` (ECEF),
+/// `EPSG:4326` or `EPSG:4979` (geographic), `EPSG:32601` through `EPSG:32660` (northern UTM
+/// zones), and `EPSG:32701` through `EPSG:32760` (southern UTM zones). Anything else, e.g. state
+/// plane, would need a general projection library such as PROJ, which this crate deliberately
+/// doesn't link; reproject the output afterwards instead.
+///
+/// Northings carry the southern false northing when a point is south of the equator, whichever
+/// hemisphere the system names, so the hemisphere only changes the well-known text.
+pub fn parse_crs(crs: &str) -> Result<(OutputFrame, Option<u8>)> {
+    let code = match crs_code(crs) {
+        Some(code) => code,
+        None => return Err(Error::UnsupportedCrs(crs.to_string())),
+    };
+    match code {
+        4978 => Ok((OutputFrame::Ecef, None)),
+        4326 | 4979 => Ok((OutputFrame::Geographic, None)),
+        32601...32660 => Ok((OutputFrame::Utm, Some((code - 32600) as u8))),
+        32701...32760 => Ok((OutputFrame::Utm, Some((code - 32700) as u8))),
+        _ => Err(Error::UnsupportedCrs(crs.to_string())),
+    }
+}
+
+/// Returns the EPSG code of a coordinate reference system string, e.g. 4979 for `EPSG:4979`.
+pub fn crs_code(crs: &str) -> Option<u32> {
+    crs.trim().to_uppercase().trim_left_matches("EPSG:").parse().ok()
+}
+
+/// Returns the natural UTM zone for a latitude and longitude, in radians.
+///
+/// This includes the Norway and Svalbard exceptions.
+pub fn natural_utm_zone(latitude: f64, longitude: f64) -> u8 {
+    let (latitude, longitude) = (latitude.to_degrees(), longitude.to_degrees());
+    let mut longitude = (longitude + 180.0) % 360.0;
+    if longitude < 0.0 {
+        longitude += 360.0;
+    }
+    let longitude = longitude - 180.0;
+    if latitude >= 56.0 && latitude < 64.0 && longitude >= 3.0 && longitude < 12.0 {
+        return 32;
+    }
+    if latitude >= 72.0 && latitude < 84.0 && longitude >= 0.0 && longitude < 42.0 {
+        return if longitude < 9.0 {
+            31
+        } else if longitude < 21.0 {
+            33
+        } else if longitude < 33.0 {
+            35
+        } else {
+            37
+        };
+    }
+    (((longitude + 180.0) / 6.0).floor() as u8 + 1).min(60)
+}
+
+/// An IMU/GNSS point in earth-centered, earth-fixed coordinates.
+#[derive(Debug, Default)]
+pub struct EcefPoint {
+    latitude: f64,
+    longitude: f64,
+    location: (f64, f64, f64),
+    roll: f64,
+    pitch: f64,
+    yaw: f64,
+    orientation: Option<Quaternion>,
+}
+
+impl EcefPoint {
+    /// Converts an IMU/GNSS point, taken to be on an ellipsoid, into an ECEF point.
+    pub fn from_latlon(point: &ImuGnssPoint, ellipsoid: Ellipsoid) -> EcefPoint {
+        EcefPoint {
+            latitude: point.latitude,
+            longitude: point.longitude,
+            location: ellipsoid.to_ecef(point.latitude, point.longitude, point.height),
+            roll: point.roll,
+            pitch: point.pitch,
+            yaw: point.yaw,
+            orientation: point.orientation,
+        }
+    }
+
+    /// Returns the rotation matrix for this ECEF point.
+    ///
+    /// The attitude rotates into the navigation frame, which is then rotated into the local
+    /// east-north-up frame and then into ECEF.
+    pub fn rotation_matrix(&self,
+                           rotation_order: &RotationOrder,
+                           frame: NavigationFrame)
+                           -> Rot3<f64> {
+        let attitude = match self.orientation {
+            Some(orientation) => orientation.to_rot3(),
+            None => rotation_order.rot3(self.roll, self.pitch, self.yaw),
+        };
+        enu_to_ecef(self.latitude, self.longitude) * frame.to_enu() * attitude
+    }
+
+    /// Returns this point's location as a vec3.
+    pub fn location(&self) -> Vec3<f64> {
+        Vec3::new(self.location.0, self.location.1, self.location.2)
+    }
+}
+
+/// A local tangent plane, with east, north, and up axes anchored at an origin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocalFrame {
+    origin: Vec3<f64>,
+    enu_to_ecef: Rot3<f64>,
+}
+
+impl LocalFrame {
+    /// Creates a local frame anchored at a latitude and longitude, in radians, and height on an
+    /// ellipsoid.
+    pub fn new(latitude: f64, longitude: f64, height: f64, ellipsoid: Ellipsoid) -> LocalFrame {
+        let (x, y, z) = ellipsoid.to_ecef(latitude, longitude, height);
+        LocalFrame {
+            origin: Vec3::new(x, y, z),
+            enu_to_ecef: enu_to_ecef(latitude, longitude),
+        }
+    }
+
+    /// Projects an ECEF location into this frame.
+    pub fn project(&self, ecef: Vec3<f64>) -> Vec3<f64> {
+        // A row vector times the matrix is the same as the transpose times a column vector.
+        (ecef - self.origin) * self.enu_to_ecef
+    }
+
+    /// Returns the ECEF location of a point in this frame.
+    pub fn unproject(&self, enu: Vec3<f64>) -> Vec3<f64> {
+        self.origin + self.enu_to_ecef * enu
+    }
+}
+
+/// Returns the rotation from the local east-north-up frame at a latitude and longitude, in
+/// radians, into ECEF.
+fn enu_to_ecef(latitude: f64, longitude: f64) -> Rot3<f64> {
+    let (sin_lat, cos_lat) = latitude.sin_cos();
+    let (sin_lon, cos_lon) = longitude.sin_cos();
+    let mut enu_to_ecef = Rot3::new_identity(3);
+    enu_to_ecef.set_col(0, Vec3::new(-sin_lon, cos_lon, 0.0));
+    enu_to_ecef.set_col(1, Vec3::new(-sin_lat * cos_lon, -sin_lat * sin_lon, cos_lat));
+    enu_to_ecef.set_col(2, Vec3::new(cos_lat * cos_lon, cos_lat * sin_lon, sin_lat));
+    enu_to_ecef
+}
+
+/// Returns the scan angle, in degrees, of a point in the scanner's coordinate system after the
+/// SOCS map has been applied.
+///
+/// This is the angle from straight down towards the right, in the plane that contains both, so
+/// points to the left of nadir have negative angles. Down and right are the body's -z and -y axes
+/// for an east-north-up navigation frame, and its +z and +y axes for north-east-down.
+pub fn scan_angle(scanner: Vec3<f64>, frame: NavigationFrame) -> f64 {
+    let (right, down) = match frame {
+        NavigationFrame::Enu => (-scanner.y, -scanner.z),
+        NavigationFrame::Ned => (scanner.y, scanner.z),
+    };
+    right.atan2(down).to_degrees()
+}
+
+/// Converts WGS84 latitude and longitude, in radians, and height to ECEF x, y, and z.
+pub fn geodetic_to_ecef(latitude: f64, longitude: f64, height: f64) -> (f64, f64, f64) {
+    Ellipsoid::wgs84().to_ecef(latitude, longitude, height)
+}
+
+/// Converts ECEF x, y, and z to WGS84 latitude and longitude, in radians, and height.
+pub fn ecef_to_geodetic(x: f64, y: f64, z: f64) -> (f64, f64, f64) {
+    Ellipsoid::wgs84().to_geodetic(x, y, z)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use nalgebra::{Eye, Rot3};
+    use nalgebra::{Eye, Rot3, Vec3};
     use pos::Radians;
 
+    use ellipsoid::Ellipsoid;
+    use imu_gnss::ImuGnssPoint;
+
     #[test]
     fn no_rotation() {
         let point = UtmPoint {
@@ -60,6 +517,121 @@ mod tests {
             ..Default::default()
         };
         let rotation_order = Default::default();
-        assert_eq!(Rot3::new_identity(3), point.rotation_matrix(&rotation_order));
+        assert_eq!(Rot3::new_identity(3),
+                   point.rotation_matrix(&rotation_order, NavigationFrame::Enu));
+    }
+
+    #[test]
+    fn ned() {
+        let point = UtmPoint::default();
+        let rotation = point.rotation_matrix(&Default::default(), NavigationFrame::Ned);
+        assert_eq!(Vec3::new(0.0, 1.0, 0.0), rotation * Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(Vec3::new(1.0, 0.0, 0.0), rotation * Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(Vec3::new(0.0, 0.0, -1.0), rotation * Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(NavigationFrame::Ned, "NED".parse().unwrap());
+        assert!("NWU".parse::<NavigationFrame>().is_err());
+    }
+
+    #[test]
+    fn zones() {
+        assert_eq!(6, natural_utm_zone(61f64.to_radians(), -149f64.to_radians()));
+        assert_eq!(1, natural_utm_zone(0.0, -180f64.to_radians()));
+        assert_eq!(60, natural_utm_zone(0.0, 179.9f64.to_radians()));
+        assert_eq!(31, natural_utm_zone(0.0, 0.0));
+        assert_eq!(32, natural_utm_zone(60f64.to_radians(), 4f64.to_radians()));
+        assert_eq!(33, natural_utm_zone(78f64.to_radians(), 10f64.to_radians()));
+    }
+
+    #[test]
+    fn crs() {
+        assert_eq!((OutputFrame::Utm, Some(6)), parse_crs("EPSG:32606").unwrap());
+        assert_eq!((OutputFrame::Ecef, None), parse_crs("epsg:4978").unwrap());
+        assert_eq!((OutputFrame::Geographic, None), parse_crs("4326").unwrap());
+        assert_eq!((OutputFrame::Utm, Some(6)), parse_crs("EPSG:32706").unwrap());
+        assert!(parse_crs("EPSG:32761").is_err());
+        assert!(parse_crs("EPSG:2927").is_err());
+        assert!(parse_crs("+proj=utm +zone=6").is_err());
+    }
+
+    #[test]
+    fn wkt() {
+        let utm = OutputFrame::Utm.wkt(6);
+        assert!(utm.contains("PARAMETER[\"central_meridian\",-147]"));
+        assert!(utm.ends_with("AUTHORITY[\"EPSG\",\"32606\"]]"));
+        assert!(OutputFrame::Ecef.wkt(6).ends_with("AUTHORITY[\"EPSG\",\"4978\"]]"));
+        assert!(OutputFrame::Geographic.wkt(6).starts_with("GEOGCS[\"WGS 84\",DATUM["));
+        assert!(!utm.contains("  "));
+        let south = OutputFrame::southern_utm_wkt(6);
+        assert!(south.starts_with("PROJCS[\"WGS 84 / UTM zone 6S\""));
+        assert!(south.contains("PARAMETER[\"false_northing\",10000000]"));
+        assert!(south.ends_with("AUTHORITY[\"EPSG\",\"32706\"]]"));
+    }
+
+    #[test]
+    fn scan_angles() {
+        let ned = |x, y, z| scan_angle(Vec3::new(x, y, z), NavigationFrame::Ned);
+        assert_eq!(0.0, ned(0.0, 0.0, 10.0));
+        assert!((ned(5.0, 10.0, 10.0) - 45.0).abs() < 1e-12);
+        assert!((ned(0.0, -10.0, 10.0) + 45.0).abs() < 1e-12);
+        assert!((ned(0.0, 10.0, 0.0) - 90.0).abs() < 1e-12);
+        let enu = |x, y, z| scan_angle(Vec3::new(x, y, z), NavigationFrame::Enu);
+        assert_eq!(0.0, enu(0.0, 0.0, -10.0));
+        assert!((enu(5.0, -10.0, -10.0) - 45.0).abs() < 1e-12);
+        assert!((enu(0.0, 10.0, -10.0) + 45.0).abs() < 1e-12);
+        assert!((enu(0.0, 0.0, 10.0).abs() - 180.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn ecef_roundtrip() {
+        let (latitude, longitude, height) = (61.0f64.to_radians(), -149.1f64.to_radians(), 123.4);
+        let (x, y, z) = geodetic_to_ecef(latitude, longitude, height);
+        let (lat, lon, h) = ecef_to_geodetic(x, y, z);
+        assert!((lat - latitude).abs() < 1e-12);
+        assert!((lon - longitude).abs() < 1e-12);
+        assert!((h - height).abs() < 1e-6);
+    }
+
+    #[test]
+    fn local_frame() {
+        let (latitude, longitude) = (61.0f64.to_radians(), -149.1f64.to_radians());
+        let frame = LocalFrame::new(latitude, longitude, 100.0, Ellipsoid::wgs84());
+        let (x, y, z) = geodetic_to_ecef(latitude, longitude, 110.0);
+        let up = frame.project(Vec3::new(x, y, z));
+        assert!(up.x.abs() < 1e-6);
+        assert!(up.y.abs() < 1e-6);
+        assert!((up.z - 10.0).abs() < 1e-6);
+        let (x, y, z) = geodetic_to_ecef(latitude + 1e-5, longitude, 100.0);
+        let north = frame.project(Vec3::new(x, y, z));
+        assert!(north.x.abs() < 1e-6);
+        assert!(north.y > 1.0);
+        let ecef = frame.unproject(Vec3::new(3.0, -4.0, 5.0));
+        let enu = frame.project(ecef);
+        assert!((enu.x - 3.0).abs() < 1e-6);
+        assert!((enu.y + 4.0).abs() < 1e-6);
+        assert!((enu.z - 5.0).abs() < 1e-6);
+        assert_eq!(OutputFrame::Local, "local".parse().unwrap());
+    }
+
+    #[test]
+    fn platform_frames() {
+        assert_eq!(OutputFrame::Sensor, "sensor".parse().unwrap());
+        assert_eq!(OutputFrame::Body, "body".parse().unwrap());
+        assert!(OutputFrame::Body.is_platform());
+        assert!(!OutputFrame::Local.is_platform());
+    }
+
+    #[test]
+    fn ecef_up() {
+        let point = EcefPoint::from_latlon(&ImuGnssPoint {
+                                               latitude: 0.0,
+                                               longitude: 0.0,
+                                               ..Default::default()
+                                           },
+                                           Ellipsoid::wgs84());
+        let up = point.rotation_matrix(&Default::default(), NavigationFrame::Enu) *
+                 Vec3::new(0.0, 0.0, 1.0);
+        assert!((up.x - 1.0).abs() < 1e-12);
+        assert!(up.y.abs() < 1e-12);
+        assert!(up.z.abs() < 1e-12);
     }
 }
@@ -0,0 +1,161 @@
+//! Raw polar measurements from the scanner.
+//!
+//! Some sources deliver what the scanner actually measured, a range and one or two encoder
+//! angles, rather than points in the scanner's own coordinate system (SOCS). A polar model reads
+//! those measurements out of each point's x, y, and z fields and converts them to SOCS x, y, and z
+//! before anything else is applied:
+//!
+//! - x is always the range.
+//! - y is the horizontal angle, counterclockwise from the SOCS x axis towards the y axis.
+//! - z is the vertical angle above the SOCS x-y plane, for the "spherical" model, and is ignored
+//!   by the "profile" model of a two-dimensional line scanner, whose beam sweeps the x-y plane.
+//!
+//! Angles are in degrees, radians, or raw encoder counts, with `counts_per_revolution` counts in
+//! a full turn. Encoder zero offsets and range errors belong in `intrinsics` and
+//! `range_correction`, which are applied to the converted point, and the SOCS map and boresight
+//! then turn the scan plane wherever the scanner is mounted. Inverse georeferencing returns SOCS
+//! x, y, and z, not polar measurements.
+//!
+//! ```toml
+//! [georef.polar]
+//! model = "profile"
+//! angle_units = "counts"
+//! counts_per_revolution = 36000.0
+//! ```
+
+use std::f64::consts::PI;
+use std::result;
+
+use nalgebra::Vec3;
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+
+/// A decodable description of a scanner's polar measurements.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct PolarConfig {
+    /// The scanner geometry, either "spherical" (the default), with a horizontal and a vertical
+    /// angle, or "profile", with a single angle in the SOCS x-y plane.
+    pub model: Option<String>,
+    /// The units of the angles, either "degrees" (the default), "radians", or "counts".
+    pub angle_units: Option<String>,
+    /// The encoder counts in a full turn, needed if the angle units are "counts".
+    pub counts_per_revolution: Option<f64>,
+}
+
+impl PolarConfig {
+    /// Creates a new polar config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<PolarConfig, toml::DecodeError> {
+        PolarConfig::decode(&mut toml::Decoder::new(table))
+    }
+
+    /// Returns a message for an unknown model or angle units, and for counts per revolution that
+    /// are missing, not positive, or given without count units.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        match self.model.as_ref().map(|s| s.as_ref()) {
+            None | Some("spherical") | Some("profile") => {}
+            Some(s) => problems.push(format!("unknown model: {}", s)),
+        }
+        match self.angle_units.as_ref().map(|s| s.as_ref()) {
+            None | Some("degrees") | Some("radians") => {
+                if self.counts_per_revolution.is_some() {
+                    problems.push("counts_per_revolution needs angle_units = \"counts\""
+                                      .to_string());
+                }
+            }
+            Some("counts") => {
+                if !self.counts_per_revolution.map_or(false, |c| c > 0.0 && c.is_finite()) {
+                    problems.push("counts_per_revolution must be greater than zero".to_string());
+                }
+            }
+            Some(s) => problems.push(format!("unknown angle units: {}", s)),
+        }
+        problems
+    }
+}
+
+/// A scanner geometry model, ready to convert polar measurements.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Polar {
+    profile: bool,
+    angle_factor: f64,
+}
+
+impl Polar {
+    /// Creates a polar model from its configuration.
+    pub fn new(config: &PolarConfig) -> Result<Polar> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("polar", problem));
+        }
+        Ok(Polar {
+            profile: config.model.as_ref().map_or(false, |model| model == "profile"),
+            angle_factor: match config.angle_units.as_ref().map(|s| s.as_ref()) {
+                Some("radians") => 1.0,
+                Some("counts") => 2.0 * PI / config.counts_per_revolution.unwrap(),
+                _ => 1f64.to_radians(),
+            },
+        })
+    }
+
+    /// Converts a range and angles, in a point's x, y, and z, to the scanner's own coordinate
+    /// system.
+    pub fn to_socs(&self, measurement: Vec3<f64>) -> Vec3<f64> {
+        let range = measurement.x;
+        let horizontal = measurement.y * self.angle_factor;
+        let vertical = if self.profile {
+            0.0
+        } else {
+            measurement.z * self.angle_factor
+        };
+        Vec3::new(range * vertical.cos() * horizontal.cos(),
+                  range * vertical.cos() * horizontal.sin(),
+                  range * vertical.sin())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::{Norm, Vec3};
+
+    fn close(a: Vec3<f64>, b: Vec3<f64>) -> bool {
+        (a - b).norm() < 1e-9
+    }
+
+    #[test]
+    fn spherical() {
+        let polar = Polar::new(&Default::default()).unwrap();
+        assert!(close(Vec3::new(0.0, 10.0, 0.0), polar.to_socs(Vec3::new(10.0, 90.0, 0.0))));
+        assert!(close(Vec3::new(0.0, 0.0, -10.0), polar.to_socs(Vec3::new(10.0, 0.0, -90.0))));
+    }
+
+    #[test]
+    fn profile_counts() {
+        let polar = Polar::new(&PolarConfig {
+                        model: Some("profile".to_string()),
+                        angle_units: Some("counts".to_string()),
+                        counts_per_revolution: Some(3600.0),
+                    })
+                        .unwrap();
+        assert!(close(Vec3::new(-5.0, 0.0, 0.0), polar.to_socs(Vec3::new(5.0, 1800.0, 42.0))));
+    }
+
+    #[test]
+    fn problems() {
+        let config = PolarConfig {
+            model: Some("cylindrical".to_string()),
+            angle_units: Some("counts".to_string()),
+            counts_per_revolution: None,
+        };
+        assert_eq!(vec!["unknown model: cylindrical".to_string(),
+                        "counts_per_revolution must be greater than zero".to_string()],
+                   config.problems());
+        let config = PolarConfig { counts_per_revolution: Some(100.0), ..Default::default() };
+        assert_eq!(vec!["counts_per_revolution needs angle_units = \"counts\"".to_string()],
+                   config.problems());
+    }
+}
@@ -0,0 +1,217 @@
+//! Corrections to the ranges measured by the scanner.
+//!
+//! Ranges are corrected in the scanner's own coordinate system, before the boresight and lever arm
+//! are applied, by scaling each point along its line of sight. The corrected range is:
+//!
+//! ```text
+//! (range * scale + offset) * (1 + atmospheric ppm * 1e-6)
+//! ```
+//!
+//! The atmospheric term accounts for the speed of light in the air at the time of the survey being
+//! different from the speed of light in the scanner's reference atmosphere. It uses the Barrel and
+//! Sears group refractivity of dry air at the laser's wavelength, scaled to the observed
+//! temperature and pressure. Humidity is ignored, which is good to a few tenths of a ppm at
+//! near-infrared wavelengths.
+//!
+//! ```toml
+//! [georef.range_correction]
+//! offset = 0.012
+//! scale = 1.0
+//! temperature = 28.0
+//! pressure = 985.0
+//! ```
+
+use std::result;
+
+use nalgebra::{Norm, Vec3};
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+
+/// The temperature of the default reference atmosphere, in degrees Celsius.
+const REFERENCE_TEMPERATURE: f64 = 12.0;
+/// The pressure of the default reference atmosphere, in hectopascals.
+const REFERENCE_PRESSURE: f64 = 1013.25;
+/// The default laser wavelength, in micrometers.
+const WAVELENGTH: f64 = 1.55;
+/// Zero degrees Celsius, in kelvin.
+const ZERO_CELSIUS: f64 = 273.15;
+/// The standard pressure that group refractivity is given at, in hectopascals.
+const STANDARD_PRESSURE: f64 = 1013.25;
+
+/// A decodable description of range corrections.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct RangeCorrectionConfig {
+    /// A constant added to every range, in meters. Defaults to zero.
+    pub offset: Option<f64>,
+    /// A factor that every range is multiplied by. Defaults to one.
+    pub scale: Option<f64>,
+    /// The air temperature during the survey, in degrees Celsius.
+    ///
+    /// The atmospheric correction is only applied if both temperature and pressure are given.
+    pub temperature: Option<f64>,
+    /// The air pressure during the survey, in hectopascals.
+    pub pressure: Option<f64>,
+    /// The temperature of the atmosphere the scanner's ranges are calibrated for, in degrees
+    /// Celsius. Defaults to 12.
+    pub reference_temperature: Option<f64>,
+    /// The pressure of the atmosphere the scanner's ranges are calibrated for, in hectopascals.
+    /// Defaults to 1013.25.
+    pub reference_pressure: Option<f64>,
+    /// The laser's wavelength, in micrometers. Defaults to 1.55.
+    pub wavelength: Option<f64>,
+}
+
+impl RangeCorrectionConfig {
+    /// Creates a new range correction config from a toml value.
+    pub fn from_toml(table: toml::Value)
+                     -> result::Result<RangeCorrectionConfig, toml::DecodeError> {
+        RangeCorrectionConfig::decode(&mut toml::Decoder::new(table))
+    }
+
+    /// Returns a message for an offset or scale out of range, for temperature without pressure or
+    /// the other way around, and for each atmospheric value that isn't physical.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.offset.map_or(false, |offset| !offset.is_finite()) {
+            problems.push("offset must be finite".to_string());
+        }
+        if self.scale.map_or(false, |scale| !(scale > 0.0 && scale.is_finite())) {
+            problems.push("scale must be greater than zero".to_string());
+        }
+        if self.temperature.is_some() != self.pressure.is_some() {
+            problems.push("temperature and pressure must be given together".to_string());
+        }
+        for &(name, temperature) in &[("temperature", self.temperature),
+                                      ("reference_temperature", self.reference_temperature)] {
+            if temperature.map_or(false, |t| !(t > -ZERO_CELSIUS && t.is_finite())) {
+                problems.push(format!("{} must be above absolute zero", name));
+            }
+        }
+        for &(name, value) in &[("pressure", self.pressure),
+                                ("reference_pressure", self.reference_pressure),
+                                ("wavelength", self.wavelength)] {
+            if value.map_or(false, |v| !(v > 0.0 && v.is_finite())) {
+                problems.push(format!("{} must be greater than zero", name));
+            }
+        }
+        problems
+    }
+}
+
+/// Range corrections, ready to be applied to points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RangeCorrection {
+    offset: f64,
+    scale: f64,
+    atmospheric_factor: f64,
+}
+
+impl RangeCorrection {
+    /// Creates a range correction from its configuration.
+    pub fn new(config: &RangeCorrectionConfig) -> Result<RangeCorrection> {
+        if let Some(problem) = config.problems().into_iter().next() {
+            return Err(Error::InvalidField("range_correction", problem));
+        }
+        let ppm = match (config.temperature, config.pressure) {
+            (Some(temperature), Some(pressure)) => {
+                let wavelength = config.wavelength.unwrap_or(WAVELENGTH);
+                refractivity(wavelength,
+                             config.reference_temperature.unwrap_or(REFERENCE_TEMPERATURE),
+                             config.reference_pressure.unwrap_or(REFERENCE_PRESSURE)) -
+                refractivity(wavelength, temperature, pressure)
+            }
+            _ => 0.0,
+        };
+        Ok(RangeCorrection {
+            offset: config.offset.unwrap_or(0.0),
+            scale: config.scale.unwrap_or(1.0),
+            atmospheric_factor: 1.0 + ppm * 1e-6,
+        })
+    }
+
+    /// Returns the correction, in parts per million, due to the atmosphere.
+    pub fn atmospheric_ppm(&self) -> f64 {
+        (self.atmospheric_factor - 1.0) * 1e6
+    }
+
+    /// Corrects the range of a point in the scanner's coordinate system.
+    pub fn apply(&self, point: Vec3<f64>) -> Vec3<f64> {
+        let range = point.norm();
+        if range == 0.0 {
+            return point;
+        }
+        point * ((range * self.scale + self.offset) * self.atmospheric_factor / range)
+    }
+}
+
+/// Returns the group refractivity, (n - 1) * 1e6, of dry air at a wavelength in micrometers, a
+/// temperature in degrees Celsius, and a pressure in hectopascals.
+fn refractivity(wavelength: f64, temperature: f64, pressure: f64) -> f64 {
+    let standard = 287.6155 + 4.88660 / wavelength.powi(2) + 0.06800 / wavelength.powi(4);
+    standard * ZERO_CELSIUS / (ZERO_CELSIUS + temperature) * pressure / STANDARD_PRESSURE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::{Norm, Vec3};
+
+    #[test]
+    fn offset_and_scale() {
+        let correction = RangeCorrection::new(&RangeCorrectionConfig {
+                             offset: Some(0.5),
+                             scale: Some(2.0),
+                             ..Default::default()
+                         })
+                             .unwrap();
+        let point = correction.apply(Vec3::new(3.0, 0.0, 4.0));
+        assert!((point.norm() - 10.5).abs() < 1e-12);
+        assert!((point.x / point.z - 0.75).abs() < 1e-12);
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0), correction.apply(Vec3::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn atmosphere() {
+        let reference = RangeCorrection::new(&RangeCorrectionConfig {
+                            temperature: Some(12.0),
+                            pressure: Some(1013.25),
+                            ..Default::default()
+                        })
+                            .unwrap();
+        assert!(reference.atmospheric_ppm().abs() < 1e-9);
+        let hot = RangeCorrection::new(&RangeCorrectionConfig {
+                      temperature: Some(32.0),
+                      pressure: Some(1013.25),
+                      ..Default::default()
+                  })
+                      .unwrap();
+        // Roughly one ppm per degree.
+        assert!(hot.atmospheric_ppm() > 15.0 && hot.atmospheric_ppm() < 25.0);
+        let high = RangeCorrection::new(&RangeCorrectionConfig {
+                       temperature: Some(12.0),
+                       pressure: Some(800.0),
+                       ..Default::default()
+                   })
+                       .unwrap();
+        assert!(high.atmospheric_ppm() > 50.0);
+    }
+
+    #[test]
+    fn problems() {
+        let config = RangeCorrectionConfig {
+            scale: Some(0.0),
+            temperature: Some(20.0),
+            wavelength: Some(-1.0),
+            ..Default::default()
+        };
+        assert_eq!(vec!["scale must be greater than zero".to_string(),
+                        "temperature and pressure must be given together".to_string(),
+                        "wavelength must be greater than zero".to_string()],
+                   config.problems());
+        assert!(RangeCorrection::new(&config).is_err());
+    }
+}
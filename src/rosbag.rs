@@ -0,0 +1,651 @@
+//! Read point clouds and trajectories from ROS bag files.
+//!
+//! Robots log their lidar and odometry to ROS bags, so they can be georeferenced without
+//! converting them first. Bags are read in the ROS 1 bag format, version 2.0. Only uncompressed
+//! chunks can be read, so run `rosbag decompress` on bags that were recorded with compression.
+//!
+//! Points come from `sensor_msgs/PointCloud2` messages, and are read in the sensor's own frame, so
+//! the cloud's frame is the scanner's own coordinate system. Each point's x, y, and z fields are
+//! required, and its intensity is read if the cloud has one. A point's time is the message's
+//! stamp, plus the point's `time` field, in seconds, or its `t` field, in nanoseconds, if the
+//! cloud has either. Points with non-finite coordinates are dropped. The `[source]` table picks
+//! the topic, and otherwise every point cloud in the bag is read:
+//!
+//! ```toml
+//! [source]
+//! topic = "/velodyne_points"
+//! ```
+//!
+//! The trajectory comes from `nav_msgs/Odometry` messages, whose pose must be in an east-north-up
+//! frame anchored at a known origin, e.g. a map frame that has been aligned with GNSS. The pose
+//! rotates the child frame, which is the body frame, into that frame, which is treated as the
+//! navigation frame, so keep the default ENU frame and rotation order. Over a few kilometers from
+//! the origin, the frames' east-north-up axes differ by less than a twentieth of a degree. The
+//! `[trajectory]` table gives the origin, in degrees and meters above the ellipsoid, and may pick
+//! the topic:
+//!
+//! ```toml
+//! [trajectory]
+//! topic = "/odometry/filtered"
+//! origin = { latitude = 61.2, longitude = -149.9, height = 100.0 }
+//! ```
+//!
+//! Message stamps are Unix times, so points and trajectories from the same bag are in the same
+//! time system. Transform trees, i.e. `tf2_msgs/TFMessage`, aren't read.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor, ErrorKind, Read};
+use std::path::Path;
+use std::result;
+use std::u16;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use nalgebra::Vec3;
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use ellipsoid::Ellipsoid;
+use error::Error;
+use georef::LocalOrigin;
+use imu_gnss::{ImuGnss, ImuGnssPoint, Quaternion};
+use point::{LocalFrame, ecef_to_geodetic};
+use stream::{Point, PointSource};
+
+/// The first line of every version 2.0 bag.
+const MAGIC: &'static [u8] = b"#ROSBAG V2.0\n";
+/// The message type of point clouds.
+const POINT_CLOUD: &'static str = "sensor_msgs/PointCloud2";
+/// The message type of odometry.
+const ODOMETRY: &'static str = "nav_msgs/Odometry";
+
+/// A decodable description of a bag's point clouds.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct RosbagSourceConfig {
+    /// The topic of the point clouds. Defaults to every point cloud in the bag.
+    pub topic: Option<String>,
+}
+
+impl RosbagSourceConfig {
+    /// Creates a new bag source config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<RosbagSourceConfig, toml::DecodeError> {
+        RosbagSourceConfig::decode(&mut toml::Decoder::new(table))
+    }
+}
+
+/// A decodable description of a bag's odometry.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct RosbagTrajectoryConfig {
+    /// The topic of the odometry. Defaults to all of the odometry in the bag.
+    pub topic: Option<String>,
+    /// The origin of the odometry's east-north-up frame.
+    pub origin: LocalOrigin,
+}
+
+impl RosbagTrajectoryConfig {
+    /// Creates a new bag trajectory config from a toml value.
+    pub fn from_toml(table: toml::Value)
+                     -> result::Result<RosbagTrajectoryConfig, toml::DecodeError> {
+        RosbagTrajectoryConfig::decode(&mut toml::Decoder::new(table))
+    }
+}
+
+/// One message from a bag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    /// The topic the message was published on.
+    pub topic: String,
+    /// The message's type, e.g. "sensor_msgs/PointCloud2".
+    pub message_type: String,
+    /// The time the message was recorded, in Unix seconds.
+    pub time: f64,
+    /// The serialized message.
+    pub data: Vec<u8>,
+}
+
+/// A reader of a bag's messages, in the order they were written.
+#[derive(Debug)]
+pub struct Reader<R: Read> {
+    reader: R,
+    chunk: Option<Cursor<Vec<u8>>>,
+    connections: HashMap<u32, (String, String)>,
+}
+
+impl Reader<BufReader<File>> {
+    /// Opens the bag at a path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::rosbag::Reader;
+    /// let reader = Reader::from_path("run.bag").unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Reader<BufReader<File>>> {
+        Reader::new(BufReader::new(try!(File::open(path))))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a new reader and checks the bag's version.
+    pub fn new(mut reader: R) -> Result<Reader<R>> {
+        let mut magic = [0; 13];
+        try!(reader.read_exact(&mut magic));
+        if &magic[..] != MAGIC {
+            return Err(Error::Rosbag("not a version 2.0 bag".to_string()));
+        }
+        Ok(Reader {
+            reader: reader,
+            chunk: None,
+            connections: HashMap::new(),
+        })
+    }
+
+    /// Reads the next message, or returns `None` at the end of the bag.
+    pub fn read_message(&mut self) -> Result<Option<Message>> {
+        loop {
+            let record = match self.chunk.take() {
+                Some(mut chunk) => {
+                    match try!(read_record(&mut chunk)) {
+                        Some(record) => {
+                            self.chunk = Some(chunk);
+                            record
+                        }
+                        None => continue,
+                    }
+                }
+                None => {
+                    match try!(read_record(&mut self.reader)) {
+                        Some(record) => record,
+                        None => return Ok(None),
+                    }
+                }
+            };
+            let (header, data) = record;
+            match try!(field(&header, "op")).first() {
+                Some(&0x02) => {
+                    let connection = try!(u32_field(&header, "conn"));
+                    let time = try!(field(&header, "time"));
+                    if time.len() != 8 {
+                        return Err(Error::Rosbag("a message's time is not eight bytes"
+                                                     .to_string()));
+                    }
+                    let (topic, message_type) = match self.connections.get(&connection) {
+                        Some(&(ref topic, ref message_type)) => {
+                            (topic.clone(), message_type.clone())
+                        }
+                        None => {
+                            return Err(Error::Rosbag(format!("a message is on unknown \
+                                                              connection {}",
+                                                             connection)))
+                        }
+                    };
+                    return Ok(Some(Message {
+                        topic: topic,
+                        message_type: message_type,
+                        time: LittleEndian::read_u32(&time[..4]) as f64 +
+                              LittleEndian::read_u32(&time[4..]) as f64 * 1e-9,
+                        data: data,
+                    }));
+                }
+                Some(&0x05) => {
+                    let compression = try!(string_field(&header, "compression"));
+                    if compression != "none" {
+                        return Err(Error::Rosbag(format!("{} chunks can't be read, run rosbag \
+                                                          decompress on the bag first",
+                                                         compression)));
+                    }
+                    self.chunk = Some(Cursor::new(data));
+                }
+                Some(&0x07) => {
+                    let connection = try!(u32_field(&header, "conn"));
+                    let topic = try!(string_field(&header, "topic"));
+                    let message_type = try!(string_field(&try!(read_header(&data)), "type"));
+                    let _ = self.connections.insert(connection, (topic, message_type));
+                }
+                Some(_) => {}
+                None => return Err(Error::Rosbag("a record has an empty op".to_string())),
+            }
+        }
+    }
+}
+
+/// Reads the point clouds from a bag.
+#[derive(Debug)]
+pub struct RosbagSource<R: Read> {
+    reader: Reader<R>,
+    topic: Option<String>,
+    points: Vec<Point>,
+}
+
+impl RosbagSource<BufReader<File>> {
+    /// Opens the bag at a path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::rosbag::{RosbagSource, RosbagSourceConfig};
+    /// let source = RosbagSource::from_path("run.bag", &RosbagSourceConfig::default()).unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P,
+                                     config: &RosbagSourceConfig)
+                                     -> Result<RosbagSource<BufReader<File>>> {
+        Ok(RosbagSource::new(try!(Reader::from_path(path)), config))
+    }
+}
+
+impl<R: Read> RosbagSource<R> {
+    /// Creates a new source around a bag reader.
+    pub fn new(reader: Reader<R>, config: &RosbagSourceConfig) -> RosbagSource<R> {
+        RosbagSource {
+            reader: reader,
+            topic: config.topic.clone(),
+            points: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read> PointSource for RosbagSource<R> {
+    fn source(&mut self, want: usize) -> Result<Option<Vec<Point>>> {
+        while self.points.len() < want {
+            match try!(self.reader.read_message()) {
+                Some(message) => {
+                    if message.message_type == POINT_CLOUD &&
+                       self.topic.as_ref().map_or(true, |topic| *topic == message.topic) {
+                        self.points.extend(try!(read_point_cloud(&message.data)));
+                    }
+                }
+                None => break,
+            }
+        }
+        PointSource::source(&mut self.points, want)
+    }
+}
+
+/// Reads the odometry in a bag into a trajectory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use georef::rosbag::{self, Reader, RosbagTrajectoryConfig};
+/// let mut reader = Reader::from_path("run.bag").unwrap();
+/// let imu_gnss = rosbag::read_imu_gnss(&mut reader, &RosbagTrajectoryConfig::default()).unwrap();
+/// ```
+pub fn read_imu_gnss<R: Read>(reader: &mut Reader<R>,
+                              config: &RosbagTrajectoryConfig)
+                              -> Result<ImuGnss> {
+    let origin = config.origin;
+    let frame = LocalFrame::new(origin.latitude.to_radians(),
+                                origin.longitude.to_radians(),
+                                origin.height,
+                                Ellipsoid::wgs84());
+    let mut points = Vec::new();
+    while let Some(message) = try!(reader.read_message()) {
+        if message.message_type != ODOMETRY ||
+           config.topic.as_ref().map_or(false, |topic| *topic != message.topic) {
+            continue;
+        }
+        let mut data = Cursor::new(message.data);
+        let time = try!(read_stamp_header(&mut data));
+        let _ = try!(read_string(&mut data));
+        let mut values = [0.0; 7];
+        for value in &mut values {
+            *value = try!(data.read_f64::<LittleEndian>());
+        }
+        let ecef = frame.unproject(Vec3::new(values[0], values[1], values[2]));
+        let (latitude, longitude, height) = ecef_to_geodetic(ecef.x, ecef.y, ecef.z);
+        let orientation = try!(Quaternion::new(values[6], values[3], values[4], values[5])
+                                   .ok_or_else(|| {
+                                       Error::Rosbag(format!("odometry at {} has an invalid \
+                                                              orientation",
+                                                             time))
+                                   }));
+        let (roll, pitch, yaw) = orientation.rpy();
+        points.push(ImuGnssPoint {
+            time: time,
+            latitude: latitude,
+            longitude: longitude,
+            height: height,
+            roll: roll,
+            pitch: pitch,
+            yaw: yaw,
+            orientation: Some(orientation),
+            ..Default::default()
+        });
+    }
+    ImuGnss::new(points)
+}
+
+/// Reads a serialized `sensor_msgs/PointCloud2` into points.
+pub fn read_point_cloud(data: &[u8]) -> Result<Vec<Point>> {
+    let mut cursor = Cursor::new(data);
+    let stamp = try!(read_stamp_header(&mut cursor));
+    let height = try!(cursor.read_u32::<LittleEndian>()) as usize;
+    let width = try!(cursor.read_u32::<LittleEndian>()) as usize;
+    let mut fields = HashMap::new();
+    for _ in 0..try!(cursor.read_u32::<LittleEndian>()) {
+        let name = try!(read_string(&mut cursor));
+        let offset = try!(cursor.read_u32::<LittleEndian>()) as usize;
+        let datatype = try!(cursor.read_u8());
+        let _ = try!(cursor.read_u32::<LittleEndian>());
+        let _ = fields.insert(name, (offset, datatype));
+    }
+    let big_endian = try!(cursor.read_u8()) != 0;
+    let point_step = try!(cursor.read_u32::<LittleEndian>()) as usize;
+    let row_step = try!(cursor.read_u32::<LittleEndian>()) as usize;
+    let length = try!(cursor.read_u32::<LittleEndian>()) as usize;
+    let start = cursor.position() as usize;
+    let fits = |a: usize, b: usize, limit: usize| a.checked_mul(b).map_or(false, |n| n <= limit);
+    if point_step == 0 || data.len() - start < length || !fits(height, row_step, length) ||
+       !fits(width, point_step, row_step) {
+        return Err(Error::Rosbag("a point cloud is shorter than its dimensions".to_string()));
+    }
+    let data = &data[start..start + length];
+    let coordinate = |name: &str| {
+        fields.get(name).cloned().ok_or_else(|| {
+            Error::Rosbag(format!("a point cloud has no {} field", name))
+        })
+    };
+    let (x, y, z) = (try!(coordinate("x")), try!(coordinate("y")), try!(coordinate("z")));
+    let intensity = fields.get("intensity").cloned();
+    let (time, time_scale) = match (fields.get("time"), fields.get("t")) {
+        (Some(&time), _) => (Some(time), 1.0),
+        (None, Some(&t)) => (Some(t), 1e-9),
+        (None, None) => (None, 0.0),
+    };
+    let mut points = Vec::with_capacity(height * width);
+    for row in 0..height {
+        for column in 0..width {
+            let bytes = &data[row * row_step + column * point_step..][..point_step];
+            let value = |(offset, datatype): (usize, u8)| {
+                read_value(bytes, offset, datatype, big_endian)
+            };
+            let (px, py, pz) = (try!(value(x)), try!(value(y)), try!(value(z)));
+            if !(px.is_finite() && py.is_finite() && pz.is_finite()) {
+                continue;
+            }
+            let offset = match time {
+                Some(time) => try!(value(time)) * time_scale,
+                None => 0.0,
+            };
+            let intensity = match intensity {
+                Some(intensity) => {
+                    Some(try!(value(intensity)).max(0.0).min(u16::MAX as f64) as u16)
+                }
+                None => None,
+            };
+            points.push(Point {
+                x: px,
+                y: py,
+                z: pz,
+                gps_time: Some(stamp + offset),
+                intensity: intensity,
+                ..Default::default()
+            });
+        }
+    }
+    Ok(points)
+}
+
+/// Reads a record's header and data, or returns `None` at the end of the bag.
+fn read_record<R: Read>(reader: &mut R) -> Result<Option<(HashMap<String, Vec<u8>>, Vec<u8>)>> {
+    let header_length = match reader.read_u32::<LittleEndian>() {
+        Ok(length) => length as usize,
+        Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let header = try!(read_bytes(reader, header_length));
+    let data_length = try!(reader.read_u32::<LittleEndian>()) as usize;
+    let data = try!(read_bytes(reader, data_length));
+    Ok(Some((try!(read_header(&header)), data)))
+}
+
+/// Reads a number of bytes, which may come from a corrupt length, so the buffer only grows as
+/// the bytes are actually read.
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let _ = try!(reader.take(len as u64).read_to_end(&mut bytes));
+    if bytes.len() < len {
+        return Err(Error::Rosbag("the bag is truncated".to_string()));
+    }
+    Ok(bytes)
+}
+
+/// Reads the name=value fields of a record header.
+fn read_header(mut bytes: &[u8]) -> Result<HashMap<String, Vec<u8>>> {
+    let mut fields = HashMap::new();
+    while !bytes.is_empty() {
+        let length = try!(bytes.read_u32::<LittleEndian>()) as usize;
+        if bytes.len() < length {
+            return Err(Error::Rosbag("a record header is truncated".to_string()));
+        }
+        let (field, rest) = bytes.split_at(length);
+        let equals = try!(field.iter().position(|&b| b == b'=').ok_or_else(|| {
+            Error::Rosbag("a record header field has no =".to_string())
+        }));
+        let _ = fields.insert(String::from_utf8_lossy(&field[..equals]).into_owned(),
+                              field[equals + 1..].to_vec());
+        bytes = rest;
+    }
+    Ok(fields)
+}
+
+fn field<'a>(header: &'a HashMap<String, Vec<u8>>, name: &str) -> Result<&'a [u8]> {
+    header.get(name)
+          .map(|value| &value[..])
+          .ok_or_else(|| Error::Rosbag(format!("a record header has no {} field", name)))
+}
+
+fn u32_field(header: &HashMap<String, Vec<u8>>, name: &str) -> Result<u32> {
+    let mut value = try!(field(header, name));
+    Ok(try!(value.read_u32::<LittleEndian>()))
+}
+
+fn string_field(header: &HashMap<String, Vec<u8>>, name: &str) -> Result<String> {
+    Ok(String::from_utf8_lossy(try!(field(header, name))).into_owned())
+}
+
+/// Reads a length-prefixed string from a serialized message.
+fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+    let len = try!(reader.read_u32::<LittleEndian>()) as usize;
+    let bytes = try!(read_bytes(reader, len));
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Reads a serialized `std_msgs/Header` and returns its stamp, in Unix seconds.
+fn read_stamp_header<R: Read>(reader: &mut R) -> Result<f64> {
+    let _ = try!(reader.read_u32::<LittleEndian>());
+    let seconds = try!(reader.read_u32::<LittleEndian>());
+    let nanoseconds = try!(reader.read_u32::<LittleEndian>());
+    let _ = try!(read_string(reader));
+    Ok(seconds as f64 + nanoseconds as f64 * 1e-9)
+}
+
+/// Reads one field of a point, by its `sensor_msgs/PointField` datatype.
+fn read_value(bytes: &[u8], offset: usize, datatype: u8, big_endian: bool) -> Result<f64> {
+    let size = match datatype {
+        1 | 2 => 1,
+        3 | 4 => 2,
+        5 | 6 | 7 => 4,
+        8 => 8,
+        _ => return Err(Error::Rosbag(format!("unknown point field datatype: {}", datatype))),
+    };
+    if bytes.len() < offset + size {
+        return Err(Error::Rosbag("a point field is past the end of its point".to_string()));
+    }
+    let bytes = &bytes[offset..offset + size];
+    Ok(if big_endian {
+        decode::<BigEndian>(bytes, datatype)
+    } else {
+        decode::<LittleEndian>(bytes, datatype)
+    })
+}
+
+fn decode<B: ByteOrder>(bytes: &[u8], datatype: u8) -> f64 {
+    match datatype {
+        1 => bytes[0] as i8 as f64,
+        2 => bytes[0] as f64,
+        3 => B::read_i16(bytes) as f64,
+        4 => B::read_u16(bytes) as f64,
+        5 => B::read_i32(bytes) as f64,
+        6 => B::read_u32(bytes) as f64,
+        7 => B::read_f32(bytes) as f64,
+        _ => B::read_f64(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    use trajectory::Trajectory;
+
+    fn header_field(name: &str, value: &[u8]) -> Vec<u8> {
+        let mut field = Vec::new();
+        field.write_u32::<LittleEndian>((name.len() + 1 + value.len()) as u32).unwrap();
+        field.extend(name.as_bytes());
+        field.push(b'=');
+        field.extend(value);
+        field
+    }
+
+    fn record(fields: &[Vec<u8>], data: &[u8]) -> Vec<u8> {
+        let header: Vec<u8> = fields.iter().flat_map(|field| field.iter().cloned()).collect();
+        let mut record = Vec::new();
+        record.write_u32::<LittleEndian>(header.len() as u32).unwrap();
+        record.extend(header);
+        record.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+        record.extend(data);
+        record
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<LittleEndian>(s.len() as u32).unwrap();
+        bytes.extend(s.as_bytes());
+        bytes
+    }
+
+    fn stamp_header(seconds: u32, nanoseconds: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<LittleEndian>(0).unwrap();
+        bytes.write_u32::<LittleEndian>(seconds).unwrap();
+        bytes.write_u32::<LittleEndian>(nanoseconds).unwrap();
+        bytes.extend(string("frame"));
+        bytes
+    }
+
+    fn connection(id: u32, topic: &str, message_type: &str) -> Vec<u8> {
+        record(&[header_field("op", &[0x07]),
+                 header_field("conn", &[id as u8, 0, 0, 0]),
+                 header_field("topic", topic.as_bytes())],
+               &header_field("type", message_type.as_bytes()))
+    }
+
+    fn message(id: u32, seconds: u32, data: &[u8]) -> Vec<u8> {
+        let mut time = Vec::new();
+        time.write_u32::<LittleEndian>(seconds).unwrap();
+        time.write_u32::<LittleEndian>(0).unwrap();
+        record(&[header_field("op", &[0x02]),
+                 header_field("conn", &[id as u8, 0, 0, 0]),
+                 header_field("time", &time)],
+               data)
+    }
+
+    fn odometry(seconds: u32, east: f64) -> Vec<u8> {
+        let mut data = stamp_header(seconds, 0);
+        data.extend(string("base_link"));
+        for &value in &[east, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0] {
+            data.write_f64::<LittleEndian>(value).unwrap();
+        }
+        data
+    }
+
+    fn point_cloud() -> Vec<u8> {
+        let mut data = stamp_header(10, 500000000);
+        data.write_u32::<LittleEndian>(1).unwrap();
+        data.write_u32::<LittleEndian>(2).unwrap();
+        data.write_u32::<LittleEndian>(4).unwrap();
+        for (i, name) in ["x", "y", "z", "time"].iter().enumerate() {
+            data.extend(string(name));
+            data.write_u32::<LittleEndian>(4 * i as u32).unwrap();
+            data.push(7);
+            data.write_u32::<LittleEndian>(1).unwrap();
+        }
+        data.push(0);
+        data.write_u32::<LittleEndian>(16).unwrap();
+        data.write_u32::<LittleEndian>(32).unwrap();
+        data.write_u32::<LittleEndian>(32).unwrap();
+        for &value in &[1.0, 2.0, 3.0, 0.25, ::std::f32::NAN, 0.0, 0.0, 0.0] {
+            data.write_f32::<LittleEndian>(value).unwrap();
+        }
+        data.push(0);
+        data
+    }
+
+    fn bag() -> Vec<u8> {
+        let mut chunk = Vec::new();
+        chunk.extend(connection(0, "/odom", ODOMETRY));
+        chunk.extend(connection(1, "/points", POINT_CLOUD));
+        chunk.extend(message(0, 10, &odometry(10, 0.0)));
+        chunk.extend(message(1, 10, &point_cloud()));
+        chunk.extend(message(0, 11, &odometry(11, 10.0)));
+        let mut bag = MAGIC.to_vec();
+        bag.extend(record(&[header_field("op", &[0x05]), header_field("compression", b"none")],
+                          &chunk));
+        bag
+    }
+
+    #[test]
+    fn points() {
+        let reader = Reader::new(Cursor::new(bag())).unwrap();
+        let mut source = RosbagSource::new(reader, &RosbagSourceConfig::default());
+        let points = source.source(10).unwrap().unwrap();
+        assert_eq!(1, points.len());
+        assert_eq!((1.0, 2.0, 3.0), (points[0].x, points[0].y, points[0].z));
+        assert_eq!(Some(10.75), points[0].gps_time);
+        assert!(source.source(10).unwrap().is_none());
+    }
+
+    #[test]
+    fn odometry_trajectory() {
+        let mut reader = Reader::new(Cursor::new(bag())).unwrap();
+        let config = RosbagTrajectoryConfig {
+            topic: Some("/odom".to_string()),
+            origin: LocalOrigin { latitude: 0.0, longitude: 0.0, height: 0.0 },
+        };
+        let imu_gnss = read_imu_gnss(&mut reader, &config).unwrap();
+        let pose = imu_gnss.pose_at(10.5, &Default::default()).unwrap();
+        assert!((pose.longitude.to_degrees() * 111319.49 - 5.0).abs() < 1e-3);
+        assert!(pose.latitude.abs() < 1e-12);
+    }
+
+    #[test]
+    fn bad_bags() {
+        assert!(Reader::new(Cursor::new(b"#ROSBAG V1.2\n".to_vec())).is_err());
+        let mut bag = MAGIC.to_vec();
+        bag.extend(record(&[header_field("op", &[0x05]), header_field("compression", b"bz2")],
+                          &[]));
+        assert!(Reader::new(Cursor::new(bag)).unwrap().read_message().is_err());
+        let mut bag = MAGIC.to_vec();
+        bag.write_u32::<LittleEndian>(u32::max_value()).unwrap();
+        assert!(Reader::new(Cursor::new(bag)).unwrap().read_message().is_err());
+    }
+
+    #[test]
+    fn bad_point_clouds() {
+        // The height and width follow the stamp header, and the point step comes before the row
+        // step, the data length, 32 bytes of data, and the dense flag.
+        let mut cloud = point_cloud();
+        let point_step = cloud.len() - 45;
+        (&mut cloud[21..25]).write_u32::<LittleEndian>(u32::max_value()).unwrap();
+        (&mut cloud[25..29]).write_u32::<LittleEndian>(u32::max_value()).unwrap();
+        assert!(read_point_cloud(&cloud).is_err());
+        let mut cloud = point_cloud();
+        (&mut cloud[25..29]).write_u32::<LittleEndian>(u32::max_value()).unwrap();
+        (&mut cloud[point_step..point_step + 4]).write_u32::<LittleEndian>(0).unwrap();
+        assert!(read_point_cloud(&cloud).is_err());
+    }
+}
@@ -6,7 +6,8 @@ use nalgebra::{Rot3, Vec3};
 
 use {Error, Result};
 
-#[derive(Debug)]
+/// The order that roll, pitch, and yaw rotations are combined in.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct RotationOrder {
     first: RotationMatrix,
     second: RotationMatrix,
@@ -36,7 +37,61 @@ impl Default for RotationOrder {
     }
 }
 
-#[derive(Debug)]
+/// How a trajectory's three attitude angles are defined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AngleConvention {
+    /// Roll, pitch, and heading, combined by the configured rotation order.
+    RollPitchHeading,
+    /// The photogrammetric omega, phi, and kappa.
+    ///
+    /// These rotate the image frame (x right, y forward, z up, with the camera looking down -z)
+    /// into the mapping frame as `R1(omega) R2(phi) R3(kappa)`, where each `Rn` is a
+    /// right-handed, counterclockwise rotation about axis n.
+    OmegaPhiKappa,
+}
+
+impl Default for AngleConvention {
+    fn default() -> AngleConvention {
+        AngleConvention::RollPitchHeading
+    }
+}
+
+impl AngleConvention {
+    /// Converts three angles, in radians, into roll, pitch, and yaw.
+    ///
+    /// Roll, pitch, and heading pass straight through. Omega, phi, and kappa become the roll,
+    /// pitch, and yaw that describe the same rotation under the default rotation order,
+    /// `R3(yaw) R2(pitch) R1(roll)`.
+    pub fn roll_pitch_yaw(self, a: f64, b: f64, c: f64) -> (f64, f64, f64) {
+        match self {
+            AngleConvention::RollPitchHeading => (a, b, c),
+            AngleConvention::OmegaPhiKappa => {
+                let (sin_omega, cos_omega) = a.sin_cos();
+                let (sin_phi, cos_phi) = b.sin_cos();
+                let (sin_kappa, cos_kappa) = c.sin_cos();
+                let r00 = cos_phi * cos_kappa;
+                let r10 = cos_omega * sin_kappa + sin_omega * sin_phi * cos_kappa;
+                let r20 = sin_omega * sin_kappa - cos_omega * sin_phi * cos_kappa;
+                let r21 = sin_omega * cos_kappa + cos_omega * sin_phi * sin_kappa;
+                let r22 = cos_omega * cos_phi;
+                (r21.atan2(r22), (-r20).max(-1.0).min(1.0).asin(), r10.atan2(r00))
+            }
+        }
+    }
+}
+
+impl FromStr for AngleConvention {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<AngleConvention> {
+        match s {
+            "roll_pitch_heading" => Ok(AngleConvention::RollPitchHeading),
+            "omega_phi_kappa" => Ok(AngleConvention::OmegaPhiKappa),
+            _ => Err(Error::ParseRotate(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct RotationMatrix {
     type_: RotationMatrixType,
     negative: bool,
@@ -54,7 +109,7 @@ impl RotationMatrix {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum RotationMatrixType {
     R1,
     R2,
@@ -71,7 +126,7 @@ impl RotationMatrixType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum RotationMatrixAngle {
     Roll,
     Pitch,
@@ -133,6 +188,8 @@ impl FromStr for RotationMatrixAngle {
 mod tests {
     use super::*;
 
+    use nalgebra::Vec3;
+
     #[test]
     fn paces() {
         assert!("r1(roll)".parse::<RotationMatrix>().is_ok());
@@ -145,4 +202,23 @@ mod tests {
         assert!("r1(rollz)".parse::<RotationMatrix>().is_err());
         assert!("r1(rol)".parse::<RotationMatrix>().is_err());
     }
+
+    #[test]
+    fn omega_phi_kappa() {
+        let (omega, phi, kappa) = (0.1, -0.2, 2.5);
+        let (roll, pitch, yaw) = AngleConvention::OmegaPhiKappa.roll_pitch_yaw(omega, phi, kappa);
+        let expected = RotationOrder::new("r1(roll)", "r2(pitch)", "r3(yaw)")
+                           .unwrap()
+                           .rot3(omega, phi, kappa);
+        let actual = RotationOrder::default().rot3(roll, pitch, yaw);
+        for v in &[Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+            let difference = expected * *v - actual * *v;
+            assert!(difference.x.abs() < 1e-12);
+            assert!(difference.y.abs() < 1e-12);
+            assert!(difference.z.abs() < 1e-12);
+        }
+        assert_eq!((0.1, 0.2, 0.3),
+                   AngleConvention::RollPitchHeading.roll_pitch_yaw(0.1, 0.2, 0.3));
+        assert!("opk".parse::<AngleConvention>().is_err());
+    }
 }
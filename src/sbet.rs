@@ -0,0 +1,400 @@
+//! Read Applanix smoothed best estimate of trajectory (SBET) files.
+//!
+//! SBET files are a headerless sequence of records, each made up of seventeen little-endian
+//! doubles. Post-processing software also writes these files with an `.out` extension.
+//!
+//! The companion smrmsg files hold the estimated accuracy of the trajectory, usually at a lower
+//! rate, as a headerless sequence of ten little-endian doubles.
+
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use Result;
+use imu_gnss::{Accuracy, ImuGnss, ImuGnssPoint};
+
+/// Radians per arc minute.
+const ARC_MINUTE: f64 = PI / 10800.0;
+
+/// A single SBET record.
+///
+/// Angles are in radians and linear quantities are in meters.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Record {
+    /// The gps time, in seconds of the week.
+    pub time: f64,
+    /// The latitude.
+    pub latitude: f64,
+    /// The longitude.
+    pub longitude: f64,
+    /// The ellipsoidal height.
+    pub altitude: f64,
+    /// The velocity in the x direction.
+    pub x_velocity: f64,
+    /// The velocity in the y direction.
+    pub y_velocity: f64,
+    /// The velocity in the z direction.
+    pub z_velocity: f64,
+    /// The roll.
+    pub roll: f64,
+    /// The pitch.
+    pub pitch: f64,
+    /// The platform heading, which is relative to the wander angle.
+    pub platform_heading: f64,
+    /// The wander angle.
+    pub wander_angle: f64,
+    /// The acceleration in the x direction.
+    pub x_acceleration: f64,
+    /// The acceleration in the y direction.
+    pub y_acceleration: f64,
+    /// The acceleration in the z direction.
+    pub z_acceleration: f64,
+    /// The angular rate about the x axis.
+    pub x_angular_rate: f64,
+    /// The angular rate about the y axis.
+    pub y_angular_rate: f64,
+    /// The angular rate about the z axis.
+    pub z_angular_rate: f64,
+}
+
+impl Record {
+    /// Returns the true heading of this record, i.e. the platform heading minus the wander angle.
+    pub fn heading(&self) -> f64 {
+        self.platform_heading - self.wander_angle
+    }
+
+    /// Returns the rates of change of roll, pitch, and heading, in radians per second.
+    ///
+    /// The angular rates are about the body axes, so they're converted to attitude rates with the
+    /// usual z-y-x kinematics. The wander angle changes slowly enough to be left out.
+    pub fn attitude_rate(&self) -> [f64; 3] {
+        let (sin_roll, cos_roll) = self.roll.sin_cos();
+        let (p, q, r) = (self.x_angular_rate, self.y_angular_rate, self.z_angular_rate);
+        let heading_component = q * sin_roll + r * cos_roll;
+        [p + heading_component * self.pitch.tan(),
+         q * cos_roll - r * sin_roll,
+         heading_component / self.pitch.cos()]
+    }
+}
+
+impl From<Record> for ImuGnssPoint {
+    fn from(record: Record) -> ImuGnssPoint {
+        ImuGnssPoint {
+            time: record.time,
+            latitude: record.latitude,
+            longitude: record.longitude,
+            height: record.altitude,
+            roll: record.roll,
+            pitch: record.pitch,
+            yaw: record.heading(),
+            attitude_rate: Some(record.attitude_rate()),
+            orientation: None,
+            accuracy: None,
+            utm: None,
+        }
+    }
+}
+
+/// A single smrmsg record, i.e. the root-mean-square errors of the trajectory at one time.
+///
+/// Positions are in meters, velocities are in meters per second, and attitudes are in arc
+/// minutes, as they are stored in the file.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SmrmsgRecord {
+    /// The gps time, in seconds of the week.
+    pub time: f64,
+    /// The north position error.
+    pub north_position: f64,
+    /// The east position error.
+    pub east_position: f64,
+    /// The down position error.
+    pub down_position: f64,
+    /// The north velocity error.
+    pub north_velocity: f64,
+    /// The east velocity error.
+    pub east_velocity: f64,
+    /// The down velocity error.
+    pub down_velocity: f64,
+    /// The roll error.
+    pub roll: f64,
+    /// The pitch error.
+    pub pitch: f64,
+    /// The heading error.
+    pub heading: f64,
+}
+
+impl From<SmrmsgRecord> for Accuracy {
+    fn from(record: SmrmsgRecord) -> Accuracy {
+        Accuracy {
+            north: record.north_position,
+            east: record.east_position,
+            down: record.down_position,
+            roll: record.roll * ARC_MINUTE,
+            pitch: record.pitch * ARC_MINUTE,
+            heading: record.heading * ARC_MINUTE,
+        }
+    }
+}
+
+/// An SBET reader.
+#[derive(Debug)]
+pub struct Reader<R: Read> {
+    reader: R,
+}
+
+impl Reader<BufReader<File>> {
+    /// Opens a reader for the SBET file at the given path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::sbet::Reader;
+    /// let reader = Reader::from_path("trajectory.sbet").unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Reader<BufReader<File>>> {
+        Ok(Reader::new(BufReader::new(try!(File::open(path)))))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a new reader around something that implements `Read`.
+    pub fn new(reader: R) -> Reader<R> {
+        Reader { reader: reader }
+    }
+
+    /// Reads the next record, or returns `None` at the end of the file.
+    pub fn read_record(&mut self) -> Result<Option<Record>> {
+        let time = match self.reader.read_f64::<LittleEndian>() {
+            Ok(time) => time,
+            Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(Record {
+            time: time,
+            latitude: try!(self.reader.read_f64::<LittleEndian>()),
+            longitude: try!(self.reader.read_f64::<LittleEndian>()),
+            altitude: try!(self.reader.read_f64::<LittleEndian>()),
+            x_velocity: try!(self.reader.read_f64::<LittleEndian>()),
+            y_velocity: try!(self.reader.read_f64::<LittleEndian>()),
+            z_velocity: try!(self.reader.read_f64::<LittleEndian>()),
+            roll: try!(self.reader.read_f64::<LittleEndian>()),
+            pitch: try!(self.reader.read_f64::<LittleEndian>()),
+            platform_heading: try!(self.reader.read_f64::<LittleEndian>()),
+            wander_angle: try!(self.reader.read_f64::<LittleEndian>()),
+            x_acceleration: try!(self.reader.read_f64::<LittleEndian>()),
+            y_acceleration: try!(self.reader.read_f64::<LittleEndian>()),
+            z_acceleration: try!(self.reader.read_f64::<LittleEndian>()),
+            x_angular_rate: try!(self.reader.read_f64::<LittleEndian>()),
+            y_angular_rate: try!(self.reader.read_f64::<LittleEndian>()),
+            z_angular_rate: try!(self.reader.read_f64::<LittleEndian>()),
+        }))
+    }
+
+    /// Reads all remaining records into an IMU/GNSS trajectory.
+    pub fn read_imu_gnss(&mut self) -> Result<ImuGnss> {
+        let mut points = Vec::new();
+        while let Some(record) = try!(self.read_record()) {
+            points.push(record.into());
+        }
+        ImuGnss::new(points)
+    }
+
+    /// Reads all remaining records into an IMU/GNSS trajectory, attaching the accuracy from a
+    /// smrmsg file to each record.
+    ///
+    /// The smrmsg records are linearly interpolated to each SBET record's time. SBET records
+    /// before the first or after the last smrmsg record get the nearest smrmsg record's accuracy.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::sbet::{Reader, SmrmsgReader};
+    /// let mut smrmsg = SmrmsgReader::from_path("smrmsg.out").unwrap();
+    /// let imu_gnss = Reader::from_path("sbet.out")
+    ///                    .unwrap()
+    ///                    .read_imu_gnss_with_smrmsg(&mut smrmsg)
+    ///                    .unwrap();
+    /// ```
+    pub fn read_imu_gnss_with_smrmsg<S: Read>(&mut self,
+                                              smrmsg: &mut SmrmsgReader<S>)
+                                              -> Result<ImuGnss> {
+        let mut accuracies = Vec::new();
+        while let Some(record) = try!(smrmsg.read_record()) {
+            accuracies.push(record);
+        }
+        let mut points = Vec::new();
+        let mut i = 0;
+        while let Some(record) = try!(self.read_record()) {
+            let mut point: ImuGnssPoint = record.into();
+            while i + 1 < accuracies.len() && accuracies[i + 1].time <= point.time {
+                i += 1;
+            }
+            point.accuracy = match (accuracies.get(i), accuracies.get(i + 1)) {
+                (Some(&before), Some(&after)) if before.time < point.time => {
+                    let factor = (point.time - before.time) / (after.time - before.time);
+                    Some(Accuracy::from(before).interpolate(&after.into(), factor))
+                }
+                (Some(&before), _) => Some(before.into()),
+                (None, _) => None,
+            };
+            points.push(point);
+        }
+        ImuGnss::new(points)
+    }
+}
+
+/// Reads records one at a time, e.g. for a `WindowedTrajectory`.
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Result<Record>> {
+        match self.read_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// A smrmsg reader.
+#[derive(Debug)]
+pub struct SmrmsgReader<R: Read> {
+    reader: R,
+}
+
+impl SmrmsgReader<BufReader<File>> {
+    /// Opens a reader for the smrmsg file at the given path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::sbet::SmrmsgReader;
+    /// let reader = SmrmsgReader::from_path("smrmsg.out").unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<SmrmsgReader<BufReader<File>>> {
+        Ok(SmrmsgReader::new(BufReader::new(try!(File::open(path)))))
+    }
+}
+
+impl<R: Read> SmrmsgReader<R> {
+    /// Creates a new reader around something that implements `Read`.
+    pub fn new(reader: R) -> SmrmsgReader<R> {
+        SmrmsgReader { reader: reader }
+    }
+
+    /// Reads the next record, or returns `None` at the end of the file.
+    pub fn read_record(&mut self) -> Result<Option<SmrmsgRecord>> {
+        let time = match self.reader.read_f64::<LittleEndian>() {
+            Ok(time) => time,
+            Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(SmrmsgRecord {
+            time: time,
+            north_position: try!(self.reader.read_f64::<LittleEndian>()),
+            east_position: try!(self.reader.read_f64::<LittleEndian>()),
+            down_position: try!(self.reader.read_f64::<LittleEndian>()),
+            north_velocity: try!(self.reader.read_f64::<LittleEndian>()),
+            east_velocity: try!(self.reader.read_f64::<LittleEndian>()),
+            down_velocity: try!(self.reader.read_f64::<LittleEndian>()),
+            roll: try!(self.reader.read_f64::<LittleEndian>()),
+            pitch: try!(self.reader.read_f64::<LittleEndian>()),
+            heading: try!(self.reader.read_f64::<LittleEndian>()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    #[test]
+    fn read_records() {
+        let mut bytes = Vec::new();
+        for i in 0..34 {
+            bytes.write_f64::<LittleEndian>(i as f64).unwrap();
+        }
+        let mut reader = Reader::new(Cursor::new(bytes));
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(0.0, record.time);
+        assert_eq!(16.0, record.z_angular_rate);
+        assert_eq!(-1.0, record.heading());
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(17.0, record.time);
+        assert!(reader.read_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn iterate() {
+        let mut bytes = Vec::new();
+        for i in 0..51 {
+            bytes.write_f64::<LittleEndian>(i as f64).unwrap();
+        }
+        let times: Vec<f64> = Reader::new(Cursor::new(bytes))
+                                  .map(|record| record.unwrap().time)
+                                  .collect();
+        assert_eq!(vec![0.0, 17.0, 34.0], times);
+    }
+
+    #[test]
+    fn attitude_rate() {
+        let record = Record {
+            x_angular_rate: 0.1,
+            y_angular_rate: 0.2,
+            z_angular_rate: 0.3,
+            ..Default::default()
+        };
+        assert_eq!([0.1, 0.2, 0.3], record.attitude_rate());
+        let rolled = Record { roll: PI / 2.0, ..record };
+        let rate = rolled.attitude_rate();
+        assert!((rate[1] + 0.3).abs() < 1e-12);
+        assert!((rate[2] - 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn truncated_record() {
+        let mut bytes = Vec::new();
+        for i in 0..3 {
+            bytes.write_f64::<LittleEndian>(i as f64).unwrap();
+        }
+        assert!(Reader::new(Cursor::new(bytes)).read_record().is_err());
+    }
+
+    #[test]
+    fn read_with_smrmsg() {
+        let mut sbet = Vec::new();
+        for &time in &[0.0, 1.0, 1.5, 3.0] {
+            sbet.write_f64::<LittleEndian>(time).unwrap();
+            for _ in 1..17 {
+                sbet.write_f64::<LittleEndian>(0.0).unwrap();
+            }
+        }
+        let mut smrmsg = Vec::new();
+        for &(time, error) in &[(1.0, 0.1), (2.0, 0.3)] {
+            smrmsg.write_f64::<LittleEndian>(time).unwrap();
+            for _ in 1..10 {
+                smrmsg.write_f64::<LittleEndian>(error).unwrap();
+            }
+        }
+        let imu_gnss = Reader::new(Cursor::new(sbet))
+                           .read_imu_gnss_with_smrmsg(&mut SmrmsgReader::new(Cursor::new(smrmsg)))
+                           .unwrap();
+        let north: Vec<f64> = imu_gnss.points()
+                                      .iter()
+                                      .map(|p| p.accuracy.unwrap().north)
+                                      .collect();
+        assert_eq!(0.1, north[0]);
+        assert_eq!(0.1, north[1]);
+        assert!((north[2] - 0.2).abs() < 1e-12);
+        assert_eq!(0.3, north[3]);
+        let heading = imu_gnss.points()[0].accuracy.unwrap().heading;
+        assert!((heading - (0.1f64 / 60.0).to_radians()).abs() < 1e-15);
+    }
+}
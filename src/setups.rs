@@ -0,0 +1,215 @@
+//! Stop-and-go setups, i.e. a platform that holds still while it scans.
+//!
+//! A tripod scanner, or a mobile platform that stops to scan, doesn't need a trajectory: each
+//! setup has one pose, e.g. from a resection or a surveyed station, that holds for as long as it
+//! scans. A setups file lists each setup's pose and the gps times that it starts and stops
+//! scanning. Points get the pose of the setup that their time falls in, and points scanned between
+//! setups, while the platform moves, are outside of the trajectory, so the error policy decides
+//! what happens to them. A single setup without a start or stop time is a static scan.
+//!
+//! Latitude, longitude, roll, pitch, and heading are in degrees, and the height is ellipsoidal, in
+//! meters. Roll, pitch, and heading default to zero. Setups may be listed in any order, but must
+//! not overlap:
+//!
+//! ```toml
+//! [[setup]]
+//! start = 1000.0
+//! stop = 1060.0
+//! latitude = 61.2
+//! longitude = -149.9
+//! height = 100.0
+//! heading = 45.0
+//!
+//! [[setup]]
+//! start = 1090.0
+//! stop = 1150.0
+//! latitude = 61.2001
+//! longitude = -149.8995
+//! height = 100.4
+//! heading = 47.5
+//! ```
+
+use std::f64;
+use std::result;
+
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+use trajectory::{Interpolation, Pose, Trajectory};
+
+/// A decodable description of one setup.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct SetupConfig {
+    /// The gps time that this setup starts scanning. Defaults to the beginning of time.
+    pub start: Option<f64>,
+    /// The gps time that this setup stops scanning. Defaults to the end of time.
+    pub stop: Option<f64>,
+    /// The latitude, in degrees.
+    pub latitude: f64,
+    /// The longitude, in degrees.
+    pub longitude: f64,
+    /// The ellipsoidal height, in meters.
+    pub height: f64,
+    /// The roll, in degrees.
+    pub roll: Option<f64>,
+    /// The pitch, in degrees.
+    pub pitch: Option<f64>,
+    /// The heading, in degrees.
+    pub heading: Option<f64>,
+}
+
+/// A decodable list of setups.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct SetupsConfig {
+    /// The setups.
+    pub setup: Vec<SetupConfig>,
+}
+
+impl SetupsConfig {
+    /// Creates a new setups config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<SetupsConfig, toml::DecodeError> {
+        SetupsConfig::decode(&mut toml::Decoder::new(table))
+    }
+}
+
+/// The poses of a platform's setups, ordered by time.
+#[derive(Clone, Debug)]
+pub struct Setups {
+    setups: Vec<Setup>,
+}
+
+#[derive(Clone, Debug)]
+struct Setup {
+    start: f64,
+    stop: f64,
+    pose: Pose,
+}
+
+impl Setups {
+    /// Creates setups from their configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use georef::setups::{SetupConfig, Setups, SetupsConfig};
+    /// let setup = SetupConfig { height: 100.0, ..Default::default() };
+    /// let setups = Setups::new(&SetupsConfig { setup: vec![setup] }).unwrap();
+    /// ```
+    pub fn new(config: &SetupsConfig) -> Result<Setups> {
+        let invalid = |message: String| Error::InvalidField("setup", message);
+        if config.setup.is_empty() {
+            return Err(invalid("there are no setups".to_string()));
+        }
+        let mut setups = Vec::new();
+        for (i, setup) in config.setup.iter().enumerate() {
+            let start = setup.start.unwrap_or(f64::NEG_INFINITY);
+            let stop = setup.stop.unwrap_or(f64::INFINITY);
+            if !(start < stop) {
+                return Err(invalid(format!("setup {} must start before it stops", i + 1)));
+            }
+            let values = [setup.latitude,
+                          setup.longitude,
+                          setup.height,
+                          setup.roll.unwrap_or(0.0),
+                          setup.pitch.unwrap_or(0.0),
+                          setup.heading.unwrap_or(0.0)];
+            if values.iter().any(|value| !value.is_finite()) {
+                return Err(invalid(format!("setup {}'s pose must be finite", i + 1)));
+            }
+            setups.push(Setup {
+                start: start,
+                stop: stop,
+                pose: Pose {
+                    latitude: values[0].to_radians(),
+                    longitude: values[1].to_radians(),
+                    height: values[2],
+                    roll: values[3].to_radians(),
+                    pitch: values[4].to_radians(),
+                    yaw: values[5].to_radians(),
+                    ..Default::default()
+                },
+            });
+        }
+        setups.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+        for pair in setups.windows(2) {
+            if pair[0].stop > pair[1].start {
+                return Err(invalid(format!("the setups starting at {} and {} overlap",
+                                           pair[0].start,
+                                           pair[1].start)));
+            }
+        }
+        Ok(Setups { setups: setups })
+    }
+}
+
+/// Setups hold still, so any interpolation options are ignored.
+impl Trajectory for Setups {
+    fn pose_at(&self, time: f64, _: &Interpolation) -> Result<Pose> {
+        self.setups
+            .iter()
+            .find(|setup| setup.start <= time && time <= setup.stop)
+            .map(|setup| Pose { time: time, ..setup.pose })
+            .ok_or(Error::OutsideOfImuGnssRecords)
+    }
+
+    fn time_bounds(&self) -> Option<(f64, f64)> {
+        let start = self.setups[0].start;
+        let stop = self.setups[self.setups.len() - 1].stop;
+        if start.is_finite() && stop.is_finite() {
+            Some((start, stop))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use trajectory::Trajectory;
+
+    fn setup(start: f64, stop: f64, heading: f64) -> SetupConfig {
+        SetupConfig {
+            start: Some(start),
+            stop: Some(stop),
+            heading: Some(heading),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pose_by_time() {
+        let config = SetupsConfig { setup: vec![setup(20.0, 30.0, 90.0), setup(0.0, 10.0, 45.0)] };
+        let setups = Setups::new(&config).unwrap();
+        let pose = setups.pose_at(5.0, &Default::default()).unwrap();
+        assert_eq!(5.0, pose.time);
+        assert!((pose.yaw - 45f64.to_radians()).abs() < 1e-12);
+        let pose = setups.pose_at(30.0, &Default::default()).unwrap();
+        assert!((pose.yaw - 90f64.to_radians()).abs() < 1e-12);
+        assert!(setups.pose_at(15.0, &Default::default()).is_err());
+        assert!(setups.pose_at(31.0, &Default::default()).is_err());
+        assert_eq!(Some((0.0, 30.0)), setups.time_bounds());
+    }
+
+    #[test]
+    fn static_scan() {
+        let setup = SetupConfig { height: 10.0, ..Default::default() };
+        let setups = Setups::new(&SetupsConfig { setup: vec![setup] }).unwrap();
+        assert_eq!(10.0, setups.pose_at(-1e9, &Default::default()).unwrap().height);
+        assert_eq!(None, setups.time_bounds());
+    }
+
+    #[test]
+    fn bad_setups() {
+        assert!(Setups::new(&SetupsConfig::default()).is_err());
+        let config = SetupsConfig { setup: vec![setup(0.0, 10.0, 0.0), setup(5.0, 15.0, 0.0)] };
+        assert!(Setups::new(&config).is_err());
+        let config = SetupsConfig { setup: vec![setup(10.0, 10.0, 0.0)] };
+        assert!(Setups::new(&config).is_err());
+        let config = SetupsConfig { setup: vec![setup(0.0, 10.0, ::std::f64::NAN)] };
+        assert!(Setups::new(&config).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Smooth a trajectory before it's interpolated.
+//!
+//! GNSS positions carry high-frequency noise that post-processing doesn't always remove, and it
+//! shows up in point clouds as ripples along the flight path. A smoothing pass replaces each
+//! record's position and attitude with a weighted average over a centered window of records,
+//! either a plain moving average or a Savitzky-Golay filter, which fits a polynomial over the
+//! window and so follows real maneuvers more closely. Records are treated as evenly spaced, and
+//! windows shrink near the ends of the trajectory so they stay centered. Smoothing is configured
+//! with the `[smoothing]` table:
+//!
+//! ```toml
+//! [smoothing]
+//! method = "savitzky_golay"
+//! window = 21
+//! order = 2
+//! ```
+
+use std::result;
+
+use rustc_serialize::Decodable;
+use toml;
+
+/// The default polynomial order of a Savitzky-Golay filter.
+const SAVITZKY_GOLAY_ORDER: usize = 2;
+
+/// A decodable description of how to smooth a trajectory.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct SmoothingConfig {
+    /// The smoothing method, either "moving_average" (the default) or "savitzky_golay".
+    pub method: Option<String>,
+    /// The number of records in the window, which must be odd.
+    pub window: usize,
+    /// The polynomial order of a Savitzky-Golay filter. Defaults to 2.
+    pub order: Option<usize>,
+}
+
+impl SmoothingConfig {
+    /// Creates a new smoothing config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<SmoothingConfig, toml::DecodeError> {
+        SmoothingConfig::decode(&mut toml::Decoder::new(table))
+    }
+
+    /// Returns a message for an even window, an unknown method, and an order that doesn't fit the
+    /// method or the window.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.window % 2 == 0 {
+            problems.push("window must be odd".to_string());
+        }
+        match self.method.as_ref().map(|s| s.as_ref()) {
+            None | Some("moving_average") => {
+                if self.order.is_some() {
+                    problems.push("order can only be used with savitzky_golay".to_string());
+                }
+            }
+            Some("savitzky_golay") => {
+                if self.order() >= self.window {
+                    problems.push("order must be less than window".to_string());
+                }
+            }
+            Some(method) => problems.push(format!("unknown method: {}", method)),
+        }
+        problems
+    }
+
+    fn order(&self) -> usize {
+        match self.method.as_ref().map(|s| s.as_ref()) {
+            Some("savitzky_golay") => self.order.unwrap_or(SAVITZKY_GOLAY_ORDER),
+            _ => 0,
+        }
+    }
+}
+
+/// Smooths a sequence of evenly spaced values.
+///
+/// The config should have no problems.
+pub fn smooth(values: &[f64], config: &SmoothingConfig) -> Vec<f64> {
+    let half_width = config.window / 2;
+    let n = values.len();
+    let mut weights: Vec<Vec<f64>> = Vec::new();
+    (0..n)
+        .map(|i| {
+            let k = half_width.min(i).min(n - 1 - i);
+            while weights.len() <= k {
+                let width = weights.len();
+                weights.push(savitzky_golay(width, config.order()));
+            }
+            weights[k].iter().zip(&values[i - k..i + k + 1]).fold(0.0, |sum, (w, v)| sum + w * v)
+        })
+        .collect()
+}
+
+/// Returns the weights of a centered Savitzky-Golay filter, i.e. the value at the center of a
+/// least-squares polynomial fit, over `2 * half_width + 1` points.
+///
+/// An order of zero is a moving average. The order is capped at what the window can fit.
+fn savitzky_golay(half_width: usize, order: usize) -> Vec<f64> {
+    let order = order.min(2 * half_width);
+    let size = order + 1;
+    let offsets: Vec<f64> = (0..2 * half_width + 1).map(|j| j as f64 - half_width as f64).collect();
+    // Solve the normal equations, (A^T A) c = e0, where A's rows are the powers of each offset;
+    // the weights are then A c. A^T A is positive definite, so it doesn't need pivoting.
+    let mut normal = vec![vec![0.0; size + 1]; size];
+    for row in 0..size {
+        for col in 0..size {
+            let power = (row + col) as i32;
+            normal[row][col] = offsets.iter().fold(0.0, |sum, x| sum + x.powi(power));
+        }
+    }
+    normal[0][size] = 1.0;
+    for col in 0..size {
+        for row in 0..size {
+            if row != col {
+                let factor = normal[row][col] / normal[col][col];
+                for k in col..size + 1 {
+                    normal[row][k] -= factor * normal[col][k];
+                }
+            }
+        }
+    }
+    let c: Vec<f64> = (0..size).map(|row| normal[row][size] / normal[row][row]).collect();
+    offsets.iter()
+           .map(|x| c.iter().enumerate().fold(0.0, |sum, (p, c)| sum + c * x.powi(p as i32)))
+           .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(method: &str, window: usize) -> SmoothingConfig {
+        SmoothingConfig {
+            method: Some(method.to_string()),
+            window: window,
+            order: None,
+        }
+    }
+
+    #[test]
+    fn moving_average() {
+        let smoothed = smooth(&[0.0, 3.0, 0.0, 3.0, 0.0], &config("moving_average", 3));
+        assert_eq!(0.0, smoothed[0]);
+        assert_eq!(1.0, smoothed[1]);
+        assert_eq!(2.0, smoothed[2]);
+        assert_eq!(0.0, smoothed[4]);
+    }
+
+    #[test]
+    fn savitzky_golay_weights() {
+        // The classic five-point quadratic filter.
+        let expected = [-3.0, 12.0, 17.0, 12.0, -3.0];
+        for (w, e) in savitzky_golay(2, 2).iter().zip(&expected) {
+            assert!((w - e / 35.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn savitzky_golay_keeps_parabolas() {
+        let values: Vec<f64> = (0..9).map(|i| (i * i) as f64).collect();
+        let smoothed = smooth(&values, &config("savitzky_golay", 5));
+        for (v, s) in values.iter().zip(&smoothed) {
+            assert!((v - s).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn problems() {
+        assert!(config("moving_average", 5).problems().is_empty());
+        assert_eq!(vec!["window must be odd".to_string()],
+                   config("moving_average", 4).problems());
+        assert_eq!(vec!["unknown method: median".to_string()],
+                   config("median", 5).problems());
+        assert_eq!(vec!["order must be less than window".to_string()],
+                   config("savitzky_golay", 1).problems());
+        let config = SmoothingConfig { order: Some(2), ..config("moving_average", 5) };
+        assert_eq!(vec!["order can only be used with savitzky_golay".to_string()],
+                   config.problems());
+    }
+}
@@ -0,0 +1,156 @@
+//! Where points come from and where they go.
+//!
+//! The georeferencer reads points from a `PointSource` and writes them to a `PointSink`. pabst's
+//! sources and sinks, i.e. files, are both, and so are plain vectors of points, so points that are
+//! already in memory can be georeferenced without a round trip through a file:
+//!
+//! ```
+//! use georef::{GeoreferencerBuilder, ImuGnss, ImuGnssPoint, Point};
+//! let georeferencer = GeoreferencerBuilder::new().utm_zone(31).build().unwrap();
+//! let imu_gnss = ImuGnss::new(vec![ImuGnssPoint { time: 0.0, ..Default::default() },
+//!                                  ImuGnssPoint { time: 1.0, ..Default::default() }])
+//!                    .unwrap();
+//! let mut source = vec![Point { gps_time: Some(0.5), ..Default::default() }];
+//! let mut sink: Vec<Point> = Vec::new();
+//! georeferencer.georeference(&mut source, &imu_gnss, &mut sink).unwrap();
+//! assert_eq!(1, sink.len());
+//! ```
+
+use std::cmp;
+
+use pabst;
+
+use {Error, Result};
+use imu_gnss::ImuGnssPoint;
+
+/// A point, as read from a source and written to a sink.
+pub type Point = pabst::Point;
+
+/// Something that points can be read from.
+pub trait PointSource {
+    /// Reads up to `want` points, or returns `None` once there are no more.
+    fn source(&mut self, want: usize) -> Result<Option<Vec<Point>>>;
+
+    /// Moves past the next `n` points without returning them, and returns how many were passed,
+    /// which is fewer than `n` only at the end of the source.
+    ///
+    /// By default the points are read and dropped. Sources that can jump ahead, or count their
+    /// points without decoding them, should do so instead.
+    fn skip(&mut self, n: usize) -> Result<usize> {
+        let mut skipped = 0;
+        while skipped < n {
+            match try!(self.source(n - skipped)) {
+                Some(points) => skipped += points.len(),
+                None => break,
+            }
+        }
+        Ok(skipped)
+    }
+}
+
+/// Something that points can be written to.
+pub trait PointSink {
+    /// Writes one point.
+    fn sink(&mut self, point: &Point) -> Result<()>;
+
+    /// Writes one point along with the trajectory record it was georeferenced with.
+    ///
+    /// Sinks that can write per-point trajectory attributes, e.g. the accuracy of the pose, use
+    /// the record. By default it's ignored.
+    fn sink_with_record(&mut self, point: &Point, _: &ImuGnssPoint) -> Result<()> {
+        self.sink(point)
+    }
+}
+
+impl<'a> PointSource for pabst::Source + 'a {
+    fn source(&mut self, want: usize) -> Result<Option<Vec<Point>>> {
+        pabst::Source::source(self, want).map_err(Error::from)
+    }
+}
+
+impl<'a> PointSink for pabst::Sink + 'a {
+    fn sink(&mut self, point: &Point) -> Result<()> {
+        pabst::Sink::sink(self, point).map_err(Error::from)
+    }
+}
+
+impl<S: PointSource + ?Sized> PointSource for Box<S> {
+    fn source(&mut self, want: usize) -> Result<Option<Vec<Point>>> {
+        (**self).source(want)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<usize> {
+        (**self).skip(n)
+    }
+}
+
+impl<S: PointSink + ?Sized> PointSink for Box<S> {
+    fn sink(&mut self, point: &Point) -> Result<()> {
+        (**self).sink(point)
+    }
+
+    fn sink_with_record(&mut self, point: &Point, record: &ImuGnssPoint) -> Result<()> {
+        (**self).sink_with_record(point, record)
+    }
+}
+
+/// Points are read from the front of the vector, which is empty once they've all been read.
+impl PointSource for Vec<Point> {
+    fn source(&mut self, want: usize) -> Result<Option<Vec<Point>>> {
+        if self.is_empty() {
+            Ok(None)
+        } else {
+            let n = cmp::min(want, self.len());
+            Ok(Some(self.drain(..n).collect()))
+        }
+    }
+
+    fn skip(&mut self, n: usize) -> Result<usize> {
+        let n = cmp::min(n, self.len());
+        let _ = self.drain(..n);
+        Ok(n)
+    }
+}
+
+/// Points are pushed onto the back of the vector.
+impl PointSink for Vec<Point> {
+    fn sink(&mut self, point: &Point) -> Result<()> {
+        self.push(point.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(time: f64) -> Point {
+        Point { gps_time: Some(time), ..Default::default() }
+    }
+
+    #[test]
+    fn vec_source() {
+        let mut source = vec![point(0.0), point(1.0), point(2.0)];
+        assert_eq!(2, source.source(2).unwrap().unwrap().len());
+        let rest = source.source(2).unwrap().unwrap();
+        assert_eq!(1, rest.len());
+        assert_eq!(Some(2.0), rest[0].gps_time);
+        assert!(source.source(2).unwrap().is_none());
+    }
+
+    #[test]
+    fn vec_skip() {
+        let mut source = vec![point(0.0), point(1.0), point(2.0)];
+        assert_eq!(2, source.skip(2).unwrap());
+        assert_eq!(vec![point(2.0)], source);
+        assert_eq!(1, source.skip(2).unwrap());
+        assert!(source.source(2).unwrap().is_none());
+    }
+
+    #[test]
+    fn vec_sink() {
+        let mut sink: Vec<Point> = Vec::new();
+        sink.sink(&point(1.0)).unwrap();
+        assert_eq!(vec![point(1.0)], sink);
+    }
+}
@@ -0,0 +1,243 @@
+//! Write georeferenced points to delimited text files.
+//!
+//! Text output is handy for quick checks in a spreadsheet or for piping into other scripts. The
+//! columns, delimiter, and precision are configurable, usually from the `[sink]` table:
+//!
+//! ```toml
+//! [sink]
+//! columns = ["x", "y", "z", "time", "intensity"]
+//! delimiter = ","
+//! precision = 3
+//! header = true
+//! ```
+//!
+//! The sigma_north, sigma_east, and sigma_up columns hold the estimated accuracy of the trajectory
+//! at each point, for quality-control maps. They're NaN where the trajectory has no accuracy.
+//! The roll, pitch, and heading columns hold the trajectory's attitude at each point, in degrees,
+//! and trajectory_height its ellipsoidal height, for tracing artifacts back to the platform's
+//! motion.
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::result;
+
+use pabst;
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+use imu_gnss::ImuGnssPoint;
+use point::Attribute;
+use stream::PointSink;
+
+/// A decodable description of a delimited text file.
+#[derive(Debug, Default, RustcDecodable)]
+pub struct TextConfig {
+    /// The columns, in order, from "x", "y", "z", "time", "intensity", "sigma_north",
+    /// "sigma_east", "sigma_up", "roll", "pitch", "heading", and "trajectory_height". Defaults
+    /// to x, y, and z.
+    pub columns: Option<Vec<String>>,
+    /// The field delimiter, either a single character or "tab". Defaults to ",".
+    pub delimiter: Option<String>,
+    /// The number of digits after the decimal point of x, y, z, and time. By default, as many
+    /// digits as are needed to represent each value exactly are written.
+    pub precision: Option<usize>,
+    /// Whether to write a header line with the column names. Defaults to true.
+    pub header: Option<bool>,
+}
+
+impl TextConfig {
+    /// Creates a new text config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<TextConfig, toml::DecodeError> {
+        TextConfig::decode(&mut toml::Decoder::new(table))
+    }
+}
+
+/// Writes points to a delimited text file, one point per line.
+#[derive(Debug)]
+pub struct TextSink<W: Write> {
+    writer: W,
+    columns: Vec<Attribute>,
+    delimiter: char,
+    precision: Option<usize>,
+}
+
+impl TextSink<BufWriter<File>> {
+    /// Creates a delimited text file at a path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::text::{TextConfig, TextSink};
+    /// let sink = TextSink::from_path("out.csv", &TextConfig::default()).unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P,
+                                     config: &TextConfig)
+                                     -> Result<TextSink<BufWriter<File>>> {
+        TextSink::new(BufWriter::new(try!(File::create(path))), config)
+    }
+}
+
+impl<W: Write> TextSink<W> {
+    /// Creates a new delimited text sink and writes its header, if any.
+    pub fn new(mut writer: W, config: &TextConfig) -> Result<TextSink<W>> {
+        let delimiter = match config.delimiter.as_ref().map(|s| s.as_ref()) {
+            None => ',',
+            Some("tab") => '\t',
+            Some(s) if s.chars().count() == 1 => s.chars().next().unwrap(),
+            Some(s) => return Err(Error::Text(format!("invalid delimiter: {}", s))),
+        };
+        let columns = match config.columns {
+            Some(ref columns) => {
+                let mut parsed = Vec::new();
+                for column in columns {
+                    parsed.push(try!(Attribute::from_name(column).ok_or_else(|| {
+                        Error::Text(format!("unknown column: {}", column))
+                    })));
+                }
+                parsed
+            }
+            None => vec![Attribute::X, Attribute::Y, Attribute::Z],
+        };
+        if columns.is_empty() {
+            return Err(Error::Text("no columns were given".to_string()));
+        }
+        if config.header.unwrap_or(true) {
+            let names: Vec<&str> = columns.iter().map(|column| column.name()).collect();
+            try!(writeln!(writer, "{}", names.join(&delimiter.to_string())));
+        }
+        Ok(TextSink {
+            writer: writer,
+            columns: columns,
+            delimiter: delimiter,
+            precision: config.precision,
+        })
+    }
+
+    /// Writes one point.
+    pub fn write_point(&mut self, point: &pabst::Point) -> io::Result<()> {
+        self.write_point_with_record(point, None)
+    }
+
+    /// Writes one point, taking the sigma and attitude columns from the trajectory record it was
+    /// georeferenced with.
+    pub fn write_point_with_record(&mut self,
+                                   point: &pabst::Point,
+                                   record: Option<&ImuGnssPoint>)
+                                   -> io::Result<()> {
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                try!(write!(self.writer, "{}", self.delimiter));
+            }
+            let value = column.value(point, record);
+            match self.precision {
+                Some(precision) if *column != Attribute::Intensity => {
+                    try!(write!(self.writer, "{:.*}", precision, value))
+                }
+                _ => try!(write!(self.writer, "{}", value)),
+            }
+        }
+        writeln!(self.writer, "")
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        try!(self.writer.flush());
+        Ok(self.writer)
+    }
+}
+
+impl<W: Debug + Write + 'static> pabst::Sink for TextSink<W> {
+    fn sink(&mut self, point: &pabst::Point) -> pabst::Result<()> {
+        try!(self.write_point(point));
+        Ok(())
+    }
+
+    fn close_sink(self: Box<Self>) -> pabst::Result<()> {
+        let _ = try!(self.finish());
+        Ok(())
+    }
+}
+
+impl<W: Write> PointSink for TextSink<W> {
+    fn sink(&mut self, point: &pabst::Point) -> Result<()> {
+        self.write_point(point).map_err(Error::from)
+    }
+
+    fn sink_with_record(&mut self, point: &pabst::Point, record: &ImuGnssPoint) -> Result<()> {
+        self.write_point_with_record(point, Some(record)).map_err(Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pabst;
+
+    use imu_gnss::Accuracy;
+
+    fn point() -> pabst::Point {
+        pabst::Point {
+            x: 1.5,
+            y: 2.0,
+            z: -3.27,
+            gps_time: Some(10.0),
+            intensity: Some(42),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default() {
+        let mut sink = TextSink::new(Vec::new(), &TextConfig::default()).unwrap();
+        sink.write_point(&point()).unwrap();
+        let text = String::from_utf8(sink.finish().unwrap()).unwrap();
+        assert_eq!("x,y,z\n1.5,2,-3.27\n", text);
+    }
+
+    #[test]
+    fn columns_and_precision() {
+        let config = TextConfig {
+            columns: Some(vec!["time".to_string(), "z".to_string(), "intensity".to_string()]),
+            delimiter: Some("tab".to_string()),
+            precision: Some(1),
+            header: Some(false),
+        };
+        let mut sink = TextSink::new(Vec::new(), &config).unwrap();
+        sink.write_point(&point()).unwrap();
+        let text = String::from_utf8(sink.finish().unwrap()).unwrap();
+        assert_eq!("10.0\t-3.3\t42\n", text);
+    }
+
+    #[test]
+    fn sigma_columns() {
+        let config = TextConfig {
+            columns: Some(vec!["sigma_north".to_string(),
+                               "sigma_east".to_string(),
+                               "sigma_up".to_string()]),
+            header: Some(false),
+            ..Default::default()
+        };
+        let record = ImuGnssPoint {
+            accuracy: Some(Accuracy { north: 0.1, east: 0.2, down: 0.3, ..Default::default() }),
+            ..Default::default()
+        };
+        let mut sink = TextSink::new(Vec::new(), &config).unwrap();
+        sink.sink_with_record(&point(), &record).unwrap();
+        sink.write_point(&point()).unwrap();
+        let text = String::from_utf8(sink.finish().unwrap()).unwrap();
+        assert_eq!("0.1,0.2,0.3\nNaN,NaN,NaN\n", text);
+    }
+
+    #[test]
+    fn bad_config() {
+        let config = TextConfig { delimiter: Some("::".to_string()), ..Default::default() };
+        assert!(TextSink::new(Vec::new(), &config).is_err());
+        let config = TextConfig { columns: Some(vec!["red".to_string()]), ..Default::default() };
+        assert!(TextSink::new(Vec::new(), &config).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! GPS time systems.
+//!
+//! Scanners and trajectories don't always stamp their records in the same GPS time system:
+//!
+//! - "week" is seconds since the start of the GPS week,
+//! - "standard" is seconds since the GPS epoch, 1980-01-06,
+//! - "adjusted" is standard GPS time minus one billion seconds, as used by LAS,
+//! - "utc" is seconds since 1970-01-01 UTC, i.e. Unix time, which doesn't count leap seconds.
+//!
+//! Point times are converted into the trajectory's system before interpolation. Converting to or
+//! from week seconds needs the GPS week, and assumes that the survey doesn't cross a week
+//! boundary.
+//!
+//! Converting to or from UTC needs the number of leap seconds between GPS time and UTC. It is
+//! looked up for the start of the GPS week in a built-in table, or in a `leap-seconds.list` file
+//! as distributed by the IERS and IETF, or it can be given directly. Surveys that span a leap
+//! second aren't supported.
+//!
+//! ```toml
+//! [georef.time_system]
+//! point_time = "week"
+//! trajectory_time = "utc"
+//! gps_week = 1890
+//! ```
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::result;
+
+use rustc_serialize::Decodable;
+use toml;
+
+use Result;
+use error::Error;
+
+/// The number of seconds in a GPS week.
+const SECONDS_PER_WEEK: f64 = 604800.0;
+/// The offset between standard and adjusted GPS time, in seconds.
+const ADJUSTED_OFFSET: f64 = 1e9;
+/// The GPS epoch, 1980-01-06, in Unix time.
+const GPS_EPOCH: f64 = 315964800.0;
+/// The start of the NTP epoch, 1900-01-01, relative to the Unix epoch.
+const NTP_EPOCH: f64 = -2208988800.0;
+/// The difference between TAI and GPS time, in seconds.
+const TAI_MINUS_GPS: i32 = 19;
+/// The leap seconds since the GPS epoch, as the Unix time at which each took effect and GPS time
+/// minus UTC from then on.
+const LEAP_SECONDS: [(f64, i32); 18] = [(362793600.0, 1),
+                                        (394329600.0, 2),
+                                        (425865600.0, 3),
+                                        (489024000.0, 4),
+                                        (567993600.0, 5),
+                                        (631152000.0, 6),
+                                        (662688000.0, 7),
+                                        (709948800.0, 8),
+                                        (741484800.0, 9),
+                                        (773020800.0, 10),
+                                        (820454400.0, 11),
+                                        (867715200.0, 12),
+                                        (915148800.0, 13),
+                                        (1136073600.0, 14),
+                                        (1230768000.0, 15),
+                                        (1341100800.0, 16),
+                                        (1435708800.0, 17),
+                                        (1483228800.0, 18)];
+
+/// A GPS time system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeSystem {
+    /// Seconds since the start of the GPS week.
+    Week,
+    /// Seconds since the GPS epoch.
+    Standard,
+    /// Standard GPS time minus one billion seconds.
+    Adjusted,
+    /// Seconds since 1970-01-01 UTC, not counting leap seconds.
+    Utc,
+}
+
+impl TimeSystem {
+    /// Returns the time system with a name, or `None` if there isn't one.
+    pub fn from_name(name: &str) -> Option<TimeSystem> {
+        match name {
+            "week" => Some(TimeSystem::Week),
+            "standard" => Some(TimeSystem::Standard),
+            "adjusted" => Some(TimeSystem::Adjusted),
+            "utc" => Some(TimeSystem::Utc),
+            _ => None,
+        }
+    }
+
+    /// Returns the seconds added to a time in this system to get standard GPS time.
+    ///
+    /// `leap_seconds` is GPS time minus UTC, which is only used for UTC.
+    fn standard_offset(self, gps_week: Option<u32>, leap_seconds: i32) -> Option<f64> {
+        match self {
+            TimeSystem::Week => gps_week.map(|week| week as f64 * SECONDS_PER_WEEK),
+            TimeSystem::Standard => Some(0.0),
+            TimeSystem::Adjusted => Some(ADJUSTED_OFFSET),
+            TimeSystem::Utc => Some(leap_seconds as f64 - GPS_EPOCH),
+        }
+    }
+}
+
+/// A table of leap seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeapSeconds {
+    /// The Unix time at which each leap second took effect and GPS time minus UTC from then on,
+    /// in order.
+    entries: Vec<(f64, i32)>,
+}
+
+impl Default for LeapSeconds {
+    fn default() -> LeapSeconds {
+        LeapSeconds { entries: LEAP_SECONDS.to_vec() }
+    }
+}
+
+impl LeapSeconds {
+    /// Reads a leap second table from a `leap-seconds.list` file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use georef::time::LeapSeconds;
+    /// let leap_seconds = LeapSeconds::from_path("leap-seconds.list").unwrap();
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<LeapSeconds> {
+        LeapSeconds::from_reader(try!(File::open(path)))
+    }
+
+    /// Reads a leap second table in the `leap-seconds.list` format from something that
+    /// implements `Read`.
+    ///
+    /// Each line that isn't a comment holds an NTP timestamp, in seconds since 1900, and TAI
+    /// minus UTC from then on.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<LeapSeconds> {
+        let mut s = String::new();
+        let _ = try!(reader.read_to_string(&mut s));
+        let mut entries = Vec::new();
+        for line in s.lines().map(|line| line.trim()).filter(|line| !line.starts_with('#')) {
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some(ntp), Some(tai_minus_utc)) => {
+                    let unix = try!(ntp.parse::<f64>()) + NTP_EPOCH;
+                    entries.push((unix, try!(tai_minus_utc.parse::<i32>()) - TAI_MINUS_GPS));
+                }
+                (None, _) => {}
+                (Some(_), None) => {
+                    return Err(Error::LeapSeconds(format!("invalid line: {}", line)))
+                }
+            }
+        }
+        if entries.is_empty() {
+            return Err(Error::LeapSeconds("no leap seconds were found".to_string()));
+        }
+        if entries.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err(Error::LeapSeconds("leap seconds are out of order".to_string()));
+        }
+        Ok(LeapSeconds { entries: entries })
+    }
+
+    /// Returns GPS time minus UTC, in seconds, at a Unix time.
+    pub fn gps_minus_utc(&self, unix: f64) -> i32 {
+        self.entries
+            .iter()
+            .take_while(|&&(start, _)| start <= unix)
+            .last()
+            .map_or(0, |&(_, leap_seconds)| leap_seconds)
+    }
+}
+
+/// A decodable description of the time systems of the points and the trajectory.
+#[derive(Clone, Debug, Default, RustcDecodable, RustcEncodable)]
+pub struct TimeSystemConfig {
+    /// The time system of the points' gps times: "week", "standard", "adjusted", or "utc".
+    pub point_time: Option<String>,
+    /// The time system of the trajectory's records: "week", "standard", "adjusted", or "utc".
+    pub trajectory_time: Option<String>,
+    /// The GPS week of the survey, needed to convert to or from week seconds and to look up leap
+    /// seconds.
+    pub gps_week: Option<u32>,
+    /// GPS time minus UTC, in seconds, which overrides the leap second table.
+    pub leap_seconds: Option<i32>,
+    /// The path to a `leap-seconds.list` file to use instead of the built-in leap second table.
+    pub leap_seconds_file: Option<String>,
+}
+
+impl TimeSystemConfig {
+    /// Creates a new time system config from a toml value.
+    pub fn from_toml(table: toml::Value) -> result::Result<TimeSystemConfig, toml::DecodeError> {
+        TimeSystemConfig::decode(&mut toml::Decoder::new(table))
+    }
+
+    /// Returns a message for each time system that's missing or unknown, and for a conversion
+    /// that needs a GPS week or leap seconds that aren't given.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut systems = Vec::new();
+        for &(name, system) in &[("point_time", &self.point_time),
+                                 ("trajectory_time", &self.trajectory_time)] {
+            match system.as_ref().map(|s| (s, TimeSystem::from_name(s))) {
+                Some((_, Some(system))) => systems.push(system),
+                Some((s, None)) => {
+                    problems.push(format!("{} must be \"week\", \"standard\", \"adjusted\", or \
+                                           \"utc\", not \"{}\"",
+                                          name,
+                                          s))
+                }
+                None => problems.push(format!("{} is required", name)),
+            }
+        }
+        if systems.len() == 2 && systems[0] != systems[1] &&
+           systems.contains(&TimeSystem::Week) && self.gps_week.is_none() {
+            problems.push("gps_week is required to convert week seconds".to_string());
+        }
+        if systems.len() == 2 && systems[0] != systems[1] &&
+           systems.contains(&TimeSystem::Utc) && self.gps_week.is_none() &&
+           self.leap_seconds.is_none() {
+            problems.push("gps_week or leap_seconds is required to convert UTC".to_string());
+        }
+        problems
+    }
+
+    /// Returns the seconds added to a point's time to get the trajectory's time.
+    pub fn offset(&self) -> Result<f64> {
+        if let Some(problem) = self.problems().into_iter().next() {
+            return Err(Error::InvalidField("time_system", problem));
+        }
+        let system = |s: &Option<String>| TimeSystem::from_name(s.as_ref().unwrap()).unwrap();
+        let (point, trajectory) = (system(&self.point_time), system(&self.trajectory_time));
+        if point == trajectory {
+            return Ok(0.0);
+        }
+        let leap_seconds = match (self.leap_seconds, self.gps_week) {
+            (Some(leap_seconds), _) => leap_seconds,
+            (None, Some(week)) if point == TimeSystem::Utc || trajectory == TimeSystem::Utc => {
+                let table = match self.leap_seconds_file {
+                    Some(ref path) => try!(LeapSeconds::from_path(path)),
+                    None => LeapSeconds::default(),
+                };
+                table.gps_minus_utc(GPS_EPOCH + week as f64 * SECONDS_PER_WEEK)
+            }
+            _ => 0,
+        };
+        Ok(point.standard_offset(self.gps_week, leap_seconds).unwrap() -
+           trajectory.standard_offset(self.gps_week, leap_seconds).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(point_time: &str, trajectory_time: &str, gps_week: Option<u32>) -> TimeSystemConfig {
+        TimeSystemConfig {
+            point_time: Some(point_time.to_string()),
+            trajectory_time: Some(trajectory_time.to_string()),
+            gps_week: gps_week,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(0.0, config("week", "week", None).offset().unwrap());
+        assert_eq!(-1e9, config("standard", "adjusted", None).offset().unwrap());
+        assert_eq!(1e9, config("adjusted", "standard", None).offset().unwrap());
+        assert_eq!(1890.0 * 604800.0 - 1e9,
+                   config("week", "adjusted", Some(1890)).offset().unwrap());
+        assert_eq!(-1890.0 * 604800.0,
+                   config("standard", "week", Some(1890)).offset().unwrap());
+    }
+
+    #[test]
+    fn utc() {
+        // GPS week 1890 started on 2016-03-27, with 17 leap seconds.
+        assert_eq!(1890.0 * 604800.0 + 315964800.0 - 17.0,
+                   config("week", "utc", Some(1890)).offset().unwrap());
+        assert_eq!(-315964800.0, config("utc", "standard", Some(1)).offset().unwrap());
+        let config = TimeSystemConfig { leap_seconds: Some(18), ..config("adjusted", "utc", None) };
+        assert_eq!(1e9 + 315964800.0 - 18.0, config.offset().unwrap());
+        let leap_seconds = LeapSeconds::default();
+        assert_eq!(0, leap_seconds.gps_minus_utc(315964800.0));
+        assert_eq!(17, leap_seconds.gps_minus_utc(1483228799.0));
+        assert_eq!(18, leap_seconds.gps_minus_utc(1483228800.0));
+    }
+
+    #[test]
+    fn leap_seconds_file() {
+        let list = "# comment\n2272060800\t10\t# 1 Jan 1972\n\n3692217600\t37\t# 1 Jan 2017\n";
+        let leap_seconds = LeapSeconds::from_reader(list.as_bytes()).unwrap();
+        assert_eq!(-9, leap_seconds.gps_minus_utc(100000000.0));
+        assert_eq!(18, leap_seconds.gps_minus_utc(1483228800.0));
+        assert!(LeapSeconds::from_reader("# nothing\n".as_bytes()).is_err());
+        assert!(LeapSeconds::from_reader("3692217600\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn problems() {
+        assert_eq!(vec!["gps_week is required to convert week seconds".to_string()],
+                   config("week", "adjusted", None).problems());
+        assert_eq!(vec!["gps_week or leap_seconds is required to convert UTC".to_string()],
+                   config("utc", "adjusted", None).problems());
+        assert!(config("week", "standard", None).offset().is_err());
+        assert_eq!(vec!["gps_week or leap_seconds is required to convert UTC".to_string()],
+                   config("utc", "standard", None).problems());
+        let config = TimeSystemConfig {
+            point_time: Some("week".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(vec!["trajectory_time is required".to_string()], config.problems());
+    }
+}
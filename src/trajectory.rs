@@ -0,0 +1,184 @@
+//! Trajectories, i.e. sources of position and attitude over time.
+
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use pos;
+
+use Result;
+use error::Error;
+use flight_line::{FlightLine, FlightLineConfig};
+use imu_gnss::ImuGnssPoint;
+use rotation::RotationOrder;
+
+/// A position and attitude at a single time.
+///
+/// This is the same as an IMU/GNSS record: angles are in radians and the height is ellipsoidal.
+/// Poses that are known without a trajectory, e.g. in a simulation, can be built directly and
+/// used with `Georeferencer::georeference_point_with_pose`.
+pub type Pose = ImuGnssPoint;
+
+/// How attitude is interpolated between two IMU/GNSS records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttitudeInterpolation {
+    /// Roll, pitch, and yaw are each interpolated linearly.
+    Linear,
+    /// The attitudes are converted to quaternions and spherically interpolated.
+    ///
+    /// Roll, pitch, and yaw are combined in the interpolation's rotation order, and the
+    /// interpolated pose carries the result as its orientation.
+    Slerp,
+    /// Roll, pitch, and yaw are each interpolated with a cubic Hermite spline, using the records'
+    /// attitude rates as the tangents.
+    ///
+    /// This follows fast dynamics between records more closely. Between records that don't both
+    /// have attitude rates, this falls back to linear interpolation.
+    Hermite,
+}
+
+impl Default for AttitudeInterpolation {
+    fn default() -> AttitudeInterpolation {
+        AttitudeInterpolation::Linear
+    }
+}
+
+impl FromStr for AttitudeInterpolation {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<AttitudeInterpolation> {
+        match s {
+            "linear" => Ok(AttitudeInterpolation::Linear),
+            "slerp" => Ok(AttitudeInterpolation::Slerp),
+            "hermite" => Ok(AttitudeInterpolation::Hermite),
+            _ => Err(Error::ParseInterpolation(s.to_string())),
+        }
+    }
+}
+
+/// How position is interpolated between two IMU/GNSS records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionInterpolation {
+    /// Latitude, longitude, and height are each interpolated linearly.
+    Linear,
+    /// Latitude, longitude, and height are interpolated with a Catmull-Rom spline through the two
+    /// records on either side of the time.
+    ///
+    /// This follows the curve of fast turns instead of cutting their corners.
+    CatmullRom,
+}
+
+impl Default for PositionInterpolation {
+    fn default() -> PositionInterpolation {
+        PositionInterpolation::Linear
+    }
+}
+
+impl FromStr for PositionInterpolation {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<PositionInterpolation> {
+        match s {
+            "linear" => Ok(PositionInterpolation::Linear),
+            "catmull-rom" => Ok(PositionInterpolation::CatmullRom),
+            _ => Err(Error::ParseInterpolation(s.to_string())),
+        }
+    }
+}
+
+/// Options that control how IMU/GNSS records are interpolated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Interpolation {
+    /// How attitude is interpolated.
+    pub attitude: AttitudeInterpolation,
+    /// How position is interpolated.
+    pub position: PositionInterpolation,
+    /// The order that roll, pitch, and yaw are combined in, for spherical interpolation.
+    pub rotation_order: RotationOrder,
+    /// The largest time, in seconds, between two records that may be interpolated across.
+    ///
+    /// If `None`, gaps of any length are interpolated across.
+    pub max_gap: Option<f64>,
+}
+
+/// A source of poses at arbitrary times.
+///
+/// Implement this to georeference against a trajectory provider other than the ones in this crate,
+/// e.g. a database or a live feed.
+///
+/// Poses are looked up through a shared reference, so one trajectory can serve several threads at
+/// once. Implementations that keep lookup state, e.g. where the last lookup was, should keep it
+/// behind an atomic or a mutex, and are then `Sync` if the rest of their data is.
+pub trait Trajectory {
+    /// Returns the pose at the given time.
+    ///
+    /// Implementations may ignore interpolation options that they do not support. If the time is
+    /// outside of the trajectory, implementations should return
+    /// `Error::OutsideOfImuGnssRecords` so that the georeferencer's error policy can be applied.
+    /// Likewise, if the records around the time are more than `interpolation.max_gap` seconds
+    /// apart, implementations should return `Error::TrajectoryGap`.
+    fn pose_at(&self, time: f64, interpolation: &Interpolation) -> Result<Pose>;
+
+    /// Returns the first and last times that can be interpolated, if known.
+    fn time_bounds(&self) -> Option<(f64, f64)> {
+        None
+    }
+
+    /// Returns statistics about the trajectory's records, if they are known.
+    fn stats(&self) -> Option<TrajectoryStats> {
+        None
+    }
+
+    /// Returns the flight lines in the trajectory, if they can be found.
+    fn flight_lines(&self, _config: &FlightLineConfig) -> Option<Vec<FlightLine>> {
+        None
+    }
+}
+
+/// Statistics about a trajectory's records.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrajectoryStats {
+    /// The number of records.
+    pub records: usize,
+    /// The times of the first and last records.
+    pub time_bounds: (f64, f64),
+    /// The time between the first and last records, in seconds.
+    pub duration: f64,
+    /// The record rate, in hertz, from the median time between records.
+    pub sample_rate: f64,
+    /// The start and end times of every gap, i.e. every pair of neighboring records more than
+    /// twice the median time between records apart.
+    pub gaps: Vec<(f64, f64)>,
+    /// The smallest and largest roll, in radians.
+    pub roll_bounds: (f64, f64),
+    /// The smallest and largest pitch, in radians.
+    pub pitch_bounds: (f64, f64),
+    /// The smallest and largest latitude, in radians.
+    pub latitude_bounds: (f64, f64),
+    /// The smallest and largest longitude, in radians.
+    ///
+    /// These don't wrap, so a trajectory that crosses the antimeridian spans nearly the globe.
+    pub longitude_bounds: (f64, f64),
+    /// The smallest and largest ellipsoidal height, in meters.
+    pub height_bounds: (f64, f64),
+    /// The natural UTM zones of the records, in order.
+    pub utm_zones: Vec<u8>,
+}
+
+/// `pos` interpolators need `&mut` to interpolate, so they're shared behind a mutex. They always
+/// interpolate linearly, so any options are ignored.
+impl Trajectory for Mutex<pos::Interpolator> {
+    fn pose_at(&self, time: f64, _: &Interpolation) -> Result<Pose> {
+        let point = try!(self.lock().unwrap().interpolate(time));
+        Ok(Pose {
+            time: time,
+            latitude: point.latitude.0,
+            longitude: point.longitude.0,
+            height: point.altitude,
+            roll: point.roll.0,
+            pitch: point.pitch.0,
+            yaw: point.yaw.0,
+            attitude_rate: None,
+            orientation: None,
+            accuracy: None,
+            utm: None,
+        })
+    }
+}
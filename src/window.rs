@@ -0,0 +1,217 @@
+//! Trajectories that are read as they're needed, instead of all at once.
+//!
+//! A long mission's trajectory can have tens of millions of records. A `WindowedTrajectory` holds
+//! only the records within a window of the latest time it was asked for, reading ahead from its
+//! records as point times advance and dropping records that fall behind. Points should be
+//! roughly in time order: a time more than a window before the latest one is outside of the held
+//! records, since those records are gone, and is handled by the georeferencer's error policy like
+//! any other point outside of the trajectory.
+//!
+//! ```no_run
+//! use georef::ImuGnssPoint;
+//! use georef::sbet::Reader;
+//! use georef::window::WindowedTrajectory;
+//! let reader = Reader::from_path("trajectory.sbet").unwrap();
+//! let records = reader.map(|record| record.map(ImuGnssPoint::from));
+//! let trajectory = WindowedTrajectory::new(records, 60.0).unwrap();
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use Result;
+use error::Error;
+use imu_gnss::{ImuGnss, ImuGnssPoint};
+use trajectory::{Interpolation, Pose, Trajectory};
+
+/// A trajectory that holds a sliding window of records.
+///
+/// The window slides as poses are looked up through a shared reference, so it's kept behind a
+/// mutex. Threads that share one take turns, and should still ask for roughly the same times.
+#[derive(Debug)]
+pub struct WindowedTrajectory<I: Iterator<Item = Result<ImuGnssPoint>>> {
+    window: f64,
+    start: Option<f64>,
+    held: Mutex<Held<I>>,
+}
+
+/// The records a windowed trajectory holds, and the records it hasn't read yet.
+#[derive(Debug)]
+struct Held<I: Iterator<Item = Result<ImuGnssPoint>>> {
+    records: I,
+    points: VecDeque<ImuGnssPoint>,
+    exhausted: bool,
+}
+
+impl<I: Iterator<Item = Result<ImuGnssPoint>>> WindowedTrajectory<I> {
+    /// Creates a trajectory from time-ordered records, holding those within `window` seconds of
+    /// the latest time asked for.
+    ///
+    /// The window should span at least a few records.
+    pub fn new(mut records: I, window: f64) -> Result<WindowedTrajectory<I>> {
+        if !(window > 0.0 && window.is_finite()) {
+            return Err(Error::InvalidField("window", "must be greater than zero".to_string()));
+        }
+        let mut points = VecDeque::new();
+        if let Some(record) = records.next() {
+            points.push_back(try!(record));
+        }
+        let start = points.front().map(|point| point.time);
+        let mut held = Held {
+            records: records,
+            points: points,
+            exhausted: start.is_none(),
+        };
+        if let Some(start) = start {
+            try!(held.advance(start, window));
+        }
+        Ok(WindowedTrajectory {
+            window: window,
+            start: start,
+            held: Mutex::new(held),
+        })
+    }
+
+    /// Returns the number of records currently held.
+    pub fn len(&self) -> usize {
+        self.held.lock().unwrap().points.len()
+    }
+
+    /// Returns true if no records are held.
+    pub fn is_empty(&self) -> bool {
+        self.held.lock().unwrap().points.is_empty()
+    }
+}
+
+impl<I: Iterator<Item = Result<ImuGnssPoint>>> Held<I> {
+    /// Reads records until there's a window after `time`, or the records run out, and drops
+    /// records more than a window before it.
+    fn advance(&mut self, time: f64, window: f64) -> Result<()> {
+        while !self.exhausted &&
+              self.points.back().map_or(true, |last| last.time < time + window) {
+            match self.records.next() {
+                Some(record) => {
+                    let point = try!(record);
+                    if self.points.back().map_or(false, |last| point.time <= last.time) {
+                        return Err(Error::NonmonotonicImuGnssRecords);
+                    }
+                    self.points.push_back(point);
+                }
+                None => self.exhausted = true,
+            }
+        }
+        // Keep two records before the cutoff for bracketing and Catmull-Rom knots.
+        if let Some(i) = self.points.iter().position(|point| point.time >= time - window) {
+            let _ = self.points.drain(..i.saturating_sub(2));
+        }
+        Ok(())
+    }
+
+    /// Interpolates the held records at a time.
+    ///
+    /// The records around the time, and their neighbors for Catmull-Rom knots, are handed to an
+    /// `ImuGnss`, so interpolation is the same as for a trajectory that's read all at once.
+    fn pose_at(&self, time: f64, interpolation: &Interpolation) -> Result<Pose> {
+        let n = self.points.len();
+        if n < 2 || !(time >= self.points[0].time && time <= self.points[n - 1].time) {
+            return Err(Error::OutsideOfImuGnssRecords);
+        }
+        let (mut low, mut high) = (0, n - 1);
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            if self.points[mid].time <= time {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        let knots = (low.saturating_sub(1)..(low + 3).min(n)).map(|i| self.points[i]).collect();
+        try!(ImuGnss::new(knots)).pose_at(time, interpolation)
+    }
+}
+
+/// Stats and flight lines aren't known until every record has been read.
+impl<I: Iterator<Item = Result<ImuGnssPoint>>> Trajectory for WindowedTrajectory<I> {
+    fn pose_at(&self, time: f64, interpolation: &Interpolation) -> Result<Pose> {
+        let mut held = self.held.lock().unwrap();
+        let refill = !held.exhausted &&
+                     held.points.back().map_or(true, |last| last.time < time + self.window / 2.0);
+        if refill {
+            try!(held.advance(time, self.window));
+        }
+        held.pose_at(time, interpolation)
+    }
+
+    /// The start is the first record's time. Until the records run out, the end is the latest
+    /// record read so far, which is where a lookup after it stops once they have.
+    fn time_bounds(&self) -> Option<(f64, f64)> {
+        let held = self.held.lock().unwrap();
+        match (self.start, held.points.back()) {
+            (Some(start), Some(last)) if last.time > start => Some((start, last.time)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(n: usize) -> Vec<Result<ImuGnssPoint>> {
+        (0..n)
+            .map(|i| {
+                Ok(ImuGnssPoint {
+                    time: i as f64,
+                    height: i as f64,
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn slide() {
+        let trajectory = WindowedTrajectory::new(records(1000).into_iter(), 10.0).unwrap();
+        let interpolation = Default::default();
+        assert!(trajectory.len() < 20);
+        assert_eq!(5.5, trajectory.pose_at(5.5, &interpolation).unwrap().height);
+        assert_eq!(500.5, trajectory.pose_at(500.5, &interpolation).unwrap().height);
+        assert!(trajectory.len() < 40);
+        assert_eq!(495.0, trajectory.pose_at(495.0, &interpolation).unwrap().height);
+        match trajectory.pose_at(5.5, &interpolation) {
+            Err(Error::OutsideOfImuGnssRecords) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(998.5, trajectory.pose_at(998.5, &interpolation).unwrap().height);
+        match trajectory.pose_at(1000.0, &interpolation) {
+            Err(Error::OutsideOfImuGnssRecords) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match trajectory.pose_at(-1.0, &interpolation) {
+            Err(Error::OutsideOfImuGnssRecords) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(Some((0.0, 999.0)), trajectory.time_bounds());
+    }
+
+    #[test]
+    fn before_start() {
+        let trajectory = WindowedTrajectory::new(records(100).into_iter(), 10.0).unwrap();
+        let (start, end) = trajectory.time_bounds().unwrap();
+        assert_eq!(0.0, start);
+        assert!(end < 99.0);
+        match trajectory.pose_at(-1.0, &Default::default()) {
+            Err(Error::OutsideOfImuGnssRecords) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert!(WindowedTrajectory::new(records(10).into_iter(), 0.0).is_err());
+        let mut bad = records(100);
+        bad[50] = Err(Error::MissingGpsTime);
+        let trajectory = WindowedTrajectory::new(bad.into_iter(), 10.0).unwrap();
+        assert!(trajectory.pose_at(45.0, &Default::default()).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Read YAML configuration.
+//!
+//! YAML documents are converted to toml tables, so YAML configs are decoded with the same structs
+//! as toml ones and can be passed on to pabst sources and sinks.
+
+use std::result;
+
+use toml;
+use yaml_rust::{Yaml, YamlLoader};
+
+use Result;
+use convert::{self, Node, ToToml};
+use error::Error;
+
+/// Parses a YAML document into a toml table.
+///
+/// The document must be a mapping with string keys. Nulls are dropped, since toml has no
+/// equivalent.
+///
+/// # Examples
+///
+/// ```
+/// use georef::yaml;
+/// let table = yaml::to_table("georef: {utm_zone: 6}").unwrap();
+/// assert!(table.contains_key("georef"));
+/// ```
+pub fn to_table(s: &str) -> Result<toml::Table> {
+    let documents = try!(YamlLoader::load_from_str(s).map_err(|err| Error::Yaml(err.to_string())));
+    match documents.into_iter().next() {
+        Some(document) => {
+            match try!(convert::to_toml(document).map_err(Error::Yaml)) {
+                Some(toml::Value::Table(table)) => Ok(table),
+                _ => Err(Error::Yaml("document is not a mapping".to_string())),
+            }
+        }
+        None => Err(Error::Yaml("no document found".to_string())),
+    }
+}
+
+impl ToToml for Yaml {
+    fn node(self) -> result::Result<Node<Yaml>, String> {
+        Ok(match self {
+            Yaml::Real(s) => {
+                match s.parse() {
+                    Ok(n) => Node::Scalar(toml::Value::Float(n)),
+                    Err(_) => return Err(format!("invalid number: {}", s)),
+                }
+            }
+            Yaml::Integer(n) => Node::Scalar(toml::Value::Integer(n)),
+            Yaml::String(s) => Node::Scalar(toml::Value::String(s)),
+            Yaml::Boolean(b) => Node::Scalar(toml::Value::Boolean(b)),
+            Yaml::Array(array) => Node::Array(array),
+            Yaml::Hash(hash) => {
+                let mut entries = Vec::new();
+                for (key, yaml) in hash {
+                    match key {
+                        Yaml::String(key) => entries.push((key, yaml)),
+                        other => return Err(format!("key is not a string: {:?}", other)),
+                    }
+                }
+                Node::Table(entries)
+            }
+            Yaml::Null => Node::Null,
+            Yaml::Alias(_) | Yaml::BadValue => {
+                return Err("aliases and invalid values are not supported".to_string())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use toml;
+
+    #[test]
+    fn scalars() {
+        let table = to_table("a: 1\nb: 1.5\nc: text\nd: true\ne: ~\nf: [1, 2]").unwrap();
+        assert_eq!(Some(&toml::Value::Integer(1)), table.get("a"));
+        assert_eq!(Some(&toml::Value::Float(1.5)), table.get("b"));
+        assert_eq!(Some(&toml::Value::String("text".to_string())), table.get("c"));
+        assert_eq!(Some(&toml::Value::Boolean(true)), table.get("d"));
+        assert!(!table.contains_key("e"));
+        assert_eq!(Some(&toml::Value::Array(vec![toml::Value::Integer(1),
+                                                 toml::Value::Integer(2)])),
+                   table.get("f"));
+    }
+
+    #[test]
+    fn not_a_mapping() {
+        assert!(to_table("- 1\n- 2").is_err());
+        assert!(to_table("").is_err());
+    }
+}
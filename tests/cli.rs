@@ -0,0 +1,68 @@
+//! Run the georef binary and check how it reports errors.
+
+extern crate rustc_serialize;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::process::Command;
+
+use rustc_serialize::json::Json;
+
+fn georef() -> Command {
+    let mut path = env::current_exe().unwrap();
+    let _ = path.pop();
+    if path.ends_with("deps") {
+        let _ = path.pop();
+    }
+    Command::new(path.join("georef"))
+}
+
+#[test]
+fn missing_config_is_an_io_error() {
+    let output = georef().arg("validate")
+                         .arg("--error-format")
+                         .arg("json")
+                         .arg("data/does-not-exist.toml")
+                         .output()
+                         .unwrap();
+    assert_eq!(Some(6), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let report = Json::from_str(stderr.lines().last().unwrap()).unwrap();
+    assert_eq!(Some("io"), report.find("kind").and_then(|kind| kind.as_string()));
+    assert_eq!(Some(6), report.find("exit_code").and_then(|code| code.as_u64()));
+}
+
+#[test]
+fn missing_georef_table_is_a_config_error() {
+    let path = env::temp_dir().join("georef-no-georef-table.toml");
+    File::create(&path).unwrap().write_all(b"[trajectory]\nrepair = \"sort\"\n").unwrap();
+    let output = georef().arg("run")
+                         .arg("--error-format")
+                         .arg("json")
+                         .arg(&path)
+                         .arg("data/does-not-exist.las")
+                         .arg("data/0916_2014_ie.pos")
+                         .arg(env::temp_dir().join("georef-no-georef-table.las"))
+                         .output()
+                         .unwrap();
+    assert_eq!(Some(2), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let report = Json::from_str(stderr.lines().last().unwrap()).unwrap();
+    assert_eq!(Some("config"), report.find("kind").and_then(|kind| kind.as_string()));
+}
+
+#[test]
+fn batch_output_names_must_differ() {
+    let output = georef().arg("batch")
+                         .arg("data/config.toml")
+                         .arg("data/0916_2014_ie.pos")
+                         .arg(env::temp_dir().join("georef-batch-collision"))
+                         .arg("a/points.las")
+                         .arg("b/points.las")
+                         .output()
+                         .unwrap();
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("a/points.las and b/points.las would both be written to points.las"));
+}
@@ -10,6 +10,7 @@ extern crate toml;
 
 use std::fs::File;
 use std::io::Read;
+use std::sync::Mutex;
 
 use georef::{Georeferencer, GeorefConfig};
 use pabst::{open_file_source, open_file_sink};
@@ -26,7 +27,7 @@ fn georeference_it() {
     let ref mut source = open_file_source("data/0916_2014_girdwood35.rxp", config.remove("source"))
                              .unwrap();
     let pos_source = Box::new(pos::pos::Reader::from_path("data/0916_2014_ie.pos").unwrap());
-    let ref mut interpolator = Interpolator::new(pos_source).unwrap();
+    let ref interpolator = Mutex::new(Interpolator::new(pos_source).unwrap());
     let mut sink = open_file_sink("target/debug/girdwood.las", config.remove("sink")).unwrap();
     georeferencer.georeference(source, interpolator, &mut sink).unwrap();
     sink.close_sink().unwrap();
@@ -0,0 +1,47 @@
+//! Write a LAS file with the header options that georef asks for, and read them back.
+
+extern crate georef;
+extern crate pabst;
+extern crate toml;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+
+use georef::OutputFrame;
+use pabst::{Sink, open_file_sink};
+
+fn u16_at(bytes: &[u8], offset: usize) -> usize {
+    bytes[offset] as usize | (bytes[offset + 1] as usize) << 8
+}
+
+#[test]
+fn version_and_wkt() {
+    let path = env::temp_dir().join("georef-las-version-and-wkt.las");
+    let path = path.to_str().unwrap();
+    let wkt = OutputFrame::Utm.wkt(6);
+    let mut config = toml::Table::new();
+    let _ = config.insert("version".to_string(), toml::Value::String("1.4".to_string()));
+    let _ = config.insert("wkt".to_string(), toml::Value::String(wkt.clone()));
+    let mut sink = open_file_sink(path, Some(toml::Value::Table(config))).unwrap();
+    sink.sink(&pabst::Point { gps_time: Some(1.0), ..Default::default() }).unwrap();
+    sink.close_sink().unwrap();
+
+    let mut bytes = Vec::new();
+    let _ = File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+    assert_eq!((1, 4), (bytes[24], bytes[25]));
+    let mut offset = u16_at(&bytes, 94);
+    let number_of_vlrs = u16_at(&bytes, 100) | u16_at(&bytes, 102) << 16;
+    let mut found = None;
+    for _ in 0..number_of_vlrs {
+        let length = u16_at(&bytes, offset + 20);
+        if bytes[offset + 2..offset + 18].starts_with(b"LASF_Projection") &&
+           u16_at(&bytes, offset + 18) == 2112 {
+            found = Some(String::from_utf8_lossy(&bytes[offset + 54..offset + 54 + length])
+                             .trim_right_matches('\0')
+                             .to_string());
+        }
+        offset += 54 + length;
+    }
+    assert_eq!(Some(wkt), found);
+}
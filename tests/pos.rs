@@ -0,0 +1,49 @@
+//! Georeference against a .pos trajectory read into memory.
+
+extern crate georef;
+extern crate pabst;
+extern crate pos;
+
+use georef::{ErrorPolicy, GeoreferencerBuilder, ImuGnss, RepairPolicy, Trajectory};
+
+fn imu_gnss() -> ImuGnss {
+    let mut reader = pos::pos::Reader::from_path("data/0916_2014_ie.pos").unwrap();
+    ImuGnss::from_pos_source(&mut reader, RepairPolicy::Error).unwrap().0
+}
+
+fn point(time: f64) -> pabst::Point {
+    pabst::Point { gps_time: Some(time), ..Default::default() }
+}
+
+#[test]
+fn skip_outside() {
+    let imu_gnss = imu_gnss();
+    let (start, end) = imu_gnss.time_bounds().unwrap();
+    assert!(imu_gnss.stats().is_some());
+    let georeferencer = GeoreferencerBuilder::new()
+                            .utm_zone(6)
+                            .error_policy(ErrorPolicy::Skip)
+                            .build()
+                            .unwrap();
+    let mut source = vec![point(start - 1.0), point((start + end) / 2.0), point(end + 1.0)];
+    let mut sink: Vec<pabst::Point> = Vec::new();
+    let summary = georeferencer.georeference(&mut source, &imu_gnss, &mut sink).unwrap();
+    assert_eq!(1, summary.points_written);
+    assert_eq!(Some((start + end) / 2.0), sink[0].gps_time);
+}
+
+#[test]
+fn max_interpolation_gap() {
+    let imu_gnss = imu_gnss();
+    let (first, second) = (imu_gnss.points()[0].time, imu_gnss.points()[1].time);
+    let time = (first + second) / 2.0;
+    let builder = || {
+        GeoreferencerBuilder::new()
+            .utm_zone(6)
+            .error_policy(ErrorPolicy::Skip)
+            .max_interpolation_gap((second - first) / 2.0)
+    };
+    assert!(!builder().build().unwrap().georeference_point(&mut point(time), &imu_gnss).unwrap());
+    let wide = builder().max_interpolation_gap(second - first).build().unwrap();
+    assert!(wide.georeference_point(&mut point(time), &imu_gnss).unwrap());
+}